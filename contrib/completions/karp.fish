@@ -0,0 +1,326 @@
+# Generated by `karp completions`. Entry paths and group names are looked up
+# in the connected database using the cached session, so nothing is offered
+# until karp has connected successfully at least once.
+
+function __karp_backend
+    set -l connection
+    set -l tokens (commandline -opc)
+    for i in (seq 2 (count $tokens))
+        switch $tokens[$i]
+            case --url --profile
+                set -a connection $tokens[$i] $tokens[(math $i + 1)]
+            case '--url=*' '--profile=*'
+                set -a connection $tokens[$i]
+        end
+    end
+    karp $connection __complete $argv 2>/dev/null
+end
+
+function __karp_entry_paths
+    __karp_backend path (commandline -ct)
+end
+
+function __karp_names
+    set -l tokens (commandline -opc)
+    set -l groups
+    set -l found_command
+    set -l skip
+    for token in $tokens[2..-1]
+        if set -q skip[1]
+            set -e skip
+            continue
+        end
+        if not set -q found_command[1]
+            contains -- $token get-form-fields password username; and set found_command 1
+            continue
+        end
+        switch $token
+            case -t --type -i --index
+                set skip 1
+            case '-*'
+            case '*'
+                set -a groups $token
+        end
+    end
+    __karp_backend names $groups
+end
+
+# Print an optspec for argparse to handle cmd's options that are independent of any subcommand.
+function __fish_karp_global_optspecs
+	string join \n profile= trace= data-dir= locale= v/verbose q/quiet output= no-pager no-notifications reveal= harden-memory allow-core-dumps timings url= ssh= database= security-level= client-display-name= client-display-description= origin= association-name= trigger-unlock no-cache-session-key allow-insecure-session-file allow-insecure-storage snapshot key-file= match= max-password-attempts= no-input prompt-timeout= password-fd= pinentry-program= h/help V/version
+end
+
+function __fish_karp_needs_command
+	# Figure out if the current invocation already has a command.
+	set -l cmd (commandline -opc)
+	set -e cmd[1]
+	argparse -s (__fish_karp_global_optspecs) -- $cmd 2>/dev/null
+	or return
+	if set -q argv[1]
+		# Also print the command, so this can be used to figure out what it is.
+		echo $argv[1]
+		return 1
+	end
+	return 0
+end
+
+function __fish_karp_using_subcommand
+	set -l cmd (__fish_karp_needs_command)
+	test -z "$cmd"
+	and return 1
+	contains -- $cmd[1] $argv
+end
+
+complete -c karp -n "__fish_karp_needs_command" -l profile -d 'The profile from the configuration file to use. Defaults to the profile named by `default_profile` in the configuration file' -r
+complete -c karp -n "__fish_karp_needs_command" -l trace -d 'Write a transcript of the messages exchanged with KeePassRPC or KeePassXC to the given file, for attaching to bug reports. Encrypted messages are written as their decrypted contents, with passwords and other secrets redacted, along with when each was sent or received' -r -F
+complete -c karp -n "__fish_karp_needs_command" -l data-dir -d 'Keep the session file, snapshots, shell history, and everything else we write in this directory, instead of the data, state, and cache directories of the platform (`XDG_DATA_HOME`, `XDG_STATE_HOME`, and `XDG_CACHE_HOME` on Linux). The configuration file stays where it is' -r -f -a "(__fish_complete_directories)"
+complete -c karp -n "__fish_karp_needs_command" -l locale -d 'The language to show messages in, such as `de`. Defaults to the language of the locale given by `LC_ALL`, `LC_MESSAGES`, or `LANG`' -r
+complete -c karp -n "__fish_karp_needs_command" -l output -d 'Print results as tables and plain values, or as JSON for other programs to read. `karp schema` describes the JSON we print' -r -f -a "{text\t'Tables and plain values',json\t'JSON documents'}"
+complete -c karp -n "__fish_karp_needs_command" -l reveal -d 'Erase passwords and other secrets we print to the terminal after this many seconds, or as soon as a key is pressed, so they don\'t stay on screen or in the scrollback' -r
+complete -c karp -n "__fish_karp_needs_command" -l url -d 'The URL to connect to. For KeePassRPC, this is a WebSocket. For KeePassXC, this is a file path to a Unix domain socket, or just `file:` to look for the socket where KeePassXC and `keepassxc-proxy` put it, under `XDG_RUNTIME_DIR`, a Snap home, or `TMPDIR`. A KeePassXC socket forwarded to a TCP port, such as with `socat`, is given as `tcp://host:port`. To start a helper like `keepassxc-proxy` and talk to KeePassXC through its standard input and output, use a `stdio` URL with its path, such as `stdio:/usr/bin/keepassxc-proxy`; add `arg` parameters to pass it arguments, and `framing=none` if it doesn\'t frame messages with their lengths like native messaging does. To read a database file directly, use a `kdbx` URL with its path, such as `kdbx:///home/me/passwords.kdbx`. To use the collections of the Freedesktop Secret Service as groups, use `secret-service:`. Defaults to the URL of the selected profile, or `ws://127.0.0.1:12546`' -r -f
+complete -c karp -n "__fish_karp_needs_command" -l ssh -d 'Reach the backend on another machine through SSH, given as a destination like `me@desktop`. The URL is then the one the backend has on that machine, such as `ws://127.0.0.1:12546`, or the path of the KeePassXC socket there. Defaults to the destination in the selected profile' -r -f -a "(__fish_print_hostnames)"
+complete -c karp -n "__fish_karp_needs_command" -l database -d 'The database to use when several are open. For KeePassRPC, this is the name or file name of the database. For KeePassXC, which only serves the active database, this is the database hash, and commands fail if another database is active' -r
+complete -c karp -n "__fish_karp_needs_command" -l security-level -d 'The security level to require of KeePassRPC. By default, we require a medium level when the session key is cached and a high level otherwise. Selecting a high level turns off caching of the session key, so the password is requested for every connection' -r -f -a "{low\t'',medium\t'',high\t''}"
+complete -c karp -n "__fish_karp_needs_command" -l client-display-name -d 'The name KeePassRPC shows when asking to authorize this client, such as `karp on build-agent-3`. Defaults to the name in the selected profile, or `Karp`. KeePassXC asks the user to name the client instead' -r
+complete -c karp -n "__fish_karp_needs_command" -l client-display-description -d 'The description KeePassRPC shows when asking to authorize this client. Defaults to the description in the selected profile, or the package description' -r
+complete -c karp -n "__fish_karp_needs_command" -l origin -d 'The `Origin` header to send to KeePassRPC, for servers that only accept connections from particular origins. Defaults to the origin in the selected profile, or `karp://karp`' -r
+complete -c karp -n "__fish_karp_needs_command" -l association-name -d 'The name to give this client when KeePassXC asks to associate with it, so it can be told apart in the list of connected clients. KeePassXC doesn\'t let us name the association ourselves, so we show the name to enter instead. Defaults to the name in the selected profile' -r
+complete -c karp -n "__fish_karp_needs_command" -l key-file -d 'The key file that, along with or instead of the master password, locks a database read directly from a `kdbx` URL. Defaults to the key file in the selected profile' -r -F
+complete -c karp -n "__fish_karp_needs_command" -l match -d 'How the title of an entry given by path is compared with the titles of the entries in its group. An entry with exactly that title is always preferred. KeePassXC looks entries up by their whole path itself, so it only finds titles that are close enough for it' -r -f -a "{exact\t'The titles must be the same',prefix\t'The title of the entry must start with the one given',substring\t'The title of the entry must contain the one given'}"
+complete -c karp -n "__fish_karp_needs_command" -l max-password-attempts -d 'How many times to ask for the password of KeePassRPC or a database file before giving up, waiting a moment after each wrong one. When they\'re used up, we exit with status 3' -r
+complete -c karp -n "__fish_karp_needs_command" -l prompt-timeout -d 'Give up on a password prompt that isn\'t answered within this many seconds, instead of waiting for as long as it takes' -r
+complete -c karp -n "__fish_karp_needs_command" -l password-fd -d 'Read the password of KeePassRPC or a database file from this inherited file descriptor, such as `3` for `karp --password-fd 3 ... 3<file`, before asking for it. Only the first line is read, and only once, so if it\'s wrong we fall back to asking' -r
+complete -c karp -n "__fish_karp_needs_command" -l pinentry-program -d 'The path to the Pinentry program to use when requesting the initial password from the plugin' -r -F
+complete -c karp -n "__fish_karp_needs_command" -s v -l verbose -d 'Show more about what we\'re doing: once for debugging messages, twice to trace everything. This overrides the default level of `KARP_LOG`'
+complete -c karp -n "__fish_karp_needs_command" -s q -l quiet -d 'Show only errors'
+complete -c karp -n "__fish_karp_needs_command" -l no-pager -d 'Print long tables straight to the terminal, instead of showing them through `PAGER` (or `less -R`) when they don\'t fit'
+complete -c karp -n "__fish_karp_needs_command" -l no-notifications -d 'Don\'t show desktop notifications when something is copied to or cleared from the clipboard'
+complete -c karp -n "__fish_karp_needs_command" -l harden-memory -d 'Lock all of our memory so session keys and decrypted entries can\'t be swapped out to disk, and stop if we can\'t. This may need a higher limit on locked memory than the system default; see `ulimit -l`. Defaults to the setting in the selected profile'
+complete -c karp -n "__fish_karp_needs_command" -l allow-core-dumps -d 'Let the system write a core file if we crash, for debugging. Core files hold whatever was in memory, including session keys and passwords, so we turn them off by default'
+complete -c karp -n "__fish_karp_needs_command" -l timings -d 'When the command finishes, show how long we spent connecting, authenticating, and waiting on each request to the backend, and how much of the rest was ours, to tell whether slowness comes from the network, KeePass, or us'
+complete -c karp -n "__fish_karp_needs_command" -l trigger-unlock -d 'Ask KeePassXC to show its unlock prompt when a command reads from a locked database, instead of waiting for the database to be unlocked. Commands that change entries always ask. Defaults to the setting in the selected profile'
+complete -c karp -n "__fish_karp_needs_command" -l no-cache-session-key -d 'Turn off caching of the shared key derived by connection negotiation'
+complete -c karp -n "__fish_karp_needs_command" -l allow-insecure-session-file -d 'Read the session file even if other users can access it. Without this, we refuse to use a session key that may have been exposed'
+complete -c karp -n "__fish_karp_needs_command" -l allow-insecure-storage -d 'Cache the session key in an unencrypted file when the keyring can\'t be reached. Without this, we keep the session key in memory only, so it has to be negotiated again next time, including in builds without keyring support. Defaults to the setting in the selected profile'
+complete -c karp -n "__fish_karp_needs_command" -l snapshot -d 'Keep an encrypted snapshot of the entries we look up, and answer from it when KeePassRPC or KeePassXC can\'t be reached. The snapshot is protected with the cached session key, so it\'s only kept once a session has been cached. Defaults to the setting in the selected profile'
+complete -c karp -n "__fish_karp_needs_command" -l no-input -d 'Never ask the user anything: not for a password, not to approve a new KeePassXC association or unlock the database, and not in commands like `edit` that are interactive by nature. Anything that would need an answer fails at once with status 4 instead, so scripts and CI jobs don\'t hang'
+complete -c karp -n "__fish_karp_needs_command" -s h -l help -d 'Print help (see more with \'--help\')'
+complete -c karp -n "__fish_karp_needs_command" -s V -l version -d 'Print version'
+complete -c karp -n "__fish_karp_needs_command" -f -a "alias" -d 'Manage saved lookups. An alias can be invoked in place of a command, as in `karp <alias>`, and expands to a `get-form-fields` invocation. Any further arguments are passed along to `get-form-fields`'
+complete -c karp -n "__fish_karp_needs_command" -f -a "benchmark" -d 'Measure how long it takes to talk to the selected backend. We time opening a connection, the first request (which includes the handshake with KeePassRPC or KeePassXC), and then a number of further requests over the same connection'
+complete -c karp -n "__fish_karp_needs_command" -f -a "cleanup" -d 'Remove everything we\'ve kept on this machine: the session data cached for every URL in the configuration file in each kind of storage, snapshots, shell history, and our state and cache directories once they\'re empty. KeePassXC and KeePassRPC keep their side of the association, which only they can remove, so we say where to find it'
+complete -c karp -n "__fish_karp_needs_command" -f -a "uninstall" -d 'Remove everything we\'ve kept on this machine: the session data cached for every URL in the configuration file in each kind of storage, snapshots, shell history, and our state and cache directories once they\'re empty. KeePassXC and KeePassRPC keep their side of the association, which only they can remove, so we say where to find it'
+complete -c karp -n "__fish_karp_needs_command" -f -a "completions" -d 'Write completion scripts for Zsh and Fish. Besides commands and options, they complete group and entry names by asking the connected database'
+complete -c karp -n "__fish_karp_needs_command" -f -a "config" -d 'Read and change the configuration file. Settings are named by their path in the file, with each table separated by a dot, such as `default_profile` or `profiles.work.url`. Changes are checked against the settings we know before they\'re saved, and only the setting changed is written, keeping comments and formatting. Settings we don\'t know elsewhere in the file don\'t get in the way, so they can be removed here'
+complete -c karp -n "__fish_karp_needs_command" -f -a "generate-passphrase" -d 'Generate a passphrase of random words without asking a backend, for backends that can\'t generate one or when a passphrase is easier to type than a password. Words are drawn from a diceware word list, like the EFF large word list, which can be saved as `eff_large_wordlist.txt` in the data directory'
+complete -c karp -n "__fish_karp_needs_command" -f -a "init" -d 'Set up Karp for the first time. We look for a running KeePassXC or KeePassRPC, connect to it so it can ask you to allow Karp, check that the session is kept so you aren\'t asked again, and save the connection as the URL of the selected profile (or `default`) in the configuration file'
+complete -c karp -n "__fish_karp_needs_command" -f -a "man" -d 'Write manual pages for karp and each of its commands'
+complete -c karp -n "__fish_karp_needs_command" -f -a "schema" -d 'Print the JSON Schema of what we print with `--output json`, so programs that read it can check it or generate code for it. Fields may be added to these documents, but not removed or changed'
+complete -c karp -n "__fish_karp_needs_command" -f -a "session" -d 'Manage the session data cached for the selected URL'
+complete -c karp -n "__fish_karp_needs_command" -f -a "verify-session" -d 'Check that the cached session with the selected backend still works, without asking for a password or approval and without reading any entries. We exit with status 0 if it does and 1 if it doesn\'t, so a batch job can find out before it starts'
+complete -c karp -n "__fish_karp_needs_command" -f -a "copy" -d 'Copy an entry to another backend. If an entry with the same title already exists in the destination group, it is updated instead'
+complete -c karp -n "__fish_karp_needs_command" -f -a "create-entry" -d 'Create an entry. Either give its path and contents as arguments, or use `--interactive` to be asked for each part of it in turn'
+complete -c karp -n "__fish_karp_needs_command" -f -a "curl-auth" -d 'Print the username and password of an entry as curl configuration, to be read with `curl --config -`, such as `karp curl-auth Work/API | curl --config - https://api.example.com/`. The password never appears in the command line or the shell history'
+complete -c karp -n "__fish_karp_needs_command" -f -a "diff" -d 'Compare the fields of two entries. Secret values are compared, but never displayed'
+complete -c karp -n "__fish_karp_needs_command" -f -a "edit" -d 'Change an entry in a text editor. The title, URLs, and form fields of the entry are written to a temporary TOML file, which is opened with `VISUAL` or `EDITOR` (or `vi`). Once the editor exits, the changes are shown and saved to the entry, and the file is overwritten and removed'
+complete -c karp -n "__fish_karp_needs_command" -f -a "get-form-fields" -d 'Get the form fields of an entry at a given path'
+complete -c karp -n "__fish_karp_needs_command" -f -a "get-totp" -d 'Print the current time-based one-time password of an entry. Only KeePassXC generates these codes for us'
+complete -c karp -n "__fish_karp_needs_command" -f -a "monitor" -d 'Stay connected and print a line of JSON whenever the database is locked or unlocked, such as `{"event":"database-locked","time":1718000000}`, so a script can react to it. KeePassXC and KeePassRPC tell us about these'
+complete -c karp -n "__fish_karp_needs_command" -f -a "netrc" -d 'Print a `.netrc` file with the usernames and passwords of entries, for tools like curl, ftp, and Mercurial that read credentials from one'
+complete -c karp -n "__fish_karp_needs_command" -f -a "open-url" -d 'Open the first URL of an entry in the default browser, like KeePass does when asked to open an entry\'s URL. A field of the entry, such as its password, can be copied to the clipboard first, ready to paste into the page'
+complete -c karp -n "__fish_karp_needs_command" -f -a "password" -d 'Print the password of an entry'
+complete -c karp -n "__fish_karp_needs_command" -f -a "search" -d 'Free-text search for a given entry'
+complete -c karp -n "__fish_karp_needs_command" -f -a "serve" -d 'Serve tools for local AI assistants over the Model Context Protocol on standard input and output: `search_entries`, which finds entries without revealing any of their fields, and `get_field`, which gives the value of one field of an entry. Passwords, and fields with placeholders that could expand to them, are only given out with `--allow-secrets`'
+complete -c karp -n "__fish_karp_needs_command" -f -a "shell" -d 'Run commands one after another over a single connection. Lines are split into arguments the way a POSIX shell would split them. Lines that begin with a space are not recorded in the history. Enter `exit` or press Ctrl-D to leave the shell'
+complete -c karp -n "__fish_karp_needs_command" -f -a "status" -d 'Show the databases the backend has open and whether each is active. The selector of a database is the value to give to `--database` to use it. Locked databases are not shown: KeePassRPC leaves them out, and KeePassXC waits for the active database to be unlocked before answering. Settings the backend reports follow, such as the ones KeePassRPC gives browser extensions, which help explain why a URL search finds nothing. Any protocol features the backend reported when we connected come last'
+complete -c karp -n "__fish_karp_needs_command" -f -a "systemd-creds" -d 'Write fields of entries to files in a directory, one credential per file, for services to load with `LoadCredential=` (or, with `--encrypt`, `LoadCredentialEncrypted=`). Each file is readable only by its owner, and is replaced as a whole so a service never reads half of one'
+complete -c karp -n "__fish_karp_needs_command" -f -a "totp" -d 'Work with the time-based one-time passwords of entries'
+complete -c karp -n "__fish_karp_needs_command" -f -a "type" -d 'Type the username and password of an entry into the active window, like KeePass auto-type, using wtype, xdotool, or ydotool. This works with any backend, not just KeePassXC'
+complete -c karp -n "__fish_karp_needs_command" -f -a "update-entry" -d 'Change parts of an entry without opening an editor. With `--generate-password`, a new password is generated and saved to the entry in the same update, then shown once, for rotating a credential'
+complete -c karp -n "__fish_karp_needs_command" -f -a "username" -d 'Print the username of an entry'
+complete -c karp -n "__fish_karp_needs_command" -f -a "help" -d 'Print this message or the help of the given subcommand(s)'
+complete -c karp -n "__fish_karp_using_subcommand alias; and not __fish_seen_subcommand_from add remove list help" -s h -l help -d 'Print help'
+complete -c karp -n "__fish_karp_using_subcommand alias; and not __fish_seen_subcommand_from add remove list help" -f -a "add" -d 'Save a lookup of an entry under a new name, replacing any existing alias with the same name'
+complete -c karp -n "__fish_karp_using_subcommand alias; and not __fish_seen_subcommand_from add remove list help" -f -a "remove" -d 'Remove a saved lookup'
+complete -c karp -n "__fish_karp_using_subcommand alias; and not __fish_seen_subcommand_from add remove list help" -f -a "list" -d 'List saved lookups'
+complete -c karp -n "__fish_karp_using_subcommand alias; and not __fish_seen_subcommand_from add remove list help" -f -a "help" -d 'Print this message or the help of the given subcommand(s)'
+complete -c karp -n "__fish_karp_using_subcommand alias; and __fish_seen_subcommand_from add" -s h -l help -d 'Print help'
+complete -c karp -n "__fish_karp_using_subcommand alias; and __fish_seen_subcommand_from remove" -s h -l help -d 'Print help'
+complete -c karp -n "__fish_karp_using_subcommand alias; and __fish_seen_subcommand_from list" -s h -l help -d 'Print help'
+complete -c karp -n "__fish_karp_using_subcommand alias; and __fish_seen_subcommand_from help" -f -a "add" -d 'Save a lookup of an entry under a new name, replacing any existing alias with the same name'
+complete -c karp -n "__fish_karp_using_subcommand alias; and __fish_seen_subcommand_from help" -f -a "remove" -d 'Remove a saved lookup'
+complete -c karp -n "__fish_karp_using_subcommand alias; and __fish_seen_subcommand_from help" -f -a "list" -d 'List saved lookups'
+complete -c karp -n "__fish_karp_using_subcommand alias; and __fish_seen_subcommand_from help" -f -a "help" -d 'Print this message or the help of the given subcommand(s)'
+complete -c karp -n "__fish_karp_using_subcommand benchmark" -s n -l count -d 'The number of requests to time after the first' -r
+complete -c karp -n "__fish_karp_using_subcommand benchmark" -l query -d 'Time searches for the given text instead of listing the open databases, which the backend can answer without looking at entries' -r
+complete -c karp -n "__fish_karp_using_subcommand benchmark" -s h -l help -d 'Print help'
+complete -c karp -n "__fish_karp_using_subcommand cleanup" -l config -d 'Remove the configuration file too'
+complete -c karp -n "__fish_karp_using_subcommand cleanup" -s y -l yes -d 'Don\'t ask before removing anything'
+complete -c karp -n "__fish_karp_using_subcommand cleanup" -s h -l help -d 'Print help'
+complete -c karp -n "__fish_karp_using_subcommand uninstall" -l config -d 'Remove the configuration file too'
+complete -c karp -n "__fish_karp_using_subcommand uninstall" -s y -l yes -d 'Don\'t ask before removing anything'
+complete -c karp -n "__fish_karp_using_subcommand uninstall" -s h -l help -d 'Print help'
+complete -c karp -n "__fish_karp_using_subcommand completions" -l out-dir -d 'The directory to write the scripts to. It is created if it doesn\'t exist' -r -f -a "(__fish_complete_directories)"
+complete -c karp -n "__fish_karp_using_subcommand completions" -s h -l help -d 'Print help'
+complete -c karp -n "__fish_karp_using_subcommand config; and not __fish_seen_subcommand_from get set unset list help" -s h -l help -d 'Print help'
+complete -c karp -n "__fish_karp_using_subcommand config; and not __fish_seen_subcommand_from get set unset list help" -f -a "get" -d 'Print the value of a setting. Text is printed as it is, and anything else as TOML'
+complete -c karp -n "__fish_karp_using_subcommand config; and not __fish_seen_subcommand_from get set unset list help" -f -a "set" -d 'Change a setting, adding the profile it belongs to if there isn\'t one'
+complete -c karp -n "__fish_karp_using_subcommand config; and not __fish_seen_subcommand_from get set unset list help" -f -a "unset" -d 'Remove a setting, or a whole profile or alias'
+complete -c karp -n "__fish_karp_using_subcommand config; and not __fish_seen_subcommand_from get set unset list help" -f -a "list" -d 'Print every setting, one per line, as `key = value`'
+complete -c karp -n "__fish_karp_using_subcommand config; and not __fish_seen_subcommand_from get set unset list help" -f -a "help" -d 'Print this message or the help of the given subcommand(s)'
+complete -c karp -n "__fish_karp_using_subcommand config; and __fish_seen_subcommand_from get" -s h -l help -d 'Print help'
+complete -c karp -n "__fish_karp_using_subcommand config; and __fish_seen_subcommand_from set" -s h -l help -d 'Print help'
+complete -c karp -n "__fish_karp_using_subcommand config; and __fish_seen_subcommand_from unset" -s h -l help -d 'Print help'
+complete -c karp -n "__fish_karp_using_subcommand config; and __fish_seen_subcommand_from list" -s h -l help -d 'Print help'
+complete -c karp -n "__fish_karp_using_subcommand config; and __fish_seen_subcommand_from help" -f -a "get" -d 'Print the value of a setting. Text is printed as it is, and anything else as TOML'
+complete -c karp -n "__fish_karp_using_subcommand config; and __fish_seen_subcommand_from help" -f -a "set" -d 'Change a setting, adding the profile it belongs to if there isn\'t one'
+complete -c karp -n "__fish_karp_using_subcommand config; and __fish_seen_subcommand_from help" -f -a "unset" -d 'Remove a setting, or a whole profile or alias'
+complete -c karp -n "__fish_karp_using_subcommand config; and __fish_seen_subcommand_from help" -f -a "list" -d 'Print every setting, one per line, as `key = value`'
+complete -c karp -n "__fish_karp_using_subcommand config; and __fish_seen_subcommand_from help" -f -a "help" -d 'Print this message or the help of the given subcommand(s)'
+complete -c karp -n "__fish_karp_using_subcommand generate-passphrase" -l words -d 'The number of words in the passphrase. With the EFF large word list, the default of 6 gives a little over 77 bits of entropy' -r
+complete -c karp -n "__fish_karp_using_subcommand generate-passphrase" -l separator -d 'The text to put between words' -r
+complete -c karp -n "__fish_karp_using_subcommand generate-passphrase" -l wordlist -d 'The word list to draw from, with one word to a line, optionally after the dice rolls that select it. Defaults to `eff_large_wordlist.txt` in the data directory' -r -F
+complete -c karp -n "__fish_karp_using_subcommand generate-passphrase" -s h -l help -d 'Print help'
+complete -c karp -n "__fish_karp_using_subcommand init" -l force -d 'Replace the URL of a profile that already has one'
+complete -c karp -n "__fish_karp_using_subcommand init" -s h -l help -d 'Print help'
+complete -c karp -n "__fish_karp_using_subcommand man" -l out-dir -d 'The directory to write the pages to. It is created if it doesn\'t exist' -r -f -a "(__fish_complete_directories)"
+complete -c karp -n "__fish_karp_using_subcommand man" -s h -l help -d 'Print help'
+complete -c karp -n "__fish_karp_using_subcommand schema" -s h -l help -d 'Print help (see more with \'--help\')'
+complete -c karp -n "__fish_karp_using_subcommand session; and not __fish_seen_subcommand_from migrate export help" -s h -l help -d 'Print help'
+complete -c karp -n "__fish_karp_using_subcommand session; and not __fish_seen_subcommand_from migrate export help" -f -a "migrate" -d 'Move the session data from the storage that holds it to another kind of storage, deleting the old copy. This is useful after installing a keyring on a machine that had fallen back to file storage'
+complete -c karp -n "__fish_karp_using_subcommand session; and not __fish_seen_subcommand_from migrate export help" -f -a "export" -d 'Print the association with KeePassXC, or the identifier KeePassRPC knows us by, as JSON, so another client or machine can reuse it instead of asking for a new one. For KeePassXC, this is an object with `protocol` set to `keepassxc` and an `associations` array, each with the `hash` of the database, the `id` of the association, and the Base64 public identity `key`. For KeePassRPC, it\'s an object with `protocol` set to `keepassrpc` and the `identifier` as a UUID. Anyone with this can use the association, so keep it secret'
+complete -c karp -n "__fish_karp_using_subcommand session; and not __fish_seen_subcommand_from migrate export help" -f -a "help" -d 'Print this message or the help of the given subcommand(s)'
+complete -c karp -n "__fish_karp_using_subcommand session; and __fish_seen_subcommand_from migrate" -l from -d 'The storage to move the session data from. Defaults to the first storage that has session data for the URL' -r -f -a "{file\t'An unencrypted file in the user\'s state directory',keychain\t'The macOS Keychain',secret-service\t'A keyring implementing the freedesktop.org Secret Service API'}"
+complete -c karp -n "__fish_karp_using_subcommand session; and __fish_seen_subcommand_from migrate" -l to -d 'The storage to move the session data to' -r -f -a "{file\t'An unencrypted file in the user\'s state directory',keychain\t'The macOS Keychain',secret-service\t'A keyring implementing the freedesktop.org Secret Service API'}"
+complete -c karp -n "__fish_karp_using_subcommand session; and __fish_seen_subcommand_from migrate" -s h -l help -d 'Print help (see more with \'--help\')'
+complete -c karp -n "__fish_karp_using_subcommand session; and __fish_seen_subcommand_from export" -s h -l help -d 'Print help'
+complete -c karp -n "__fish_karp_using_subcommand session; and __fish_seen_subcommand_from help" -f -a "migrate" -d 'Move the session data from the storage that holds it to another kind of storage, deleting the old copy. This is useful after installing a keyring on a machine that had fallen back to file storage'
+complete -c karp -n "__fish_karp_using_subcommand session; and __fish_seen_subcommand_from help" -f -a "export" -d 'Print the association with KeePassXC, or the identifier KeePassRPC knows us by, as JSON, so another client or machine can reuse it instead of asking for a new one. For KeePassXC, this is an object with `protocol` set to `keepassxc` and an `associations` array, each with the `hash` of the database, the `id` of the association, and the Base64 public identity `key`. For KeePassRPC, it\'s an object with `protocol` set to `keepassrpc` and the `identifier` as a UUID. Anyone with this can use the association, so keep it secret'
+complete -c karp -n "__fish_karp_using_subcommand session; and __fish_seen_subcommand_from help" -f -a "help" -d 'Print this message or the help of the given subcommand(s)'
+complete -c karp -n "__fish_karp_using_subcommand verify-session" -s h -l help -d 'Print help'
+complete -c karp -n "__fish_karp_using_subcommand copy" -l from -d 'The URL to copy the entry from. Defaults to the URL selected globally' -r -f
+complete -c karp -n "__fish_karp_using_subcommand copy" -l to -d 'The URL to copy the entry to' -r -f
+complete -c karp -n "__fish_karp_using_subcommand copy" -l to-group -d 'The group to place the entry in at the destination, with each subgroup separated by a slash. Defaults to the group given in the path of the entry, or the root group if the entry is given by ID' -r
+complete -c karp -n "__fish_karp_using_subcommand copy" -s h -l help -d 'Print help'
+complete -c karp -n "__fish_karp_using_subcommand create-entry" -l username -d 'The username of the entry' -r
+complete -c karp -n "__fish_karp_using_subcommand create-entry" -l entry-url -d 'A URL of the entry. Give this more than once for several URLs' -r -f
+complete -c karp -n "__fish_karp_using_subcommand create-entry" -l field -d 'Another field of the entry, as `NAME=VALUE`. Give this more than once for several fields' -r
+complete -c karp -n "__fish_karp_using_subcommand create-entry" -l generate-password -d 'Generate the password instead of asking for it, either ourselves or with the backend\'s generator, and print it once the entry is created' -r -f -a "{local\t'Our own generator, which works with any backend',server\t'The generator of KeePass or KeePassXC, with its default settings'}"
+complete -c karp -n "__fish_karp_using_subcommand create-entry" -l interactive -d 'Ask for the group, title, username, password, URL, and any other fields of the entry one at a time, completing group names from the database, and show a summary to confirm before creating it'
+complete -c karp -n "__fish_karp_using_subcommand create-entry" -l password-stdin -d 'Read the password from the first line of standard input, instead of asking for it on the terminal'
+complete -c karp -n "__fish_karp_using_subcommand create-entry" -l copy-password -d 'Copy the generated password to the clipboard instead of printing it'
+complete -c karp -n "__fish_karp_using_subcommand create-entry" -s h -l help -d 'Print help (see more with \'--help\')'
+complete -c karp -n "__fish_karp_using_subcommand curl-auth" -l header -d 'Send the credentials in an `Authorization: Basic` header instead of with `--user`, so curl sends them even to hosts it\'s redirected to'
+complete -c karp -n "__fish_karp_using_subcommand curl-auth" -s h -l help -d 'Print help'
+complete -c karp -n "__fish_karp_using_subcommand diff" -l other-url -d 'Look up the second entry using a different URL instead of the one the first entry is retrieved from' -r -f
+complete -c karp -n "__fish_karp_using_subcommand diff" -s h -l help -d 'Print help'
+complete -c karp -n "__fish_karp_using_subcommand edit" -l with-secrets -d 'Write the values of passwords to the file, so they can be changed too. Otherwise they\'re left out, and keep their current value'
+complete -c karp -n "__fish_karp_using_subcommand edit" -s h -l help -d 'Print help'
+complete -c karp -n "__fish_karp_using_subcommand get-form-fields" -s t -l type -d 'Filter the form fields returned to those matching a particular type' -r -f -a "{username\t'',password\t'',text\t'',select\t'',radio\t'',checkbox\t'',totp\t'The current code of an entry\'s time-based one-time password, which KeePassXC generates when it\'s asked for the entry. It is only ever read, never stored'}"
+complete -c karp -n "__fish_karp_using_subcommand get-form-fields" -s i -l index -d 'The numerical index of a particular field to select. Indexing is performed after any filtering is applied. When this option is selected, only the field\'s value is printed. This option can be given more than once to print several values, one per line' -r
+complete -c karp -n "__fish_karp_using_subcommand get-form-fields" -l paths-from -d 'Read the entries to look up from a file, or from standard input if the file is `-`. Each entry is given by its path (`Group/Subgroup/Title`) or ID, separated by NUL characters or, if there are none, by newlines' -r -F
+complete -c karp -n "__fish_karp_using_subcommand get-form-fields" -l entry -d 'The path (`Group/Subgroup/Title`) or ID of an entry to look up. This option can be given more than once. The results are printed as a JSON object keyed by each entry as given here, as with `--output json`' -r -f -a "(__karp_entry_paths)"
+complete -c karp -n "__fish_karp_using_subcommand get-form-fields" -l all-values -d 'Print the value of every field that matches the filter, one per line, instead of a table. With `--entry`, the values of each entry are given as an object keyed by display name; if several fields share a display name, the first is used'
+complete -c karp -n "__fish_karp_using_subcommand get-form-fields" -l no-resolve-placeholders -d 'Print values as they are stored, instead of expanding KeePass placeholders like `{USERNAME}` or `{REF:P@I:...}` in them'
+complete -c karp -n "__fish_karp_using_subcommand get-form-fields" -s h -l help -d 'Print help (see more with \'--help\')'
+complete -c karp -n "__fish_karp_using_subcommand get-totp" -l period -d 'How long each code is valid for, in seconds, which KeePassXC doesn\'t tell us' -r
+complete -c karp -n "__fish_karp_using_subcommand get-totp" -l next-if-under -d 'If the current code expires in less than this many seconds, wait for the next one instead, so there\'s time to enter it' -r
+complete -c karp -n "__fish_karp_using_subcommand get-totp" -l clip -d 'Copy the code to the clipboard instead of printing it, and clear it when the code expires. We wait until then before exiting, and leave the clipboard alone if something else has been copied in the meantime'
+complete -c karp -n "__fish_karp_using_subcommand get-totp" -s h -l help -d 'Print help'
+complete -c karp -n "__fish_karp_using_subcommand monitor" -s h -l help -d 'Print help'
+complete -c karp -n "__fish_karp_using_subcommand netrc" -l map -d 'A machine to give credentials for, as `HOST=ENTRY`, where the entry is given by its path (`Group/Subgroup/Title`) or ID. This option can be given more than once' -r
+complete -c karp -n "__fish_karp_using_subcommand netrc" -s o -l output -d 'Write to this file, readable only by its owner, instead of printing. The file is replaced as a whole' -r -F
+complete -c karp -n "__fish_karp_using_subcommand netrc" -s h -l help -d 'Print help'
+complete -c karp -n "__fish_karp_using_subcommand open-url" -l copy -d 'Copy the value of the first field of the given type to the clipboard before opening the URL' -r -f -a "{username\t'',password\t'',text\t'',select\t'',radio\t'',checkbox\t'',totp\t'The current code of an entry\'s time-based one-time password, which KeePassXC generates when it\'s asked for the entry. It is only ever read, never stored'}"
+complete -c karp -n "__fish_karp_using_subcommand open-url" -l clear-after -d 'How long to leave the copied value on the clipboard before clearing it, in seconds. We wait this long before exiting, and leave the clipboard alone if something else has been copied in the meantime. Use 0 to never clear it' -r
+complete -c karp -n "__fish_karp_using_subcommand open-url" -l no-resolve-placeholders -d 'Use the URL and copied value as they are stored, instead of expanding KeePass placeholders like `{USERNAME}` or `{REF:P@I:...}` in them'
+complete -c karp -n "__fish_karp_using_subcommand open-url" -s h -l help -d 'Print help (see more with \'--help\')'
+complete -c karp -n "__fish_karp_using_subcommand password" -l no-resolve-placeholders -d 'Print the value as it is stored, instead of expanding KeePass placeholders like `{USERNAME}` or `{REF:P@I:...}` in it'
+complete -c karp -n "__fish_karp_using_subcommand password" -s h -l help -d 'Print help'
+complete -c karp -n "__fish_karp_using_subcommand search" -s c -l count -d 'The number of possible entries to return. KeePassRPC and KeePassXC still send every match, so this only shortens the output there, without making the search any cheaper' -r
+complete -c karp -n "__fish_karp_using_subcommand search" -l dedupe -d 'Show entries that are in more than one of the databases searched only once, along with the databases that have a copy' -r -f -a "{by-uuid\t'Entries with the same ID',by-title-url\t'Entries with the same title and URLs'}"
+complete -c karp -n "__fish_karp_using_subcommand search" -l ids -d 'Print only the ID of each entry, one per line, for use with `get-form-fields --paths-from`'
+complete -c karp -n "__fish_karp_using_subcommand search" -s h -l help -d 'Print help (see more with \'--help\')'
+complete -c karp -n "__fish_karp_using_subcommand serve" -l allow-group -d 'Only show the assistant entries in this group or below it. May be given more than once; without it, every entry can be seen. Backends that only give the name of an entry\'s group, like KeePassXC, refuse to start with it' -r -f -a "(__karp_entry_paths)"
+complete -c karp -n "__fish_karp_using_subcommand serve" -l confirm -d 'When to ask, through Pinentry, before giving the assistant the value of a field' -r -f -a "{always\t'Before every value',secrets\t'Before passwords and one-time passwords',never\t'Never'}"
+complete -c karp -n "__fish_karp_using_subcommand serve" -l mcp -d 'Speak the Model Context Protocol, which is the only protocol we serve'
+complete -c karp -n "__fish_karp_using_subcommand serve" -l allow-secrets -d 'Let `get_field` give out passwords and one-time passwords, not just usernames and other fields'
+complete -c karp -n "__fish_karp_using_subcommand serve" -s h -l help -d 'Print help (see more with \'--help\')'
+complete -c karp -n "__fish_karp_using_subcommand shell" -l no-history -d 'Don\'t read or write the history file'
+complete -c karp -n "__fish_karp_using_subcommand shell" -s h -l help -d 'Print help'
+complete -c karp -n "__fish_karp_using_subcommand status" -s h -l help -d 'Print help'
+complete -c karp -n "__fish_karp_using_subcommand systemd-creds" -l directory -d 'The directory to write the credentials to, such as `/etc/credstore` or `/run/credstore`. It\'s created if it doesn\'t exist' -r -f -a "(__fish_complete_directories)"
+complete -c karp -n "__fish_karp_using_subcommand systemd-creds" -l encrypt -d 'Encrypt each credential with `systemd-creds encrypt`, so it can only be read on this machine'
+complete -c karp -n "__fish_karp_using_subcommand systemd-creds" -s h -l help -d 'Print help'
+complete -c karp -n "__fish_karp_using_subcommand totp; and not __fish_seen_subcommand_from verify help" -s h -l help -d 'Print help'
+complete -c karp -n "__fish_karp_using_subcommand totp; and not __fish_seen_subcommand_from verify help" -f -a "verify" -d 'Check a code against the TOTP seed of an entry, accepting the codes just before and after the current one too. We exit with status 0 if the code is valid and 1 if it isn\'t, for enrollment and validation scripts. If the backend only gives us the current code and not the seed, as KeePassXC does, only the current code is accepted'
+complete -c karp -n "__fish_karp_using_subcommand totp; and not __fish_seen_subcommand_from verify help" -f -a "help" -d 'Print this message or the help of the given subcommand(s)'
+complete -c karp -n "__fish_karp_using_subcommand totp; and __fish_seen_subcommand_from verify" -s h -l help -d 'Print help'
+complete -c karp -n "__fish_karp_using_subcommand totp; and __fish_seen_subcommand_from help" -f -a "verify" -d 'Check a code against the TOTP seed of an entry, accepting the codes just before and after the current one too. We exit with status 0 if the code is valid and 1 if it isn\'t, for enrollment and validation scripts. If the backend only gives us the current code and not the seed, as KeePassXC does, only the current code is accepted'
+complete -c karp -n "__fish_karp_using_subcommand totp; and __fish_seen_subcommand_from help" -f -a "help" -d 'Print this message or the help of the given subcommand(s)'
+complete -c karp -n "__fish_karp_using_subcommand type" -l delay -d 'How long to wait before typing, in seconds, to give time to switch to the window to type into' -r
+complete -c karp -n "__fish_karp_using_subcommand type" -s h -l help -d 'Print help'
+complete -c karp -n "__fish_karp_using_subcommand update-entry" -l username -d 'The new username of the entry' -r
+complete -c karp -n "__fish_karp_using_subcommand update-entry" -l entry-url -d 'Replace the URLs of the entry. Give this more than once for several URLs' -r -f
+complete -c karp -n "__fish_karp_using_subcommand update-entry" -l field -d 'Set the field with the given name, as `NAME=VALUE`, adding it if the entry doesn\'t have one. Give this more than once for several fields' -r
+complete -c karp -n "__fish_karp_using_subcommand update-entry" -l generate-password -d 'Generate a new password, either ourselves or with the backend\'s generator, and print it once the entry is updated' -r -f -a "{local\t'Our own generator, which works with any backend',server\t'The generator of KeePass or KeePassXC, with its default settings'}"
+complete -c karp -n "__fish_karp_using_subcommand update-entry" -l password-stdin -d 'Read the new password from the first line of standard input'
+complete -c karp -n "__fish_karp_using_subcommand update-entry" -l copy-password -d 'Copy the generated password to the clipboard instead of printing it'
+complete -c karp -n "__fish_karp_using_subcommand update-entry" -s h -l help -d 'Print help (see more with \'--help\')'
+complete -c karp -n "__fish_karp_using_subcommand username" -l no-resolve-placeholders -d 'Print the value as it is stored, instead of expanding KeePass placeholders like `{USERNAME}` or `{REF:P@I:...}` in it'
+complete -c karp -n "__fish_karp_using_subcommand username" -s h -l help -d 'Print help'
+complete -c karp -n "__fish_karp_using_subcommand help; and not __fish_seen_subcommand_from alias benchmark cleanup completions config generate-passphrase init man schema session verify-session copy create-entry curl-auth diff edit get-form-fields get-totp monitor netrc open-url password search serve shell status systemd-creds totp type update-entry username help" -f -a "alias" -d 'Manage saved lookups. An alias can be invoked in place of a command, as in `karp <alias>`, and expands to a `get-form-fields` invocation. Any further arguments are passed along to `get-form-fields`'
+complete -c karp -n "__fish_karp_using_subcommand help; and not __fish_seen_subcommand_from alias benchmark cleanup completions config generate-passphrase init man schema session verify-session copy create-entry curl-auth diff edit get-form-fields get-totp monitor netrc open-url password search serve shell status systemd-creds totp type update-entry username help" -f -a "benchmark" -d 'Measure how long it takes to talk to the selected backend. We time opening a connection, the first request (which includes the handshake with KeePassRPC or KeePassXC), and then a number of further requests over the same connection'
+complete -c karp -n "__fish_karp_using_subcommand help; and not __fish_seen_subcommand_from alias benchmark cleanup completions config generate-passphrase init man schema session verify-session copy create-entry curl-auth diff edit get-form-fields get-totp monitor netrc open-url password search serve shell status systemd-creds totp type update-entry username help" -f -a "cleanup" -d 'Remove everything we\'ve kept on this machine: the session data cached for every URL in the configuration file in each kind of storage, snapshots, shell history, and our state and cache directories once they\'re empty. KeePassXC and KeePassRPC keep their side of the association, which only they can remove, so we say where to find it'
+complete -c karp -n "__fish_karp_using_subcommand help; and not __fish_seen_subcommand_from alias benchmark cleanup completions config generate-passphrase init man schema session verify-session copy create-entry curl-auth diff edit get-form-fields get-totp monitor netrc open-url password search serve shell status systemd-creds totp type update-entry username help" -f -a "completions" -d 'Write completion scripts for Zsh and Fish. Besides commands and options, they complete group and entry names by asking the connected database'
+complete -c karp -n "__fish_karp_using_subcommand help; and not __fish_seen_subcommand_from alias benchmark cleanup completions config generate-passphrase init man schema session verify-session copy create-entry curl-auth diff edit get-form-fields get-totp monitor netrc open-url password search serve shell status systemd-creds totp type update-entry username help" -f -a "config" -d 'Read and change the configuration file. Settings are named by their path in the file, with each table separated by a dot, such as `default_profile` or `profiles.work.url`. Changes are checked against the settings we know before they\'re saved, and only the setting changed is written, keeping comments and formatting. Settings we don\'t know elsewhere in the file don\'t get in the way, so they can be removed here'
+complete -c karp -n "__fish_karp_using_subcommand help; and not __fish_seen_subcommand_from alias benchmark cleanup completions config generate-passphrase init man schema session verify-session copy create-entry curl-auth diff edit get-form-fields get-totp monitor netrc open-url password search serve shell status systemd-creds totp type update-entry username help" -f -a "generate-passphrase" -d 'Generate a passphrase of random words without asking a backend, for backends that can\'t generate one or when a passphrase is easier to type than a password. Words are drawn from a diceware word list, like the EFF large word list, which can be saved as `eff_large_wordlist.txt` in the data directory'
+complete -c karp -n "__fish_karp_using_subcommand help; and not __fish_seen_subcommand_from alias benchmark cleanup completions config generate-passphrase init man schema session verify-session copy create-entry curl-auth diff edit get-form-fields get-totp monitor netrc open-url password search serve shell status systemd-creds totp type update-entry username help" -f -a "init" -d 'Set up Karp for the first time. We look for a running KeePassXC or KeePassRPC, connect to it so it can ask you to allow Karp, check that the session is kept so you aren\'t asked again, and save the connection as the URL of the selected profile (or `default`) in the configuration file'
+complete -c karp -n "__fish_karp_using_subcommand help; and not __fish_seen_subcommand_from alias benchmark cleanup completions config generate-passphrase init man schema session verify-session copy create-entry curl-auth diff edit get-form-fields get-totp monitor netrc open-url password search serve shell status systemd-creds totp type update-entry username help" -f -a "man" -d 'Write manual pages for karp and each of its commands'
+complete -c karp -n "__fish_karp_using_subcommand help; and not __fish_seen_subcommand_from alias benchmark cleanup completions config generate-passphrase init man schema session verify-session copy create-entry curl-auth diff edit get-form-fields get-totp monitor netrc open-url password search serve shell status systemd-creds totp type update-entry username help" -f -a "schema" -d 'Print the JSON Schema of what we print with `--output json`, so programs that read it can check it or generate code for it. Fields may be added to these documents, but not removed or changed'
+complete -c karp -n "__fish_karp_using_subcommand help; and not __fish_seen_subcommand_from alias benchmark cleanup completions config generate-passphrase init man schema session verify-session copy create-entry curl-auth diff edit get-form-fields get-totp monitor netrc open-url password search serve shell status systemd-creds totp type update-entry username help" -f -a "session" -d 'Manage the session data cached for the selected URL'
+complete -c karp -n "__fish_karp_using_subcommand help; and not __fish_seen_subcommand_from alias benchmark cleanup completions config generate-passphrase init man schema session verify-session copy create-entry curl-auth diff edit get-form-fields get-totp monitor netrc open-url password search serve shell status systemd-creds totp type update-entry username help" -f -a "verify-session" -d 'Check that the cached session with the selected backend still works, without asking for a password or approval and without reading any entries. We exit with status 0 if it does and 1 if it doesn\'t, so a batch job can find out before it starts'
+complete -c karp -n "__fish_karp_using_subcommand help; and not __fish_seen_subcommand_from alias benchmark cleanup completions config generate-passphrase init man schema session verify-session copy create-entry curl-auth diff edit get-form-fields get-totp monitor netrc open-url password search serve shell status systemd-creds totp type update-entry username help" -f -a "copy" -d 'Copy an entry to another backend. If an entry with the same title already exists in the destination group, it is updated instead'
+complete -c karp -n "__fish_karp_using_subcommand help; and not __fish_seen_subcommand_from alias benchmark cleanup completions config generate-passphrase init man schema session verify-session copy create-entry curl-auth diff edit get-form-fields get-totp monitor netrc open-url password search serve shell status systemd-creds totp type update-entry username help" -f -a "create-entry" -d 'Create an entry. Either give its path and contents as arguments, or use `--interactive` to be asked for each part of it in turn'
+complete -c karp -n "__fish_karp_using_subcommand help; and not __fish_seen_subcommand_from alias benchmark cleanup completions config generate-passphrase init man schema session verify-session copy create-entry curl-auth diff edit get-form-fields get-totp monitor netrc open-url password search serve shell status systemd-creds totp type update-entry username help" -f -a "curl-auth" -d 'Print the username and password of an entry as curl configuration, to be read with `curl --config -`, such as `karp curl-auth Work/API | curl --config - https://api.example.com/`. The password never appears in the command line or the shell history'
+complete -c karp -n "__fish_karp_using_subcommand help; and not __fish_seen_subcommand_from alias benchmark cleanup completions config generate-passphrase init man schema session verify-session copy create-entry curl-auth diff edit get-form-fields get-totp monitor netrc open-url password search serve shell status systemd-creds totp type update-entry username help" -f -a "diff" -d 'Compare the fields of two entries. Secret values are compared, but never displayed'
+complete -c karp -n "__fish_karp_using_subcommand help; and not __fish_seen_subcommand_from alias benchmark cleanup completions config generate-passphrase init man schema session verify-session copy create-entry curl-auth diff edit get-form-fields get-totp monitor netrc open-url password search serve shell status systemd-creds totp type update-entry username help" -f -a "edit" -d 'Change an entry in a text editor. The title, URLs, and form fields of the entry are written to a temporary TOML file, which is opened with `VISUAL` or `EDITOR` (or `vi`). Once the editor exits, the changes are shown and saved to the entry, and the file is overwritten and removed'
+complete -c karp -n "__fish_karp_using_subcommand help; and not __fish_seen_subcommand_from alias benchmark cleanup completions config generate-passphrase init man schema session verify-session copy create-entry curl-auth diff edit get-form-fields get-totp monitor netrc open-url password search serve shell status systemd-creds totp type update-entry username help" -f -a "get-form-fields" -d 'Get the form fields of an entry at a given path'
+complete -c karp -n "__fish_karp_using_subcommand help; and not __fish_seen_subcommand_from alias benchmark cleanup completions config generate-passphrase init man schema session verify-session copy create-entry curl-auth diff edit get-form-fields get-totp monitor netrc open-url password search serve shell status systemd-creds totp type update-entry username help" -f -a "get-totp" -d 'Print the current time-based one-time password of an entry. Only KeePassXC generates these codes for us'
+complete -c karp -n "__fish_karp_using_subcommand help; and not __fish_seen_subcommand_from alias benchmark cleanup completions config generate-passphrase init man schema session verify-session copy create-entry curl-auth diff edit get-form-fields get-totp monitor netrc open-url password search serve shell status systemd-creds totp type update-entry username help" -f -a "monitor" -d 'Stay connected and print a line of JSON whenever the database is locked or unlocked, such as `{"event":"database-locked","time":1718000000}`, so a script can react to it. KeePassXC and KeePassRPC tell us about these'
+complete -c karp -n "__fish_karp_using_subcommand help; and not __fish_seen_subcommand_from alias benchmark cleanup completions config generate-passphrase init man schema session verify-session copy create-entry curl-auth diff edit get-form-fields get-totp monitor netrc open-url password search serve shell status systemd-creds totp type update-entry username help" -f -a "netrc" -d 'Print a `.netrc` file with the usernames and passwords of entries, for tools like curl, ftp, and Mercurial that read credentials from one'
+complete -c karp -n "__fish_karp_using_subcommand help; and not __fish_seen_subcommand_from alias benchmark cleanup completions config generate-passphrase init man schema session verify-session copy create-entry curl-auth diff edit get-form-fields get-totp monitor netrc open-url password search serve shell status systemd-creds totp type update-entry username help" -f -a "open-url" -d 'Open the first URL of an entry in the default browser, like KeePass does when asked to open an entry\'s URL. A field of the entry, such as its password, can be copied to the clipboard first, ready to paste into the page'
+complete -c karp -n "__fish_karp_using_subcommand help; and not __fish_seen_subcommand_from alias benchmark cleanup completions config generate-passphrase init man schema session verify-session copy create-entry curl-auth diff edit get-form-fields get-totp monitor netrc open-url password search serve shell status systemd-creds totp type update-entry username help" -f -a "password" -d 'Print the password of an entry'
+complete -c karp -n "__fish_karp_using_subcommand help; and not __fish_seen_subcommand_from alias benchmark cleanup completions config generate-passphrase init man schema session verify-session copy create-entry curl-auth diff edit get-form-fields get-totp monitor netrc open-url password search serve shell status systemd-creds totp type update-entry username help" -f -a "search" -d 'Free-text search for a given entry'
+complete -c karp -n "__fish_karp_using_subcommand help; and not __fish_seen_subcommand_from alias benchmark cleanup completions config generate-passphrase init man schema session verify-session copy create-entry curl-auth diff edit get-form-fields get-totp monitor netrc open-url password search serve shell status systemd-creds totp type update-entry username help" -f -a "serve" -d 'Serve tools for local AI assistants over the Model Context Protocol on standard input and output: `search_entries`, which finds entries without revealing any of their fields, and `get_field`, which gives the value of one field of an entry. Passwords, and fields with placeholders that could expand to them, are only given out with `--allow-secrets`'
+complete -c karp -n "__fish_karp_using_subcommand help; and not __fish_seen_subcommand_from alias benchmark cleanup completions config generate-passphrase init man schema session verify-session copy create-entry curl-auth diff edit get-form-fields get-totp monitor netrc open-url password search serve shell status systemd-creds totp type update-entry username help" -f -a "shell" -d 'Run commands one after another over a single connection. Lines are split into arguments the way a POSIX shell would split them. Lines that begin with a space are not recorded in the history. Enter `exit` or press Ctrl-D to leave the shell'
+complete -c karp -n "__fish_karp_using_subcommand help; and not __fish_seen_subcommand_from alias benchmark cleanup completions config generate-passphrase init man schema session verify-session copy create-entry curl-auth diff edit get-form-fields get-totp monitor netrc open-url password search serve shell status systemd-creds totp type update-entry username help" -f -a "status" -d 'Show the databases the backend has open and whether each is active. The selector of a database is the value to give to `--database` to use it. Locked databases are not shown: KeePassRPC leaves them out, and KeePassXC waits for the active database to be unlocked before answering. Settings the backend reports follow, such as the ones KeePassRPC gives browser extensions, which help explain why a URL search finds nothing. Any protocol features the backend reported when we connected come last'
+complete -c karp -n "__fish_karp_using_subcommand help; and not __fish_seen_subcommand_from alias benchmark cleanup completions config generate-passphrase init man schema session verify-session copy create-entry curl-auth diff edit get-form-fields get-totp monitor netrc open-url password search serve shell status systemd-creds totp type update-entry username help" -f -a "systemd-creds" -d 'Write fields of entries to files in a directory, one credential per file, for services to load with `LoadCredential=` (or, with `--encrypt`, `LoadCredentialEncrypted=`). Each file is readable only by its owner, and is replaced as a whole so a service never reads half of one'
+complete -c karp -n "__fish_karp_using_subcommand help; and not __fish_seen_subcommand_from alias benchmark cleanup completions config generate-passphrase init man schema session verify-session copy create-entry curl-auth diff edit get-form-fields get-totp monitor netrc open-url password search serve shell status systemd-creds totp type update-entry username help" -f -a "totp" -d 'Work with the time-based one-time passwords of entries'
+complete -c karp -n "__fish_karp_using_subcommand help; and not __fish_seen_subcommand_from alias benchmark cleanup completions config generate-passphrase init man schema session verify-session copy create-entry curl-auth diff edit get-form-fields get-totp monitor netrc open-url password search serve shell status systemd-creds totp type update-entry username help" -f -a "type" -d 'Type the username and password of an entry into the active window, like KeePass auto-type, using wtype, xdotool, or ydotool. This works with any backend, not just KeePassXC'
+complete -c karp -n "__fish_karp_using_subcommand help; and not __fish_seen_subcommand_from alias benchmark cleanup completions config generate-passphrase init man schema session verify-session copy create-entry curl-auth diff edit get-form-fields get-totp monitor netrc open-url password search serve shell status systemd-creds totp type update-entry username help" -f -a "update-entry" -d 'Change parts of an entry without opening an editor. With `--generate-password`, a new password is generated and saved to the entry in the same update, then shown once, for rotating a credential'
+complete -c karp -n "__fish_karp_using_subcommand help; and not __fish_seen_subcommand_from alias benchmark cleanup completions config generate-passphrase init man schema session verify-session copy create-entry curl-auth diff edit get-form-fields get-totp monitor netrc open-url password search serve shell status systemd-creds totp type update-entry username help" -f -a "username" -d 'Print the username of an entry'
+complete -c karp -n "__fish_karp_using_subcommand help; and not __fish_seen_subcommand_from alias benchmark cleanup completions config generate-passphrase init man schema session verify-session copy create-entry curl-auth diff edit get-form-fields get-totp monitor netrc open-url password search serve shell status systemd-creds totp type update-entry username help" -f -a "help" -d 'Print this message or the help of the given subcommand(s)'
+complete -c karp -n "__fish_karp_using_subcommand help; and __fish_seen_subcommand_from alias" -f -a "add" -d 'Save a lookup of an entry under a new name, replacing any existing alias with the same name'
+complete -c karp -n "__fish_karp_using_subcommand help; and __fish_seen_subcommand_from alias" -f -a "remove" -d 'Remove a saved lookup'
+complete -c karp -n "__fish_karp_using_subcommand help; and __fish_seen_subcommand_from alias" -f -a "list" -d 'List saved lookups'
+complete -c karp -n "__fish_karp_using_subcommand help; and __fish_seen_subcommand_from config" -f -a "get" -d 'Print the value of a setting. Text is printed as it is, and anything else as TOML'
+complete -c karp -n "__fish_karp_using_subcommand help; and __fish_seen_subcommand_from config" -f -a "set" -d 'Change a setting, adding the profile it belongs to if there isn\'t one'
+complete -c karp -n "__fish_karp_using_subcommand help; and __fish_seen_subcommand_from config" -f -a "unset" -d 'Remove a setting, or a whole profile or alias'
+complete -c karp -n "__fish_karp_using_subcommand help; and __fish_seen_subcommand_from config" -f -a "list" -d 'Print every setting, one per line, as `key = value`'
+complete -c karp -n "__fish_karp_using_subcommand help; and __fish_seen_subcommand_from session" -f -a "migrate" -d 'Move the session data from the storage that holds it to another kind of storage, deleting the old copy. This is useful after installing a keyring on a machine that had fallen back to file storage'
+complete -c karp -n "__fish_karp_using_subcommand help; and __fish_seen_subcommand_from session" -f -a "export" -d 'Print the association with KeePassXC, or the identifier KeePassRPC knows us by, as JSON, so another client or machine can reuse it instead of asking for a new one. For KeePassXC, this is an object with `protocol` set to `keepassxc` and an `associations` array, each with the `hash` of the database, the `id` of the association, and the Base64 public identity `key`. For KeePassRPC, it\'s an object with `protocol` set to `keepassrpc` and the `identifier` as a UUID. Anyone with this can use the association, so keep it secret'
+complete -c karp -n "__fish_karp_using_subcommand help; and __fish_seen_subcommand_from totp" -f -a "verify" -d 'Check a code against the TOTP seed of an entry, accepting the codes just before and after the current one too. We exit with status 0 if the code is valid and 1 if it isn\'t, for enrollment and validation scripts. If the backend only gives us the current code and not the seed, as KeePassXC does, only the current code is accepted'
+complete -c karp -n "__fish_karp_using_subcommand copy" -f -a "(__karp_entry_paths)"
+complete -c karp -n "__fish_karp_using_subcommand create-entry" -f -a "(__karp_entry_paths)"
+complete -c karp -n "__fish_karp_using_subcommand curl-auth" -f -a "(__karp_entry_paths)"
+complete -c karp -n "__fish_karp_using_subcommand diff" -f -a "(__karp_entry_paths)"
+complete -c karp -n "__fish_karp_using_subcommand edit" -f -a "(__karp_entry_paths)"
+complete -c karp -n "__fish_karp_using_subcommand get-form-fields" -f -a "(__karp_names)"
+complete -c karp -n "__fish_karp_using_subcommand get-totp" -f -a "(__karp_entry_paths)"
+complete -c karp -n "__fish_karp_using_subcommand open-url" -f -a "(__karp_entry_paths)"
+complete -c karp -n "__fish_karp_using_subcommand password" -f -a "(__karp_names)"
+complete -c karp -n "__fish_karp_using_subcommand totp; and __fish_seen_subcommand_from verify" -f -a "(__karp_entry_paths)"
+complete -c karp -n "__fish_karp_using_subcommand type" -f -a "(__karp_entry_paths)"
+complete -c karp -n "__fish_karp_using_subcommand update-entry" -f -a "(__karp_entry_paths)"
+complete -c karp -n "__fish_karp_using_subcommand username" -f -a "(__karp_names)"
@@ -0,0 +1,137 @@
+# SPDX-FileCopyrightText: 2024 Noah Fontes
+#
+# SPDX-License-Identifier: Apache-2.0
+
+error-encountered = Es ist ein Fehler aufgetreten: { $error }
+
+prompt-title = Passwort - { $client }
+prompt-label = Passwort
+prompt-label-terminal = Passwort:{" "}
+prompt-error-terminal = Fehler: { $error }
+prompt-required = Sie müssen ein Passwort eingeben, um fortzufahren.
+prompt-required-keepassrpc = Sie müssen das von KeePassRPC angezeigte Passwort eingeben, um fortzufahren.
+prompt-incorrect-password = Falsches Passwort.
+prompt-kdbx-description = Geben Sie das Master-Passwort der Datenbank ein.
+prompt-kdbx-incorrect = Das Passwort oder die Schlüsseldatei ist falsch.
+
+status-no-databases = Es sind keine Datenbanken geöffnet
+status-settings = Einstellungen:
+status-features = Funktionen:
+
+progress-connecting = Verbindung wird hergestellt…
+progress-authenticating = Anmeldung läuft…
+progress-waiting-for-approval = Warten auf Ihre Freigabe dieser Verbindung in KeePass…
+progress-fetching = Einträge werden abgerufen…
+
+timings-connecting = Verbindungsaufbau
+timings-authenticating = Anmeldung und Handshake
+timings-waiting-for-approval = Warten auf Freigabe
+timings-fetching = Anfrage
+timings-call = Anfrage { $method }
+timings-other = Karp, einschließlich Ausgabe
+timings-total = Gesamt
+
+benchmark-connect = Verbindungsaufbau
+benchmark-first-request = Erste Anfrage, mit Handshake
+benchmark-min = Schnellste Anfrage
+benchmark-p50 = Median der Anfragen
+benchmark-p90 = 90. Perzentil der Anfragen
+benchmark-p99 = 99. Perzentil der Anfragen
+benchmark-max = Langsamste Anfrage
+
+init-found = { $backend } wurde gefunden.
+init-choose = Welches soll Karp verwenden? [1]{" "}
+init-connected = Verbunden; { $databases } geöffnete Datenbanken wurden gefunden.
+init-stored = Die Sitzung wird im Speicher { $storage } aufbewahrt, Sie werden also nicht erneut gefragt.
+init-stored-unusable = Die Sitzung wird im Speicher { $storage } aufbewahrt, aber damit konnte keine Verbindung hergestellt werden: { $error }
+init-not-stored = Die Sitzung wird nur im Arbeitsspeicher aufbewahrt, Sie werden also beim nächsten Mal erneut gefragt. Setzen Sie allow_insecure_storage im Profil, um sie in einer Datei aufzubewahren.
+init-saved = Die Verbindung wurde im Profil { $profile } gespeichert.
+
+cleanup-confirm = Die zwischengespeicherten Sitzungen, Schnappschüsse und alles andere, was Karp auf diesem Rechner aufbewahrt, entfernen? [j/N]{" "}
+cleanup-confirm-yes = j
+cleanup-session = Die Sitzung für { $url } wurde aus dem Speicher { $storage } entfernt.
+cleanup-removed = { $path } wurde entfernt.
+cleanup-keepassxc = KeePassXC kennt den Schlüssel dieses Rechners weiterhin; entfernen Sie ihn unter Datenbank → Datenbank-Einstellungen → Browser-Integration.
+cleanup-keepassrpc = KeePassRPC kennt diesen Client weiterhin; entfernen Sie ihn unter Extras → KeePassRPC (Kee) Optionen → Autorisierte Clients.
+
+verify-session-valid = Die Sitzung ist gültig.
+
+snapshot-name = Schnappschuss von vor { $age }
+snapshot-answering = Wir konnten keine Verbindung zu { $url } herstellen ({ $error }) und antworten deshalb aus einem Schnappschuss von vor { $age }. Bedenken Sie, dass er veraltet sein kann.
+age-second = 1 Sekunde
+age-seconds = { $count } Sekunden
+age-minute = 1 Minute
+age-minutes = { $count } Minuten
+age-hour = 1 Stunde
+age-hours = { $count } Stunden
+age-day = 1 Tag
+age-days = { $count } Tagen
+open-url-copied = Das Feld { $field } wurde in die Zwischenablage kopiert.
+open-url-clearing = Das Feld { $field } wurde in die Zwischenablage kopiert. Sie wird in { $seconds } Sekunden geleert.
+
+serve-confirm-title = Karp
+serve-confirm = Soll der Assistent das Feld { $field } von { $entry } lesen dürfen?
+totp-valid = Der Code ist gültig.
+totp-clearing = Der Code wurde in die Zwischenablage kopiert. Sie wird geleert, wenn er in { $seconds } Sekunden abläuft.
+type-waiting = In { $seconds } Sekunden wird in das aktive Fenster getippt.
+systemd-creds-written = Die Anmeldeinformation { $name } wurde nach { $path } geschrieben.
+reveal-hiding = Dies wird in { $seconds } Sekunden gelöscht, oder wenn Sie eine Taste drücken.
+
+notify-copied = Das Feld { $field } von { $entry } wurde in die Zwischenablage kopiert.
+notify-copied-clearing = Das Feld { $field } von { $entry } wurde in die Zwischenablage kopiert. Sie wird in { $seconds } Sekunden geleert.
+notify-cleared = Das Feld { $field } von { $entry } wurde aus der Zwischenablage entfernt.
+
+diff-equal = gleich
+diff-different = verschieden
+diff-missing-from-first = fehlt im ersten
+diff-missing-from-second = fehlt im zweiten
+
+edit-instructions = Ändern Sie den Eintrag und speichern Sie die Datei, um die Änderungen zu übernehmen. Felder ohne Wert behalten ihren bisherigen.
+edit-unchanged = Der Eintrag wurde nicht geändert.
+edit-saved = Die Änderungen am Eintrag wurden gespeichert.
+
+associate-denied = KeePassXC hat Karp die Verbindung nicht erlaubt. Wenn es erneut fragt, geben Sie einen Namen für die Verbindung ein und klicken Sie auf „Speichern und Zugriff erlauben“.
+associate-retry = KeePassXC erneut fragen? [J/n]{" "}
+associate-retry-yes = j
+
+create-group = Gruppe:{" "}
+create-title = Titel:{" "}
+create-username = Benutzername:{" "}
+create-password = Passwort (leer lassen, um eines zu erzeugen):{" "}
+create-password-terminal = Passwort (leer lassen für keines):{" "}
+create-password-generated = Ein Passwort wurde erzeugt.
+create-url = URL:{" "}
+create-field-name = Name eines weiteren Feldes (leer lassen zum Beenden):{" "}
+create-field-value = Wert:{" "}
+create-confirm = Diesen Eintrag anlegen? [j/N]{" "}
+create-confirm-yes = j
+create-created = Der Eintrag wurde angelegt.
+
+generated-password-copied = Das erzeugte Passwort wurde in die Zwischenablage kopiert.
+
+header-active = Aktiv
+header-after = Nachher
+header-arguments = Argumente
+header-association = Verknüpfung
+header-before = Vorher
+header-databases = Datenbanken
+header-display-name = Anzeigename
+header-field = Feld
+header-first = Erster
+header-group = Gruppe
+header-id = ID
+header-index = Index
+header-measurement = Messung
+header-name = Name
+header-page = Seite
+header-path = Pfad
+header-placeholders = Platzhalter
+header-second = Zweiter
+header-selector = Auswahl
+header-setting = Einstellung
+header-source = Quelle
+header-status = Status
+header-time = Zeit
+header-title = Titel
+header-type = Typ
+header-value = Wert
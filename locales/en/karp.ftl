@@ -0,0 +1,142 @@
+# SPDX-FileCopyrightText: 2024 Noah Fontes
+#
+# SPDX-License-Identifier: Apache-2.0
+#
+# Messages shown to users of karp. Each message is a line of the form
+# `id = text`, where `{ $name }` is replaced by the named argument. Other
+# catalogs fall back to this one for any message they leave out.
+
+error-encountered = We encountered an error: { $error }
+
+prompt-title = Password - { $client }
+prompt-label = Password
+prompt-label-terminal = Password:{" "}
+prompt-error-terminal = Error: { $error }
+prompt-required = You must enter a password to continue.
+prompt-required-keepassrpc = You must enter the password presented by KeePassRPC to continue.
+prompt-incorrect-password = Incorrect password.
+prompt-kdbx-description = Enter the master password of the database.
+prompt-kdbx-incorrect = The password or key file is incorrect.
+
+status-no-databases = No databases are open
+status-settings = Settings:
+status-features = Features:
+
+progress-connecting = Connecting…
+progress-authenticating = Authenticating…
+progress-waiting-for-approval = Waiting for you to approve this connection in KeePass…
+progress-fetching = Fetching entries…
+
+timings-connecting = Connect
+timings-authenticating = Authentication and handshake
+timings-waiting-for-approval = Waiting for approval
+timings-fetching = Request
+timings-call = Request { $method }
+timings-other = Karp, including output
+timings-total = Total
+
+benchmark-connect = Connect
+benchmark-first-request = First request, with handshake
+benchmark-min = Fastest request
+benchmark-p50 = Median request
+benchmark-p90 = 90th percentile request
+benchmark-p99 = 99th percentile request
+benchmark-max = Slowest request
+
+init-found = Found { $backend }.
+init-choose = Which one should Karp use? [1]{" "}
+init-connected = Connected, and found { $databases } open databases.
+init-stored = The session is kept in { $storage } storage, so you won't be asked again.
+init-stored-unusable = The session is kept in { $storage } storage, but we couldn't connect with it: { $error }
+init-not-stored = The session is only kept in memory, so you'll be asked again next time. Set allow_insecure_storage in the profile to keep it in a file.
+init-saved = Saved the connection in the profile { $profile }.
+
+cleanup-confirm = Remove the cached sessions, snapshots, and everything else Karp keeps on this machine? [y/N]{" "}
+cleanup-confirm-yes = y
+cleanup-session = Removed the session for { $url } from { $storage } storage.
+cleanup-removed = Removed { $path }.
+cleanup-keepassxc = KeePassXC still knows this machine's key; remove it under Database → Database Settings → Browser Integration.
+cleanup-keepassrpc = KeePassRPC still knows this client; remove it under Tools → KeePassRPC (Kee) Options → Authorised clients.
+
+verify-session-valid = The session is valid.
+
+snapshot-name = Snapshot from { $age } ago
+snapshot-answering = We couldn't connect to { $url } ({ $error }), so we're answering from a snapshot taken { $age } ago that may be out of date.
+age-second = 1 second
+age-seconds = { $count } seconds
+age-minute = 1 minute
+age-minutes = { $count } minutes
+age-hour = 1 hour
+age-hours = { $count } hours
+age-day = 1 day
+age-days = { $count } days
+
+open-url-copied = Copied the { $field } field to the clipboard.
+open-url-clearing = Copied the { $field } field to the clipboard. It will be cleared in { $seconds } seconds.
+
+serve-confirm-title = Karp
+serve-confirm = Allow the assistant to read the { $field } field of { $entry }?
+totp-valid = The code is valid.
+totp-clearing = Copied the code to the clipboard. It will be cleared when it expires in { $seconds } seconds.
+type-waiting = Typing into the active window in { $seconds } seconds.
+systemd-creds-written = Wrote the credential { $name } to { $path }.
+reveal-hiding = This will be erased in { $seconds } seconds, or when you press a key.
+
+notify-copied = The { $field } field of { $entry } was copied to the clipboard.
+notify-copied-clearing = The { $field } field of { $entry } was copied to the clipboard. It will be cleared in { $seconds } seconds.
+notify-cleared = The { $field } field of { $entry } was cleared from the clipboard.
+
+diff-equal = equal
+diff-different = different
+diff-missing-from-first = missing from first
+diff-missing-from-second = missing from second
+
+edit-instructions = Change the entry and save the file to apply. Fields without a value keep the one they have.
+edit-unchanged = The entry was not changed.
+edit-saved = Saved the changes to the entry.
+
+associate-denied = KeePassXC did not allow Karp to connect. When it asks again, enter a name for the connection and click "Save and allow access".
+associate-retry = Ask KeePassXC again? [Y/n]{" "}
+associate-retry-yes = y
+
+create-group = Group:{" "}
+create-title = Title:{" "}
+create-username = Username:{" "}
+create-password = Password (leave empty to generate one):{" "}
+create-password-terminal = Password (leave empty for none):{" "}
+create-password-generated = Generated a password.
+create-url = URL:{" "}
+create-field-name = Name of another field (leave empty to finish):{" "}
+create-field-value = Value:{" "}
+create-confirm = Create this entry? [y/N]{" "}
+create-confirm-yes = y
+create-created = Created the entry.
+
+generated-password-copied = Copied the generated password to the clipboard.
+
+header-active = Active
+header-after = After
+header-arguments = Arguments
+header-association = Association
+header-before = Before
+header-databases = Databases
+header-display-name = Display Name
+header-field = Field
+header-first = First
+header-group = Group
+header-id = ID
+header-index = Index
+header-measurement = Measurement
+header-name = Name
+header-page = Page
+header-path = Path
+header-placeholders = Placeholders
+header-second = Second
+header-selector = Selector
+header-setting = Setting
+header-source = Source
+header-status = Status
+header-time = Time
+header-title = Title
+header-type = Type
+header-value = Value
@@ -0,0 +1,269 @@
+// SPDX-FileCopyrightText: 2024 Noah Fontes
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! A client that spreads requests over several backends at once, for profiles
+//! that configure more than one.
+
+use std::num::NonZeroUsize;
+
+use async_trait::async_trait;
+use futures_util::{
+    future,
+    stream::{self, BoxStream},
+    StreamExt as _,
+};
+use secrecy::SecretString;
+use uuid::Uuid;
+
+use crate::{
+    client,
+    error::{Error, Result},
+};
+
+/// A backend taking part, along with the name it's configured under, which
+/// we show as the source of the entries it finds.
+struct Member<'channel> {
+    name: String,
+    client: &'channel (dyn client::Client + Send + Sync),
+}
+
+pub(crate) struct Client<'channel> {
+    members: Vec<Member<'channel>>,
+}
+
+impl<'channel> Client<'channel> {
+    /// Combine the given clients, named by their backends. Where more than one
+    /// backend has an entry, the first of them wins.
+    pub(crate) fn new(
+        members: impl IntoIterator<Item = (String, &'channel (dyn client::Client + Send + Sync))>,
+    ) -> Self {
+        Self {
+            members: members
+                .into_iter()
+                .map(|(name, client)| Member { name, client })
+                .collect(),
+        }
+    }
+
+    /// Mark an entry as coming from the member with the given name.
+    fn tag(name: &str, mut entry: client::Entry) -> client::Entry {
+        entry.source = Some(name.to_owned());
+        entry
+    }
+
+    /// Take the first entry any member found, or, if none had it, the first
+    /// error that isn't just the entry missing from that member.
+    fn first_found(&self, results: Vec<Result<client::Entry>>) -> Result<client::Entry> {
+        let mut missing = None;
+        let mut failed = None;
+        for (member, result) in self.members.iter().zip(results) {
+            match result {
+                Ok(entry) => return Ok(Self::tag(&member.name, entry)),
+                Err(e) if is_not_found(&e) => {
+                    missing = missing.or(Some(e));
+                }
+                Err(e) => {
+                    failed = failed.or(Some(e));
+                }
+            }
+        }
+        Err(failed.or(missing).unwrap_or(Error::NoBackends))
+    }
+
+    /// The member that has the entry with the given ID.
+    async fn owner(&self, id: Uuid) -> Result<&Member<'channel>> {
+        let results = future::join_all(
+            self.members
+                .iter()
+                .map(|member| member.client.get_entry_by_id(id)),
+        )
+        .await;
+        let mut failed = None;
+        for (member, result) in self.members.iter().zip(results) {
+            match result {
+                Ok(_) => return Ok(member),
+                Err(e) if is_not_found(&e) => {}
+                Err(e) => {
+                    failed = failed.or(Some(e));
+                }
+            }
+        }
+        Err(failed.unwrap_or(Error::EntryIdNotFound { id }))
+    }
+
+    fn primary(&self) -> Result<&Member<'channel>> {
+        self.members.first().ok_or(Error::NoBackends)
+    }
+}
+
+/// Whether an error only says that a member doesn't have what we asked for,
+/// so another member may.
+const fn is_not_found(error: &Error) -> bool {
+    matches!(
+        *error,
+        Error::EntryNotFound { .. } | Error::EntryIdNotFound { .. } | Error::GroupNotFound { .. }
+    )
+}
+
+#[async_trait]
+impl client::Client for Client<'_> {
+    async fn get_entry(
+        &self,
+        group_names: &mut (dyn Iterator<Item = &str> + Send + Sync),
+        title: &str,
+    ) -> Result<client::Entry> {
+        let groups = &group_names.collect::<Vec<_>>();
+        let results = future::join_all(self.members.iter().map(|member| async move {
+            member
+                .client
+                .get_entry(&mut groups.iter().copied(), title)
+                .await
+        }))
+        .await;
+        self.first_found(results)
+    }
+
+    async fn get_entry_by_id(&self, id: Uuid) -> Result<client::Entry> {
+        let results = future::join_all(
+            self.members
+                .iter()
+                .map(|member| member.client.get_entry_by_id(id)),
+        )
+        .await;
+        self.first_found(results)
+    }
+
+    async fn find_entries(
+        &self,
+        query: &str,
+        limit: Option<NonZeroUsize>,
+    ) -> Result<Vec<client::Entry>> {
+        let results = future::try_join_all(self.members.iter().map(|member| async move {
+            Ok::<_, Error>(
+                member
+                    .client
+                    .find_entries(query, limit)
+                    .await?
+                    .into_iter()
+                    .map(|entry| Self::tag(&member.name, entry)),
+            )
+        }))
+        .await?;
+        Ok(results
+            .into_iter()
+            .flatten()
+            .take(limit.map_or(usize::MAX, NonZeroUsize::get))
+            .collect())
+    }
+
+    async fn create_entry(
+        &self,
+        group_names: &mut (dyn Iterator<Item = &str> + Send + Sync),
+        entry: &client::Entry,
+    ) -> Result<()> {
+        // New entries have no owner yet, so they go to the first backend.
+        self.primary()?
+            .client
+            .create_entry(group_names, entry)
+            .await
+    }
+
+    async fn update_entry(&self, id: Uuid, entry: &client::Entry) -> Result<()> {
+        self.owner(id).await?.client.update_entry(id, entry).await
+    }
+
+    async fn list_group(
+        &self,
+        group_names: &mut (dyn Iterator<Item = &str> + Send + Sync),
+    ) -> Result<client::GroupListing> {
+        let groups = &group_names.collect::<Vec<_>>();
+        let results = future::join_all(self.members.iter().map(|member| async move {
+            member.client.list_group(&mut groups.iter().copied()).await
+        }))
+        .await;
+
+        let mut listing: Option<client::GroupListing> = None;
+        let mut missing = None;
+        for result in results {
+            match result {
+                Ok(found) => {
+                    let merged = listing.get_or_insert_with(client::GroupListing::default);
+                    for name in found.groups {
+                        if !merged.groups.contains(&name) {
+                            merged.groups.push(name);
+                        }
+                    }
+                    for name in found.entries {
+                        if !merged.entries.contains(&name) {
+                            merged.entries.push(name);
+                        }
+                    }
+                }
+                Err(e) if is_not_found(&e) => {
+                    missing = missing.or(Some(e));
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        listing.ok_or_else(|| missing.unwrap_or(Error::NoBackends))
+    }
+
+    async fn databases(&self) -> Result<Vec<client::Database>> {
+        let results =
+            future::try_join_all(self.members.iter().map(|member| member.client.databases()))
+                .await?;
+        Ok(results.into_iter().flatten().collect())
+    }
+
+    async fn features(&self) -> Result<Vec<String>> {
+        let results =
+            future::try_join_all(self.members.iter().map(|member| member.client.features()))
+                .await?;
+        let mut features: Vec<String> = vec![];
+        for feature in results.into_iter().flatten() {
+            if !features.contains(&feature) {
+                features.push(feature);
+            }
+        }
+        Ok(features)
+    }
+
+    async fn generate_password(&self) -> Result<SecretString> {
+        self.primary()?.client.generate_password().await
+    }
+
+    async fn settings(&self) -> Result<Vec<client::Setting>> {
+        let results = future::try_join_all(self.members.iter().map(|member| async move {
+            Ok::<_, Error>(member.client.settings().await?.into_iter().map(|setting| {
+                client::Setting {
+                    name: format!("{}: {}", member.name, setting.name),
+                    value: setting.value,
+                }
+            }))
+        }))
+        .await?;
+        Ok(results.into_iter().flatten().collect())
+    }
+
+    fn group_paths(&self) -> bool {
+        self.members
+            .iter()
+            .all(|member| member.client.group_paths())
+    }
+
+    async fn watch(&self) -> Result<BoxStream<'static, client::DatabaseEvent>> {
+        let mut streams = vec![];
+        for member in &self.members {
+            match member.client.watch().await {
+                Ok(stream) => streams.push(stream),
+                Err(Error::WatchUnsupported) => {}
+                Err(e) => return Err(e),
+            }
+        }
+        if streams.is_empty() {
+            return Err(Error::WatchUnsupported);
+        }
+        Ok(stream::select_all(streams).boxed())
+    }
+}
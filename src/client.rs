@@ -2,16 +2,27 @@
 //
 // SPDX-License-Identifier: Apache-2.0
 
+use std::{num::NonZeroUsize, str::FromStr};
+
 use async_trait::async_trait;
 use clap::ValueEnum;
-use futures_util::future::BoxFuture;
+use futures_util::{future::BoxFuture, stream::BoxStream};
 use inflector::Inflector as _;
-use secrecy::{ExposeSecret as _, SecretString};
+use secrecy::{ExposeSecret as _, SecretString, SecretVec};
+use serde::{Deserialize, Serialize};
 use tabled::Tabled;
+use tokio::task::JoinHandle;
+use unicode_normalization::UnicodeNormalization as _;
+use url::Url;
+use uuid::Uuid;
 
-use crate::error::Result;
+use crate::{
+    error::{self, Result},
+    hooks, progress,
+};
 
-#[derive(Copy, Clone, Debug, PartialEq, Tabled, ValueEnum)]
+#[derive(Copy, Clone, Debug, PartialEq, Deserialize, Serialize, Tabled, ValueEnum)]
+#[serde(rename_all = "lowercase")]
 pub(crate) enum FormFieldType {
     Username,
     Password,
@@ -19,6 +30,10 @@ pub(crate) enum FormFieldType {
     Select,
     Radio,
     Checkbox,
+    /// The current code of an entry's time-based one-time password, which
+    /// KeePassXC generates when it's asked for the entry. It is only ever
+    /// read, never stored.
+    Totp,
 }
 
 impl std::fmt::Display for FormFieldType {
@@ -36,12 +51,49 @@ pub(crate) struct FormField {
     pub(crate) display_name: String,
     #[tabled(rename = "Value", display_with("Self::format_value", self))]
     pub(crate) value: SecretString,
+    #[tabled(skip)]
+    pub(crate) metadata: Option<FormFieldMetadata>,
+}
+
+/// Whether a browser extension should resolve KeePass placeholders in the
+/// value of a field before filling it in.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum PlaceholderHandling {
+    #[default]
+    Default,
+    Enabled,
+    Disabled,
+}
+
+impl std::fmt::Display for PlaceholderHandling {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match *self {
+            Self::Default => "default",
+            Self::Enabled => "enabled",
+            Self::Disabled => "disabled",
+        })
+    }
+}
+
+/// What KeePassRPC tells browser extensions like Kee about where a field
+/// goes in a form. Other backends don't keep this.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize, Tabled)]
+pub(crate) struct FormFieldMetadata {
+    #[tabled(rename = "ID")]
+    pub(crate) id: String,
+    #[tabled(rename = "Name")]
+    pub(crate) name: String,
+    #[tabled(rename = "Page")]
+    pub(crate) page: i32,
+    #[tabled(rename = "Placeholders")]
+    pub(crate) placeholder_handling: PlaceholderHandling,
 }
 
 impl FormField {
-    fn format_value(&self) -> String {
+    pub(crate) fn format_value(&self) -> String {
         match self.type_ {
-            FormFieldType::Password => "⋆⋆⋆⋆⋆⋆⋆⋆⋆⋆".to_owned(),
+            FormFieldType::Password | FormFieldType::Totp => "⋆⋆⋆⋆⋆⋆⋆⋆⋆⋆".to_owned(),
             FormFieldType::Radio
             | FormFieldType::Username
             | FormFieldType::Text
@@ -66,10 +118,22 @@ pub(crate) struct Entry {
     #[tabled(rename = "Title")]
     pub(crate) title: String,
     #[tabled(skip)]
+    pub(crate) urls: Vec<String>,
+    #[tabled(skip)]
     pub(crate) form_fields: Vec<FormField>,
+    /// The name of the database the entry is in, for backends that search
+    /// several databases at once.
+    #[tabled(skip)]
+    pub(crate) database: Option<String>,
+    /// The name of the backend the entry came from, when a profile uses
+    /// several backends at once.
+    #[tabled(skip)]
+    pub(crate) source: Option<String>,
 }
 
 impl Entry {
+    // LINT: The signature is dictated by Tabled's `display_with`.
+    #[allow(clippy::ref_option)]
     fn format_group(group: &Option<Group>) -> String {
         match group.as_ref() {
             Some(g) => g.path.clone(),
@@ -78,6 +142,144 @@ impl Entry {
     }
 }
 
+/// A database the backend has open and unlocked.
+#[derive(Clone, Debug, Tabled)]
+pub(crate) struct Database {
+    #[tabled(rename = "Name")]
+    pub(crate) name: String,
+    /// The identifier to select the database with using `--database`.
+    #[tabled(rename = "Selector")]
+    pub(crate) selector: String,
+    #[tabled(rename = "Active")]
+    pub(crate) active: bool,
+    /// The name the backend knows us by for this database, for backends that
+    /// authorize clients per database.
+    #[tabled(rename = "Association", display_with = "Self::format_association")]
+    pub(crate) association: Option<String>,
+}
+
+impl Database {
+    // LINT: The signature is dictated by Tabled's `display_with`.
+    #[allow(clippy::ref_option)]
+    fn format_association(association: &Option<String>) -> String {
+        association.clone().unwrap_or_default()
+    }
+}
+
+/// A setting of the backend, as it reports it.
+#[derive(Clone, Debug, Tabled)]
+pub(crate) struct Setting {
+    #[tabled(rename = "Setting")]
+    pub(crate) name: String,
+    #[tabled(rename = "Value")]
+    pub(crate) value: String,
+}
+
+/// The names of the groups and entries directly inside a group.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct GroupListing {
+    pub(crate) groups: Vec<String>,
+    pub(crate) entries: Vec<String>,
+}
+
+/// Whether two group or entry names are the same once both are normalized to
+/// composed characters, so a name typed on macOS, which decomposes them,
+/// matches one stored elsewhere.
+pub(crate) fn same_name(a: &str, b: &str) -> bool {
+    a.nfc().eq(b.nfc())
+}
+
+/// How the title of an entry given on the command line is compared with the
+/// titles of the entries in its group.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub(crate) enum TitleMatch {
+    /// The titles must be the same.
+    #[default]
+    Exact,
+    /// The title of the entry must start with the one given.
+    Prefix,
+    /// The title of the entry must contain the one given.
+    Substring,
+}
+
+impl TitleMatch {
+    pub(crate) fn matches(self, title: &str, wanted: &str) -> bool {
+        let (composed, wanted_composed) = (
+            title.nfc().collect::<String>(),
+            wanted.nfc().collect::<String>(),
+        );
+        match self {
+            Self::Exact => composed == wanted_composed,
+            Self::Prefix => composed.starts_with(&wanted_composed),
+            Self::Substring => composed.contains(&wanted_composed),
+        }
+    }
+
+    /// Pick the entry whose title matches out of the entries of a group,
+    /// preferring one with exactly the title given.
+    pub(crate) fn select<T>(
+        self,
+        entries: impl IntoIterator<Item = T>,
+        title: impl Fn(&T) -> &str,
+        wanted: &str,
+    ) -> Option<T> {
+        let mut found = None;
+        for entry in entries {
+            if same_name(title(&entry), wanted) {
+                return Some(entry);
+            }
+            if found.is_none() && self.matches(title(&entry), wanted) {
+                found = Some(entry);
+            }
+        }
+        found
+    }
+}
+
+/// Something the backend tells us happened to the database without our
+/// asking.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) enum DatabaseEvent {
+    Locked,
+    Unlocked,
+}
+
+/// A reference to an entry given on the command line, either as a path
+/// through the group hierarchy (`Group/Subgroup/Title`) or as the entry's
+/// unique identifier.
+///
+/// Paths that begin with a slash are absolute; other paths may be resolved
+/// against a group prefix.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) enum EntryRef {
+    Path {
+        groups: Vec<String>,
+        title: String,
+        absolute: bool,
+    },
+    Id(Uuid),
+}
+
+impl FromStr for EntryRef {
+    type Err = error::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        if let Ok(id) = Uuid::try_parse(s) {
+            return Ok(Self::Id(id));
+        }
+
+        let mut groups: Vec<String> = s.trim_matches('/').split('/').map(str::to_owned).collect();
+        match groups.pop() {
+            Some(title) if !title.is_empty() => Ok(Self::Path {
+                groups,
+                title,
+                absolute: s.starts_with('/'),
+            }),
+            _ => Err(error::Error::InvalidEntryRef(s.to_owned())),
+        }
+    }
+}
+
 #[async_trait]
 pub(crate) trait Client {
     async fn get_entry(
@@ -86,21 +288,204 @@ pub(crate) trait Client {
         title: &str,
     ) -> Result<Entry>;
 
-    async fn find_entries(&self, query: &str) -> Result<Vec<Entry>>;
+    async fn get_entry_by_id(&self, id: Uuid) -> Result<Entry>;
+
+    /// Search for entries matching the given text, returning at most `limit`
+    /// of them. The Secret Service stops asking for secrets once it has
+    /// enough. KeePassRPC and KeePassXC have no way to ask for fewer, so every
+    /// match, secrets and all, is still sent to us and decrypted, and the
+    /// limit only trims what we return.
+    async fn find_entries(&self, query: &str, limit: Option<NonZeroUsize>) -> Result<Vec<Entry>>;
+
+    /// Create a new entry in the given group using the title, URLs, and form
+    /// fields of the given entry.
+    async fn create_entry(
+        &self,
+        group_names: &mut (dyn Iterator<Item = &str> + Send + Sync),
+        entry: &Entry,
+    ) -> Result<()>;
+
+    /// Replace the contents of the entry with the given ID with the title,
+    /// URLs, and form fields of the given entry.
+    async fn update_entry(&self, id: Uuid, entry: &Entry) -> Result<()>;
+
+    /// List the names of the child groups and entries of the given group.
+    /// Backends that can't enumerate entries only list child groups.
+    async fn list_group(
+        &self,
+        group_names: &mut (dyn Iterator<Item = &str> + Send + Sync),
+    ) -> Result<GroupListing>;
+
+    /// List the databases the backend can see.
+    async fn databases(&self) -> Result<Vec<Database>>;
+
+    /// List the names of the protocol features the backend said it supports
+    /// when we connected.
+    async fn features(&self) -> Result<Vec<String>>;
+
+    /// Generate a password with the backend's own generator and its default
+    /// settings. Backends without a generator refuse.
+    async fn generate_password(&self) -> Result<SecretString> {
+        Err(error::Error::GeneratorUnsupported)
+    }
+
+    /// Follow what the backend tells us happens to the database from now on,
+    /// until the connection ends. Backends that don't tell us refuse.
+    async fn watch(&self) -> Result<BoxStream<'static, DatabaseEvent>> {
+        Err(error::Error::WatchUnsupported)
+    }
+
+    /// Describe the settings of the backend that decide what it finds, for
+    /// `status`. Most backends have none to tell.
+    async fn settings(&self) -> Result<Vec<Setting>> {
+        Ok(vec![])
+    }
+
+    /// Whether the group of each entry is its full path from the root of the
+    /// database, rather than just the name of the group it's in.
+    fn group_paths(&self) -> bool {
+        true
+    }
+
+    async fn get_entry_by_ref(&self, entry: &EntryRef) -> Result<Entry>
+    where
+        Self: Sync,
+    {
+        match *entry {
+            EntryRef::Path {
+                ref groups,
+                ref title,
+                ..
+            } => {
+                self.get_entry(&mut groups.iter().map(String::as_str), title)
+                    .await
+            }
+            EntryRef::Id(id) => self.get_entry_by_id(id).await,
+        }
+    }
+}
+
+#[async_trait]
+impl<T: Client + Send + Sync + ?Sized> Client for Box<T> {
+    async fn get_entry(
+        &self,
+        group_names: &mut (dyn Iterator<Item = &str> + Send + Sync),
+        title: &str,
+    ) -> Result<Entry> {
+        (**self).get_entry(group_names, title).await
+    }
+
+    async fn get_entry_by_id(&self, id: Uuid) -> Result<Entry> {
+        (**self).get_entry_by_id(id).await
+    }
+
+    async fn find_entries(&self, query: &str, limit: Option<NonZeroUsize>) -> Result<Vec<Entry>> {
+        (**self).find_entries(query, limit).await
+    }
+
+    async fn create_entry(
+        &self,
+        group_names: &mut (dyn Iterator<Item = &str> + Send + Sync),
+        entry: &Entry,
+    ) -> Result<()> {
+        (**self).create_entry(group_names, entry).await
+    }
+
+    async fn update_entry(&self, id: Uuid, entry: &Entry) -> Result<()> {
+        (**self).update_entry(id, entry).await
+    }
+
+    async fn list_group(
+        &self,
+        group_names: &mut (dyn Iterator<Item = &str> + Send + Sync),
+    ) -> Result<GroupListing> {
+        (**self).list_group(group_names).await
+    }
+
+    async fn databases(&self) -> Result<Vec<Database>> {
+        (**self).databases().await
+    }
+
+    async fn features(&self) -> Result<Vec<String>> {
+        (**self).features().await
+    }
+
+    async fn generate_password(&self) -> Result<SecretString> {
+        (**self).generate_password().await
+    }
+
+    async fn watch(&self) -> Result<BoxStream<'static, DatabaseEvent>> {
+        (**self).watch().await
+    }
+
+    async fn settings(&self) -> Result<Vec<Setting>> {
+        (**self).settings().await
+    }
+
+    fn group_paths(&self) -> bool {
+        (**self).group_paths()
+    }
 }
 
 #[async_trait]
-impl Client for Box<dyn Client + Send + Sync + '_> {
+impl<T: Client + Send + Sync + ?Sized> Client for &T {
     async fn get_entry(
         &self,
         group_names: &mut (dyn Iterator<Item = &str> + Send + Sync),
         title: &str,
     ) -> Result<Entry> {
-        <dyn Client>::get_entry(self.as_ref(), group_names, title).await
+        (**self).get_entry(group_names, title).await
+    }
+
+    async fn get_entry_by_id(&self, id: Uuid) -> Result<Entry> {
+        (**self).get_entry_by_id(id).await
+    }
+
+    async fn find_entries(&self, query: &str, limit: Option<NonZeroUsize>) -> Result<Vec<Entry>> {
+        (**self).find_entries(query, limit).await
+    }
+
+    async fn create_entry(
+        &self,
+        group_names: &mut (dyn Iterator<Item = &str> + Send + Sync),
+        entry: &Entry,
+    ) -> Result<()> {
+        (**self).create_entry(group_names, entry).await
+    }
+
+    async fn update_entry(&self, id: Uuid, entry: &Entry) -> Result<()> {
+        (**self).update_entry(id, entry).await
     }
 
-    async fn find_entries(&self, query: &str) -> Result<Vec<Entry>> {
-        <dyn Client>::find_entries(self.as_ref(), query).await
+    async fn list_group(
+        &self,
+        group_names: &mut (dyn Iterator<Item = &str> + Send + Sync),
+    ) -> Result<GroupListing> {
+        (**self).list_group(group_names).await
+    }
+
+    async fn databases(&self) -> Result<Vec<Database>> {
+        (**self).databases().await
+    }
+
+    async fn features(&self) -> Result<Vec<String>> {
+        (**self).features().await
+    }
+
+    async fn generate_password(&self) -> Result<SecretString> {
+        (**self).generate_password().await
+    }
+
+    async fn watch(&self) -> Result<BoxStream<'static, DatabaseEvent>> {
+        (**self).watch().await
+    }
+
+    async fn settings(&self) -> Result<Vec<Setting>> {
+        (**self).settings().await
+    }
+
+    fn group_paths(&self) -> bool {
+        (**self).group_paths()
     }
 }
 
@@ -112,4 +497,108 @@ pub(crate) trait Protocol<'channel> {
         BoxFuture<'channel, Result<()>>,
         Box<dyn Client + Send + Sync + 'channel>,
     )>;
+
+    /// The secret the stored session data is keyed on, for backends that keep
+    /// one, so other data can be protected at rest with keys derived from it.
+    async fn session_secret(&self) -> Result<Option<SecretVec<u8>>> {
+        Ok(None)
+    }
+}
+
+/// A client connected to a backend along with the worker task that services
+/// it.
+pub(crate) struct Channel {
+    worker: JoinHandle<Result<()>>,
+    client: Box<dyn Client + Send + Sync>,
+}
+
+impl Channel {
+    pub(crate) async fn open(proto: &(dyn Protocol<'static> + Send + Sync)) -> Result<Self> {
+        let (worker, client) = {
+            let _progress = progress::start(progress::Phase::Connecting);
+            proto.channel().await?
+        };
+        Ok(Self {
+            worker: tokio::spawn(worker),
+            client: Box::new(hooks::Client::new(Box::new(progress::Client::new(client)))),
+        })
+    }
+
+    pub(crate) fn client(&self) -> &(dyn Client + Send + Sync) {
+        self.client.as_ref()
+    }
+
+    /// Disconnect the client and wait for the worker to finish any remaining
+    /// processing.
+    pub(crate) async fn close(self) -> Result<()> {
+        drop(self.client);
+        self.worker.await?
+    }
+}
+
+/// Opens channels to backends other than the one selected globally, for
+/// commands that need to work with more than one at a time.
+#[async_trait]
+pub(crate) trait Connector: Send + Sync {
+    async fn connect(&self, url: &Url) -> Result<Channel>;
+}
+
+#[cfg(test)]
+mod tests {
+    use uuid::uuid;
+
+    use super::*;
+
+    #[test]
+    fn entry_ref_from_path() -> Result<()> {
+        assert_eq!(
+            "Servers/Web/web01".parse::<EntryRef>()?,
+            EntryRef::Path {
+                groups: vec!["Servers".to_owned(), "Web".to_owned()],
+                title: "web01".to_owned(),
+                absolute: false,
+            }
+        );
+        assert_eq!(
+            "/web01".parse::<EntryRef>()?,
+            EntryRef::Path {
+                groups: vec![],
+                title: "web01".to_owned(),
+                absolute: true,
+            }
+        );
+        assert!("Servers/".parse::<EntryRef>().is_ok());
+        assert!("".parse::<EntryRef>().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn title_match() {
+        let titles = ["GitHub (old)", "My GitHub", "GitHub"];
+        let select = |mode: TitleMatch, wanted| mode.select(titles, |t| t, wanted);
+        assert_eq!(select(TitleMatch::Exact, "GitHub"), Some("GitHub"));
+        assert_eq!(select(TitleMatch::Exact, "Git"), None);
+        assert_eq!(select(TitleMatch::Prefix, "GitHub"), Some("GitHub"));
+        assert_eq!(select(TitleMatch::Prefix, "Git"), Some("GitHub (old)"));
+        assert_eq!(select(TitleMatch::Substring, "Hub"), Some("GitHub (old)"));
+        assert_eq!(select(TitleMatch::Prefix, "Hub"), None);
+
+        // "Café", composed and decomposed.
+        assert!(same_name("Caf\u{e9}", "Cafe\u{301}"));
+        assert!(TitleMatch::Prefix.matches("Caf\u{e9} Menu", "Cafe\u{301}"));
+    }
+
+    #[test]
+    fn entry_ref_from_id() -> Result<()> {
+        let id = uuid!("46640aca-1245-44d2-8ca9-d19750597d6c");
+        assert_eq!(
+            "46640ACA124544D28CA9D19750597D6C".parse::<EntryRef>()?,
+            EntryRef::Id(id)
+        );
+        assert_eq!(
+            "46640aca-1245-44d2-8ca9-d19750597d6c".parse::<EntryRef>()?,
+            EntryRef::Id(id)
+        );
+        Ok(())
+    }
 }
@@ -0,0 +1,116 @@
+// SPDX-FileCopyrightText: 2024 Noah Fontes
+//
+// SPDX-License-Identifier: Apache-2.0
+
+use clap::{Parser, Subcommand};
+use tabled::{
+    settings::{
+        object::{Rows, Segment},
+        Alignment, Format, Modify, Style,
+    },
+    Table, Tabled,
+};
+
+use crate::{
+    config::Config,
+    error::{self, Result},
+    locale, pager,
+};
+
+/// Manage saved lookups. An alias can be invoked in place of a command, as in
+/// `karp <alias>`, and expands to a `get-form-fields` invocation. Any further
+/// arguments are passed along to `get-form-fields`.
+#[derive(Debug, Parser)]
+pub(crate) struct Command {
+    #[clap(subcommand)]
+    action: Action,
+}
+
+#[derive(Debug, Subcommand)]
+enum Action {
+    /// Save a lookup of an entry under a new name, replacing any existing
+    /// alias with the same name.
+    Add {
+        /// The name to invoke the alias with.
+        #[clap()]
+        name: String,
+
+        /// The group containing the entry, with each subgroup separated by a
+        /// slash.
+        #[clap()]
+        group: String,
+
+        /// The name of the entry to look up.
+        #[clap()]
+        entry: String,
+    },
+
+    /// Remove a saved lookup.
+    Remove {
+        /// The name of the alias to remove.
+        #[clap()]
+        name: String,
+    },
+
+    /// List saved lookups.
+    List,
+}
+
+#[derive(Tabled)]
+struct Row<'cfg> {
+    #[tabled(rename = "Name")]
+    name: &'cfg str,
+    #[tabled(rename = "Arguments")]
+    arguments: String,
+}
+
+/// Convert a group path and entry name to the positional arguments of
+/// `get-form-fields`. A leading slash is kept on the first group so that the
+/// path stays absolute.
+fn alias_arguments(group: &str, entry: &str) -> Vec<String> {
+    let absolute = group.starts_with('/');
+    let mut args: Vec<String> = group
+        .split('/')
+        .filter(|name| !name.is_empty())
+        .map(str::to_owned)
+        .collect();
+    if absolute {
+        match args.first_mut() {
+            Some(first) => first.insert(0, '/'),
+            None => args.push("/".to_owned()),
+        }
+    }
+    args.push(entry.to_owned());
+    args
+}
+
+impl Command {
+    pub(crate) fn run(self, mut config: Config) -> Result<()> {
+        match self.action {
+            Action::Add { name, group, entry } => {
+                let _ = config.aliases.insert(name, alias_arguments(&group, &entry));
+                config.save()
+            }
+            Action::Remove { name } => {
+                if config.aliases.remove(&name).is_none() {
+                    return Err(error::Config::UnknownAlias(name).into());
+                }
+                config.save()
+            }
+            Action::List => {
+                if config.aliases.is_empty() {
+                    return Ok(());
+                }
+                pager::print(
+                    Table::new(config.aliases.iter().map(|(name, args)| Row {
+                        name,
+                        arguments: args.join(" "),
+                    }))
+                    .with(Style::rounded())
+                    .with(Modify::new(Rows::first()).with(Format::content(locale::header)))
+                    .with(Modify::new(Segment::all()).with(Alignment::left())),
+                )
+            }
+        }
+    }
+}
@@ -0,0 +1,131 @@
+// SPDX-FileCopyrightText: 2024 Noah Fontes
+//
+// SPDX-License-Identifier: Apache-2.0
+
+use std::{
+    num,
+    time::{Duration, Instant},
+};
+
+use clap::Parser;
+use tabled::{
+    settings::{
+        object::{Rows, Segment},
+        Alignment, Format, Modify, Style,
+    },
+    Table, Tabled,
+};
+use url::Url;
+
+use crate::{
+    client::{Client, Connector},
+    error::Result,
+    locale,
+};
+
+/// Measure how long it takes to talk to the selected backend. We time opening
+/// a connection, the first request (which includes the handshake with
+/// KeePassRPC or KeePassXC), and then a number of further requests over the
+/// same connection.
+#[derive(Debug, Parser)]
+pub(crate) struct Command {
+    /// The number of requests to time after the first.
+    #[arg(short = 'n', long, default_value = "20")]
+    count: num::NonZeroUsize,
+
+    /// Time searches for the given text instead of listing the open
+    /// databases, which the backend can answer without looking at entries.
+    #[arg(long)]
+    query: Option<String>,
+}
+
+#[derive(Tabled)]
+struct Row {
+    #[tabled(rename = "Measurement")]
+    measurement: String,
+    #[tabled(rename = "Time")]
+    time: String,
+}
+
+impl Row {
+    fn new(id: &str, duration: Duration) -> Self {
+        Self {
+            measurement: locale::text(id),
+            time: format!("{:.3} ms", duration.as_secs_f64() * 1000.0),
+        }
+    }
+}
+
+/// Find the given percentile of sorted durations using the nearest-rank
+/// method.
+fn percentile(sorted: &[Duration], percent: usize) -> Duration {
+    let rank = (percent * sorted.len()).div_ceil(100).max(1);
+    sorted[rank.min(sorted.len()) - 1]
+}
+
+impl Command {
+    async fn request(&self, client: &(dyn Client + Send + Sync)) -> Result<Duration> {
+        let start = Instant::now();
+        match self.query {
+            Some(ref query) => drop(client.find_entries(query, None).await?),
+            None => drop(client.databases().await?),
+        }
+        Ok(start.elapsed())
+    }
+
+    pub(crate) async fn run(self, connector: &dyn Connector, url: &Url) -> Result<()> {
+        let start = Instant::now();
+        let channel = connector.connect(url).await?;
+        let connect = start.elapsed();
+
+        let result = self.measure(channel.client()).await;
+        channel.close().await?;
+        let (first, mut calls) = result?;
+        calls.sort_unstable();
+
+        let rows = [
+            Row::new("benchmark-connect", connect),
+            Row::new("benchmark-first-request", first),
+            Row::new("benchmark-min", percentile(&calls, 0)),
+            Row::new("benchmark-p50", percentile(&calls, 50)),
+            Row::new("benchmark-p90", percentile(&calls, 90)),
+            Row::new("benchmark-p99", percentile(&calls, 99)),
+            Row::new("benchmark-max", percentile(&calls, 100)),
+        ];
+        println!(
+            "{}",
+            Table::new(rows)
+                .with(Style::rounded())
+                .with(Modify::new(Rows::first()).with(Format::content(locale::header)))
+                .with(Modify::new(Segment::new(1.., 1..)).with(Alignment::right()))
+        );
+        Ok(())
+    }
+
+    async fn measure(
+        &self,
+        client: &(dyn Client + Send + Sync),
+    ) -> Result<(Duration, Vec<Duration>)> {
+        let first = self.request(client).await?;
+        let mut calls = Vec::with_capacity(self.count.get());
+        for _ in 0..self.count.get() {
+            calls.push(self.request(client).await?);
+        }
+        Ok((first, calls))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nearest_rank_percentile() {
+        let sorted: Vec<_> = (1..=10).map(Duration::from_millis).collect();
+        assert_eq!(percentile(&sorted, 0), Duration::from_millis(1));
+        assert_eq!(percentile(&sorted, 50), Duration::from_millis(5));
+        assert_eq!(percentile(&sorted, 90), Duration::from_millis(9));
+        assert_eq!(percentile(&sorted, 99), Duration::from_millis(10));
+        assert_eq!(percentile(&sorted, 100), Duration::from_millis(10));
+    }
+}
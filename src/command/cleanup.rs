@@ -0,0 +1,226 @@
+// SPDX-FileCopyrightText: 2024 Noah Fontes
+//
+// SPDX-License-Identifier: Apache-2.0
+
+use std::{
+    collections::BTreeSet,
+    fs,
+    io::{self, IsTerminal as _, Write as _},
+    path::Path,
+};
+
+use clap::Parser;
+use log::warn;
+use url::Url;
+
+use super::shell;
+use crate::{
+    config::Config,
+    error::{self, Result},
+    locale, metadata, snapshot,
+    storage::{self, Storage as _},
+};
+
+/// Remove everything we've kept on this machine: the session data cached for
+/// every URL in the configuration file in each kind of storage, snapshots,
+/// shell history, and our state and cache directories once they're empty.
+/// KeePassXC and KeePassRPC keep their side of the association, which only
+/// they can remove, so we say where to find it.
+#[derive(Debug, Parser)]
+#[command(visible_alias = "uninstall")]
+pub(crate) struct Command {
+    /// Remove the configuration file too.
+    #[arg(long)]
+    config: bool,
+
+    /// Don't ask before removing anything.
+    #[arg(short, long)]
+    yes: bool,
+}
+
+/// The URLs we may have kept session data for: the one selected, and those of
+/// every profile.
+fn known_urls(config: &Config, selected: &Url) -> BTreeSet<Url> {
+    let mut urls = BTreeSet::from([selected.clone()]);
+    for profile in config.profiles.values() {
+        urls.extend(profile.url.iter().cloned());
+        urls.extend(
+            profile
+                .backends
+                .iter()
+                .flat_map(|members| members.values().cloned()),
+        );
+    }
+    urls
+}
+
+/// Remove a file, if it's there.
+fn remove_file(path: &Path) -> Result<()> {
+    match fs::remove_file(path) {
+        Ok(()) => {
+            println!(
+                "{}",
+                locale::format("cleanup-removed", &[("path", &path.display())])
+            );
+            Ok(())
+        }
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Remove a directory we created, but only if nothing else was put in it.
+fn remove_empty_dir(path: &Path) {
+    if fs::remove_dir(path).is_ok() {
+        println!(
+            "{}",
+            locale::format("cleanup-removed", &[("path", &path.display())])
+        );
+    }
+}
+
+fn confirm() -> Result<bool> {
+    eprint!("{}", locale::text("cleanup-confirm"));
+    io::stderr().flush()?;
+    let mut line = String::new();
+    let _ = io::stdin().read_line(&mut line)?;
+    Ok(line
+        .trim()
+        .to_lowercase()
+        .starts_with(&locale::text("cleanup-confirm-yes")))
+}
+
+impl Command {
+    /// Clear the session data for the URL from every kind of storage that
+    /// has some, returning whether any did.
+    async fn clear_sessions(url: &Url, options: storage::Options) -> bool {
+        let mut found = false;
+        for &kind in storage::Kind::SUPPORTED {
+            let result = async {
+                let mut source = kind.open::<serde_json::Value>(url, options).await?;
+                if source.get().await?.is_none() {
+                    return Ok(false);
+                }
+                source.clear().await?;
+                Ok::<_, error::Error>(true)
+            }
+            .await;
+            match result {
+                Ok(true) => {
+                    found = true;
+                    println!(
+                        "{}",
+                        locale::format("cleanup-session", &[("url", &url), ("storage", &kind)])
+                    );
+                }
+                Ok(false) => {}
+                Err(e) => warn!("We could not clear {} storage for {}: {}", kind, url, e),
+            }
+        }
+        found
+    }
+
+    pub(crate) async fn run(
+        self,
+        config: &Config,
+        url: &Url,
+        options: storage::Options,
+        interactive: bool,
+    ) -> Result<()> {
+        if !self.yes {
+            if !interactive || !io::stdin().is_terminal() {
+                return Err(error::Error::InputRequired);
+            }
+            if !confirm()? {
+                return Err(error::Error::Cancelled);
+            }
+        }
+
+        let mut reminders = BTreeSet::new();
+        for known in known_urls(config, url) {
+            if Self::clear_sessions(&known, options).await {
+                let _ = reminders.insert(match known.scheme() {
+                    "ws" | "wss" => "cleanup-keepassrpc",
+                    _ => "cleanup-keepassxc",
+                });
+            }
+        }
+
+        for path in [shell::history_path(), storage::Lock::path()]
+            .into_iter()
+            .flatten()
+        {
+            remove_file(&path)?;
+        }
+        if let Some(dir) = snapshot::dir().filter(|dir| dir.exists()) {
+            fs::remove_dir_all(&dir)?;
+            println!(
+                "{}",
+                locale::format("cleanup-removed", &[("path", &dir.display())])
+            );
+        }
+        if self.config {
+            if let Some(path) = Config::path() {
+                remove_file(&path)?;
+                if let Some(parent) = path.parent() {
+                    remove_empty_dir(parent);
+                }
+            }
+        }
+        for dir in [
+            metadata::Dir::Cache,
+            metadata::Dir::State,
+            metadata::Dir::Data,
+        ]
+        .into_iter()
+        .filter_map(metadata::Dir::path)
+        {
+            remove_empty_dir(&dir);
+        }
+
+        for reminder in reminders {
+            println!("{}", locale::text(reminder));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Profile;
+
+    fn url(text: &str) -> Result<Url> {
+        Ok(Url::parse(text).map_err(error::Conversion::from)?)
+    }
+
+    #[test]
+    fn urls_of_every_profile_are_known() -> Result<()> {
+        let mut config = Config::default();
+        let _ = config.profiles.insert(
+            "work".to_owned(),
+            Profile {
+                url: Some(url("ws://work:12546")?),
+                ..Profile::default()
+            },
+        );
+        let _ = config.profiles.insert(
+            "both".to_owned(),
+            Profile {
+                backends: Some([("home".to_owned(), url("file:")?)].into()),
+                ..Profile::default()
+            },
+        );
+
+        let selected = url("ws://127.0.0.1:12546")?;
+        let urls: Vec<_> = known_urls(&config, &selected)
+            .into_iter()
+            .map(String::from)
+            .collect();
+        assert_eq!(
+            urls,
+            ["file:///", "ws://127.0.0.1:12546/", "ws://work:12546/"]
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,71 @@
+// SPDX-FileCopyrightText: 2024 Noah Fontes
+//
+// SPDX-License-Identifier: Apache-2.0
+
+use async_trait::async_trait;
+use clap::{Parser, Subcommand};
+
+use super::Context;
+use crate::{client::Client, error::Result};
+
+/// Print completion candidates for an argument, one per line, for use by
+/// shell completion scripts. Only a cached session is used; we never prompt
+/// for a password.
+#[derive(Debug, Parser)]
+pub(crate) struct Command {
+    #[clap(subcommand)]
+    kind: Kind,
+}
+
+#[derive(Debug, Subcommand)]
+enum Kind {
+    /// Complete a slash-separated entry path, like the ones `copy` and `diff`
+    /// take.
+    Path {
+        /// The part of the path typed so far.
+        #[clap(default_value = "")]
+        partial: String,
+    },
+
+    /// Complete the next positional argument of `get-form-fields`.
+    Names {
+        /// The groups given so far.
+        #[clap()]
+        groups: Vec<String>,
+    },
+}
+
+#[async_trait]
+impl super::Command for Command {
+    async fn execute(self, client: impl Client + Send + Sync, ctx: &Context<'_>) -> Result<()> {
+        match self.kind {
+            Kind::Path { partial } => {
+                let (parent, fragment) =
+                    partial.split_at(partial.rfind('/').map_or(0, |pos| pos + 1));
+
+                let groups = ctx.resolve_group_path(parent);
+                let listing = client
+                    .list_group(&mut groups.iter().map(String::as_str))
+                    .await?;
+
+                for group in listing.groups.iter().filter(|g| g.starts_with(fragment)) {
+                    println!("{parent}{group}/");
+                }
+                for entry in listing.entries.iter().filter(|e| e.starts_with(fragment)) {
+                    println!("{parent}{entry}");
+                }
+            }
+            Kind::Names { groups: given } => {
+                let groups = ctx.resolve_groups(&given);
+                let listing = client
+                    .list_group(&mut groups.iter().map(String::as_str))
+                    .await?;
+
+                for name in listing.groups.iter().chain(&listing.entries) {
+                    println!("{name}");
+                }
+            }
+        }
+        Ok(())
+    }
+}
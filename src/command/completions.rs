@@ -0,0 +1,267 @@
+// SPDX-FileCopyrightText: 2024 Noah Fontes
+//
+// SPDX-License-Identifier: Apache-2.0
+
+use std::{any::TypeId, fmt::Write as _, fs, path::PathBuf};
+
+use clap::{builder::PossibleValuesParser, Arg, Parser};
+use clap_complete::{generate, Generator, Shell};
+use log::info;
+
+use crate::{client::EntryRef, error::Result};
+
+/// Looks entry paths and group names up with `__complete`, passing along the
+/// connection options given so far. Goes right after the `#compdef` line.
+const ZSH_HOOK: &str = r#"#
+# Generated by `karp completions`. Entry paths and group names are looked up
+# in the connected database using the cached session, so nothing is offered
+# until karp has connected successfully at least once.
+
+_karp_backend() {
+  local -a connection
+  (( $+opt_args[--url] )) && connection+=(--url "$opt_args[--url]")
+  (( $+opt_args[--profile] )) && connection+=(--profile "$opt_args[--profile]")
+  karp "${connection[@]}" __complete "$@" 2>/dev/null
+}
+
+_karp_entry_paths() {
+  local -a paths
+  paths=("${(@f)$(_karp_backend path "$PREFIX")}")
+  compadd -U -S '' -- "${paths[@]}"
+}
+
+_karp_names() {
+  local -a groups names
+  local i
+  for (( i = 2; i < CURRENT; i++ )); do
+    case $words[i] in
+      -t|--type|-i|--index) (( i++ )) ;;
+      -*) ;;
+      *) groups+=("$words[i]") ;;
+    esac
+  done
+  names=("${(@f)$(_karp_backend names "${groups[@]}")}")
+  compadd -- "${names[@]}"
+}
+"#;
+
+/// The Fish counterpart of [`ZSH_HOOK`], which goes first.
+const FISH_HOOK: &str = r"# Generated by `karp completions`. Entry paths and group names are looked up
+# in the connected database using the cached session, so nothing is offered
+# until karp has connected successfully at least once.
+
+function __karp_backend
+    set -l connection
+    set -l tokens (commandline -opc)
+    for i in (seq 2 (count $tokens))
+        switch $tokens[$i]
+            case --url --profile
+                set -a connection $tokens[$i] $tokens[(math $i + 1)]
+            case '--url=*' '--profile=*'
+                set -a connection $tokens[$i]
+        end
+    end
+    karp $connection __complete $argv 2>/dev/null
+end
+
+function __karp_entry_paths
+    __karp_backend path (commandline -ct)
+end
+
+function __karp_names
+    set -l tokens (commandline -opc)
+    set -l groups
+    set -l found_command
+    set -l skip
+    for token in $tokens[2..-1]
+        if set -q skip[1]
+            set -e skip
+            continue
+        end
+        if not set -q found_command[1]
+            contains -- $token get-form-fields password username; and set found_command 1
+            continue
+        end
+        switch $token
+            case -t --type -i --index
+                set skip 1
+            case '-*'
+            case '*'
+                set -a groups $token
+        end
+    end
+    __karp_backend names $groups
+end
+
+";
+
+/// Write completion scripts for Zsh and Fish. Besides commands and options,
+/// they complete group and entry names by asking the connected database.
+#[derive(Debug, Parser)]
+pub(crate) struct Command {
+    /// The directory to write the scripts to. It is created if it doesn't
+    /// exist.
+    #[arg(long, default_value = ".", value_hint = clap::ValueHint::DirPath)]
+    out_dir: PathBuf,
+}
+
+/// How the values of an argument are looked up in the database.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Lookup {
+    /// A slash-separated path to an entry or group.
+    Path,
+    /// The next of the separate group and title arguments that
+    /// `get-form-fields` takes.
+    Names,
+}
+
+impl Lookup {
+    /// Arguments that take an [`EntryRef`], and options whose values are
+    /// named like one, are paths.
+    fn of(arg: &Arg) -> Option<Self> {
+        if arg.get_value_parser().type_id() == TypeId::of::<EntryRef>() {
+            return Some(Self::Path);
+        }
+        if arg.is_positional() {
+            return (arg.get_id() == "names").then_some(Self::Names);
+        }
+        match arg.get_value_names()?.first()?.as_str() {
+            "ENTRY" | "GROUP" => Some(Self::Path),
+            _ => None,
+        }
+    }
+
+    /// The only possible value the argument is given while the scripts are
+    /// generated, so we can find it in them.
+    const fn placeholder(self) -> &'static str {
+        match self {
+            Self::Path => "__karp_path",
+            Self::Names => "__karp_names",
+        }
+    }
+
+    /// The function of the hooks that completes the argument.
+    const fn function(self, shell: Shell) -> &'static str {
+        match (self, shell) {
+            (Self::Path, Shell::Zsh) => "_karp_entry_paths",
+            (Self::Names, Shell::Zsh) => "_karp_names",
+            (Self::Path, _) => "__karp_entry_paths",
+            (Self::Names, _) => "__karp_names",
+        }
+    }
+}
+
+/// The command without its hidden subcommands, which `clap_complete` would
+/// offer along with the rest. Only what ends up in the scripts is kept.
+fn without_hidden(cmd: &clap::Command) -> clap::Command {
+    let mut visible = clap::Command::new(cmd.get_name().to_owned())
+        .args(cmd.get_arguments().cloned())
+        .groups(cmd.get_groups().cloned())
+        .subcommands(
+            cmd.get_subcommands()
+                .filter(|sub| !sub.is_hide_set())
+                .cloned(),
+        );
+    if let Some(about) = cmd.get_about() {
+        visible = visible.about(about.clone());
+    }
+    if let Some(version) = cmd.get_version() {
+        visible = visible.version(version.to_owned());
+    }
+    visible
+}
+
+/// Give each argument we look up its placeholder as its only possible value.
+fn mark(cmd: clap::Command) -> clap::Command {
+    let names: Vec<_> = cmd
+        .get_subcommands()
+        .map(|sub| sub.get_name().to_owned())
+        .collect();
+    let marked = cmd.mut_args(|arg| match Lookup::of(&arg) {
+        Some(lookup) => arg.value_parser(PossibleValuesParser::new([lookup.placeholder()])),
+        None => arg,
+    });
+    names
+        .iter()
+        .fold(marked, |parent, name| parent.mut_subcommand(name, mark))
+}
+
+/// The Fish completions of the positional arguments we look up, which
+/// `clap_complete` leaves out. Like its own, they only go two commands deep.
+fn fish_positionals(cmd: &clap::Command, parents: &[&str], out: &mut String) {
+    if let [first, ref rest @ ..] = *parents {
+        let mut condition = format!("__fish_karp_using_subcommand {first}");
+        for sub in rest {
+            let _ = write!(condition, "; and __fish_seen_subcommand_from {sub}");
+        }
+        let mut lookups: Vec<_> = cmd.get_positionals().filter_map(Lookup::of).collect();
+        lookups.dedup();
+        for lookup in lookups {
+            let _ = writeln!(
+                out,
+                "complete -c karp -n \"{condition}\" -f -a \"({})\"",
+                lookup.function(Shell::Fish)
+            );
+        }
+    }
+    if parents.len() < 2 {
+        for sub in cmd.get_subcommands() {
+            let path: Vec<_> = parents.iter().copied().chain([sub.get_name()]).collect();
+            fish_positionals(sub, &path, out);
+        }
+    }
+}
+
+/// Generate the script for a shell, with the placeholders of the marked
+/// command replaced by calls of the hooks.
+fn script(shell: Shell, cmd: &clap::Command) -> String {
+    let name = cmd.get_name().to_owned();
+    let mut generated = vec![];
+    generate(shell, &mut mark(cmd.clone()), &name, &mut generated);
+    let mut script = String::from_utf8_lossy(&generated).into_owned();
+
+    for lookup in [Lookup::Path, Lookup::Names] {
+        let (from, to) = if shell == Shell::Fish {
+            (
+                format!("\"{{{}\\t''}}\"", lookup.placeholder()),
+                format!("\"({})\"", lookup.function(shell)),
+            )
+        } else {
+            (
+                format!("({})", lookup.placeholder()),
+                lookup.function(shell).to_owned(),
+            )
+        };
+        script = script.replace(&from, &to);
+    }
+
+    if shell == Shell::Fish {
+        fish_positionals(cmd, &[], &mut script);
+        format!("{FISH_HOOK}{script}")
+    } else {
+        let (compdef, rest) = script.split_once('\n').unwrap_or((&script, ""));
+        format!("{compdef}\n{ZSH_HOOK}{rest}")
+    }
+}
+
+/// The completion scripts for the command, by file name.
+pub(crate) fn scripts(cmd: &clap::Command) -> Vec<(String, String)> {
+    let visible = without_hidden(cmd);
+    [Shell::Zsh, Shell::Fish]
+        .into_iter()
+        .map(|shell| (shell.file_name(visible.get_name()), script(shell, &visible)))
+        .collect()
+}
+
+impl Command {
+    pub(crate) fn run(self, cmd: &clap::Command) -> Result<()> {
+        fs::create_dir_all(&self.out_dir)?;
+
+        for (name, script) in scripts(cmd) {
+            let path = self.out_dir.join(name);
+            info!("Writing {}", path.display());
+            fs::write(path, script)?;
+        }
+        Ok(())
+    }
+}
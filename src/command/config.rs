@@ -0,0 +1,230 @@
+// SPDX-FileCopyrightText: 2024 Noah Fontes
+//
+// SPDX-License-Identifier: Apache-2.0
+
+use clap::{Parser, Subcommand};
+use toml::{Table, Value};
+
+use crate::{
+    config::Config,
+    error::{self, Result},
+};
+
+/// Read and change the configuration file. Settings are named by their path
+/// in the file, with each table separated by a dot, such as `default_profile`
+/// or `profiles.work.url`. Changes are checked against the settings we know
+/// before they're saved, and only the setting changed is written, keeping
+/// comments and formatting. Settings we don't know elsewhere in the file don't
+/// get in the way, so they can be removed here.
+#[derive(Debug, Parser)]
+pub(crate) struct Command {
+    #[clap(subcommand)]
+    action: Action,
+}
+
+#[derive(Debug, Subcommand)]
+enum Action {
+    /// Print the value of a setting. Text is printed as it is, and anything
+    /// else as TOML.
+    Get {
+        /// The name of the setting.
+        #[clap()]
+        key: String,
+    },
+
+    /// Change a setting, adding the profile it belongs to if there isn't one.
+    Set {
+        /// The name of the setting.
+        #[clap()]
+        key: String,
+
+        /// The new value, as a TOML value such as `true` or `["a", "b"]`. Text
+        /// doesn't need to be quoted.
+        #[clap()]
+        value: String,
+    },
+
+    /// Remove a setting, or a whole profile or alias.
+    Unset {
+        /// The name of the setting.
+        #[clap()]
+        key: String,
+    },
+
+    /// Print every setting, one per line, as `key = value`.
+    List,
+}
+
+fn lookup<'cfg>(table: &'cfg Table, key: &str) -> Option<&'cfg Value> {
+    let mut parts = key.split('.');
+    let first = table.get(parts.next()?)?;
+    parts.try_fold(first, |value, part| value.as_table()?.get(part))
+}
+
+/// Read a value given on the command line, taking anything that isn't valid
+/// TOML as text.
+fn parse_value(text: &str) -> Value {
+    toml::from_str::<Table>(&format!("value = {text}"))
+        .ok()
+        .and_then(|mut table| table.remove("value"))
+        .unwrap_or_else(|| Value::String(text.to_owned()))
+}
+
+/// Change or remove a setting, leaving the others as they are.
+fn assign(settings: &Table, key: &str, value: Option<Value>) -> Result<Table> {
+    let invalid_key = || error::Config::InvalidKey(key.to_owned());
+    let mut root = settings.clone();
+    let (parents, name) = match key.rsplit_once('.') {
+        Some((path, last)) => (path.split('.').collect(), last),
+        None => (vec![], key),
+    };
+
+    let mut table = &mut root;
+    for part in parents {
+        table = table
+            .entry(part)
+            .or_insert_with(|| Value::Table(Table::new()))
+            .as_table_mut()
+            .ok_or_else(invalid_key)?;
+    }
+    match value {
+        Some(new) => {
+            let _ = table.insert(name.to_owned(), new);
+        }
+        None => {
+            if table.remove(name).is_none() {
+                return Err(error::Config::NotSet(key.to_owned()).into());
+            }
+        }
+    }
+
+    Ok(root)
+}
+
+/// Check that a setting is one we know and that the value suits it. Each is
+/// checked on its own, so a mistake elsewhere in the file can still be fixed.
+fn check(key: &str, value: &Value) -> Result<()> {
+    let settings = assign(&Table::new(), key, Some(value.clone()))?;
+    let _: Config = Value::Table(settings)
+        .try_into()
+        .map_err(|source| error::Config::Invalid {
+            key: key.to_owned(),
+            source,
+        })?;
+    Ok(())
+}
+
+/// Change a setting. A value that looks like a number or boolean but belongs
+/// in a text setting is taken as text.
+fn set(settings: &Table, key: &str, text: &str) -> Result<Table> {
+    let value = match parse_value(text) {
+        parsed if !parsed.is_str() && check(key, &parsed).is_err() => {
+            Value::String(text.to_owned())
+        }
+        parsed => parsed,
+    };
+    check(key, &value)?;
+    assign(settings, key, Some(value))
+}
+
+/// Collect every setting that isn't a table, with its full name.
+fn flatten<'cfg>(prefix: &str, table: &'cfg Table, settings: &mut Vec<(String, &'cfg Value)>) {
+    for (name, value) in table {
+        let key = if prefix.is_empty() {
+            name.clone()
+        } else {
+            format!("{prefix}.{name}")
+        };
+        match value.as_table() {
+            Some(inner) => flatten(&key, inner, settings),
+            None => settings.push((key, value)),
+        }
+    }
+}
+
+impl Command {
+    pub(crate) fn run(self) -> Result<()> {
+        let table = Config::load_table()?;
+        match self.action {
+            Action::Get { key } => {
+                let value = lookup(&table, &key).ok_or(error::Config::NotSet(key))?;
+                if let Some(text) = value.as_str() {
+                    println!("{text}");
+                } else if let Some(inner) = value.as_table() {
+                    print!("{inner}");
+                } else {
+                    println!("{value}");
+                }
+                Ok(())
+            }
+            Action::Set { key, value } => Config::save_table(&set(&table, &key, &value)?),
+            Action::Unset { key } => Config::save_table(&assign(&table, &key, None)?),
+            Action::List => {
+                let mut settings = vec![];
+                flatten("", &table, &mut settings);
+                for (key, value) in settings {
+                    println!("{key} = {value}");
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn load(settings: Table) -> Result<Config> {
+        Ok(Value::Table(settings)
+            .try_into()
+            .map_err(error::Config::from)?)
+    }
+
+    #[test]
+    fn settings_are_validated() -> Result<()> {
+        let mut settings = set(&Table::new(), "profiles.work.url", "ws://work:12546")?;
+        settings = set(&settings, "profiles.work.trigger_unlock", "true")?;
+        settings = set(&settings, "profiles.work.client_display_name", "1234")?;
+        settings = set(&settings, "aliases.pw", r#"["work", "Mail"]"#)?;
+        let config = load(settings.clone())?;
+
+        let profile = config.profile(Some("work"))?;
+        assert_eq!(
+            profile.url.map(String::from).as_deref(),
+            Some("ws://work:12546/")
+        );
+        assert_eq!(profile.trigger_unlock, Some(true));
+        assert_eq!(profile.client_display_name.as_deref(), Some("1234"));
+        assert_eq!(config.aliases.get("pw").map(Vec::len), Some(2));
+
+        assert!(set(&settings, "profiles.work.colour", "blue").is_err());
+        assert!(set(&settings, "profiles.work.url", "not a url").is_err());
+        assert!(set(&settings, "default_profile.name", "work").is_err());
+
+        settings = assign(&settings, "profiles.work.url", None)?;
+        assert!(load(settings.clone())?.profile(Some("work"))?.url.is_none());
+        assert!(assign(&settings, "profiles.work.url", None).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn unknown_settings_can_be_fixed() -> Result<()> {
+        let broken: Table = toml::from_str(
+            r#"
+                [profiles.work]
+                colour = "blue"
+            "#,
+        )
+        .map_err(error::Config::from)?;
+        assert!(load(broken.clone()).is_err());
+
+        let settings = set(&broken, "profiles.work.trigger_unlock", "true")?;
+        let fixed = assign(&settings, "profiles.work.colour", None)?;
+        assert_eq!(
+            load(fixed)?.profile(Some("work"))?.trigger_unlock,
+            Some(true)
+        );
+        Ok(())
+    }
+}
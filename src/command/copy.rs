@@ -0,0 +1,89 @@
+// SPDX-FileCopyrightText: 2024 Noah Fontes
+//
+// SPDX-License-Identifier: Apache-2.0
+
+use async_trait::async_trait;
+use clap::Parser;
+use log::info;
+use url::Url;
+use uuid::Uuid;
+
+use super::Context;
+use crate::{
+    client::{same_name, Client, Entry, EntryRef},
+    error::{self, Result},
+};
+
+/// Copy an entry to another backend. If an entry with the same title already
+/// exists in the destination group, it is updated instead.
+#[derive(Debug, Parser)]
+pub(crate) struct Command {
+    /// The URL to copy the entry from. Defaults to the URL selected globally.
+    #[arg(long, value_parser = Url::parse, value_hint = clap::ValueHint::Url)]
+    from: Option<Url>,
+
+    /// The URL to copy the entry to.
+    #[arg(long, value_parser = Url::parse, value_hint = clap::ValueHint::Url)]
+    to: Url,
+
+    /// The group to place the entry in at the destination, with each
+    /// subgroup separated by a slash. Defaults to the group given in the path
+    /// of the entry, or the root group if the entry is given by ID.
+    #[arg(long)]
+    to_group: Option<String>,
+
+    /// The path (`Group/Subgroup/Title`) or ID of the entry to copy.
+    #[clap()]
+    entry: EntryRef,
+}
+
+async fn copy(target: &(dyn Client + Send + Sync), groups: &[&str], entry: &Entry) -> Result<()> {
+    // The destination may match titles loosely, but only an entry with
+    // exactly this title is the same one. Backends prefer an exact match, so
+    // if they found another, there isn't one.
+    match target
+        .get_entry(&mut groups.iter().copied(), &entry.title)
+        .await
+    {
+        Ok(existing) if same_name(&existing.title, &entry.title) => {
+            info!("Updating existing entry {} at destination", existing.id);
+            target
+                .update_entry(
+                    Uuid::try_parse(&existing.id).map_err(error::Conversion::from)?,
+                    entry,
+                )
+                .await
+        }
+        Ok(_) | Err(error::Error::EntryNotFound { .. }) => {
+            info!("Creating new entry at destination");
+            target
+                .create_entry(&mut groups.iter().copied(), entry)
+                .await
+        }
+        Err(err) => Err(err),
+    }
+}
+
+#[async_trait]
+impl super::Command for Command {
+    fn url(&self) -> Option<&Url> {
+        self.from.as_ref()
+    }
+
+    async fn execute(self, client: impl Client + Send + Sync, ctx: &Context<'_>) -> Result<()> {
+        let entry_ref = ctx.resolve_entry(&self.entry);
+        let entry = client.get_entry_by_ref(&entry_ref).await?;
+
+        let group_names: Vec<String> = match (self.to_group.as_deref(), entry_ref) {
+            (Some(path), _) => ctx.resolve_group_path(path),
+            (None, EntryRef::Path { groups, .. }) => groups,
+            (None, EntryRef::Id(_)) => vec![],
+        };
+        let groups: Vec<&str> = group_names.iter().map(String::as_str).collect();
+
+        let channel = ctx.connector.connect(&self.to).await?;
+        let result = copy(channel.client(), &groups, &entry).await;
+        channel.close().await?;
+        result
+    }
+}
@@ -0,0 +1,435 @@
+// SPDX-FileCopyrightText: 2024 Noah Fontes
+//
+// SPDX-License-Identifier: Apache-2.0
+
+use std::io;
+
+use async_trait::async_trait;
+use clap::{Parser, ValueEnum};
+use rustyline::{
+    completion::Completer, error::ReadlineError, highlight::Highlighter, hint::Hinter,
+    history::DefaultHistory, validate::Validator, Editor, Helper,
+};
+use secrecy::{ExposeSecret as _, SecretString};
+use tabled::{
+    settings::{
+        object::{Rows, Segment},
+        Alignment, Format, Modify, Style,
+    },
+    Table, Tabled,
+};
+use tokio::{runtime::Handle, task};
+
+use super::Context;
+use crate::{
+    client::{Client, Entry, EntryRef, FormField, FormFieldType},
+    desktop,
+    error::{self, Result},
+    generator, locale, progress, reveal,
+};
+
+/// Create an entry. Either give its path and contents as arguments, or use
+/// `--interactive` to be asked for each part of it in turn.
+#[derive(Debug, Parser)]
+pub(crate) struct Command {
+    /// Ask for the group, title, username, password, URL, and any other
+    /// fields of the entry one at a time, completing group names from the
+    /// database, and show a summary to confirm before creating it.
+    #[arg(long, conflicts_with_all = ["entry", "username", "entry_urls", "fields", "password_stdin", "generate_password"])]
+    interactive: bool,
+
+    /// The username of the entry.
+    #[arg(long)]
+    username: Option<String>,
+
+    /// A URL of the entry. Give this more than once for several URLs.
+    #[arg(long = "entry-url", value_name = "URL", value_hint = clap::ValueHint::Url)]
+    entry_urls: Vec<String>,
+
+    /// Another field of the entry, as `NAME=VALUE`. Give this more than once
+    /// for several fields.
+    #[arg(long = "field", value_name = "NAME=VALUE", value_parser = parse_field)]
+    fields: Vec<(String, String)>,
+
+    /// Read the password from the first line of standard input, instead of
+    /// asking for it on the terminal.
+    #[arg(long)]
+    password_stdin: bool,
+
+    /// Generate the password instead of asking for it, either ourselves or
+    /// with the backend's generator, and print it once the entry is created.
+    #[arg(
+        long,
+        value_enum,
+        value_name = "GENERATOR",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "local",
+        conflicts_with = "password_stdin"
+    )]
+    generate_password: Option<PasswordSource>,
+
+    /// Copy the generated password to the clipboard instead of printing it.
+    #[arg(long, requires = "generate_password")]
+    copy_password: bool,
+
+    /// The path (`Group/Subgroup/Title`) of the entry to create.
+    #[arg(required_unless_present = "interactive")]
+    entry: Option<EntryRef>,
+}
+
+/// Where a generated password comes from.
+#[derive(Copy, Clone, Debug, ValueEnum)]
+pub(super) enum PasswordSource {
+    /// Our own generator, which works with any backend.
+    Local,
+    /// The generator of KeePass or KeePassXC, with its default settings.
+    Server,
+}
+
+impl PasswordSource {
+    pub(super) async fn generate(
+        self,
+        client: &(dyn Client + Send + Sync),
+    ) -> Result<SecretString> {
+        match self {
+            Self::Local => Ok(generator::password(generator::DEFAULT_PASSWORD_LENGTH)),
+            Self::Server => client.generate_password().await,
+        }
+    }
+}
+
+/// Show a generated password once it's been saved, so it's never shown for
+/// an entry that couldn't be.
+pub(super) fn reveal(password: &SecretString, title: &str, copy: bool) -> Result<()> {
+    if copy {
+        desktop::copy(password)?;
+        desktop::notify_copied(&FormFieldType::Password, title, None);
+        println!("{}", locale::text("generated-password-copied"));
+    } else {
+        reveal::print([password.expose_secret().as_str()])?;
+    }
+    Ok(())
+}
+
+pub(super) fn parse_field(arg: &str) -> Result<(String, String), String> {
+    match arg.split_once('=') {
+        Some((name, value)) if !name.is_empty() => Ok((name.to_owned(), value.to_owned())),
+        Some(_) | None => Err("expected NAME=VALUE".to_owned()),
+    }
+}
+
+pub(super) fn field(type_: FormFieldType, display_name: &str, value: SecretString) -> FormField {
+    FormField {
+        type_,
+        display_name: display_name.to_owned(),
+        value,
+        metadata: None,
+    }
+}
+
+/// The parts of a new entry, in the order we ask for them.
+#[derive(Default)]
+struct Draft {
+    groups: Vec<String>,
+    title: String,
+    username: Option<String>,
+    password: Option<SecretString>,
+    urls: Vec<String>,
+    fields: Vec<(String, String)>,
+}
+
+impl Draft {
+    fn into_entry(self) -> (Vec<String>, Entry) {
+        let form_fields =
+            self.username
+                .map(|username| {
+                    field(
+                        FormFieldType::Username,
+                        "Username",
+                        SecretString::new(username),
+                    )
+                })
+                .into_iter()
+                .chain(
+                    self.password
+                        .map(|password| field(FormFieldType::Password, "Password", password)),
+                )
+                .chain(self.fields.into_iter().map(|(name, value)| {
+                    field(FormFieldType::Text, &name, SecretString::new(value))
+                }))
+                .collect();
+
+        let entry = Entry {
+            id: String::new(),
+            parent: None,
+            title: self.title,
+            urls: self.urls,
+            form_fields,
+            database: None,
+            source: None,
+        };
+        (self.groups, entry)
+    }
+}
+
+#[derive(Tabled)]
+struct Row {
+    #[tabled(rename = "Field")]
+    field: String,
+    #[tabled(rename = "Value")]
+    value: String,
+}
+
+fn summary(groups: &[String], entry: &Entry) -> Table {
+    let rows = [
+        Row {
+            field: locale::header("Group"),
+            value: format!("/{}", groups.join("/")),
+        },
+        Row {
+            field: locale::header("Title"),
+            value: entry.title.clone(),
+        },
+    ]
+    .into_iter()
+    .chain(entry.form_fields.iter().map(|field| Row {
+        field: field.display_name.clone(),
+        value: field.format_value(),
+    }))
+    .chain(entry.urls.iter().map(|url| Row {
+        field: "URL".to_owned(),
+        value: url.clone(),
+    }));
+
+    let mut table = Table::new(rows);
+    _ = table
+        .with(Style::rounded())
+        .with(Modify::new(Rows::first()).with(Format::content(locale::header)))
+        .with(Modify::new(Segment::all()).with(Alignment::left()));
+    table
+}
+
+/// Completes the group path typed so far by listing the groups in the
+/// database as the user goes.
+struct GroupCompleter<'ctx> {
+    client: &'ctx (dyn Client + Send + Sync),
+    ctx: &'ctx Context<'ctx>,
+    handle: Handle,
+}
+
+impl Completer for GroupCompleter<'_> {
+    type Candidate = String;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _: &rustyline::Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<String>)> {
+        let partial = line.get(..pos).unwrap_or(line);
+        let start = partial.rfind('/').map_or(0, |pos| pos + 1);
+        let (parent, fragment) = partial.split_at(start);
+
+        let groups = self.ctx.resolve_group_path(parent);
+        // We're called from inside `block_in_place`, so we can wait on the
+        // runtime here.
+        let result = self.handle.block_on(
+            self.client
+                .list_group(&mut groups.iter().map(String::as_str)),
+        );
+        Ok(match result {
+            Ok(listing) => (
+                start,
+                listing
+                    .groups
+                    .into_iter()
+                    .filter(|group| group.starts_with(fragment))
+                    .map(|group| format!("{group}/"))
+                    .collect(),
+            ),
+            Err(_) => (start, vec![]),
+        })
+    }
+}
+
+impl Hinter for GroupCompleter<'_> {
+    type Hint = String;
+}
+
+impl Highlighter for GroupCompleter<'_> {}
+
+impl Validator for GroupCompleter<'_> {}
+
+impl Helper for GroupCompleter<'_> {}
+
+fn ask<H: Helper>(editor: &mut Editor<H, DefaultHistory>, id: &str) -> Result<String> {
+    match task::block_in_place(|| editor.readline(&locale::text(id))) {
+        Ok(line) => Ok(line.trim().to_owned()),
+        Err(ReadlineError::Interrupted | ReadlineError::Eof) => Err(error::Error::Cancelled),
+        Err(e) => Err(e.into()),
+    }
+}
+
+pub(super) fn read_stdin() -> Result<SecretString> {
+    let mut line = String::new();
+    let _ = io::stdin().read_line(&mut line)?;
+    Ok(SecretString::new(
+        line.trim_end_matches(['\r', '\n']).to_owned(),
+    ))
+}
+
+pub(super) async fn ask_hidden(id: &str) -> Result<SecretString> {
+    let prompt = locale::text(id);
+    Ok(
+        task::spawn_blocking(move || rpassword::prompt_password(prompt).map(SecretString::new))
+            .await??,
+    )
+}
+
+/// Ask for each part of a new entry in turn.
+async fn wizard(client: &(dyn Client + Send + Sync), ctx: &Context<'_>) -> Result<Draft> {
+    let mut draft = Draft::default();
+    let mut editor = Editor::<GroupCompleter<'_>, DefaultHistory>::new()?;
+
+    editor.set_helper(Some(GroupCompleter {
+        client,
+        ctx,
+        handle: Handle::current(),
+    }));
+    draft.groups = ctx.resolve_group_path(&ask(&mut editor, "create-group")?);
+    editor.set_helper(None);
+
+    while draft.title.is_empty() {
+        draft.title = ask(&mut editor, "create-title")?;
+    }
+    draft.username = Some(ask(&mut editor, "create-username")?).filter(|u| !u.is_empty());
+
+    let password = ask_hidden("create-password").await?;
+    draft.password = Some(if password.expose_secret().is_empty() {
+        println!("{}", locale::text("create-password-generated"));
+        generator::password(generator::DEFAULT_PASSWORD_LENGTH)
+    } else {
+        password
+    });
+
+    let url = ask(&mut editor, "create-url")?;
+    if !url.is_empty() {
+        draft.urls.push(url);
+    }
+
+    loop {
+        let name = ask(&mut editor, "create-field-name")?;
+        if name.is_empty() {
+            break;
+        }
+        let value = ask(&mut editor, "create-field-value")?;
+        draft.fields.push((name, value));
+    }
+
+    Ok(draft)
+}
+
+impl Command {
+    async fn draft(self, client: &(dyn Client + Send + Sync), ctx: &Context<'_>) -> Result<Draft> {
+        let (groups, title) = match self.entry.as_ref().map(|entry| ctx.resolve_entry(entry)) {
+            Some(EntryRef::Path { groups, title, .. }) => (groups, title),
+            Some(EntryRef::Id(id)) => {
+                return Err(error::Error::InvalidEntryRef(id.simple().to_string()))
+            }
+            None => return Err(error::Error::Command),
+        };
+
+        let password = if let Some(source) = self.generate_password {
+            source.generate(client).await?
+        } else if self.password_stdin {
+            read_stdin()?
+        } else {
+            let _pause = progress::pause();
+            ask_hidden("create-password-terminal").await?
+        };
+
+        Ok(Draft {
+            groups,
+            title,
+            username: self.username,
+            password: Some(password).filter(|given| !given.expose_secret().is_empty()),
+            urls: self.entry_urls,
+            fields: self.fields,
+        })
+    }
+}
+
+#[async_trait]
+impl super::Command for Command {
+    async fn execute(self, client: impl Client + Send + Sync, ctx: &Context<'_>) -> Result<()> {
+        let reveal_copy = self.generate_password.map(|_| self.copy_password);
+        let draft = if self.interactive {
+            ctx.require_input()?;
+            let _pause = progress::pause();
+            let draft = wizard(&client, ctx).await?;
+            let (groups, entry) = draft.into_entry();
+            println!("{}", summary(&groups, &entry));
+
+            let answer = {
+                let mut editor = Editor::<(), DefaultHistory>::new()?;
+                ask(&mut editor, "create-confirm")?
+            };
+            if !answer
+                .to_lowercase()
+                .starts_with(&locale::text("create-confirm-yes"))
+            {
+                return Err(error::Error::Cancelled);
+            }
+            (groups, entry)
+        } else {
+            self.draft(&client, ctx).await?.into_entry()
+        };
+
+        let (groups, entry) = draft;
+        client
+            .create_entry(&mut groups.iter().map(String::as_str), &entry)
+            .await?;
+        println!("{}", locale::text("create-created"));
+        if let Some(copy) = reveal_copy {
+            if let Some(password) = entry
+                .form_fields
+                .iter()
+                .find(|field| field.type_ == FormFieldType::Password)
+            {
+                reveal(&password.value, &entry.title, copy)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fields_are_split_at_the_first_equals_sign() {
+        assert_eq!(
+            parse_field("PIN=12=34"),
+            Ok(("PIN".to_owned(), "12=34".to_owned()))
+        );
+        assert!(parse_field("=1234").is_err());
+        assert!(parse_field("PIN").is_err());
+    }
+
+    #[test]
+    fn drafts_leave_out_missing_fields() {
+        let (groups, entry) = Draft {
+            groups: vec!["Servers".to_owned()],
+            title: "web01".to_owned(),
+            password: Some(SecretString::new("hunter2".to_owned())),
+            fields: vec![("PIN".to_owned(), "1234".to_owned())],
+            ..Draft::default()
+        }
+        .into_entry();
+        assert_eq!(groups, ["Servers"]);
+        let types: Vec<_> = entry.form_fields.iter().map(|field| field.type_).collect();
+        assert_eq!(types, [FormFieldType::Password, FormFieldType::Text]);
+    }
+}
@@ -0,0 +1,184 @@
+// SPDX-FileCopyrightText: 2024 Noah Fontes
+//
+// SPDX-License-Identifier: Apache-2.0
+
+use std::fmt::{self, Display, Formatter};
+
+use async_trait::async_trait;
+use clap::Parser;
+use secrecy::ExposeSecret as _;
+use subtle::ConstantTimeEq as _;
+use tabled::{
+    settings::{
+        object::{Rows, Segment},
+        Alignment, Format, Modify, Style,
+    },
+    Table, Tabled,
+};
+use url::Url;
+
+use super::Context;
+use crate::{
+    client::{Client, Entry, EntryRef, FormField},
+    error::Result,
+    locale, pager,
+};
+
+/// Compare the fields of two entries. Secret values are compared, but never
+/// displayed.
+#[derive(Debug, Parser)]
+pub(crate) struct Command {
+    /// Look up the second entry using a different URL instead of the one the
+    /// first entry is retrieved from.
+    #[arg(long, value_parser = Url::parse, value_hint = clap::ValueHint::Url)]
+    other_url: Option<Url>,
+
+    /// The path (`Group/Subgroup/Title`) or ID of the first entry.
+    #[clap()]
+    first: EntryRef,
+
+    /// The path (`Group/Subgroup/Title`) or ID of the second entry.
+    #[clap()]
+    second: EntryRef,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub(super) enum Status {
+    Equal,
+    Different,
+    MissingFromFirst,
+    MissingFromSecond,
+}
+
+impl Display for Status {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(&locale::text(match *self {
+            Self::Equal => "diff-equal",
+            Self::Different => "diff-different",
+            Self::MissingFromFirst => "diff-missing-from-first",
+            Self::MissingFromSecond => "diff-missing-from-second",
+        }))
+    }
+}
+
+#[derive(Clone, Debug, Tabled)]
+pub(super) struct Row {
+    #[tabled(rename = "Field")]
+    pub(super) field: String,
+    #[tabled(rename = "First")]
+    pub(super) first: String,
+    #[tabled(rename = "Second")]
+    pub(super) second: String,
+    #[tabled(rename = "Status")]
+    pub(super) status: Status,
+}
+
+impl Row {
+    pub(super) fn new_plain(field: &str, first: String, second: String) -> Self {
+        let status = if first == second {
+            Status::Equal
+        } else {
+            Status::Different
+        };
+        Self {
+            field: field.to_owned(),
+            first,
+            second,
+            status,
+        }
+    }
+
+    fn new_form_field(first: Option<&FormField>, second: Option<&FormField>) -> Option<Self> {
+        let (field, status) = match (first, second) {
+            (Some(a), Some(b)) => {
+                let equal = a
+                    .value
+                    .expose_secret()
+                    .as_bytes()
+                    .ct_eq(b.value.expose_secret().as_bytes());
+                (
+                    a,
+                    if bool::from(equal) {
+                        Status::Equal
+                    } else {
+                        Status::Different
+                    },
+                )
+            }
+            (Some(a), None) => (a, Status::MissingFromSecond),
+            (None, Some(b)) => (b, Status::MissingFromFirst),
+            (None, None) => return None,
+        };
+
+        Some(Self {
+            field: format!("{} ({})", field.display_name, field.type_),
+            first: first.map(FormField::format_value).unwrap_or_default(),
+            second: second.map(FormField::format_value).unwrap_or_default(),
+            status,
+        })
+    }
+}
+
+pub(super) fn compare(first: &Entry, second: &Entry) -> Vec<Row> {
+    let format_group = |entry: &Entry| {
+        entry
+            .parent
+            .as_ref()
+            .map(|group| group.path.clone())
+            .unwrap_or_default()
+    };
+
+    let mut rows = vec![
+        Row::new_plain("Title", first.title.clone(), second.title.clone()),
+        Row::new_plain("Group", format_group(first), format_group(second)),
+    ];
+
+    // Fields are paired up by their type and display name in the order they
+    // appear, so repeated fields are compared positionally.
+    let mut unmatched: Vec<Option<&FormField>> = second.form_fields.iter().map(Some).collect();
+    for field in &first.form_fields {
+        let candidate = unmatched
+            .iter_mut()
+            .find(|candidate| {
+                candidate.is_some_and(|other| {
+                    other.type_ == field.type_ && other.display_name == field.display_name
+                })
+            })
+            .and_then(Option::take);
+        rows.extend(Row::new_form_field(Some(field), candidate));
+    }
+    rows.extend(
+        unmatched
+            .into_iter()
+            .flatten()
+            .filter_map(|field| Row::new_form_field(None, Some(field))),
+    );
+
+    rows
+}
+
+#[async_trait]
+impl super::Command for Command {
+    async fn execute(self, client: impl Client + Send + Sync, ctx: &Context<'_>) -> Result<()> {
+        let first = client
+            .get_entry_by_ref(&ctx.resolve_entry(&self.first))
+            .await?;
+        let second_ref = ctx.resolve_entry(&self.second);
+        let second = match self.other_url {
+            Some(ref url) => {
+                let channel = ctx.connector.connect(url).await?;
+                let result = channel.client().get_entry_by_ref(&second_ref).await;
+                channel.close().await?;
+                result?
+            }
+            None => client.get_entry_by_ref(&second_ref).await?,
+        };
+
+        pager::print(
+            Table::new(compare(&first, &second))
+                .with(Style::rounded())
+                .with(Modify::new(Rows::first()).with(Format::content(locale::header)))
+                .with(Modify::new(Segment::all()).with(Alignment::left())),
+        )
+    }
+}
@@ -0,0 +1,390 @@
+// SPDX-FileCopyrightText: 2024 Noah Fontes
+//
+// SPDX-License-Identifier: Apache-2.0
+
+use std::{
+    env,
+    fs::{self, File},
+    io::{self, Read as _, Write as _},
+    path::PathBuf,
+    process,
+};
+
+use async_trait::async_trait;
+use clap::Parser;
+use rand::RngCore as _;
+use secrecy::{zeroize::Zeroize as _, ExposeSecret, SecretString};
+use serde::{Deserialize, Serialize, Serializer};
+use tabled::{
+    settings::{
+        object::{Rows, Segment},
+        Alignment, Format, Modify, Style,
+    },
+    Table, Tabled,
+};
+use uuid::Uuid;
+
+use super::{diff, Context};
+use crate::{
+    client::{Client, Entry, EntryRef, FormField, FormFieldType},
+    desktop,
+    error::{self, Result},
+    locale, progress, rng,
+};
+
+/// Change an entry in a text editor. The title, URLs, and form fields of the
+/// entry are written to a temporary TOML file, which is opened with `VISUAL`
+/// or `EDITOR` (or `vi`). Once the editor exits, the changes are shown and
+/// saved to the entry, and the file is overwritten and removed.
+#[derive(Debug, Parser)]
+pub(crate) struct Command {
+    /// Write the values of passwords to the file, so they can be changed too.
+    /// Otherwise they're left out, and keep their current value.
+    #[arg(long)]
+    with_secrets: bool,
+
+    /// The path (`Group/Subgroup/Title`) or ID of the entry.
+    #[clap()]
+    entry: EntryRef,
+}
+
+// LINT: The signature is dictated by Serde's `serialize_with`.
+#[allow(clippy::ref_option)]
+fn serialize_secret<S: Serializer>(
+    value: &Option<SecretString>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    value
+        .as_ref()
+        .map(ExposeSecret::expose_secret)
+        .serialize(serializer)
+}
+
+/// The parts of an entry that can be edited, as they're written to the file.
+#[derive(Debug, Deserialize, Serialize)]
+struct Document {
+    title: String,
+    #[serde(default)]
+    urls: Vec<String>,
+    #[serde(default, rename = "field")]
+    fields: Vec<Field>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct Field {
+    #[serde(rename = "type")]
+    type_: FormFieldType,
+    name: String,
+    /// The value of the field, unless it's a secret that was left out.
+    #[serde(
+        default,
+        serialize_with = "serialize_secret",
+        skip_serializing_if = "Option::is_none"
+    )]
+    value: Option<SecretString>,
+}
+
+impl Document {
+    fn new(entry: &Entry, with_secrets: bool) -> Self {
+        Self {
+            title: entry.title.clone(),
+            urls: entry.urls.clone(),
+            // One-time passwords are generated, not stored, so there's nothing
+            // to edit.
+            fields: entry
+                .form_fields
+                .iter()
+                .filter(|field| field.type_ != FormFieldType::Totp)
+                .map(|field| Field {
+                    type_: field.type_,
+                    name: field.display_name.clone(),
+                    value: (with_secrets || field.type_ != FormFieldType::Password)
+                        .then(|| field.value.clone()),
+                })
+                .collect(),
+        }
+    }
+
+    /// Make a copy of the original entry with the contents of the document.
+    /// Fields are matched to those of the original entry by their type and
+    /// name in the order they appear, to keep values that were left out and
+    /// what KeePassRPC knows about where they go in a form. One-time
+    /// passwords aren't in the document, so they're carried over as they are.
+    fn apply(self, original: &Entry) -> Result<Entry> {
+        let mut unmatched: Vec<Option<&FormField>> =
+            original.form_fields.iter().map(Some).collect();
+        let mut form_fields = Vec::with_capacity(self.fields.len());
+        for field in self.fields {
+            let matched = unmatched
+                .iter_mut()
+                .find(|candidate| {
+                    candidate.is_some_and(|other| {
+                        other.type_ == field.type_ && other.display_name == field.name
+                    })
+                })
+                .and_then(Option::take);
+            let value = match (field.value, matched) {
+                (Some(value), _) => value,
+                (None, Some(other)) => other.value.clone(),
+                (None, None) => return Err(error::Edit::MissingValue(field.name).into()),
+            };
+            form_fields.push(FormField {
+                type_: field.type_,
+                display_name: field.name,
+                value,
+                metadata: matched.and_then(|other| other.metadata.clone()),
+            });
+        }
+        form_fields.extend(
+            unmatched
+                .into_iter()
+                .flatten()
+                .filter(|field| field.type_ == FormFieldType::Totp)
+                .cloned(),
+        );
+
+        Ok(Entry {
+            title: self.title,
+            urls: self.urls,
+            form_fields,
+            ..original.clone()
+        })
+    }
+}
+
+/// A file only we can read, which is overwritten before it's removed.
+struct Scratch {
+    path: PathBuf,
+}
+
+impl Scratch {
+    fn create(contents: &str) -> Result<Self> {
+        // The runtime directory is private to the user and usually kept in
+        // memory, so prefer it to the shared temporary directory.
+        let dir = env::var_os("XDG_RUNTIME_DIR")
+            .filter(|dir| !dir.is_empty())
+            .map_or_else(env::temp_dir, PathBuf::from);
+        let name = rng::map(|rng| format!("karp-edit-{:016x}.toml", rng.next_u64()));
+        let path = dir.join(name);
+
+        let mut options = fs::OpenOptions::new();
+        let _ = options.write(true).create_new(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt as _;
+
+            let _ = options.mode(0o600);
+        }
+        let mut file = options.open(&path)?;
+        let scratch = Self { path };
+        file.write_all(contents.as_bytes())?;
+        file.sync_all()?;
+        Ok(scratch)
+    }
+
+    fn read(&self) -> Result<SecretString> {
+        let mut contents = String::new();
+        let _ = File::open(&self.path)?.read_to_string(&mut contents)?;
+        Ok(SecretString::new(contents))
+    }
+
+    fn shred(&self) -> io::Result<()> {
+        // Editors may have replaced the file rather than writing to it, so
+        // its length may have changed since we wrote it.
+        let len = fs::metadata(&self.path)?.len();
+        let mut file = fs::OpenOptions::new().write(true).open(&self.path)?;
+        let _ = io::copy(&mut io::repeat(0).take(len), &mut file)?;
+        file.sync_all()
+    }
+}
+
+impl Drop for Scratch {
+    fn drop(&mut self) {
+        if let Err(e) = self.shred() {
+            log::warn!("We could not overwrite {}: {}", self.path.display(), e);
+        }
+        if let Err(e) = fs::remove_file(&self.path) {
+            log::warn!("We could not remove {}: {}", self.path.display(), e);
+        }
+    }
+}
+
+fn editor() -> Vec<String> {
+    ["VISUAL", "EDITOR"]
+        .into_iter()
+        .filter_map(|var| env::var(var).ok())
+        .filter_map(|editor| shell_words::split(&editor).ok())
+        .find(|words| !words.is_empty())
+        .unwrap_or_else(|| vec!["vi".to_owned()])
+}
+
+fn run_editor(scratch: &Scratch) -> Result<()> {
+    let command = editor();
+    let Some((name, args)) = command.split_first() else {
+        return Err(error::Error::Command);
+    };
+
+    let _pause = progress::pause();
+    let status = process::Command::new(name)
+        .args(args)
+        .arg(&scratch.path)
+        .status()?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(error::Edit::EditorFailed {
+            program: name.clone(),
+            status,
+        }
+        .into())
+    }
+}
+
+#[derive(Tabled)]
+struct Change {
+    #[tabled(rename = "Field")]
+    field: String,
+    #[tabled(rename = "Before")]
+    before: String,
+    #[tabled(rename = "After")]
+    after: String,
+}
+
+fn changes(before: &Entry, after: &Entry) -> Vec<Change> {
+    let urls = diff::Row::new_plain("URLs", before.urls.join(" "), after.urls.join(" "));
+    diff::compare(before, after)
+        .into_iter()
+        .chain([urls])
+        .filter(|row| row.status != diff::Status::Equal)
+        .map(|row| Change {
+            field: row.field,
+            before: row.first,
+            after: row.second,
+        })
+        .collect()
+}
+
+#[async_trait]
+impl super::Command for Command {
+    async fn execute(self, client: impl Client + Send + Sync, ctx: &Context<'_>) -> Result<()> {
+        ctx.require_input()?;
+        let entry = client
+            .get_entry_by_ref(&ctx.resolve_entry(&self.entry))
+            .await?;
+        let id = Uuid::try_parse(&entry.id).map_err(error::Conversion::from)?;
+        if self.with_secrets {
+            desktop::verify_user()?;
+        }
+
+        let mut contents = format!("# {}\n\n", locale::text("edit-instructions"));
+        let mut document = toml::to_string(&Document::new(&entry, self.with_secrets))
+            .map_err(error::Edit::Serialize)?;
+        contents.push_str(&document);
+        document.zeroize();
+        let created = Scratch::create(&contents);
+        contents.zeroize();
+        let scratch = created?;
+
+        run_editor(&scratch)?;
+        let edited = scratch.read()?;
+        drop(scratch);
+        let document: Document =
+            toml::from_str(edited.expose_secret()).map_err(error::Edit::Parse)?;
+        let updated = document.apply(&entry)?;
+
+        let changes = changes(&entry, &updated);
+        if changes.is_empty() {
+            println!("{}", locale::text("edit-unchanged"));
+            return Ok(());
+        }
+        println!(
+            "{}",
+            Table::new(changes)
+                .with(Style::rounded())
+                .with(Modify::new(Rows::first()).with(Format::content(locale::header)))
+                .with(Modify::new(Segment::all()).with(Alignment::left()))
+        );
+        client.update_entry(id, &updated).await?;
+        println!("{}", locale::text("edit-saved"));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry() -> Entry {
+        let field = |type_, display_name: &str, value: &str| FormField {
+            type_,
+            display_name: display_name.to_owned(),
+            value: SecretString::new(value.to_owned()),
+            metadata: None,
+        };
+        Entry {
+            id: "04040404040404040404040404040404".to_owned(),
+            parent: None,
+            title: "Example".to_owned(),
+            urls: vec!["https://example.com".to_owned()],
+            form_fields: vec![
+                field(FormFieldType::Username, "Username", "alice"),
+                field(FormFieldType::Password, "Password", "hunter2"),
+                field(FormFieldType::Totp, "TOTP", "123456"),
+            ],
+            database: None,
+            source: None,
+        }
+    }
+
+    #[test]
+    fn secrets_are_left_out_and_kept() -> Result<()> {
+        let original = entry();
+        let text =
+            toml::to_string(&Document::new(&original, false)).map_err(error::Edit::Serialize)?;
+        assert!(!text.contains("hunter2"));
+        assert!(!text.contains("123456"));
+
+        let edited = text.replace("alice", "bob");
+        let document: Document = toml::from_str(&edited).map_err(error::Edit::Parse)?;
+        let updated = document.apply(&original)?;
+        let values: Vec<_> = updated
+            .form_fields
+            .iter()
+            .map(|field| field.value.expose_secret().as_str())
+            .collect();
+        assert_eq!(values, ["bob", "hunter2", "123456"]);
+        Ok(())
+    }
+
+    #[test]
+    fn unchanged_documents_have_no_changes() -> Result<()> {
+        let original = entry();
+        for with_secrets in [false, true] {
+            let text = toml::to_string(&Document::new(&original, with_secrets))
+                .map_err(error::Edit::Serialize)?;
+            let document: Document = toml::from_str(&text).map_err(error::Edit::Parse)?;
+            let updated = document.apply(&original)?;
+            assert!(changes(&original, &updated).is_empty());
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn new_fields_need_a_value() -> Result<()> {
+        let document: Document = toml::from_str(
+            r#"
+                title = "Example"
+
+                [[field]]
+                type = "password"
+                name = "PIN"
+            "#,
+        )
+        .map_err(error::Edit::Parse)?;
+        assert!(matches!(
+            document.apply(&entry()),
+            Err(error::Error::Edit(error::Edit::MissingValue(_)))
+        ));
+        Ok(())
+    }
+}
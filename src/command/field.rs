@@ -0,0 +1,31 @@
+// SPDX-FileCopyrightText: 2024 Noah Fontes
+//
+// SPDX-License-Identifier: Apache-2.0
+
+use clap::Parser;
+
+use super::get_form_fields;
+use crate::client::FormFieldType;
+
+/// Print the value of the first field of one type in an entry, without having
+/// to know its index.
+#[derive(Debug, Parser)]
+pub(crate) struct Command {
+    /// The location of the entry to look up within the group hierarchy,
+    /// followed by the name of the entry. If the first group begins with a
+    /// slash, the location is not resolved against the group prefix of the
+    /// selected profile.
+    #[clap(value_name = "NAME", required = true)]
+    names: Vec<String>,
+
+    /// Print the value as it is stored, instead of expanding KeePass
+    /// placeholders like `{USERNAME}` or `{REF:P@I:...}` in it.
+    #[arg(long)]
+    no_resolve_placeholders: bool,
+}
+
+impl Command {
+    pub(crate) fn into_get_form_fields(self, type_: FormFieldType) -> get_form_fields::Command {
+        get_form_fields::Command::first_of_type(type_, self.names, self.no_resolve_placeholders)
+    }
+}
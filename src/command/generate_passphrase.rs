@@ -0,0 +1,67 @@
+// SPDX-FileCopyrightText: 2024 Noah Fontes
+//
+// SPDX-License-Identifier: Apache-2.0
+
+use std::{fs, num::NonZeroUsize, path::PathBuf};
+
+use clap::Parser;
+use log::debug;
+use secrecy::ExposeSecret as _;
+
+use crate::{
+    error::{self, Result},
+    generator, metadata, reveal,
+};
+
+/// The name of the word list we look for in the data directory.
+const WORDLIST_FILE: &str = "eff_large_wordlist.txt";
+
+/// Generate a passphrase of random words without asking a backend, for
+/// backends that can't generate one or when a passphrase is easier to type
+/// than a password. Words are drawn from a diceware word list, like the EFF
+/// large word list, which can be saved as `eff_large_wordlist.txt` in the
+/// data directory.
+#[derive(Debug, Parser)]
+pub(crate) struct Command {
+    /// The number of words in the passphrase. With the EFF large word list,
+    /// the default of 6 gives a little over 77 bits of entropy.
+    #[arg(long, default_value = "6")]
+    words: NonZeroUsize,
+
+    /// The text to put between words.
+    #[arg(long, default_value = "-")]
+    separator: String,
+
+    /// The word list to draw from, with one word to a line, optionally after
+    /// the dice rolls that select it. Defaults to `eff_large_wordlist.txt` in
+    /// the data directory.
+    #[arg(long, value_hint = clap::ValueHint::FilePath)]
+    wordlist: Option<PathBuf>,
+}
+
+impl Command {
+    pub(crate) fn run(self) -> Result<()> {
+        let path = self
+            .wordlist
+            .or_else(|| metadata::Dir::Data.file(WORDLIST_FILE))
+            .ok_or(error::Storage::NoProjectDirs)?;
+        let text = fs::read_to_string(&path).map_err(|source| error::Generator::ReadWordlist {
+            path: path.clone(),
+            source,
+        })?;
+
+        let wordlist = generator::parse_wordlist(&text);
+        if wordlist.len() < 2 {
+            return Err(error::Generator::ShortWordlist { path }.into());
+        }
+        debug!(
+            "Drawing {} words from a list of {}, for {:.1} bits of entropy",
+            self.words,
+            wordlist.len(),
+            generator::entropy(wordlist.len(), self.words.get()),
+        );
+
+        let passphrase = generator::passphrase(&wordlist, self.words.get(), &self.separator);
+        reveal::print([passphrase.expose_secret().as_str()])
+    }
+}
@@ -2,23 +2,35 @@
 //
 // SPDX-License-Identifier: Apache-2.0
 
+use std::{
+    collections::BTreeMap,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
 use async_trait::async_trait;
 use clap::Parser;
 use log::error;
 use secrecy::ExposeSecret;
+use serde::Serialize;
 use tabled::{
-    settings::{object::Cell, Format, Modify, Style},
+    settings::{
+        object::{Cell, Rows},
+        Format, Modify, Panel, Style,
+    },
     Table,
 };
 
+use super::Context;
 use crate::{
-    client::{Client, FormFieldType},
+    client::{Client, Entry, EntryRef, FormField, FormFieldMetadata, FormFieldType},
+    desktop,
     error::{self, Result},
+    locale, output, pager, placeholder, reveal,
 };
 
 /// Get the form fields of an entry at a given path.
 #[derive(Debug, Parser)]
-#[command(allow_missing_positional = true)]
 pub(crate) struct Command {
     /// Filter the form fields returned to those matching a particular type.
     #[arg(long, short, value_enum)]
@@ -26,49 +38,342 @@ pub(crate) struct Command {
 
     /// The numerical index of a particular field to select. Indexing is
     /// performed after any filtering is applied. When this option is selected,
-    /// only the field's value is printed.
+    /// only the field's value is printed. This option can be given more than
+    /// once to print several values, one per line.
     #[arg(long, short)]
-    index: Option<usize>,
+    index: Vec<usize>,
+
+    /// Print the value of every field that matches the filter, one per line,
+    /// instead of a table. With `--entry`, the values of each entry are given
+    /// as an object keyed by display name; if several fields share a display
+    /// name, the first is used.
+    #[arg(long, conflicts_with = "index")]
+    all_values: bool,
+
+    /// Print values as they are stored, instead of expanding KeePass
+    /// placeholders like `{USERNAME}` or `{REF:P@I:...}` in them.
+    #[arg(long)]
+    no_resolve_placeholders: bool,
 
-    /// The location of the entry to look up within the group hierarchy.
-    #[clap()]
-    groups: Vec<String>,
+    /// Read the entries to look up from a file, or from standard input if the
+    /// file is `-`. Each entry is given by its path (`Group/Subgroup/Title`)
+    /// or ID, separated by NUL characters or, if there are none, by newlines.
+    #[arg(long, value_hint = clap::ValueHint::FilePath, conflicts_with = "names")]
+    paths_from: Option<PathBuf>,
 
-    /// The name of the entry to look up.
-    #[clap()]
-    entry: String,
+    /// The path (`Group/Subgroup/Title`) or ID of an entry to look up. This
+    /// option can be given more than once. The results are printed as a JSON
+    /// object keyed by each entry as given here, as with `--output json`.
+    #[arg(long = "entry", value_name = "ENTRY", conflicts_with_all = ["names", "paths_from"])]
+    entries: Vec<String>,
+
+    /// The location of the entry to look up within the group hierarchy,
+    /// followed by the name of the entry. If the first group begins with a
+    /// slash, the location is not resolved against the group prefix of the
+    /// selected profile.
+    #[clap(value_name = "NAME", required_unless_present_any = ["paths_from", "entries"])]
+    names: Vec<String>,
 }
 
-#[async_trait]
-impl super::Command for Command {
-    async fn execute(self, client: impl Client + Send) -> Result<()> {
+/// Parse each entry as given, keeping what was given to key the output.
+fn parse_specs(specs: Vec<String>) -> Result<Vec<(String, EntryRef)>> {
+    specs
+        .into_iter()
+        .map(|spec| {
+            let entry_ref = spec.parse()?;
+            Ok((spec, entry_ref))
+        })
+        .collect()
+}
+
+fn read_paths(source: &Path) -> Result<Vec<String>> {
+    let data = if source == Path::new("-") {
+        io::read_to_string(io::stdin())?
+    } else {
+        fs::read_to_string(source)?
+    };
+
+    let separator = if data.contains('\0') { '\0' } else { '\n' };
+    Ok(data
+        .split(separator)
+        .map(|path| path.trim_end_matches('\r'))
+        .filter(|path| !path.is_empty())
+        .map(str::to_owned)
+        .collect())
+}
+
+#[derive(Serialize)]
+struct JsonField<'entry> {
+    #[serde(rename = "type")]
+    type_: FormFieldType,
+    display_name: &'entry str,
+    value: &'entry str,
+    #[serde(flatten)]
+    metadata: Option<&'entry FormFieldMetadata>,
+}
+
+impl<'entry> From<&'entry FormField> for JsonField<'entry> {
+    fn from(value: &'entry FormField) -> Self {
+        Self {
+            type_: value.type_,
+            display_name: &value.display_name,
+            value: value.value.expose_secret(),
+            metadata: value.metadata.as_ref(),
+        }
+    }
+}
+
+/// The fields of one entry in JSON output: either every field that matches
+/// the filter, the value of the one field selected by index, or the values
+/// of several selected fields keyed by display name.
+#[derive(Serialize)]
+#[serde(untagged)]
+enum JsonResult<'entry> {
+    Fields(Vec<JsonField<'entry>>),
+    Value(&'entry str),
+    Values(BTreeMap<&'entry str, &'entry str>),
+}
+
+impl<'entry> JsonResult<'entry> {
+    fn values(fields: &[&'entry FormField]) -> Self {
+        let mut values = BTreeMap::new();
+        for field in fields {
+            let _ = values
+                .entry(field.display_name.as_str())
+                .or_insert_with(|| field.value.expose_secret().as_str());
+        }
+        Self::Values(values)
+    }
+}
+
+impl Command {
+    /// Look up only the value of the first field of the given type.
+    pub(super) fn first_of_type(
+        type_: FormFieldType,
+        names: Vec<String>,
+        no_resolve_placeholders: bool,
+    ) -> Self {
+        Self {
+            type_: Some(type_),
+            index: vec![0],
+            all_values: false,
+            no_resolve_placeholders,
+            paths_from: None,
+            entries: vec![],
+            names,
+        }
+    }
+
+    async fn fetch(
+        &self,
+        client: &(dyn Client + Send + Sync),
+        ctx: &Context<'_>,
+        entry_ref: &EntryRef,
+    ) -> Result<Entry> {
         let entry = client
-            .get_entry(&mut self.groups.iter().map(String::as_ref), &self.entry)
+            .get_entry_by_ref(&ctx.resolve_entry(entry_ref))
             .await?;
+        if self.no_resolve_placeholders {
+            Ok(entry)
+        } else {
+            placeholder::resolve(client, &entry).await
+        }
+    }
+
+    fn filter<'entry>(&self, entry: &'entry Entry) -> impl Iterator<Item = &'entry FormField> {
+        let type_ = self.type_;
+        entry
+            .form_fields
+            .iter()
+            .filter(move |field| type_.is_none_or(|field_type| field.type_ == field_type))
+    }
 
-        let mut fields_iter = entry.form_fields.into_iter().filter(|field| {
-            self.type_
-                .map_or(true, |field_type| field.type_ == field_type)
-        });
+    /// The fields whose values alone should be printed, if any were selected
+    /// by index or with `--all-values`.
+    fn select<'entry>(&self, entry: &'entry Entry) -> Result<Option<Vec<&'entry FormField>>> {
+        if self.all_values {
+            return Ok(Some(self.filter(entry).collect()));
+        }
+        if self.index.is_empty() {
+            return Ok(None);
+        }
+
+        let fields: Vec<_> = self.filter(entry).collect();
+        self.index
+            .iter()
+            .map(|&n| {
+                fields.get(n).copied().ok_or_else(|| {
+                    error!("No form field with index {}", n);
+                    error::Error::Command
+                })
+            })
+            .collect::<Result<_>>()
+            .map(Some)
+    }
+
+    /// The entry named by the positional arguments: its groups, then its
+    /// title.
+    fn named_entry(&self) -> Result<EntryRef> {
+        let Some((title, parents)) = self.names.split_last() else {
+            return Err(error::Error::Command);
+        };
+        Ok(EntryRef::Path {
+            groups: parents.to_vec(),
+            title: title.clone(),
+            absolute: false,
+        })
+    }
+
+    /// Print the entries as a JSON object keyed by each entry as it was
+    /// given.
+    async fn print_json(
+        &self,
+        client: impl Client + Send + Sync,
+        ctx: &Context<'_>,
+        specs: Vec<(String, EntryRef)>,
+    ) -> Result<()> {
+        let mut entries = BTreeMap::new();
+        for (spec, entry_ref) in specs {
+            let entry = self.fetch(&client, ctx, &entry_ref).await?;
+            let _ = entries.insert(spec, entry);
+        }
 
-        if let Some(n) = self.index {
-            if let Some(field) = fields_iter.nth(n) {
-                println!("{}", field.value.expose_secret());
-                Ok(())
+        desktop::verify_user()?;
+        let mut results = BTreeMap::new();
+        for (spec, entry) in &entries {
+            let selected = self.select(entry).inspect_err(|_| {
+                error!("We could not select the form fields of {}", spec);
+            })?;
+            let result = match selected.as_deref() {
+                Some(&[field]) if !self.all_values => {
+                    JsonResult::Value(field.value.expose_secret())
+                }
+                Some(fields) => JsonResult::values(fields),
+                None => JsonResult::Fields(self.filter(entry).map(JsonField::from).collect()),
+            };
+            let _ = results.insert(spec.as_str(), result);
+        }
+
+        println!("{}", serde_json::to_string_pretty(&results)?);
+        Ok(())
+    }
+
+    fn print(&self, entry: &Entry, header: Option<&str>) -> Result<()> {
+        if let Some(fields) = self.select(entry)? {
+            // The table masks secrets, so only showing values needs the user
+            // verified.
+            desktop::verify_user()?;
+            reveal::print(
+                fields
+                    .into_iter()
+                    .map(|field| field.value.expose_secret().as_str()),
+            )
+        } else {
+            // Only KeePassRPC tells us where fields go in a form, so we leave
+            // out the columns for it otherwise.
+            let mut table = if self.filter(entry).any(|field| field.metadata.is_some()) {
+                Table::new((0_u32..).zip(self.filter(entry)).map(|(i, field)| {
+                    (i, field.clone(), field.metadata.clone().unwrap_or_default())
+                }))
             } else {
-                error!("No form field with index {}", n);
-                Err(error::Error::Command)
+                Table::new((0_u32..).zip(self.filter(entry).cloned()))
+            };
+            let _ = table
+                .with(Style::rounded())
+                .with(Modify::new(Rows::first()).with(Format::content(locale::header)))
+                .with(
+                    Modify::new(Cell::new(0, 0)).with(Format::content(|_| locale::header("Index"))),
+                );
+            if let Some(text) = header {
+                let _ = table.with(Panel::header(text));
+            }
+            pager::print(table)
+        }
+    }
+}
+
+#[async_trait]
+impl super::Command for Command {
+    async fn execute(self, client: impl Client + Send + Sync, ctx: &Context<'_>) -> Result<()> {
+        if !self.entries.is_empty() {
+            let specs = parse_specs(self.entries.clone())?;
+            return self.print_json(client, ctx, specs).await;
+        }
+        if output::is_json() {
+            // The names are already split into groups and title, so they're
+            // only joined to key the output, never parsed again.
+            let specs = match self.paths_from {
+                Some(ref source) => parse_specs(read_paths(source)?)?,
+                None => vec![(self.names.join("/"), self.named_entry()?)],
+            };
+            return self.print_json(client, ctx, specs).await;
+        }
+
+        let Some(ref source) = self.paths_from else {
+            return self.print(&self.fetch(&client, ctx, &self.named_entry()?).await?, None);
+        };
+
+        let mut failed = false;
+        for path in read_paths(source)? {
+            let result = match path.parse::<EntryRef>() {
+                Ok(entry_ref) => self.fetch(&client, ctx, &entry_ref).await,
+                Err(e) => Err(e),
+            };
+            if let Err(e) = result.and_then(|entry| self.print(&entry, Some(&path))) {
+                error!("We could not get the form fields of {}: {}", path, e);
+                failed = true;
             }
+        }
+
+        if failed {
+            Err(error::Error::Command)
         } else {
-            println!(
-                "{}",
-                Table::new((0_u32..).zip(fields_iter))
-                    .with(Style::rounded())
-                    .with(
-                        Modify::new(Cell::new(0, 0)).with(Format::content(|_| "Index".to_owned()))
-                    )
-            );
             Ok(())
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use secrecy::SecretString;
+
+    use super::*;
+    use crate::client::PlaceholderHandling;
+
+    #[test]
+    fn json_result_shape() -> Result<()> {
+        let field = FormField {
+            type_: FormFieldType::Password,
+            display_name: "Password".to_owned(),
+            value: SecretString::new("hunter2".to_owned()),
+            metadata: None,
+        };
+
+        assert_eq!(
+            serde_json::to_string(&JsonResult::Fields(vec![(&field).into()]))?,
+            r#"[{"type":"password","display_name":"Password","value":"hunter2"}]"#
+        );
+        assert_eq!(
+            serde_json::to_string(&JsonResult::Value("hunter2"))?,
+            r#""hunter2""#
+        );
+        let field_with_metadata = FormField {
+            metadata: Some(FormFieldMetadata {
+                id: "pass".to_owned(),
+                name: "password".to_owned(),
+                page: 1,
+                placeholder_handling: PlaceholderHandling::Disabled,
+            }),
+            ..field.clone()
+        };
+        assert_eq!(
+            serde_json::to_string(&JsonField::from(&field_with_metadata))?,
+            r#"{"type":"password","display_name":"Password","value":"hunter2","id":"pass","name":"password","page":1,"placeholder_handling":"disabled"}"#
+        );
+        assert_eq!(
+            serde_json::to_string(&JsonResult::values(&[&field, &field]))?,
+            r#"{"Password":"hunter2"}"#
+        );
+        Ok(())
+    }
+}
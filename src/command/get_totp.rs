@@ -0,0 +1,138 @@
+// SPDX-FileCopyrightText: 2024 Noah Fontes
+//
+// SPDX-License-Identifier: Apache-2.0
+
+use std::{
+    num::NonZeroU64,
+    thread,
+    time::{Duration, SystemTime},
+};
+
+use async_trait::async_trait;
+use clap::Parser;
+use log::info;
+use secrecy::{ExposeSecret as _, SecretString};
+use tokio::task;
+
+use super::Context;
+use crate::{
+    client::{Client, EntryRef, FormFieldType},
+    desktop,
+    error::{self, Result},
+    hooks, locale, reveal,
+};
+
+/// Print the current time-based one-time password of an entry. Only
+/// KeePassXC generates these codes for us.
+#[derive(Debug, Parser)]
+pub(crate) struct Command {
+    /// Copy the code to the clipboard instead of printing it, and clear it
+    /// when the code expires. We wait until then before exiting, and leave
+    /// the clipboard alone if something else has been copied in the meantime.
+    #[arg(long)]
+    clip: bool,
+
+    /// How long each code is valid for, in seconds, which KeePassXC doesn't
+    /// tell us.
+    #[arg(long, value_name = "SECONDS", default_value = "30")]
+    period: NonZeroU64,
+
+    /// If the current code expires in less than this many seconds, wait for
+    /// the next one instead, so there's time to enter it.
+    #[arg(long, value_name = "SECONDS")]
+    next_if_under: Option<u64>,
+
+    /// The path (`Group/Subgroup/Title`) or ID of the entry.
+    #[clap()]
+    entry: EntryRef,
+}
+
+/// How long the code for the given time stays valid.
+fn remaining_at(now: Duration, period: NonZeroU64) -> Duration {
+    let period_ms = u128::from(period.get()) * 1000;
+    let left = period_ms - now.as_millis() % period_ms;
+    Duration::from_millis(u64::try_from(left).unwrap_or(u64::MAX))
+}
+
+fn remaining(period: NonZeroU64) -> Duration {
+    let now = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default();
+    remaining_at(now, period)
+}
+
+/// Look up the title of the entry and its current code.
+async fn code(
+    client: &(dyn Client + Send + Sync),
+    entry: &EntryRef,
+) -> Result<(String, SecretString)> {
+    let found = client.get_entry_by_ref(entry).await?;
+    let value = found
+        .form_fields
+        .into_iter()
+        .find(|field| field.type_ == FormFieldType::Totp)
+        .ok_or(error::Error::FieldTypeNotFound(FormFieldType::Totp))?
+        .value;
+    Ok((found.title, value))
+}
+
+async fn sleep(duration: Duration) -> Result<()> {
+    task::spawn_blocking(move || thread::sleep(duration)).await?;
+    Ok(())
+}
+
+#[async_trait]
+impl super::Command for Command {
+    async fn execute(self, client: impl Client + Send + Sync, ctx: &Context<'_>) -> Result<()> {
+        let entry = ctx.resolve_entry(&self.entry);
+        let (title, mut value) = code(&client, &entry).await?;
+        let mut left = remaining(self.period);
+        if let Some(under) = self.next_if_under {
+            if left < Duration::from_secs(under) {
+                info!("Waiting {:?} for the next code", left);
+                sleep(left).await?;
+                (_, value) = code(&client, &entry).await?;
+                left = remaining(self.period);
+            }
+        }
+
+        if !self.clip {
+            desktop::verify_user()?;
+            return reveal::print([value.expose_secret().as_str()]);
+        }
+
+        desktop::copy(&value)?;
+        desktop::notify_copied(&FormFieldType::Totp, &title, Some(left));
+        println!(
+            "{}",
+            locale::format("totp-clearing", &[("seconds", &left.as_secs())])
+        );
+        sleep(left).await?;
+        if desktop::clear(&value)? {
+            desktop::notify_cleared(&FormFieldType::Totp, &title);
+            hooks::fire(
+                hooks::Event::ClipboardCleared,
+                &[("KARP_ENTRY_TITLE", &title), ("KARP_FIELD", "totp")],
+            );
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn codes_expire_at_the_end_of_their_period() {
+        let period = NonZeroU64::MIN.saturating_add(29);
+        assert_eq!(
+            remaining_at(Duration::from_secs(75), period),
+            Duration::from_secs(15)
+        );
+        assert_eq!(
+            remaining_at(Duration::from_millis(89_500), period),
+            Duration::from_millis(500)
+        );
+    }
+}
@@ -0,0 +1,212 @@
+// SPDX-FileCopyrightText: 2024 Noah Fontes
+//
+// SPDX-License-Identifier: Apache-2.0
+
+use std::{
+    io::{self, IsTerminal as _, Write as _},
+    net::Ipv4Addr,
+    time::Duration,
+};
+
+use clap::Parser;
+use log::warn;
+use tokio::{net::TcpStream, task, time};
+use url::Url;
+
+use super::session;
+use crate::{
+    client::Connector,
+    config::Config,
+    error::{self, Error, Result},
+    keepassxc, locale, storage,
+};
+
+/// The profile we write when none is selected.
+const DEFAULT_PROFILE: &str = "default";
+
+/// Where KeePassRPC listens unless it's told otherwise.
+const KEEPASSRPC_ADDRESS: (Ipv4Addr, u16) = (Ipv4Addr::LOCALHOST, 12546);
+
+/// How long we give KeePassRPC to accept a connection when looking for it.
+const PROBE_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Set up Karp for the first time. We look for a running KeePassXC or
+/// KeePassRPC, connect to it so it can ask you to allow Karp, check that the
+/// session is kept so you aren't asked again, and save the connection as the
+/// URL of the selected profile (or `default`) in the configuration file.
+#[derive(Debug, Parser)]
+pub(crate) struct Command {
+    /// Replace the URL of a profile that already has one.
+    #[arg(long)]
+    force: bool,
+}
+
+/// What `init` needs from the rest of the command line.
+pub(crate) struct Setup<'args> {
+    pub(crate) config: Config,
+    /// The profile given on the command line.
+    pub(crate) profile: Option<&'args str>,
+    /// The URL given on the command line, which is used instead of looking.
+    pub(crate) url: Option<&'args Url>,
+    pub(crate) storage: storage::Options,
+    pub(crate) interactive: bool,
+}
+
+/// A backend we found listening.
+struct Candidate {
+    name: &'static str,
+    url: Url,
+}
+
+fn parse_url(text: &str) -> Result<Url> {
+    Ok(Url::parse(text).map_err(error::Conversion::from)?)
+}
+
+/// Look for the backends running on this machine.
+async fn detect() -> Result<Vec<Candidate>> {
+    let mut found = vec![];
+    if keepassxc::find_socket().is_ok() {
+        // The socket moves between versions and packages, so we look for it
+        // each time instead of saving where it is now.
+        found.push(Candidate {
+            name: "KeePassXC",
+            url: parse_url("file:")?,
+        });
+    }
+    if matches!(
+        time::timeout(PROBE_TIMEOUT, TcpStream::connect(KEEPASSRPC_ADDRESS)).await,
+        Ok(Ok(_))
+    ) {
+        let (host, port) = KEEPASSRPC_ADDRESS;
+        found.push(Candidate {
+            name: "KeePassRPC",
+            url: parse_url(&format!("ws://{host}:{port}"))?,
+        });
+    }
+    Ok(found)
+}
+
+/// The index of the backend numbered in the answer, or of the first one if
+/// the answer isn't one of the numbers.
+fn parse_choice(answer: &str, count: usize) -> usize {
+    answer
+        .trim()
+        .parse::<usize>()
+        .ok()
+        .filter(|choice| (1..=count).contains(choice))
+        .map_or(0, |choice| choice - 1)
+}
+
+/// Ask which of several backends to use, by number, defaulting to the first.
+fn ask_choice(candidates: &[Candidate]) -> Result<usize> {
+    for (i, candidate) in candidates.iter().enumerate() {
+        eprintln!("  {}. {} ({})", i + 1, candidate.name, candidate.url);
+    }
+    eprint!("{}", locale::text("init-choose"));
+    io::stderr().flush()?;
+    let mut line = String::new();
+    let _ = io::stdin().read_line(&mut line)?;
+    Ok(parse_choice(&line, candidates.len()))
+}
+
+impl Command {
+    /// Find the backend to use, asking when there's more than one.
+    async fn select(setup: &Setup<'_>) -> Result<Url> {
+        if let Some(url) = setup.url {
+            return Ok(url.clone());
+        }
+        let mut candidates = detect().await?;
+        if candidates.len() > 1 && setup.interactive && io::stdin().is_terminal() {
+            let chosen = task::block_in_place(|| ask_choice(&candidates))?;
+            return Ok(candidates.swap_remove(chosen).url);
+        }
+        let first = candidates.into_iter().next().ok_or(Error::NoBackendFound)?;
+        println!(
+            "{}",
+            locale::format("init-found", &[("backend", &first.name)])
+        );
+        Ok(first.url)
+    }
+
+    /// Check that the session was kept, by connecting again without being
+    /// able to ask the user anything.
+    async fn check_storage(verifier: &dyn Connector, url: &Url, options: storage::Options) {
+        // Only KeePassRPC and KeePassXC keep sessions.
+        if !matches!(url.scheme(), "ws" | "wss" | "file" | "tcp" | "stdio") {
+            return;
+        }
+        let Ok((kind, _, _)) = session::find(url, storage::Kind::SUPPORTED, options).await else {
+            warn!("{}", locale::text("init-not-stored"));
+            return;
+        };
+        let result = async {
+            let channel = verifier.connect(url).await?;
+            let databases = channel.client().databases().await;
+            channel.close().await?;
+            databases
+        }
+        .await;
+        match result {
+            Ok(_) => println!("{}", locale::format("init-stored", &[("storage", &kind)])),
+            Err(e) => warn!(
+                "{}",
+                locale::format("init-stored-unusable", &[("storage", &kind), ("error", &e)])
+            ),
+        }
+    }
+
+    pub(crate) async fn run(
+        self,
+        connector: &dyn Connector,
+        verifier: &dyn Connector,
+        setup: Setup<'_>,
+    ) -> Result<()> {
+        let name = setup.profile.unwrap_or(DEFAULT_PROFILE).to_owned();
+        if !self.force
+            && setup
+                .config
+                .profiles
+                .get(&name)
+                .is_some_and(|profile| profile.url.is_some())
+        {
+            return Err(error::Config::UrlAlreadySet(name).into());
+        }
+
+        let url = Self::select(&setup).await?;
+
+        // Connecting is what has the backend ask the user to allow us.
+        let channel = connector.connect(&url).await?;
+        let result = channel.client().databases().await;
+        channel.close().await?;
+        let databases = result?;
+        println!(
+            "{}",
+            locale::format("init-connected", &[("databases", &databases.len())])
+        );
+
+        Self::check_storage(verifier, &url, setup.storage).await;
+
+        let mut config = setup.config;
+        config.profiles.entry(name.clone()).or_default().url = Some(url);
+        if config.default_profile.is_none() {
+            config.default_profile = Some(name.clone());
+        }
+        config.save()?;
+        println!("{}", locale::format("init-saved", &[("profile", &name)]));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn choices_default_to_the_first() {
+        assert_eq!(parse_choice("2\n", 2), 1);
+        assert_eq!(parse_choice("\n", 2), 0);
+        assert_eq!(parse_choice("3", 2), 0);
+        assert_eq!(parse_choice("0", 2), 0);
+        assert_eq!(parse_choice("KeePassRPC", 2), 0);
+    }
+}
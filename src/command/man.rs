@@ -0,0 +1,55 @@
+// SPDX-FileCopyrightText: 2024 Noah Fontes
+//
+// SPDX-License-Identifier: Apache-2.0
+
+use std::{fs, path::PathBuf};
+
+use clap::Parser;
+use log::info;
+
+use crate::error::Result;
+
+/// Write manual pages for karp and each of its commands.
+#[derive(Debug, Parser)]
+pub(crate) struct Command {
+    /// The directory to write the pages to. It is created if it doesn't exist.
+    #[arg(long, default_value = ".", value_hint = clap::ValueHint::DirPath)]
+    out_dir: PathBuf,
+}
+
+impl Command {
+    /// Write the page for a command, named after its full path through the
+    /// command hierarchy (`karp-alias-add`), followed by the pages of its
+    /// subcommands. Hidden commands and the generated `help` commands are
+    /// left out.
+    fn render(&self, cmd: &clap::Command, name: &str, version: Option<&str>) -> Result<()> {
+        let mut page_cmd = cmd.clone().name(name.to_owned());
+        if let Some(v) = version {
+            page_cmd = page_cmd.version(v.to_owned());
+        }
+
+        let mut page = vec![];
+        clap_mangen::Man::new(page_cmd).render(&mut page)?;
+
+        let path = self.out_dir.join(format!("{name}.1"));
+        info!("Writing {}", path.display());
+        fs::write(path, page)?;
+
+        for sub in cmd
+            .get_subcommands()
+            .filter(|sub| !sub.is_hide_set() && sub.get_name() != "help")
+        {
+            self.render(sub, &format!("{name}-{}", sub.get_name()), version)?;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn run(self, mut cmd: clap::Command) -> Result<()> {
+        fs::create_dir_all(&self.out_dir)?;
+
+        cmd.build();
+        let name = cmd.get_name().to_owned();
+        let version = cmd.get_version().map(str::to_owned);
+        self.render(&cmd, &name, version.as_deref())
+    }
+}
@@ -3,13 +3,136 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use async_trait::async_trait;
+use url::Url;
 
-use crate::{client::Client, error::Result};
+use crate::{
+    client::{Client, Connector, EntryRef},
+    error::{self, Result},
+};
 
+pub(crate) mod alias;
+pub(crate) mod benchmark;
+pub(crate) mod cleanup;
+pub(crate) mod complete;
+pub(crate) mod completions;
+pub(crate) mod config;
+pub(crate) mod copy;
+pub(crate) mod create_entry;
+pub(crate) mod curl_auth;
+pub(crate) mod diff;
+pub(crate) mod edit;
+pub(crate) mod field;
+pub(crate) mod generate_passphrase;
 pub(crate) mod get_form_fields;
+pub(crate) mod get_totp;
+pub(crate) mod init;
+pub(crate) mod man;
+pub(crate) mod monitor;
+pub(crate) mod netrc;
+pub(crate) mod open_url;
+pub(crate) mod schema;
 pub(crate) mod search;
+pub(crate) mod serve;
+pub(crate) mod session;
+pub(crate) mod shell;
+pub(crate) mod status;
+pub(crate) mod systemd_creds;
+pub(crate) mod totp;
+pub(crate) mod type_;
+pub(crate) mod update_entry;
+pub(crate) mod verify_session;
+
+fn split_group_path(path: &str) -> impl Iterator<Item = &str> {
+    path.split('/').filter(|name| !name.is_empty())
+}
+
+/// State shared by every command: the settings of the selected profile and a
+/// way to reach other backends.
+pub(crate) struct Context<'ctx> {
+    pub(crate) connector: &'ctx dyn Connector,
+    group_prefix: Vec<String>,
+    allow_input: bool,
+}
+
+impl<'ctx> Context<'ctx> {
+    pub(crate) fn new(
+        connector: &'ctx dyn Connector,
+        group_prefix: Option<&str>,
+        allow_input: bool,
+    ) -> Self {
+        Self {
+            connector,
+            group_prefix: group_prefix
+                .map(|path| split_group_path(path).map(str::to_owned).collect())
+                .unwrap_or_default(),
+            allow_input,
+        }
+    }
+
+    /// Fail if we may not ask the user anything, for commands that can't do
+    /// without.
+    pub(crate) const fn require_input(&self) -> Result<()> {
+        if self.allow_input {
+            Ok(())
+        } else {
+            Err(error::Error::InputRequired)
+        }
+    }
+
+    /// Resolve a list of group names given on the command line against the
+    /// group prefix. If the first group name begins with a slash, the list is
+    /// absolute and is used as-is.
+    pub(crate) fn resolve_groups<S: AsRef<str>>(&self, groups: &[S]) -> Vec<String> {
+        match groups.split_first() {
+            Some((first, rest)) if first.as_ref().starts_with('/') => {
+                split_group_path(first.as_ref())
+                    .chain(rest.iter().map(AsRef::as_ref))
+                    .map(str::to_owned)
+                    .collect()
+            }
+            _ => self
+                .group_prefix
+                .iter()
+                .map(String::as_str)
+                .chain(groups.iter().map(AsRef::as_ref))
+                .map(str::to_owned)
+                .collect(),
+        }
+    }
+
+    /// Resolve a slash-separated group path given on the command line against
+    /// the group prefix.
+    pub(crate) fn resolve_group_path(&self, path: &str) -> Vec<String> {
+        if path.starts_with('/') {
+            split_group_path(path).map(str::to_owned).collect()
+        } else {
+            self.resolve_groups(&split_group_path(path).collect::<Vec<_>>())
+        }
+    }
+
+    pub(crate) fn resolve_entry(&self, entry: &EntryRef) -> EntryRef {
+        match *entry {
+            EntryRef::Path {
+                ref groups,
+                ref title,
+                absolute: false,
+            } => EntryRef::Path {
+                groups: self.resolve_groups(groups),
+                title: title.clone(),
+                absolute: true,
+            },
+            EntryRef::Path { .. } | EntryRef::Id(_) => entry.clone(),
+        }
+    }
+}
 
 #[async_trait]
 pub(crate) trait Command {
-    async fn execute(self, proto: impl Client + Send) -> Result<()>;
+    /// The URL to connect to instead of the one selected globally, if the
+    /// command has its own option for it.
+    fn url(&self) -> Option<&Url> {
+        None
+    }
+
+    async fn execute(self, client: impl Client + Send + Sync, ctx: &Context<'_>) -> Result<()>;
 }
@@ -0,0 +1,75 @@
+// SPDX-FileCopyrightText: 2024 Noah Fontes
+//
+// SPDX-License-Identifier: Apache-2.0
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use async_trait::async_trait;
+use clap::Parser;
+use futures_util::StreamExt as _;
+use serde::Serialize;
+
+use super::Context;
+use crate::{
+    client::{Client, DatabaseEvent},
+    error::Result,
+};
+
+/// Stay connected and print a line of JSON whenever the database is locked or
+/// unlocked, such as `{"event":"database-locked","time":1718000000}`, so a
+/// script can react to it. KeePassXC and KeePassRPC tell us about these.
+#[derive(Debug, Parser)]
+pub(crate) struct Command {}
+
+/// An event as we print it.
+#[derive(Serialize)]
+struct JsonEvent {
+    event: &'static str,
+    /// When we heard about the event, in seconds since the Unix epoch.
+    time: u64,
+}
+
+impl JsonEvent {
+    fn new(event: DatabaseEvent, time: SystemTime) -> Self {
+        Self {
+            event: match event {
+                DatabaseEvent::Locked => "database-locked",
+                DatabaseEvent::Unlocked => "database-unlocked",
+            },
+            time: time
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |since| since.as_secs()),
+        }
+    }
+}
+
+#[async_trait]
+impl super::Command for Command {
+    async fn execute(self, client: impl Client + Send + Sync, _: &Context<'_>) -> Result<()> {
+        let mut events = client.watch().await?;
+        while let Some(event) = events.next().await {
+            println!(
+                "{}",
+                serde_json::to_string(&JsonEvent::new(event, SystemTime::now()))?
+            );
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn events_are_printed_as_json() -> Result<()> {
+        let time = UNIX_EPOCH + Duration::from_secs(1_718_000_000);
+        assert_eq!(
+            serde_json::to_string(&JsonEvent::new(DatabaseEvent::Locked, time))?,
+            r#"{"event":"database-locked","time":1718000000}"#
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,146 @@
+// SPDX-FileCopyrightText: 2024 Noah Fontes
+//
+// SPDX-License-Identifier: Apache-2.0
+
+use std::{fmt::Write as _, fs, io::Write as _, path::PathBuf};
+
+use async_trait::async_trait;
+use clap::Parser;
+use secrecy::{ExposeSecret as _, SecretString};
+
+use super::{systemd_creds, Context};
+use crate::{
+    client::{Client, Entry, EntryRef, FormFieldType},
+    desktop,
+    error::{self, Result},
+    placeholder,
+};
+
+/// Print a `.netrc` file with the usernames and passwords of entries, for
+/// tools like curl, ftp, and Mercurial that read credentials from one.
+#[derive(Debug, Parser)]
+pub(crate) struct Command {
+    /// A machine to give credentials for, as `HOST=ENTRY`, where the entry is
+    /// given by its path (`Group/Subgroup/Title`) or ID. This option can be
+    /// given more than once.
+    #[arg(long = "map", value_name = "HOST=ENTRY", value_parser = parse_mapping, required = true)]
+    mappings: Vec<(String, EntryRef)>,
+
+    /// Write to this file, readable only by its owner, instead of printing.
+    /// The file is replaced as a whole.
+    #[arg(long, short, value_hint = clap::ValueHint::FilePath)]
+    output: Option<PathBuf>,
+}
+
+fn parse_mapping(s: &str) -> Result<(String, EntryRef)> {
+    match s.split_once('=') {
+        Some((host, entry)) if !host.is_empty() && !host.contains(char::is_whitespace) => {
+            Ok((host.to_owned(), entry.parse()?))
+        }
+        _ => Err(error::Error::InvalidMapping(s.to_owned())),
+    }
+}
+
+/// Quote a token if it has characters that would otherwise end it, as curl
+/// understands.
+fn token(value: &str) -> String {
+    if !value.is_empty() && !value.contains(|c: char| c.is_whitespace() || c == '"') {
+        return value.to_owned();
+    }
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('"');
+    for c in value.chars() {
+        match c {
+            '"' | '\\' => {
+                quoted.push('\\');
+                quoted.push(c);
+            }
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            _ => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+fn render(machines: &[(String, Entry)]) -> SecretString {
+    let mut netrc = String::new();
+    for (host, entry) in machines {
+        let _ = writeln!(netrc, "machine {}", token(host));
+        for (keyword, type_) in [
+            ("login", FormFieldType::Username),
+            ("password", FormFieldType::Password),
+        ] {
+            if let Some(field) = entry.form_fields.iter().find(|field| field.type_ == type_) {
+                let _ = writeln!(netrc, "  {keyword} {}", token(field.value.expose_secret()));
+            }
+        }
+    }
+    SecretString::new(netrc)
+}
+
+#[async_trait]
+impl super::Command for Command {
+    async fn execute(self, client: impl Client + Send + Sync, ctx: &Context<'_>) -> Result<()> {
+        let mut machines = Vec::with_capacity(self.mappings.len());
+        for (host, entry) in self.mappings {
+            let stored = client.get_entry_by_ref(&ctx.resolve_entry(&entry)).await?;
+            machines.push((host, placeholder::resolve(&client, &stored).await?));
+        }
+        desktop::verify_user()?;
+        let netrc = render(&machines);
+
+        let Some(path) = self.output else {
+            print!("{}", netrc.expose_secret());
+            return Ok(());
+        };
+        let mut temp_name = path.file_name().unwrap_or_default().to_owned();
+        temp_name.push(".tmp");
+        let temp_path = path.with_file_name(temp_name);
+        let _ = fs::remove_file(&temp_path);
+        let written = systemd_creds::create_file(&temp_path, 0o600)
+            .and_then(|mut file| file.write_all(netrc.expose_secret().as_bytes()))
+            .and_then(|()| fs::rename(&temp_path, &path));
+        if written.is_err() {
+            let _ = fs::remove_file(&temp_path);
+        }
+        Ok(written?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::FormField;
+
+    #[test]
+    fn render_machines() -> Result<()> {
+        let field = |type_, value: &str| FormField {
+            type_,
+            display_name: String::new(),
+            value: SecretString::new(value.to_owned()),
+            metadata: None,
+        };
+        let entry = Entry {
+            id: String::new(),
+            parent: None,
+            title: "API".to_owned(),
+            urls: vec![],
+            form_fields: vec![
+                field(FormFieldType::Username, "alice"),
+                field(FormFieldType::Password, r#"correct "horse""#),
+            ],
+            database: None,
+            source: None,
+        };
+        let (host, _) = parse_mapping("api.example.com=Work/API")?;
+        assert_eq!(
+            render(&[(host, entry)]).expose_secret(),
+            "machine api.example.com\n  login alice\n  password \"correct \\\"horse\\\"\"\n"
+        );
+        assert!(parse_mapping("Work/API").is_err());
+        Ok(())
+    }
+}
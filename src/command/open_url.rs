@@ -0,0 +1,116 @@
+// SPDX-FileCopyrightText: 2024 Noah Fontes
+//
+// SPDX-License-Identifier: Apache-2.0
+
+use std::{thread, time::Duration};
+
+use async_trait::async_trait;
+use clap::Parser;
+use tokio::task;
+
+use super::Context;
+use crate::{
+    client::{Client, EntryRef, FormFieldType},
+    desktop,
+    error::{self, Result},
+    hooks, locale, placeholder,
+};
+
+/// Open the first URL of an entry in the default browser, like KeePass does
+/// when asked to open an entry's URL. A field of the entry, such as its
+/// password, can be copied to the clipboard first, ready to paste into the
+/// page.
+#[derive(Debug, Parser)]
+pub(crate) struct Command {
+    /// Copy the value of the first field of the given type to the clipboard
+    /// before opening the URL.
+    #[arg(long, value_enum, value_name = "TYPE")]
+    copy: Option<FormFieldType>,
+
+    /// How long to leave the copied value on the clipboard before clearing
+    /// it, in seconds. We wait this long before exiting, and leave the
+    /// clipboard alone if something else has been copied in the meantime. Use
+    /// 0 to never clear it.
+    #[arg(long, value_name = "SECONDS", default_value = "45", requires = "copy")]
+    clear_after: u64,
+
+    /// Use the URL and copied value as they are stored, instead of expanding
+    /// KeePass placeholders like `{USERNAME}` or `{REF:P@I:...}` in them.
+    #[arg(long)]
+    no_resolve_placeholders: bool,
+
+    /// The path (`Group/Subgroup/Title`) or ID of the entry.
+    #[clap()]
+    entry: EntryRef,
+}
+
+#[async_trait]
+impl super::Command for Command {
+    async fn execute(self, client: impl Client + Send + Sync, ctx: &Context<'_>) -> Result<()> {
+        let mut entry = client
+            .get_entry_by_ref(&ctx.resolve_entry(&self.entry))
+            .await?;
+        if !self.no_resolve_placeholders {
+            entry = placeholder::resolve(&client, &entry).await?;
+        }
+        let url = entry
+            .urls
+            .first()
+            .ok_or(error::Error::EntryHasNoUrl)?
+            .clone();
+
+        let copied = match self.copy {
+            Some(type_) => {
+                let value = entry
+                    .form_fields
+                    .iter()
+                    .find(|field| field.type_ == type_)
+                    .ok_or(error::Error::FieldTypeNotFound(type_))?
+                    .value
+                    .clone();
+                desktop::copy(&value)?;
+                Some((type_, value))
+            }
+            None => None,
+        };
+
+        desktop::open_url(&url)?;
+
+        if let Some((type_, value)) = copied {
+            if self.clear_after == 0 {
+                desktop::notify_copied(&type_, &entry.title, None);
+                println!(
+                    "{}",
+                    locale::format("open-url-copied", &[("field", &type_)])
+                );
+                return Ok(());
+            }
+
+            println!(
+                "{}",
+                locale::format(
+                    "open-url-clearing",
+                    &[("field", &type_), ("seconds", &self.clear_after)]
+                )
+            );
+            let clear_after = Duration::from_secs(self.clear_after);
+            desktop::notify_copied(&type_, &entry.title, Some(clear_after));
+            if task::spawn_blocking(move || {
+                thread::sleep(clear_after);
+                desktop::clear(&value)
+            })
+            .await??
+            {
+                desktop::notify_cleared(&type_, &entry.title);
+                hooks::fire(
+                    hooks::Event::ClipboardCleared,
+                    &[
+                        ("KARP_ENTRY_TITLE", &entry.title),
+                        ("KARP_FIELD", &type_.to_string().to_lowercase()),
+                    ],
+                );
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,187 @@
+// SPDX-FileCopyrightText: 2024 Noah Fontes
+//
+// SPDX-License-Identifier: Apache-2.0
+
+use clap::{Parser, ValueEnum};
+use serde_json::{json, Value};
+
+use crate::error::Result;
+
+const DRAFT: &str = "https://json-schema.org/draft/2020-12/schema";
+
+#[derive(Copy, Clone, Debug, ValueEnum)]
+enum Document {
+    /// The entries printed by `search`.
+    Entries,
+    /// The form fields printed by `get-form-fields`, keyed by entry.
+    Fields,
+    /// Errors, which are printed to standard error.
+    Errors,
+}
+
+/// Print the JSON Schema of what we print with `--output json`, so programs
+/// that read it can check it or generate code for it. Fields may be added to
+/// these documents, but not removed or changed.
+#[derive(Debug, Parser)]
+pub(crate) struct Command {
+    /// The document to describe.
+    #[arg(value_enum)]
+    document: Document,
+}
+
+fn entries() -> Value {
+    json!({
+        "$schema": DRAFT,
+        "title": "Entries",
+        "description": "Entries found by `karp search`, most relevant first.",
+        "type": "array",
+        "items": {
+            "type": "object",
+            "properties": {
+                "id": { "type": "string", "description": "The unique ID of the entry." },
+                "title": { "type": "string" },
+                "group": {
+                    "type": ["string", "null"],
+                    "description": "The path of the group the entry is in, if the backend gives it.",
+                },
+                "urls": { "type": "array", "items": { "type": "string" } },
+                "databases": {
+                    "type": "array",
+                    "items": { "type": "string" },
+                    "description": "The names of the databases that have a copy of the entry, for backends that search several.",
+                },
+                "source": {
+                    "type": "string",
+                    "description": "The name of the backend the entry came from, for profiles that use several.",
+                },
+            },
+            "required": ["id", "title", "group", "urls"],
+        },
+    })
+}
+
+fn fields() -> Value {
+    json!({
+        "$schema": DRAFT,
+        "title": "Form fields",
+        "description": "The form fields of each entry looked up by `karp get-form-fields`, keyed by the entry as it was given.",
+        "type": "object",
+        "additionalProperties": {
+            "oneOf": [
+                {
+                    "description": "Every field that matches the filter.",
+                    "type": "array",
+                    "items": { "$ref": "#/$defs/field" },
+                },
+                {
+                    "description": "The value of the one field selected with `--index`.",
+                    "type": "string",
+                },
+                {
+                    "description": "The values of the fields selected with `--index` or `--all-values`, keyed by display name.",
+                    "type": "object",
+                    "additionalProperties": { "type": "string" },
+                },
+            ],
+        },
+        "$defs": {
+            "field": {
+                "type": "object",
+                "properties": {
+                    "type": {
+                        "enum": ["username", "password", "text", "select", "radio", "checkbox", "totp"],
+                    },
+                    "display_name": { "type": "string" },
+                    "value": { "type": "string" },
+                    "id": {
+                        "type": "string",
+                        "description": "The ID of the field in the form, from KeePassRPC only.",
+                    },
+                    "name": {
+                        "type": "string",
+                        "description": "The name of the field in the form, from KeePassRPC only.",
+                    },
+                    "page": {
+                        "type": "integer",
+                        "description": "The page of the form the field is on, from KeePassRPC only.",
+                    },
+                    "placeholder_handling": {
+                        "enum": ["default", "enabled", "disabled"],
+                        "description": "Whether placeholders in the value are resolved, from KeePassRPC only.",
+                    },
+                },
+                "required": ["type", "display_name", "value"],
+            },
+        },
+    })
+}
+
+fn errors() -> Value {
+    json!({
+        "$schema": DRAFT,
+        "title": "Error",
+        "description": "Why a command failed, printed to standard error as one line.",
+        "type": "object",
+        "properties": {
+            "error": {
+                "type": "object",
+                "properties": {
+                    "kind": {
+                        "type": "string",
+                        "description": "What sort of error this is, such as `config` or `keepassxc`.",
+                    },
+                    "code": {
+                        "type": ["string", "null"],
+                        "description": "The error code the backend gave, such as `NoLoginsFound`, if any.",
+                    },
+                    "message": { "type": "string", "description": "The error as we'd show it to a person." },
+                    "exit_code": { "type": "integer", "description": "The status we exit with." },
+                },
+                "required": ["kind", "code", "message", "exit_code"],
+            },
+        },
+        "required": ["error"],
+    })
+}
+
+impl Command {
+    pub(crate) fn run(self) -> Result<()> {
+        let schema = match self.document {
+            Document::Entries => entries(),
+            Document::Fields => fields(),
+            Document::Errors => errors(),
+        };
+        println!("{}", serde_json::to_string_pretty(&schema)?);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Check that every property an object requires is one it describes.
+    fn check_required(schema: &Value) {
+        if let Some(required) = schema.get("required").and_then(Value::as_array) {
+            for name in required.iter().filter_map(Value::as_str) {
+                assert!(
+                    schema["properties"].get(name).is_some(),
+                    "{name} is required but not described"
+                );
+            }
+        }
+        match *schema {
+            Value::Object(ref members) => members.values().for_each(check_required),
+            Value::Array(ref items) => items.iter().for_each(check_required),
+            Value::Null | Value::Bool(_) | Value::Number(_) | Value::String(_) => {}
+        }
+    }
+
+    #[test]
+    fn schemas_are_consistent() {
+        for schema in [entries(), fields(), errors()] {
+            assert_eq!(schema["$schema"], DRAFT);
+            check_required(&schema);
+        }
+    }
+}
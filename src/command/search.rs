@@ -3,45 +3,276 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use core::num;
+use std::collections::HashMap;
 
 use async_trait::async_trait;
-use clap::Parser;
+use clap::{Parser, ValueEnum};
+use serde::Serialize;
 use tabled::{
-    settings::{object::Segment, Alignment, Modify, Style},
-    Table,
+    settings::{
+        object::{Rows, Segment},
+        Alignment, Format, Modify, Style,
+    },
+    Table, Tabled,
 };
 
-use crate::{client::Client, error::Result};
+use super::Context;
+use crate::{
+    client::{Client, Entry},
+    error::Result,
+    locale, output, pager,
+};
+
+/// How to tell that two entries found in different databases are copies of
+/// the same entry.
+#[derive(Copy, Clone, Debug, ValueEnum)]
+enum Dedupe {
+    /// Entries with the same ID.
+    ByUuid,
+    /// Entries with the same title and URLs.
+    ByTitleUrl,
+}
+
+impl Dedupe {
+    fn key(self, entry: &Entry) -> (String, Vec<String>) {
+        match self {
+            Self::ByUuid => (entry.id.clone(), vec![]),
+            Self::ByTitleUrl => (entry.title.clone(), entry.urls.clone()),
+        }
+    }
+}
+
+/// An entry along with the names of the databases that have a copy of it.
+#[derive(Tabled)]
+struct Copies {
+    #[tabled(inline)]
+    entry: Entry,
+    #[tabled(rename = "Databases", display_with = "Self::format_databases")]
+    databases: Vec<String>,
+}
+
+impl Copies {
+    // LINT: The signature is dictated by Tabled's `display_with`.
+    #[allow(clippy::ptr_arg)]
+    fn format_databases(databases: &Vec<String>) -> String {
+        databases.join(", ")
+    }
+}
+
+/// An entry along with the name of the backend it came from, when the
+/// profile searches several.
+#[derive(Tabled)]
+struct Sourced {
+    #[tabled(inline)]
+    entry: Entry,
+    #[tabled(rename = "Source")]
+    source: String,
+}
+
+/// Collapse entries that are copies of each other into the first of them.
+/// Entries from backends that don't name their database count as being in a
+/// database named for the backend.
+fn dedupe(entries: Vec<Entry>, by: Dedupe) -> Vec<Copies> {
+    let mut found: Vec<Copies> = vec![];
+    let mut seen = HashMap::new();
+    for entry in entries {
+        let database = entry.database.clone().or_else(|| entry.source.clone());
+        let index = *seen.entry(by.key(&entry)).or_insert_with(|| {
+            found.push(Copies {
+                entry,
+                databases: vec![],
+            });
+            found.len() - 1
+        });
+        if let Some(name) = database {
+            let databases = &mut found[index].databases;
+            if !databases.contains(&name) {
+                databases.push(name);
+            }
+        }
+    }
+    found
+}
+
+/// An entry in JSON output.
+#[derive(Serialize)]
+struct JsonEntry<'entry> {
+    id: &'entry str,
+    title: &'entry str,
+    group: Option<&'entry str>,
+    urls: &'entry [String],
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    databases: &'entry [String],
+    #[serde(skip_serializing_if = "Option::is_none")]
+    source: Option<&'entry str>,
+}
+
+impl<'entry> JsonEntry<'entry> {
+    fn new(entry: &'entry Entry, databases: &'entry [String]) -> Self {
+        Self {
+            id: &entry.id,
+            title: &entry.title,
+            group: entry.parent.as_ref().map(|group| group.path.as_str()),
+            urls: &entry.urls,
+            databases,
+            source: entry.source.as_deref(),
+        }
+    }
+}
 
 /// Free-text search for a given entry.
 #[derive(Debug, Parser)]
 pub(crate) struct Command {
-    /// The number of possible entries to return.
+    /// The number of possible entries to return. KeePassRPC and KeePassXC
+    /// still send every match, so this only shortens the output there,
+    /// without making the search any cheaper.
     #[arg(short, long)]
     count: Option<num::NonZeroUsize>,
 
+    /// Print only the ID of each entry, one per line, for use with
+    /// `get-form-fields --paths-from`.
+    #[arg(long)]
+    ids: bool,
+
+    /// Show entries that are in more than one of the databases searched only
+    /// once, along with the databases that have a copy.
+    #[arg(long, value_name = "BY")]
+    dedupe: Option<Dedupe>,
+
     /// The text to search for.
     #[clap()]
     query: String,
 }
 
+impl Command {
+    fn print<T: Tabled>(
+        &self,
+        rows: &[T],
+        id: impl Fn(&T) -> &str,
+        json: impl Fn(&T) -> JsonEntry<'_>,
+    ) -> Result<()> {
+        if output::is_json() {
+            let entries: Vec<_> = rows.iter().map(json).collect();
+            println!("{}", serde_json::to_string_pretty(&entries)?);
+        } else if self.ids {
+            for row in rows {
+                println!("{}", id(row));
+            }
+        } else if !rows.is_empty() {
+            pager::print(
+                Table::new(rows)
+                    .with(Style::rounded())
+                    .with(Modify::new(Rows::first()).with(Format::content(locale::header)))
+                    .with(Modify::new(Segment::new(1.., 1..)).with(Alignment::left())),
+            )?;
+        }
+        Ok(())
+    }
+}
+
 #[async_trait]
 impl super::Command for Command {
-    async fn execute(self, client: impl Client + Send) -> Result<()> {
-        let entries = client.find_entries(&self.query).await?;
-
-        if !entries.is_empty() {
-            println!(
-                "{}",
-                Table::new(
-                    entries
-                        .iter()
-                        .take(self.count.map_or(usize::MAX, num::NonZeroUsize::get))
-                )
-                .with(Style::rounded())
-                .with(Modify::new(Segment::new(1.., 1..=2)).with(Alignment::left()))
+    async fn execute(self, client: impl Client + Send + Sync, _: &Context<'_>) -> Result<()> {
+        let Some(by) = self.dedupe else {
+            let entries = client.find_entries(&self.query, self.count).await?;
+            if entries.iter().any(|entry| entry.source.is_some()) {
+                let rows: Vec<_> = entries
+                    .into_iter()
+                    .map(|entry| Sourced {
+                        source: entry.source.clone().unwrap_or_default(),
+                        entry,
+                    })
+                    .collect();
+                return self.print(
+                    &rows,
+                    |row| &row.entry.id,
+                    |row| JsonEntry::new(&row.entry, row.entry.database.as_slice()),
+                );
+            }
+            return self.print(
+                &entries,
+                |entry| &entry.id,
+                |entry| JsonEntry::new(entry, entry.database.as_slice()),
             );
+        };
+
+        // Copies only count once, so we can't stop at the number of entries
+        // we want.
+        let mut found = dedupe(client.find_entries(&self.query, None).await?, by);
+        found.truncate(self.count.map_or(usize::MAX, num::NonZeroUsize::get));
+        self.print(
+            &found,
+            |copies| &copies.entry.id,
+            |copies| JsonEntry::new(&copies.entry, &copies.databases),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(id: &str, title: &str, database: &str) -> Entry {
+        Entry {
+            id: id.to_owned(),
+            parent: None,
+            title: title.to_owned(),
+            urls: vec!["https://example.com".to_owned()],
+            form_fields: vec![],
+            database: Some(database.to_owned()),
+            source: None,
         }
-        Ok(())
+    }
+
+    #[test]
+    fn dedupe_counts_backends_as_databases() {
+        let from = |source: &str| Entry {
+            database: None,
+            source: Some(source.to_owned()),
+            ..entry("01", "Example", "")
+        };
+        let found = dedupe(vec![from("home"), from("work")], Dedupe::ByUuid);
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].databases, ["home", "work"]);
+    }
+
+    #[test]
+    fn dedupe_collapses_copies() {
+        let entries = || {
+            vec![
+                entry("01", "Example", "Personal"),
+                entry("02", "Example", "Work"),
+                entry("01", "Example", "Work"),
+                entry("03", "Other", "Work"),
+            ]
+        };
+        let summary = |found: Vec<Copies>| -> Vec<(String, Vec<String>)> {
+            found
+                .into_iter()
+                .map(|copies| (copies.entry.id, copies.databases))
+                .collect()
+        };
+
+        assert_eq!(
+            summary(dedupe(entries(), Dedupe::ByUuid)),
+            [
+                (
+                    "01".to_owned(),
+                    vec!["Personal".to_owned(), "Work".to_owned()]
+                ),
+                ("02".to_owned(), vec!["Work".to_owned()]),
+                ("03".to_owned(), vec!["Work".to_owned()]),
+            ]
+        );
+        assert_eq!(
+            summary(dedupe(entries(), Dedupe::ByTitleUrl)),
+            [
+                (
+                    "01".to_owned(),
+                    vec!["Personal".to_owned(), "Work".to_owned()]
+                ),
+                ("03".to_owned(), vec!["Work".to_owned()]),
+            ]
+        );
     }
 }
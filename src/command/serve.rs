@@ -0,0 +1,506 @@
+// SPDX-FileCopyrightText: 2024 Noah Fontes
+//
+// SPDX-License-Identifier: Apache-2.0
+
+use std::{io, num::NonZeroUsize};
+
+use async_trait::async_trait;
+use clap::{Parser, ValueEnum};
+use log::{debug, warn};
+use secrecy::ExposeSecret as _;
+use serde_json::{json, Value};
+use tokio::task;
+use uuid::Uuid;
+
+use super::Context;
+use crate::{
+    client::{
+        same_name, Client, Database, Entry, EntryRef, FormField, FormFieldType, Group, GroupListing,
+    },
+    desktop,
+    error::{Error, Result},
+    locale, placeholder,
+};
+
+/// The version of the Model Context Protocol we speak.
+const PROTOCOL_VERSION: &str = "2024-11-05";
+
+/// JSON-RPC error codes.
+const PARSE_ERROR: i64 = -32700;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+
+/// When to ask the user before giving the assistant the value of a field.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, ValueEnum)]
+enum Confirm {
+    /// Before every value.
+    Always,
+    /// Before passwords and one-time passwords.
+    #[default]
+    Secrets,
+    /// Never.
+    Never,
+}
+
+/// Serve tools for local AI assistants over the Model Context Protocol on
+/// standard input and output: `search_entries`, which finds entries without
+/// revealing any of their fields, and `get_field`, which gives the value of
+/// one field of an entry. Passwords, and fields with placeholders that could
+/// expand to them, are only given out with `--allow-secrets`.
+#[derive(Debug, Parser)]
+pub(crate) struct Command {
+    /// Speak the Model Context Protocol, which is the only protocol we serve.
+    #[arg(long, required = true)]
+    mcp: bool,
+
+    /// Only show the assistant entries in this group or below it. May be
+    /// given more than once; without it, every entry can be seen. Backends
+    /// that only give the name of an entry's group, like KeePassXC, refuse
+    /// to start with it.
+    #[arg(long = "allow-group", value_name = "GROUP")]
+    allow_groups: Vec<String>,
+
+    /// Let `get_field` give out passwords and one-time passwords, not just
+    /// usernames and other fields.
+    #[arg(long)]
+    allow_secrets: bool,
+
+    /// When to ask, through Pinentry, before giving the assistant the value of
+    /// a field.
+    #[arg(long, value_enum, default_value_t)]
+    confirm: Confirm,
+}
+
+/// The failure of a tool, which the assistant is told about rather than
+/// ending the session.
+#[derive(Debug)]
+struct ToolError(String);
+
+impl From<Error> for ToolError {
+    fn from(value: Error) -> Self {
+        Self(value.to_string())
+    }
+}
+
+/// Whether the field is a password or one-time password, or could expand to
+/// one: any field can hold a placeholder like `{PASSWORD}` or `{REF:P@I:...}`.
+fn is_secret(field: &FormField) -> bool {
+    matches!(field.type_, FormFieldType::Password | FormFieldType::Totp)
+        || field.value.expose_secret().contains('{')
+}
+
+fn tools() -> Value {
+    json!([
+        {
+            "name": "search_entries",
+            "description": "Search the password database. Returns the ID, title, group, and URLs of each entry found, but none of its fields.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "query": { "type": "string", "description": "The text to search for." },
+                    "limit": { "type": "integer", "minimum": 1, "description": "The most entries to return." },
+                },
+                "required": ["query"],
+            },
+        },
+        {
+            "name": "get_field",
+            "description": "Get the value of one field of an entry. The user may be asked to allow it first, and passwords may be refused.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "entry": { "type": "string", "description": "The ID of the entry, or its path as Group/Subgroup/Title." },
+                    "field": { "type": "string", "description": "The type of the field, such as username or password, or its name." },
+                },
+                "required": ["entry", "field"],
+            },
+        },
+    ])
+}
+
+fn response(id: &Value, result: &Value) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "result": result })
+}
+
+fn error_response(id: &Value, code: i64, message: &str) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "error": { "code": code, "message": message } })
+}
+
+fn read_line() -> io::Result<Option<String>> {
+    let mut line = String::new();
+    if io::stdin().read_line(&mut line)? == 0 {
+        return Ok(None);
+    }
+    Ok(Some(line))
+}
+
+impl Command {
+    /// Whether the assistant may see entries in the group with the given
+    /// path. This relies on the path being the full one, which `execute`
+    /// makes sure of.
+    fn allows(&self, entry: &Entry) -> bool {
+        if self.allow_groups.is_empty() {
+            return true;
+        }
+        let Some(ref parent) = entry.parent else {
+            return false;
+        };
+        let path: Vec<_> = parent
+            .path
+            .split('/')
+            .filter(|name| !name.is_empty())
+            .collect();
+        self.allow_groups.iter().any(|allowed| {
+            let wanted: Vec<_> = allowed.split('/').filter(|name| !name.is_empty()).collect();
+            wanted.len() <= path.len()
+                && wanted
+                    .iter()
+                    .zip(&path)
+                    .all(|(want, have)| same_name(want, have))
+        })
+    }
+
+    /// Whether the field is a secret, refusing it if secrets may not be given
+    /// out.
+    fn check_secret(&self, field: &FormField) -> Result<bool, ToolError> {
+        let secret = is_secret(field);
+        if secret && !self.allow_secrets {
+            return Err(ToolError("secrets may not be given out".to_owned()));
+        }
+        Ok(secret)
+    }
+
+    async fn search(
+        &self,
+        client: &(dyn Client + Send + Sync),
+        arguments: &Value,
+    ) -> Result<String, ToolError> {
+        let query = arguments["query"]
+            .as_str()
+            .ok_or_else(|| ToolError("query must be a string".to_owned()))?;
+        let limit = arguments["limit"]
+            .as_u64()
+            .and_then(|limit| usize::try_from(limit).ok())
+            .and_then(NonZeroUsize::new);
+        let entries: Vec<_> = client
+            .find_entries(query, None)
+            .await?
+            .iter()
+            .filter(|entry| self.allows(entry))
+            .take(limit.map_or(usize::MAX, NonZeroUsize::get))
+            .map(|entry| {
+                json!({
+                    "id": entry.id,
+                    "title": entry.title,
+                    "group": entry.parent.as_ref().map(|group| group.path.as_str()),
+                    "urls": entry.urls,
+                })
+            })
+            .collect();
+        Ok(serde_json::to_string(&entries).map_err(Error::from)?)
+    }
+
+    /// Ask the user whether the assistant may have the field.
+    async fn confirm(
+        &self,
+        entry: &Entry,
+        field: &FormField,
+        secret: bool,
+    ) -> Result<bool, ToolError> {
+        let needed = match self.confirm {
+            Confirm::Always => true,
+            Confirm::Secrets => secret,
+            Confirm::Never => false,
+        };
+        if !needed {
+            return Ok(true);
+        }
+        let query = locale::format(
+            "serve-confirm",
+            &[("field", &field.display_name), ("entry", &entry.title)],
+        );
+        let title = locale::text("serve-confirm-title");
+        let answer = task::spawn_blocking(move || {
+            let Some(mut dialog) = pinentry::ConfirmationDialog::with_default_binary() else {
+                warn!("Pinentry could not be found, so we can't ask whether to give out the field");
+                return Ok(false);
+            };
+            dialog.with_title(&title).confirm(&query)
+        })
+        .await
+        .map_err(Error::from)?;
+        Ok(answer.map_err(Error::from)?)
+    }
+
+    async fn get_field(
+        &self,
+        client: &(dyn Client + Send + Sync),
+        ctx: &Context<'_>,
+        arguments: &Value,
+    ) -> Result<String, ToolError> {
+        let (Some(spec), Some(wanted)) = (arguments["entry"].as_str(), arguments["field"].as_str())
+        else {
+            return Err(ToolError("entry and field must be strings".to_owned()));
+        };
+        let entry_ref = spec.parse::<EntryRef>()?;
+        let stored = client
+            .get_entry_by_ref(&ctx.resolve_entry(&entry_ref))
+            .await?;
+        if !self.allows(&stored) {
+            return Err(ToolError("the entry is not in an allowed group".to_owned()));
+        }
+
+        // Whether a field is secret depends on what it holds before its
+        // placeholders are expanded.
+        let type_ = FormFieldType::from_str(wanted, true).ok();
+        let index = stored
+            .form_fields
+            .iter()
+            .position(|field| type_.map_or(field.display_name == wanted, |t| field.type_ == t))
+            .ok_or_else(|| Error::FieldNotFound(wanted.to_owned()))?;
+        let secret = self.check_secret(&stored.form_fields[index])?;
+
+        // References may only reach entries the assistant could see anyway.
+        let restricted = Restricted {
+            inner: client,
+            command: self,
+        };
+        let entry = placeholder::resolve(&restricted, &stored).await?;
+        let field = entry
+            .form_fields
+            .get(index)
+            .ok_or_else(|| Error::FieldNotFound(wanted.to_owned()))?;
+        if !self.confirm(&entry, field, secret).await? {
+            return Err(ToolError("the user did not allow it".to_owned()));
+        }
+        if secret {
+            desktop::verify_user()?;
+        }
+        Ok(field.value.expose_secret().clone())
+    }
+
+    async fn call_tool(
+        &self,
+        client: &(dyn Client + Send + Sync),
+        ctx: &Context<'_>,
+        params: &Value,
+    ) -> Option<Value> {
+        let arguments = &params["arguments"];
+        let result = match params["name"].as_str()? {
+            "search_entries" => self.search(client, arguments).await,
+            "get_field" => self.get_field(client, ctx, arguments).await,
+            _ => return None,
+        };
+        let (text, is_error) = match result {
+            Ok(text) => (text, false),
+            Err(ToolError(message)) => (message, true),
+        };
+        Some(json!({ "content": [{ "type": "text", "text": text }], "isError": is_error }))
+    }
+
+    /// Answer one message, or return `None` for notifications, which get no
+    /// answer.
+    async fn handle(
+        &self,
+        client: &(dyn Client + Send + Sync),
+        ctx: &Context<'_>,
+        message: &Value,
+    ) -> Option<Value> {
+        let id = message.get("id")?;
+        let method = message["method"].as_str().unwrap_or_default();
+        debug!("Handling {}", method);
+        Some(match method {
+            "initialize" => response(
+                id,
+                &json!({
+                    "protocolVersion": PROTOCOL_VERSION,
+                    "capabilities": { "tools": {} },
+                    "serverInfo": { "name": "karp", "version": env!("CARGO_PKG_VERSION") },
+                }),
+            ),
+            "ping" => response(id, &json!({})),
+            "tools/list" => response(id, &json!({ "tools": tools() })),
+            "tools/call" => match self.call_tool(client, ctx, &message["params"]).await {
+                Some(result) => response(id, &result),
+                None => error_response(id, INVALID_PARAMS, "unknown tool"),
+            },
+            _ => error_response(id, METHOD_NOT_FOUND, "method not found"),
+        })
+    }
+}
+
+/// A client that can't see entries outside the groups the assistant is
+/// allowed, as if they weren't there.
+struct Restricted<'cmd> {
+    inner: &'cmd (dyn Client + Send + Sync),
+    command: &'cmd Command,
+}
+
+#[async_trait]
+impl Client for Restricted<'_> {
+    async fn get_entry(
+        &self,
+        group_names: &mut (dyn Iterator<Item = &str> + Send + Sync),
+        title: &str,
+    ) -> Result<Entry> {
+        let names: Vec<_> = group_names.collect();
+        let entry = self
+            .inner
+            .get_entry(&mut names.iter().copied(), title)
+            .await?;
+        if !self.command.allows(&entry) {
+            return Err(Error::EntryNotFound {
+                parent: Group {
+                    path: names.join("/"),
+                },
+                name: title.to_owned(),
+            });
+        }
+        Ok(entry)
+    }
+
+    async fn get_entry_by_id(&self, id: Uuid) -> Result<Entry> {
+        let entry = self.inner.get_entry_by_id(id).await?;
+        if !self.command.allows(&entry) {
+            return Err(Error::EntryIdNotFound { id });
+        }
+        Ok(entry)
+    }
+
+    async fn find_entries(&self, query: &str, limit: Option<NonZeroUsize>) -> Result<Vec<Entry>> {
+        let mut entries = self.inner.find_entries(query, None).await?;
+        entries.retain(|entry| self.command.allows(entry));
+        entries.truncate(limit.map_or(usize::MAX, NonZeroUsize::get));
+        Ok(entries)
+    }
+
+    async fn create_entry(
+        &self,
+        group_names: &mut (dyn Iterator<Item = &str> + Send + Sync),
+        entry: &Entry,
+    ) -> Result<()> {
+        self.inner.create_entry(group_names, entry).await
+    }
+
+    async fn update_entry(&self, id: Uuid, entry: &Entry) -> Result<()> {
+        self.inner.update_entry(id, entry).await
+    }
+
+    async fn list_group(
+        &self,
+        group_names: &mut (dyn Iterator<Item = &str> + Send + Sync),
+    ) -> Result<GroupListing> {
+        self.inner.list_group(group_names).await
+    }
+
+    async fn databases(&self) -> Result<Vec<Database>> {
+        self.inner.databases().await
+    }
+
+    async fn features(&self) -> Result<Vec<String>> {
+        self.inner.features().await
+    }
+
+    fn group_paths(&self) -> bool {
+        self.inner.group_paths()
+    }
+}
+
+#[async_trait]
+impl super::Command for Command {
+    async fn execute(self, client: impl Client + Send + Sync, ctx: &Context<'_>) -> Result<()> {
+        // With only the names of groups, an entry in Personal/Work would pass
+        // for one in Work.
+        if !self.allow_groups.is_empty() && !client.group_paths() {
+            return Err(Error::GroupPathsUnsupported);
+        }
+        while let Some(line) = task::spawn_blocking(read_line).await?? {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let reply = match serde_json::from_str::<Value>(&line) {
+                Ok(message) => self.handle(&client, ctx, &message).await,
+                Err(e) => Some(error_response(&Value::Null, PARSE_ERROR, &e.to_string())),
+            };
+            if let Some(answer) = reply {
+                println!("{}", serde_json::to_string(&answer)?);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use secrecy::SecretString;
+
+    use super::*;
+
+    fn command(allow_groups: &[&str]) -> Command {
+        Command {
+            mcp: true,
+            allow_groups: allow_groups.iter().map(|&group| group.to_owned()).collect(),
+            allow_secrets: false,
+            confirm: Confirm::Secrets,
+        }
+    }
+
+    fn entry(group: Option<&str>) -> Entry {
+        Entry {
+            id: String::new(),
+            parent: group.map(|path| Group {
+                path: path.to_owned(),
+            }),
+            title: String::new(),
+            urls: vec![],
+            form_fields: vec![],
+            database: None,
+            source: None,
+        }
+    }
+
+    #[test]
+    fn groups_are_allowed_with_their_subgroups() {
+        assert!(command(&[]).allows(&entry(None)));
+        let cmd = command(&["Work/Servers"]);
+        assert!(cmd.allows(&entry(Some("Work/Servers"))));
+        assert!(cmd.allows(&entry(Some("Work/Servers/Linux"))));
+        assert!(!cmd.allows(&entry(Some("Work/ServersOld"))));
+        assert!(!cmd.allows(&entry(Some("Work"))));
+        assert!(!cmd.allows(&entry(None)));
+        // Names are compared the way the rest of karp compares them.
+        let composed = command(&["Caf\u{e9}"]);
+        assert!(composed.allows(&entry(Some("Cafe\u{301}/Menus"))));
+    }
+
+    fn field(type_: FormFieldType, value: &str) -> FormField {
+        FormField {
+            type_,
+            display_name: String::new(),
+            value: SecretString::new(value.to_owned()),
+            metadata: None,
+        }
+    }
+
+    #[test]
+    fn placeholders_are_secrets() -> std::result::Result<(), ToolError> {
+        let cmd = command(&[]);
+        assert!(!cmd.check_secret(&field(FormFieldType::Text, "acme"))?);
+        assert!(cmd
+            .check_secret(&field(FormFieldType::Text, "{PASSWORD}"))
+            .is_err());
+        assert!(cmd
+            .check_secret(&field(FormFieldType::Username, "{REF:P@I:0404}"))
+            .is_err());
+        assert!(cmd
+            .check_secret(&field(FormFieldType::Password, "hunter2"))
+            .is_err());
+
+        let allowing = Command {
+            allow_secrets: true,
+            ..command(&[])
+        };
+        assert!(allowing.check_secret(&field(FormFieldType::Text, "{PASSWORD}"))?);
+        Ok(())
+    }
+}
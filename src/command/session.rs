@@ -0,0 +1,146 @@
+// SPDX-FileCopyrightText: 2024 Noah Fontes
+//
+// SPDX-License-Identifier: Apache-2.0
+
+use clap::{Parser, Subcommand};
+use log::{error, warn};
+use serde::Serialize;
+use url::Url;
+use uuid::Uuid;
+
+use crate::{
+    error::{self, Result},
+    keepass, keepassxc,
+    storage::{self, Storage, Versioned as _},
+};
+
+/// Manage the session data cached for the selected URL.
+#[derive(Debug, Parser)]
+pub(crate) struct Command {
+    #[clap(subcommand)]
+    action: Action,
+}
+
+#[derive(Debug, Subcommand)]
+enum Action {
+    /// Move the session data from the storage that holds it to another kind
+    /// of storage, deleting the old copy. This is useful after installing a
+    /// keyring on a machine that had fallen back to file storage.
+    Migrate {
+        /// The storage to move the session data from. Defaults to the first
+        /// storage that has session data for the URL.
+        #[arg(long, value_enum)]
+        from: Option<storage::Kind>,
+
+        /// The storage to move the session data to.
+        #[arg(long, value_enum)]
+        to: storage::Kind,
+    },
+    /// Print the association with KeePassXC, or the identifier KeePassRPC
+    /// knows us by, as JSON, so another client or machine can reuse it
+    /// instead of asking for a new one. For KeePassXC, this is an object with
+    /// `protocol` set to `keepassxc` and an `associations` array, each with the
+    /// `hash` of the database, the `id` of the association, and the Base64
+    /// public identity `key`. For KeePassRPC, it's an object with `protocol`
+    /// set to `keepassrpc` and the `identifier` as a UUID. Anyone with this can
+    /// use the association, so keep it secret.
+    Export,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "protocol", rename_all = "lowercase")]
+enum Export {
+    Keepassrpc {
+        identifier: Uuid,
+    },
+    Keepassxc {
+        associations: Vec<keepassxc::session::Association>,
+    },
+}
+
+impl Export {
+    fn new(url: &Url, data: Data) -> Result<Self> {
+        Ok(match url.scheme() {
+            "ws" | "wss" => Self::Keepassrpc {
+                identifier: keepass::session::Data::decode(data)?.identifier(),
+            },
+            "file" | "tcp" | "stdio" => Self::Keepassxc {
+                associations: keepassxc::session::Data::decode(data)?.associations(),
+            },
+            scheme => {
+                error!("Connections to {} URLs don't have session data", scheme);
+                return Err(error::Error::Command);
+            }
+        })
+    }
+}
+
+type Data = serde_json::Value;
+
+/// Find the session data for the URL in the given kinds of storage, skipping
+/// any that can't be read unless only one was given.
+pub(super) async fn find(
+    url: &Url,
+    kinds: &[storage::Kind],
+    options: storage::Options,
+) -> Result<(storage::Kind, Box<dyn Storage<Data>>, Data)> {
+    for &kind in kinds {
+        let result = async {
+            let mut source = kind.open::<Data>(url, options).await?;
+            let data = source.get().await?;
+            Ok::<_, error::Error>(data.map(|found| (source, found)))
+        }
+        .await;
+
+        match result {
+            Ok(Some((source, data))) => return Ok((kind, source, data)),
+            Ok(None) => {}
+            Err(e) if kinds.len() > 1 => {
+                warn!(
+                    "We skipped {} storage because we can't read it: {}",
+                    kind, e
+                );
+            }
+            Err(e) => return Err(e),
+        }
+    }
+
+    Err(error::Storage::NoSessionData.into())
+}
+
+impl Command {
+    pub(crate) async fn run(self, url: &Url, options: storage::Options) -> Result<()> {
+        match self.action {
+            Action::Migrate { from, to } => {
+                if from == Some(to) {
+                    return Ok(());
+                }
+
+                let kinds: Vec<storage::Kind> = match from {
+                    Some(kind) => vec![kind],
+                    None => storage::Kind::SUPPORTED
+                        .iter()
+                        .copied()
+                        .filter(|&kind| kind != to)
+                        .collect(),
+                };
+                let mut target = to.open::<Data>(url, options).await?;
+                let (kind, mut source, data) = find(url, &kinds, options).await?;
+
+                target.update(&data).await?;
+                source.clear().await?;
+
+                println!("Moved session data from {kind} storage to {to} storage");
+                Ok(())
+            }
+            Action::Export => {
+                let (_, _, data) = find(url, storage::Kind::SUPPORTED, options).await?;
+                println!(
+                    "{}",
+                    serde_json::to_string_pretty(&Export::new(url, data)?)?
+                );
+                Ok(())
+            }
+        }
+    }
+}
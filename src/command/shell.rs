@@ -0,0 +1,125 @@
+// SPDX-FileCopyrightText: 2024 Noah Fontes
+//
+// SPDX-License-Identifier: Apache-2.0
+
+use std::{fs, io, path::PathBuf};
+
+use clap::{Parser, Subcommand};
+use log::{error, warn};
+use rustyline::{error::ReadlineError, history::DefaultHistory, Editor};
+use tokio::task;
+
+use super::Context;
+use crate::{client::Client, error::Result, metadata};
+
+/// Run commands one after another over a single connection. Lines are split
+/// into arguments the way a POSIX shell would split them. Lines that begin
+/// with a space are not recorded in the history. Enter `exit` or press
+/// Ctrl-D to leave the shell.
+#[derive(Debug, Parser)]
+pub(crate) struct Command {
+    /// Don't read or write the history file.
+    #[arg(long)]
+    no_history: bool,
+}
+
+#[derive(Debug, Parser)]
+#[command(no_binary_name = true, disable_version_flag = true)]
+struct Line<T: Subcommand> {
+    #[clap(subcommand)]
+    command: T,
+}
+
+pub(super) fn history_path() -> Option<PathBuf> {
+    metadata::Dir::State.file("shell_history")
+}
+
+impl Command {
+    fn editor(&self) -> Result<(Editor<(), DefaultHistory>, Option<PathBuf>)> {
+        let config = rustyline::Config::builder()
+            .auto_add_history(false)
+            .history_ignore_space(true)
+            .build();
+        let mut editor = Editor::with_config(config)?;
+
+        let history = if self.no_history {
+            None
+        } else {
+            history_path()
+        };
+        if let Some(ref path) = history {
+            match editor.load_history(path) {
+                Ok(()) => {}
+                Err(ReadlineError::Io(e)) if e.kind() == io::ErrorKind::NotFound => {}
+                Err(e) => warn!("We could not read the shell history: {}", e),
+            }
+        }
+
+        Ok((editor, history))
+    }
+
+    /// Read and run commands until the user leaves the shell. Each line is
+    /// parsed as one of the commands in `T`. Commands that select their own
+    /// URL get a separate connection for the duration of the command.
+    pub(crate) async fn run<T>(
+        self,
+        client: &(dyn Client + Send + Sync),
+        ctx: &Context<'_>,
+    ) -> Result<()>
+    where
+        T: Subcommand + super::Command + Send,
+    {
+        let (mut editor, history) = self.editor()?;
+
+        loop {
+            let line = match task::block_in_place(|| editor.readline("karp> ")) {
+                Ok(line) => line,
+                Err(ReadlineError::Interrupted) => continue,
+                Err(ReadlineError::Eof) => break,
+                Err(e) => return Err(e.into()),
+            };
+            let _ = editor.add_history_entry(line.as_str())?;
+
+            let args = match shell_words::split(&line) {
+                Ok(args) if args.is_empty() => continue,
+                Ok(args) if args == ["exit"] => break,
+                Ok(args) => args,
+                Err(e) => {
+                    error!("We could not parse the command: {}", e);
+                    continue;
+                }
+            };
+            let cmd = match Line::<T>::try_parse_from(args) {
+                Ok(line) => line.command,
+                Err(e) => {
+                    e.print()?;
+                    continue;
+                }
+            };
+
+            let result = match cmd.url().cloned() {
+                Some(url) => match ctx.connector.connect(&url).await {
+                    Ok(channel) => {
+                        let result = cmd.execute(channel.client(), ctx).await;
+                        channel.close().await.and(result)
+                    }
+                    Err(e) => Err(e),
+                },
+                None => cmd.execute(client, ctx).await,
+            };
+            if let Err(e) = result {
+                error!("We encountered an error: {}", e);
+            }
+        }
+
+        if let Some(ref path) = history {
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            if let Err(e) = editor.save_history(path) {
+                warn!("We could not save the shell history: {}", e);
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,66 @@
+// SPDX-FileCopyrightText: 2024 Noah Fontes
+//
+// SPDX-License-Identifier: Apache-2.0
+
+use async_trait::async_trait;
+use clap::Parser;
+use tabled::{
+    settings::{
+        object::{Rows, Segment},
+        Alignment, Format, Modify, Style,
+    },
+    Table,
+};
+
+use super::Context;
+use crate::{client::Client, error::Result, locale};
+
+/// Show the databases the backend has open and whether each is active. The
+/// selector of a database is the value to give to `--database` to use it.
+/// Locked databases are not shown: KeePassRPC leaves them out, and KeePassXC
+/// waits for the active database to be unlocked before answering. Settings the
+/// backend reports follow, such as the ones KeePassRPC gives browser
+/// extensions, which help explain why a URL search finds nothing. Any
+/// protocol features the backend reported when we connected come last.
+#[derive(Debug, Parser)]
+pub(crate) struct Command {}
+
+#[async_trait]
+impl super::Command for Command {
+    async fn execute(self, client: impl Client + Send + Sync, _: &Context<'_>) -> Result<()> {
+        let databases = client.databases().await?;
+
+        if databases.is_empty() {
+            println!("{}", locale::text("status-no-databases"));
+        } else {
+            println!(
+                "{}",
+                Table::new(databases)
+                    .with(Style::rounded())
+                    .with(Modify::new(Rows::first()).with(Format::content(locale::header)))
+                    .with(Modify::new(Segment::all()).with(Alignment::left()))
+            );
+        }
+
+        let settings = client.settings().await?;
+        if !settings.is_empty() {
+            println!("{}", locale::text("status-settings"));
+            println!(
+                "{}",
+                Table::new(settings)
+                    .with(Style::rounded())
+                    .with(Modify::new(Rows::first()).with(Format::content(locale::header)))
+                    .with(Modify::new(Segment::all()).with(Alignment::left()))
+            );
+        }
+
+        let features = client.features().await?;
+        if !features.is_empty() {
+            println!("{}", locale::text("status-features"));
+            for feature in features {
+                println!("  {feature}");
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,211 @@
+// SPDX-FileCopyrightText: 2024 Noah Fontes
+//
+// SPDX-License-Identifier: Apache-2.0
+
+use std::{
+    fs,
+    io::Write as _,
+    path::{Path, PathBuf},
+    process::{self, Stdio},
+    str::FromStr,
+};
+
+use async_trait::async_trait;
+use clap::{Parser, ValueEnum as _};
+use secrecy::{ExposeSecret as _, SecretString};
+
+use super::Context;
+use crate::{
+    client::{Client, Entry, EntryRef, FormFieldType},
+    desktop,
+    error::{self, Result},
+    locale, placeholder, storage,
+};
+
+/// Write fields of entries to files in a directory, one credential per file,
+/// for services to load with `LoadCredential=` (or, with `--encrypt`,
+/// `LoadCredentialEncrypted=`). Each file is readable only by its owner, and
+/// is replaced as a whole so a service never reads half of one.
+#[derive(Debug, Parser)]
+pub(crate) struct Command {
+    /// The directory to write the credentials to, such as `/etc/credstore`
+    /// or `/run/credstore`. It's created if it doesn't exist.
+    #[arg(long, value_hint = clap::ValueHint::DirPath)]
+    directory: PathBuf,
+
+    /// Encrypt each credential with `systemd-creds encrypt`, so it can only be
+    /// read on this machine.
+    #[arg(long)]
+    encrypt: bool,
+
+    /// A credential to write, as `[NAME=]ENTRY:FIELD`. The entry is given by
+    /// its path (`Group/Subgroup/Title`) or ID, and the field by its type,
+    /// such as `password`, or its name. The credential is named after the
+    /// title of the entry and the field unless a name is given.
+    #[clap(value_name = "CREDENTIAL", required = true)]
+    credentials: Vec<Credential>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Credential {
+    name: Option<String>,
+    entry: EntryRef,
+    field: String,
+}
+
+impl FromStr for Credential {
+    type Err = error::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (name, spec) = match s.split_once('=') {
+            Some((name, spec)) => (Some(name.to_owned()), spec),
+            None => (None, s),
+        };
+        let (entry, field) = spec
+            .rsplit_once(':')
+            .filter(|&(_, field)| !field.is_empty())
+            .ok_or_else(|| error::Error::InvalidCredential(s.to_owned()))?;
+        if name.as_deref().is_some_and(|given| !is_valid_name(given)) {
+            return Err(error::Error::InvalidCredential(s.to_owned()));
+        }
+        Ok(Self {
+            name,
+            entry: entry.parse()?,
+            field: field.to_owned(),
+        })
+    }
+}
+
+/// Whether systemd accepts the name for a credential, which becomes a file
+/// name.
+fn is_valid_name(name: &str) -> bool {
+    !name.is_empty() && name != "." && name != ".." && !name.contains(['/', '\0'])
+}
+
+/// The value of the field given by type or name.
+fn field_value(entry: &Entry, field: &str) -> Result<SecretString> {
+    let found = match FormFieldType::from_str(field, true) {
+        Ok(type_) => entry.form_fields.iter().find(|other| other.type_ == type_),
+        Err(_) => entry
+            .form_fields
+            .iter()
+            .find(|other| other.display_name.eq_ignore_ascii_case(field)),
+    };
+    found
+        .map(|other| other.value.clone())
+        .ok_or_else(|| error::Error::FieldNotFound(field.to_owned()))
+}
+
+/// Create a new file that only its owner can access, with the given
+/// permissions on Unix.
+#[cfg(unix)]
+pub(super) fn create_file(path: &Path, mode: u32) -> std::io::Result<fs::File> {
+    use std::os::unix::fs::OpenOptionsExt as _;
+
+    fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(mode)
+        .open(path)
+}
+
+#[cfg(not(unix))]
+pub(super) fn create_file(path: &Path, _: u32) -> std::io::Result<fs::File> {
+    fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(path)
+}
+
+/// Write the data to a file next to the credential, then move it into place.
+fn write_credential(path: &Path, name: &str, value: &SecretString, encrypt: bool) -> Result<()> {
+    let temp_path = path.with_file_name(format!(".{name}.tmp"));
+    let _ = fs::remove_file(&temp_path);
+    let result = (|| {
+        let mut file = create_file(&temp_path, 0o400)?;
+        if encrypt {
+            let mut child = process::Command::new("systemd-creds")
+                .arg("encrypt")
+                .arg(format!("--name={name}"))
+                .args(["-", "-"])
+                .stdin(Stdio::piped())
+                .stdout(file)
+                .spawn()?;
+            if let Some(mut stdin) = child.stdin.take() {
+                stdin.write_all(value.expose_secret().as_bytes())?;
+            }
+            let status = child.wait()?;
+            if !status.success() {
+                return Err(error::Error::SystemdCreds(status));
+            }
+        } else {
+            file.write_all(value.expose_secret().as_bytes())?;
+            file.sync_all()?;
+        }
+        fs::rename(&temp_path, path)?;
+        Ok(())
+    })();
+    if result.is_err() {
+        let _ = fs::remove_file(&temp_path);
+    }
+    result
+}
+
+#[async_trait]
+impl super::Command for Command {
+    async fn execute(self, client: impl Client + Send + Sync, ctx: &Context<'_>) -> Result<()> {
+        // Look everything up first, so a missing field doesn't leave some
+        // credentials updated and others not.
+        let mut values = Vec::with_capacity(self.credentials.len());
+        for credential in &self.credentials {
+            let stored = client
+                .get_entry_by_ref(&ctx.resolve_entry(&credential.entry))
+                .await?;
+            let entry = placeholder::resolve(&client, &stored).await?;
+            let name = match credential.name {
+                Some(ref given) => given.clone(),
+                None => format!("{}.{}", entry.title, credential.field).replace('/', "-"),
+            };
+            values.push((name, field_value(&entry, &credential.field)?));
+        }
+
+        desktop::verify_user()?;
+        storage::create_dir(&self.directory)?;
+        for (name, value) in values {
+            let path = self.directory.join(&name);
+            write_credential(&path, &name, &value, self.encrypt)?;
+            println!(
+                "{}",
+                locale::format(
+                    "systemd-creds-written",
+                    &[("name", &name), ("path", &path.display())]
+                )
+            );
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_credentials() -> Result<()> {
+        assert_eq!(
+            "db-password=Servers/DB:password".parse::<Credential>()?,
+            Credential {
+                name: Some("db-password".to_owned()),
+                entry: "Servers/DB".parse()?,
+                field: "password".to_owned(),
+            }
+        );
+        assert_eq!(
+            "Servers/DB:Tenant ID".parse::<Credential>()?.field,
+            "Tenant ID"
+        );
+        assert!("Servers/DB".parse::<Credential>().is_err());
+        assert!("../x=Servers/DB:password".parse::<Credential>().is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,81 @@
+// SPDX-FileCopyrightText: 2024 Noah Fontes
+//
+// SPDX-License-Identifier: Apache-2.0
+
+use std::time::SystemTime;
+
+use async_trait::async_trait;
+use clap::{Parser, Subcommand};
+use log::warn;
+use secrecy::ExposeSecret as _;
+use subtle::ConstantTimeEq as _;
+
+use super::Context;
+use crate::{
+    client::{Client, EntryRef, FormFieldType},
+    error::{Error, Result},
+    locale, output, totp,
+};
+
+/// Work with the time-based one-time passwords of entries.
+#[derive(Debug, Parser)]
+pub(crate) struct Command {
+    #[clap(subcommand)]
+    action: Action,
+}
+
+#[derive(Debug, Subcommand)]
+enum Action {
+    /// Check a code against the TOTP seed of an entry, accepting the codes
+    /// just before and after the current one too. We exit with status 0 if
+    /// the code is valid and 1 if it isn't, for enrollment and validation
+    /// scripts. If the backend only gives us the current code and not the
+    /// seed, as KeePassXC does, only the current code is accepted.
+    Verify {
+        /// The path (`Group/Subgroup/Title`) or ID of the entry.
+        #[clap()]
+        entry: EntryRef,
+
+        /// The code to check.
+        #[clap()]
+        code: String,
+    },
+}
+
+#[async_trait]
+impl super::Command for Command {
+    async fn execute(self, client: impl Client + Send + Sync, ctx: &Context<'_>) -> Result<()> {
+        let Action::Verify { entry, code } = self.action;
+        let found = client.get_entry_by_ref(&ctx.resolve_entry(&entry)).await?;
+        let valid = if let Some(seed) = totp::Seed::from_entry(&found)? {
+            let now = SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .unwrap_or_default();
+            seed.verify(&code, now)
+        } else {
+            let current = found
+                .form_fields
+                .iter()
+                .find(|field| field.type_ == FormFieldType::Totp)
+                .ok_or(Error::FieldTypeNotFound(FormFieldType::Totp))?;
+            warn!(
+                "The backend gives us only the current code, so we can't accept the ones around it"
+            );
+            bool::from(
+                current
+                    .value
+                    .expose_secret()
+                    .as_bytes()
+                    .ct_eq(code.as_bytes()),
+            )
+        };
+
+        if !valid {
+            return Err(Error::TotpMismatch);
+        }
+        if !output::is_json() {
+            println!("{}", locale::text("totp-valid"));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,197 @@
+// SPDX-FileCopyrightText: 2024 Noah Fontes
+//
+// SPDX-License-Identifier: Apache-2.0
+
+use std::time::Duration;
+
+use async_trait::async_trait;
+use clap::Parser;
+use secrecy::{ExposeSecret as _, SecretString};
+use tokio::task;
+
+use super::Context;
+use crate::{
+    client::{Client, EntryRef},
+    desktop::{self, Key, Keystroke},
+    error::{self, Result},
+    locale, placeholder,
+};
+
+/// Type the username and password of an entry into the active window, like
+/// KeePass auto-type, using wtype, xdotool, or ydotool. This works with any
+/// backend, not just KeePassXC.
+#[derive(Debug, Parser)]
+pub(crate) struct Command {
+    /// How long to wait before typing, in seconds, to give time to switch to
+    /// the window to type into.
+    #[arg(long, value_name = "SECONDS", default_value = "3")]
+    delay: u64,
+
+    /// The path (`Group/Subgroup/Title`) or ID of the entry.
+    #[clap()]
+    entry: EntryRef,
+
+    /// What to type, in the syntax of KeePass auto-type sequences. Any
+    /// placeholder, such as `{USERNAME}` or `{S:PIN}`, types its value; keys
+    /// are given as `{TAB}`, `{ENTER}`, `{SPACE}`, and so on, optionally
+    /// repeated like `{TAB 2}`; `{DELAY 500}` pauses for that many
+    /// milliseconds; and `{{}` and `{}}` type braces.
+    #[clap(default_value = "{USERNAME}{TAB}{PASSWORD}{ENTER}")]
+    sequence: String,
+}
+
+#[derive(Debug, PartialEq)]
+enum Token<'seq> {
+    Text(&'seq str),
+    Key(Key, usize),
+    Delay(Duration),
+    Placeholder(&'seq str),
+}
+
+fn key(name: &str) -> Option<Key> {
+    Some(match name.to_ascii_uppercase().as_str() {
+        "TAB" => Key::Tab,
+        "ENTER" => Key::Enter,
+        "SPACE" => Key::Space,
+        "BACKSPACE" | "BKSP" | "BS" => Key::Backspace,
+        "DELETE" | "DEL" => Key::Delete,
+        "ESC" => Key::Escape,
+        "UP" => Key::Up,
+        "DOWN" => Key::Down,
+        "LEFT" => Key::Left,
+        "RIGHT" => Key::Right,
+        "HOME" => Key::Home,
+        "END" => Key::End,
+        _ => return None,
+    })
+}
+
+/// Interpret what's inside a pair of braces.
+fn token(inner: &str) -> Result<Token<'_>> {
+    let invalid = || error::Error::InvalidSequence(format!("{{{inner}}}"));
+    let (name, argument) = match inner.split_once(' ') {
+        Some((name, argument)) => (name, Some(argument.trim())),
+        None => (inner, None),
+    };
+
+    if name.eq_ignore_ascii_case("DELAY") {
+        let millis = argument
+            .and_then(|millis| millis.parse().ok())
+            .ok_or_else(invalid)?;
+        return Ok(Token::Delay(Duration::from_millis(millis)));
+    }
+    match key(name) {
+        Some(key) => {
+            let times = match argument {
+                Some(count) => count.parse().map_err(|_| invalid())?,
+                None => 1,
+            };
+            Ok(Token::Key(key, times))
+        }
+        None => Ok(Token::Placeholder(inner)),
+    }
+}
+
+fn parse(sequence: &str) -> Result<Vec<Token<'_>>> {
+    let mut tokens = vec![];
+    let mut rest = sequence;
+    while let Some(start) = rest.find('{') {
+        if start > 0 {
+            tokens.push(Token::Text(&rest[..start]));
+        }
+        rest = &rest[start..];
+
+        if let Some(brace) = ["{{}", "{}}"].iter().find(|brace| rest.starts_with(*brace)) {
+            tokens.push(Token::Text(&brace[1..2]));
+            rest = &rest[3..];
+            continue;
+        }
+        let len = rest
+            .find('}')
+            .ok_or_else(|| error::Error::InvalidSequence(rest.to_owned()))?;
+        tokens.push(token(&rest[1..len])?);
+        rest = &rest[len + 1..];
+    }
+    if !rest.is_empty() {
+        tokens.push(Token::Text(rest));
+    }
+    Ok(tokens)
+}
+
+/// Add text to the keystrokes, joining it to the text before it so it's
+/// typed at once.
+fn push_text(keystrokes: &mut Vec<Keystroke>, text: &str) {
+    match keystrokes.last_mut() {
+        Some(&mut Keystroke::Text(ref mut last)) => {
+            *last = SecretString::new(format!("{}{text}", last.expose_secret()));
+        }
+        _ => keystrokes.push(Keystroke::Text(SecretString::new(text.to_owned()))),
+    }
+}
+
+#[async_trait]
+impl super::Command for Command {
+    async fn execute(self, client: impl Client + Send + Sync, ctx: &Context<'_>) -> Result<()> {
+        let tokens = parse(&self.sequence)?;
+        let entry = client
+            .get_entry_by_ref(&ctx.resolve_entry(&self.entry))
+            .await?;
+
+        let mut keystrokes = vec![Keystroke::Delay(Duration::from_secs(self.delay))];
+        for token in tokens {
+            match token {
+                Token::Text(text) => push_text(&mut keystrokes, text),
+                Token::Key(key, times) => {
+                    keystrokes.extend((0..times).map(|_| Keystroke::Key(key)));
+                }
+                Token::Delay(duration) => keystrokes.push(Keystroke::Delay(duration)),
+                Token::Placeholder(name) => {
+                    let value = placeholder::expand_one(&client, &entry, name)
+                        .await?
+                        .ok_or_else(|| error::Error::UnknownPlaceholder(name.to_owned()))?;
+                    push_text(&mut keystrokes, &value);
+                }
+            }
+        }
+
+        println!(
+            "{}",
+            locale::format("type-waiting", &[("seconds", &self.delay)])
+        );
+        task::spawn_blocking(move || desktop::type_keystrokes(&keystrokes)).await??;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_sequences() -> Result<()> {
+        assert_eq!(
+            parse("{USERNAME}{TAB}{PASSWORD}{ENTER}")?,
+            [
+                Token::Placeholder("USERNAME"),
+                Token::Key(Key::Tab, 1),
+                Token::Placeholder("PASSWORD"),
+                Token::Key(Key::Enter, 1),
+            ]
+        );
+        assert_eq!(
+            parse("id {{}{S:Tenant ID}{}}{tab 2}{DELAY 250}!")?,
+            [
+                Token::Text("id "),
+                Token::Text("{"),
+                Token::Placeholder("S:Tenant ID"),
+                Token::Text("}"),
+                Token::Key(Key::Tab, 2),
+                Token::Delay(Duration::from_millis(250)),
+                Token::Text("!"),
+            ]
+        );
+        assert!(parse("{USERNAME").is_err());
+        assert!(parse("{DELAY soon}").is_err());
+        Ok(())
+    }
+}
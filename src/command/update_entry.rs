@@ -0,0 +1,194 @@
+// SPDX-FileCopyrightText: 2024 Noah Fontes
+//
+// SPDX-License-Identifier: Apache-2.0
+
+use async_trait::async_trait;
+use clap::{ArgGroup, Parser};
+use secrecy::SecretString;
+use uuid::Uuid;
+
+use super::{
+    create_entry::{self, PasswordSource},
+    Context,
+};
+use crate::{
+    client::{Client, Entry, EntryRef, FormField, FormFieldType},
+    error::{self, Result},
+    locale,
+};
+
+/// Change parts of an entry without opening an editor. With
+/// `--generate-password`, a new password is generated and saved to the entry
+/// in the same update, then shown once, for rotating a credential.
+#[derive(Debug, Parser)]
+#[command(group(
+    ArgGroup::new("changes")
+        .required(true)
+        .multiple(true)
+        .args(["username", "entry_urls", "fields", "password_stdin", "generate_password"])
+))]
+pub(crate) struct Command {
+    /// The new username of the entry.
+    #[arg(long)]
+    username: Option<String>,
+
+    /// Replace the URLs of the entry. Give this more than once for several
+    /// URLs.
+    #[arg(long = "entry-url", value_name = "URL", value_hint = clap::ValueHint::Url)]
+    entry_urls: Vec<String>,
+
+    /// Set the field with the given name, as `NAME=VALUE`, adding it if the
+    /// entry doesn't have one. Give this more than once for several fields.
+    #[arg(long = "field", value_name = "NAME=VALUE", value_parser = create_entry::parse_field)]
+    fields: Vec<(String, String)>,
+
+    /// Read the new password from the first line of standard input.
+    #[arg(long)]
+    password_stdin: bool,
+
+    /// Generate a new password, either ourselves or with the backend's
+    /// generator, and print it once the entry is updated.
+    #[arg(
+        long,
+        value_enum,
+        value_name = "GENERATOR",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "local",
+        conflicts_with = "password_stdin"
+    )]
+    generate_password: Option<PasswordSource>,
+
+    /// Copy the generated password to the clipboard instead of printing it.
+    #[arg(long, requires = "generate_password")]
+    copy_password: bool,
+
+    /// The path (`Group/Subgroup/Title`) or ID of the entry.
+    #[clap()]
+    entry: EntryRef,
+}
+
+/// Set the value of the first field that `matches`, or add a field with the
+/// given type and name if there isn't one.
+fn set_field(
+    entry: &mut Entry,
+    matches: impl Fn(&FormField) -> bool,
+    type_: FormFieldType,
+    display_name: &str,
+    value: SecretString,
+) {
+    match entry.form_fields.iter_mut().find(|field| matches(field)) {
+        Some(field) => field.value = value,
+        None => entry
+            .form_fields
+            .push(create_entry::field(type_, display_name, value)),
+    }
+}
+
+impl Command {
+    fn apply(&self, entry: &mut Entry, password: Option<SecretString>) {
+        if let Some(ref username) = self.username {
+            set_field(
+                entry,
+                |field| field.type_ == FormFieldType::Username,
+                FormFieldType::Username,
+                "Username",
+                SecretString::new(username.clone()),
+            );
+        }
+        if let Some(value) = password {
+            set_field(
+                entry,
+                |field| field.type_ == FormFieldType::Password,
+                FormFieldType::Password,
+                "Password",
+                value,
+            );
+        }
+        if !self.entry_urls.is_empty() {
+            entry.urls.clone_from(&self.entry_urls);
+        }
+        // One-time passwords are generated, not stored, so they can't be set.
+        for (name, value) in &self.fields {
+            set_field(
+                entry,
+                |field| field.type_ != FormFieldType::Totp && field.display_name == *name,
+                FormFieldType::Text,
+                name,
+                SecretString::new(value.clone()),
+            );
+        }
+    }
+}
+
+#[async_trait]
+impl super::Command for Command {
+    async fn execute(self, client: impl Client + Send + Sync, ctx: &Context<'_>) -> Result<()> {
+        let mut entry = client
+            .get_entry_by_ref(&ctx.resolve_entry(&self.entry))
+            .await?;
+        let id = Uuid::try_parse(&entry.id).map_err(error::Conversion::from)?;
+
+        let password = match self.generate_password {
+            Some(source) => Some(source.generate(&client).await?),
+            None if self.password_stdin => Some(create_entry::read_stdin()?),
+            None => None,
+        };
+        self.apply(&mut entry, password.clone());
+
+        client.update_entry(id, &entry).await?;
+        println!("{}", locale::text("edit-saved"));
+        if let (Some(generated), Some(_)) = (password, self.generate_password) {
+            create_entry::reveal(&generated, &entry.title, self.copy_password)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use secrecy::ExposeSecret as _;
+
+    use super::*;
+
+    #[test]
+    fn fields_are_replaced_or_added() -> Result<()> {
+        let command = Command::try_parse_from([
+            "update-entry",
+            "--username=bob",
+            "--field=PIN=1234",
+            "Example",
+        ])
+        .map_err(|_| error::Error::Command)?;
+        let mut entry = Entry {
+            id: String::new(),
+            parent: None,
+            title: "Example".to_owned(),
+            urls: vec![],
+            form_fields: vec![create_entry::field(
+                FormFieldType::Username,
+                "Login",
+                SecretString::new("alice".to_owned()),
+            )],
+            database: None,
+            source: None,
+        };
+        command.apply(&mut entry, Some(SecretString::new("hunter2".to_owned())));
+
+        let fields: Vec<_> = entry
+            .form_fields
+            .iter()
+            .map(|field| {
+                (
+                    field.display_name.as_str(),
+                    field.value.expose_secret().as_str(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            fields,
+            [("Login", "bob"), ("Password", "hunter2"), ("PIN", "1234")]
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,39 @@
+// SPDX-FileCopyrightText: 2024 Noah Fontes
+//
+// SPDX-License-Identifier: Apache-2.0
+
+use clap::Parser;
+use url::Url;
+
+use crate::{
+    client::Connector,
+    error::{Error, Result},
+    locale, output,
+};
+
+/// Check that the cached session with the selected backend still works,
+/// without asking for a password or approval and without reading any
+/// entries. We exit with status 0 if it does and 1 if it doesn't, so a batch
+/// job can find out before it starts.
+#[derive(Debug, Parser)]
+pub(crate) struct Command {}
+
+impl Command {
+    /// Connect without being able to ask the user anything, so the
+    /// connection only succeeds on the strength of the cached session, and
+    /// make a request that needs it.
+    pub(crate) async fn run(self, connector: &dyn Connector, url: &Url) -> Result<()> {
+        let channel = connector
+            .connect(url)
+            .await
+            .map_err(|e| Error::SessionInvalid(Box::new(e)))?;
+        let result = channel.client().databases().await;
+        channel.close().await?;
+        let _ = result.map_err(|e| Error::SessionInvalid(Box::new(e)))?;
+
+        if !output::is_json() {
+            println!("{}", locale::text("verify-session-valid"));
+        }
+        Ok(())
+    }
+}
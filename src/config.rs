@@ -0,0 +1,334 @@
+// SPDX-FileCopyrightText: 2024 Noah Fontes
+//
+// SPDX-License-Identifier: Apache-2.0
+
+use std::{collections::BTreeMap, fs, io, mem, path::PathBuf};
+
+use serde::{Deserialize, Serialize};
+use toml_edit::{DocumentMut, Item};
+use url::Url;
+
+use crate::{
+    error::{self, Result},
+    metadata, password,
+};
+
+/// Settings for a particular connection that can be selected by name on the
+/// command line.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct Profile {
+    /// The URL to connect to when none is given on the command line.
+    pub(crate) url: Option<Url>,
+    /// Several backends to use together instead of a single URL, each named
+    /// for showing where entries came from. Searches go to all of them, and
+    /// other lookups to whichever has the entry, in the order of their names.
+    pub(crate) backends: Option<BTreeMap<String, Url>>,
+    /// A group path, with each subgroup separated by a slash, that relative
+    /// entry paths are resolved against.
+    pub(crate) group_prefix: Option<String>,
+    /// The name KeePassRPC shows when asking to authorize this client.
+    pub(crate) client_display_name: Option<String>,
+    /// The description KeePassRPC shows when asking to authorize this client.
+    pub(crate) client_display_description: Option<String>,
+    /// The `Origin` header to send to KeePassRPC.
+    pub(crate) origin: Option<String>,
+    /// The name to give this client when KeePassXC asks to associate with it.
+    pub(crate) association_name: Option<String>,
+    /// Whether reading from a locked KeePassXC database should bring up its
+    /// unlock prompt.
+    pub(crate) trigger_unlock: Option<bool>,
+    /// A program and its arguments to run when the backend isn't running, such
+    /// as `["keepassxc"]`. We wait a while for it to start listening before
+    /// giving up.
+    pub(crate) launch_command: Option<Vec<String>>,
+    /// The SSH destination to reach the backend through.
+    pub(crate) ssh: Option<String>,
+    /// The key file that locks a database read directly from a file.
+    pub(crate) key_file: Option<PathBuf>,
+    /// Whether to keep an encrypted snapshot of looked up entries to answer
+    /// from when the backend can't be reached.
+    pub(crate) snapshot: Option<bool>,
+    /// Whether to lock our memory so secrets can't be swapped out to disk.
+    pub(crate) harden_memory: Option<bool>,
+    /// Whether to have the operating system verify the user, with polkit or
+    /// Touch ID, before we print, copy, or type a secret.
+    pub(crate) require_user_verification: Option<bool>,
+    /// Whether to cache the session key in an unencrypted file when there's
+    /// no keyring or it can't be reached.
+    pub(crate) allow_insecure_storage: Option<bool>,
+    /// The title of the Pinentry dialog, instead of our own.
+    pub(crate) pinentry_title: Option<String>,
+    /// The label next to the password field of the Pinentry dialog.
+    pub(crate) pinentry_prompt: Option<String>,
+    /// The text explaining which password the Pinentry dialog asks for.
+    pub(crate) pinentry_description: Option<String>,
+}
+
+impl Profile {
+    /// The text to show in Pinentry dialogs for a connection to the given URL,
+    /// with `{url}` in any of it replaced by the URL.
+    pub(crate) fn pinentry_text(&self, url: &Url) -> password::PinentryText {
+        let expand =
+            |setting: Option<&String>| setting.map(|text| text.replace("{url}", url.as_str()));
+        password::PinentryText {
+            title: expand(self.pinentry_title.as_ref()),
+            prompt: expand(self.pinentry_prompt.as_ref()),
+            description: expand(self.pinentry_description.as_ref()),
+        }
+    }
+}
+
+/// Commands to run when something happens, each given as a program and its
+/// arguments.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct Hooks {
+    /// Run when we authenticate with a backend.
+    pub(crate) session_authenticated: Option<Vec<String>>,
+    /// Run when the backend tells us the database was locked.
+    pub(crate) database_locked: Option<Vec<String>>,
+    /// Run when the backend tells us the database was unlocked.
+    pub(crate) database_unlocked: Option<Vec<String>>,
+    /// Run when we read an entry, with its ID, title, and group in
+    /// `KARP_ENTRY_ID`, `KARP_ENTRY_TITLE`, and `KARP_ENTRY_GROUP`. A search
+    /// runs it once for each entry it returns.
+    pub(crate) entry_accessed: Option<Vec<String>>,
+    /// Run when we clear the clipboard, with the entry and field that were
+    /// copied in `KARP_ENTRY_TITLE` and `KARP_FIELD`.
+    pub(crate) clipboard_cleared: Option<Vec<String>>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct Config {
+    /// The profile to use when none is given on the command line.
+    pub(crate) default_profile: Option<String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub(crate) profiles: BTreeMap<String, Profile>,
+    /// Saved lookups, each of which expands to the arguments of a
+    /// `get-form-fields` invocation.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub(crate) aliases: BTreeMap<String, Vec<String>>,
+    /// Commands to run when something happens.
+    #[serde(default)]
+    pub(crate) hooks: Hooks,
+}
+
+impl Config {
+    pub(crate) fn path() -> Option<PathBuf> {
+        metadata::PROJECT_DIRS
+            .as_ref()
+            .map(|dirs| dirs.config_dir().join("config.toml"))
+    }
+
+    /// The contents of the configuration file, or `None` if there isn't one.
+    fn read() -> Result<Option<String>> {
+        let Some(path) = Self::path() else {
+            return Ok(None);
+        };
+
+        match fs::read_to_string(&path) {
+            Ok(data) => Ok(Some(data)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Read the configuration file, treating a missing file as an empty
+    /// configuration.
+    pub(crate) fn load() -> Result<Self> {
+        match Self::read()? {
+            Some(data) => Ok(toml::from_str(&data).map_err(error::Config::from)?),
+            None => Ok(Self::default()),
+        }
+    }
+
+    /// Read the settings in the configuration file without checking that we
+    /// know them, so mistakes in it can be fixed.
+    pub(crate) fn load_table() -> Result<toml::Table> {
+        match Self::read()? {
+            Some(data) => Ok(toml::from_str(&data).map_err(error::Config::from)?),
+            None => Ok(toml::Table::new()),
+        }
+    }
+
+    /// The settings as they're written to the configuration file.
+    pub(crate) fn to_table(&self) -> Result<toml::Table> {
+        let value = toml::Value::try_from(self).map_err(error::Config::from)?;
+        Ok(value.as_table().cloned().unwrap_or_default())
+    }
+
+    /// Write the configuration file. Only the settings that changed are
+    /// touched, so comments and formatting are kept.
+    pub(crate) fn save(&self) -> Result<()> {
+        write(&self.to_table()?, true)
+    }
+
+    /// Write settings we may not know to the configuration file, as they are
+    /// given, touching only those that changed.
+    pub(crate) fn save_table(settings: &toml::Table) -> Result<()> {
+        write(settings, false)
+    }
+
+    /// Select the profile with the given name, or the default profile if no
+    /// name is given. Having no default profile is equivalent to selecting an
+    /// empty profile.
+    pub(crate) fn profile(&self, name: Option<&str>) -> Result<Profile> {
+        match name.or(self.default_profile.as_deref()) {
+            Some(selected) => Ok(self
+                .profiles
+                .get(selected)
+                .cloned()
+                .ok_or_else(|| error::Config::UnknownProfile(selected.to_owned()))?),
+            None => Ok(Profile::default()),
+        }
+    }
+}
+
+fn write(settings: &toml::Table, normalized: bool) -> Result<()> {
+    let path = Config::path().ok_or(error::Config::NoPath)?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let text = Config::read()?.unwrap_or_default();
+    fs::write(path, update(&text, settings, normalized)?)?;
+    Ok(())
+}
+
+/// Change the text of a configuration file to hold the given settings. When
+/// they're normalized, as they are once read into a [`Config`], what the file
+/// holds is normalized too before they're compared, so a URL written without
+/// a trailing slash isn't taken for a change.
+fn update(text: &str, settings: &toml::Table, normalized: bool) -> Result<String> {
+    let raw: toml::Table = toml::from_str(text).map_err(error::Config::from)?;
+    let current = if normalized {
+        toml::from_str::<Config>(text)
+            .ok()
+            .and_then(|config| config.to_table().ok())
+            .unwrap_or(raw)
+    } else {
+        raw
+    };
+
+    let mut document: DocumentMut = text.parse().map_err(error::Config::from)?;
+    let mut fresh = toml_edit::ser::to_document(settings).map_err(error::Config::from)?;
+    expand_tables(fresh.as_table_mut());
+    merge(document.as_item_mut(), fresh.as_item(), &current, settings);
+    Ok(document.to_string())
+}
+
+/// Write inline tables as their own sections, as `toml::to_string_pretty`
+/// would. Tables are left implicit, so those with nothing but other tables in
+/// them don't get a header, and have no position, so each new one goes after
+/// the tables that come before it.
+fn expand_tables(table: &mut toml_edit::Table) {
+    for (_, item) in table.iter_mut() {
+        let taken = mem::take(item);
+        *item = match taken.into_table() {
+            Ok(mut inner) => {
+                inner.set_implicit(true);
+                expand_tables(&mut inner);
+                Item::Table(inner)
+            }
+            Err(original) => original,
+        };
+    }
+}
+
+/// Make the document hold the new settings, replacing only the items whose
+/// values changed with those written out afresh.
+fn merge(document: &mut Item, fresh: &Item, current: &toml::Table, new: &toml::Table) {
+    let inline = document.is_inline_table();
+    let (Some(table), Some(fresh_table)) = (document.as_table_like_mut(), fresh.as_table_like())
+    else {
+        return;
+    };
+
+    for key in current.keys().filter(|key| !new.contains_key(*key)) {
+        let _ = table.remove(key);
+    }
+    for (key, value) in new {
+        let old = current.get(key);
+        if old == Some(value) {
+            continue;
+        }
+        let Some(replacement) = fresh_table.get(key) else {
+            continue;
+        };
+        if let (Some(inner), Some(old_table), Some(new_table)) = (
+            table.get_mut(key).filter(|item| item.is_table_like()),
+            old.and_then(toml::Value::as_table),
+            value.as_table(),
+        ) {
+            merge(inner, replacement, old_table, new_table);
+            continue;
+        }
+        // Inline tables can only hold values.
+        let mut item = if inline {
+            match replacement.clone().into_value() {
+                Ok(converted) => Item::Value(converted),
+                Err(_) => continue,
+            }
+        } else {
+            replacement.clone()
+        };
+        // Keep the comment after a value that changed.
+        if let (Some(value), Some(existing)) =
+            (item.as_value_mut(), table.get(key).and_then(Item::as_value))
+        {
+            *value.decor_mut() = existing.decor().clone();
+        }
+        let _ = table.insert(key, item);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_changed_settings_are_touched() -> Result<()> {
+        let text = r#"# Which profile to use.
+default_profile = "work"
+
+[profiles.work]
+# The office.
+url = "ws://work:12546"
+trigger_unlock = true # for now
+
+[aliases]
+pw = ["work", "Mail"] # Mail
+"#;
+        let mut config: Config = toml::from_str(text).map_err(error::Config::from)?;
+        if let Some(profile) = config.profiles.get_mut("work") {
+            profile.trigger_unlock = Some(false);
+        }
+        let _ = config.profiles.insert(
+            "home".to_owned(),
+            Profile {
+                snapshot: Some(true),
+                ..Profile::default()
+            },
+        );
+        let _ = config.aliases.remove("pw");
+
+        let updated = update(text, &config.to_table()?, true)?;
+        assert_eq!(
+            updated,
+            r#"# Which profile to use.
+default_profile = "work"
+
+[profiles.work]
+# The office.
+url = "ws://work:12546"
+trigger_unlock = false # for now
+
+[profiles.home]
+snapshot = true
+"#
+        );
+        Ok(())
+    }
+}
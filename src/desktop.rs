@@ -0,0 +1,565 @@
+// SPDX-FileCopyrightText: 2024 Noah Fontes
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Handing things off to the user's desktop: opening URLs in their browser,
+//! putting text on their clipboard, typing into the active window, and
+//! telling them about it with notifications. We run the programs desktops provide for these rather than
+//! talking to the window system ourselves.
+
+use std::{
+    env,
+    fmt::Display,
+    io,
+    io::Write as _,
+    process::{Command, Stdio},
+    sync::atomic::{AtomicBool, Ordering},
+    thread,
+    time::Duration,
+};
+
+use log::debug;
+use secrecy::{ExposeSecret as _, SecretString};
+use subtle::ConstantTimeEq as _;
+
+use crate::{
+    error::{self, Result},
+    locale, metadata, progress,
+};
+
+/// A program and its arguments.
+type Program = &'static [&'static str];
+
+#[cfg(target_os = "macos")]
+const OPENERS: &[Program] = &[&["open"]];
+#[cfg(not(target_os = "macos"))]
+const OPENERS: &[Program] = &[&["xdg-open"]];
+
+/// The program that shows a notification, given its title and body as the
+/// last two arguments.
+#[cfg(target_os = "macos")]
+const NOTIFIER: Program = &[
+    "osascript",
+    "-e",
+    "on run argv",
+    "-e",
+    "display notification (item 2 of argv) with title (item 1 of argv)",
+    "-e",
+    "end run",
+];
+#[cfg(not(target_os = "macos"))]
+const NOTIFIER: Program = &["notify-send", "--app-name", "karp"];
+
+static NOTIFICATIONS: AtomicBool = AtomicBool::new(true);
+
+/// Asks the operating system to verify the user, succeeding only if they
+/// are. On macOS this is Touch ID or the login password, through
+/// LocalAuthentication.
+#[cfg(target_os = "macos")]
+const VERIFIER: Program = &[
+    "osascript",
+    "-l",
+    "JavaScript",
+    "-e",
+    "ObjC.import('LocalAuthentication');
+ObjC.import('stdlib');
+var context = $.LAContext.alloc.init;
+var verified = null;
+context.evaluatePolicyLocalizedReasonReply(2, 'reveal a secret', function (ok) {
+    verified = ok;
+});
+while (verified === null) {
+    $.NSRunLoop.currentRunLoop.runUntilDate($.NSDate.dateWithTimeIntervalSinceNow(0.1));
+}
+$.exit(verified ? 0 : 1);",
+];
+/// On Linux, polkit asks for the user's own password through their
+/// authentication agent. The action comes from the policy in
+/// `contrib/polkit`, which must be installed; the process to check is given
+/// after this, along with its start time and user so polkit can't be fooled
+/// by another process reusing our ID (CVE-2013-4288).
+#[cfg(not(target_os = "macos"))]
+const VERIFIER: Program = &[
+    "pkcheck",
+    "--action-id",
+    "io.github.impl.karp.reveal",
+    "--allow-user-interaction",
+    "--process",
+];
+
+/// Whether the user has to be verified before we reveal a secret, and
+/// whether they have been yet.
+static VERIFY: AtomicBool = AtomicBool::new(false);
+static VERIFIED: AtomicBool = AtomicBool::new(false);
+
+struct Clipboard {
+    /// The environment variable that must be set for the programs to find
+    /// the clipboard, if any.
+    requires: Option<&'static str>,
+    copy: Program,
+    /// Copies the text along with a hint that it's a secret, which clipboard
+    /// managers take as a request to keep it out of their history.
+    ///
+    /// On X11 and Wayland the hint is the KDE `x-kde-passwordManagerHint`
+    /// type with the content `secret`, which is also what makes `wl-paste
+    /// --watch` tell clipboard managers `CLIPBOARD_STATE=sensitive`. It has to
+    /// be offered alongside the text by the same owner, but `wl-copy`, `xclip`
+    /// and `xsel` each offer a single type, and a second copy replaces the
+    /// first. Until we serve the clipboard ourselves, copies there rely on
+    /// being cleared after a while.
+    copy_secret: Option<Program>,
+    paste: Program,
+}
+
+/// Puts standard input on the pasteboard along with the concealed type that
+/// macOS clipboard managers leave out of their history, per nspasteboard.org.
+#[cfg(target_os = "macos")]
+const COPY_CONCEALED: &str = "ObjC.import('AppKit');
+var input = $.NSFileHandle.fileHandleWithStandardInput.readDataToEndOfFile;
+var text = $.NSString.alloc.initWithDataEncoding(input, $.NSUTF8StringEncoding);
+var pasteboard = $.NSPasteboard.generalPasteboard;
+pasteboard.clearContents;
+pasteboard.setStringForType(text, $.NSPasteboardTypeString);
+pasteboard.setStringForType($(''), 'org.nspasteboard.ConcealedType');";
+
+#[cfg(target_os = "macos")]
+const CLIPBOARDS: &[Clipboard] = &[Clipboard {
+    requires: None,
+    copy: &["pbcopy"],
+    copy_secret: Some(&["osascript", "-l", "JavaScript", "-e", COPY_CONCEALED]),
+    paste: &["pbpaste"],
+}];
+#[cfg(not(target_os = "macos"))]
+const CLIPBOARDS: &[Clipboard] = &[
+    Clipboard {
+        requires: Some("WAYLAND_DISPLAY"),
+        copy: &["wl-copy"],
+        copy_secret: None,
+        paste: &["wl-paste", "--no-newline"],
+    },
+    Clipboard {
+        requires: Some("DISPLAY"),
+        copy: &["xclip", "-selection", "clipboard"],
+        copy_secret: None,
+        paste: &["xclip", "-selection", "clipboard", "-o"],
+    },
+    Clipboard {
+        requires: Some("DISPLAY"),
+        copy: &["xsel", "--clipboard", "--input"],
+        copy_secret: None,
+        paste: &["xsel", "--clipboard", "--output"],
+    },
+];
+
+/// A key that can be pressed while typing.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Key {
+    Tab,
+    Enter,
+    Space,
+    Backspace,
+    Delete,
+    Escape,
+    Up,
+    Down,
+    Left,
+    Right,
+    Home,
+    End,
+}
+
+impl Key {
+    /// The name of the key as an X keysym, which wtype and xdotool take.
+    const fn keysym(self) -> &'static str {
+        match self {
+            Self::Tab => "Tab",
+            Self::Enter => "Return",
+            Self::Space => "space",
+            Self::Backspace => "BackSpace",
+            Self::Delete => "Delete",
+            Self::Escape => "Escape",
+            Self::Up => "Up",
+            Self::Down => "Down",
+            Self::Left => "Left",
+            Self::Right => "Right",
+            Self::Home => "Home",
+            Self::End => "End",
+        }
+    }
+
+    /// The Linux input event code of the key, which ydotool takes.
+    const fn code(self) -> u16 {
+        match self {
+            Self::Tab => 15,
+            Self::Enter => 28,
+            Self::Space => 57,
+            Self::Backspace => 14,
+            Self::Delete => 111,
+            Self::Escape => 1,
+            Self::Up => 103,
+            Self::Down => 108,
+            Self::Left => 105,
+            Self::Right => 106,
+            Self::Home => 102,
+            Self::End => 107,
+        }
+    }
+}
+
+/// Something to do while typing into the active window.
+#[derive(Debug)]
+pub(crate) enum Keystroke {
+    Text(SecretString),
+    Key(Key),
+    Delay(Duration),
+}
+
+struct Typer {
+    /// The environment variable that must be set for the program to find the
+    /// window system, if any.
+    requires: Option<&'static str>,
+    /// Types the text given on standard input, so it never appears in the
+    /// arguments of a process.
+    text: Program,
+    /// Presses the key given as the last arguments.
+    key: Program,
+    key_args: fn(Key) -> Vec<String>,
+}
+
+// Typing into other windows on macOS needs an accessibility permission for
+// whatever runs us, so we leave it to KeePassXC there.
+#[cfg(target_os = "macos")]
+const TYPERS: &[Typer] = &[];
+#[cfg(not(target_os = "macos"))]
+const TYPERS: &[Typer] = &[
+    Typer {
+        requires: Some("WAYLAND_DISPLAY"),
+        text: &["wtype", "-"],
+        key: &["wtype", "-k"],
+        key_args: |key| vec![key.keysym().to_owned()],
+    },
+    Typer {
+        requires: Some("DISPLAY"),
+        text: &["xdotool", "type", "--clearmodifiers", "--file", "-"],
+        key: &["xdotool", "key", "--clearmodifiers"],
+        key_args: |key| vec![key.keysym().to_owned()],
+    },
+    // ydotool works through the kernel, so it types into Wayland compositors
+    // wtype doesn't support, as long as its daemon is running.
+    Typer {
+        requires: None,
+        text: &["ydotool", "type", "--file", "-"],
+        key: &["ydotool", "key"],
+        key_args: |key| vec![format!("{}:1", key.code()), format!("{}:0", key.code())],
+    },
+];
+
+fn available<T>(
+    candidates: &'static [T],
+    requires: fn(&T) -> Option<&'static str>,
+) -> impl Iterator<Item = &'static T> {
+    candidates.iter().filter(move |candidate| {
+        requires(candidate)
+            .is_none_or(|var| env::var_os(var).is_some_and(|value| !value.is_empty()))
+    })
+}
+
+/// Run a program with some more arguments, giving it the input on standard
+/// input. Returns `None` if the program isn't installed.
+fn run(program: Program, extra: &[String], input: &[u8]) -> Result<Option<()>> {
+    let Some((name, args)) = program.split_first() else {
+        return Ok(None);
+    };
+
+    // Copying programs may stay around to serve the clipboard, so we mustn't
+    // wait for their output to close.
+    let mut child = match Command::new(name)
+        .args(args)
+        .args(extra)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+    {
+        Ok(child) => child,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(input)?;
+    }
+
+    let status = child.wait()?;
+    if !status.success() {
+        return Err(error::Desktop::Failed {
+            program: name,
+            status,
+        }
+        .into());
+    }
+    Ok(Some(()))
+}
+
+fn clipboards() -> impl Iterator<Item = &'static Clipboard> {
+    available(CLIPBOARDS, |clipboard| clipboard.requires)
+}
+
+/// Open a URL in the user's default browser.
+pub(crate) fn open_url(url: &str) -> Result<()> {
+    for &opener in OPENERS {
+        let Some((name, args)) = opener.split_first() else {
+            continue;
+        };
+        match Command::new(name)
+            .args(args)
+            .arg(url)
+            .stdin(Stdio::null())
+            .status()
+        {
+            Ok(status) if status.success() => return Ok(()),
+            Ok(status) => {
+                return Err(error::Desktop::Failed {
+                    program: name,
+                    status,
+                }
+                .into())
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e.into()),
+        }
+    }
+
+    Err(error::Desktop::NoOpener.into())
+}
+
+/// Put a secret on the clipboard, asking clipboard managers to keep it out of
+/// their history where we can.
+pub(crate) fn copy(text: &SecretString) -> Result<()> {
+    verify_user()?;
+    let input = text.expose_secret().as_bytes();
+    for clipboard in clipboards() {
+        if let Some(program) = clipboard.copy_secret {
+            match run(program, &[], input) {
+                Ok(Some(())) => return Ok(()),
+                Ok(None) => {}
+                Err(e) => debug!("We could not mark the copied text as secret: {}", e),
+            }
+        }
+        if run(clipboard.copy, &[], input)?.is_some() {
+            return Ok(());
+        }
+    }
+
+    Err(error::Desktop::NoClipboard.into())
+}
+
+/// Empty the clipboard, unless something other than the given text has been
+/// copied since we put it there. If we can't read the clipboard, we empty it
+/// anyway. Returns whether the clipboard was emptied.
+pub(crate) fn clear(text: &SecretString) -> Result<bool> {
+    for clipboard in clipboards() {
+        let Some((name, args)) = clipboard.paste.split_first() else {
+            continue;
+        };
+        let pasted = match Command::new(name)
+            .args(args)
+            .stdin(Stdio::null())
+            .stderr(Stdio::null())
+            .output()
+        {
+            Ok(output) if output.status.success() => {
+                String::from_utf8(output.stdout).ok().map(SecretString::new)
+            }
+            Ok(_) => None,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e.into()),
+        };
+
+        let replaced = pasted.is_some_and(|current| {
+            !bool::from(
+                current
+                    .expose_secret()
+                    .as_bytes()
+                    .ct_eq(text.expose_secret().as_bytes()),
+            )
+        });
+        if replaced {
+            return Ok(false);
+        }
+        if run(clipboard.copy, &[], &[])?.is_some() {
+            return Ok(true);
+        }
+    }
+
+    Err(error::Desktop::NoClipboard.into())
+}
+
+impl Typer {
+    /// Carry out the keystrokes. Returns `None` if the program isn't
+    /// installed, which we find out before typing anything.
+    fn perform(&self, keystrokes: &[Keystroke]) -> Result<Option<()>> {
+        for keystroke in keystrokes {
+            let done = match *keystroke {
+                Keystroke::Text(ref text) => run(self.text, &[], text.expose_secret().as_bytes())?,
+                Keystroke::Key(key) => run(self.key, &(self.key_args)(key), &[])?,
+                Keystroke::Delay(duration) => {
+                    thread::sleep(duration);
+                    Some(())
+                }
+            };
+            if done.is_none() {
+                return Ok(None);
+            }
+        }
+        Ok(Some(()))
+    }
+}
+
+/// Type into whichever window is active, as if the user were typing.
+pub(crate) fn type_keystrokes(keystrokes: &[Keystroke]) -> Result<()> {
+    verify_user()?;
+    for typer in available(TYPERS, |typer| typer.requires) {
+        if typer.perform(keystrokes)?.is_some() {
+            return Ok(());
+        }
+    }
+
+    Err(error::Desktop::NoTyper.into())
+}
+
+/// Ask the operating system to verify the user before we print, copy, or type
+/// any secret.
+pub(crate) fn require_verification() {
+    VERIFY.store(true, Ordering::Relaxed);
+}
+
+/// The start time of a process, in clock ticks since boot, from the contents
+/// of its `/proc/<pid>/stat`. The name of the program comes second and may
+/// hold anything, so we count fields from the parenthesis that ends it.
+#[cfg(not(target_os = "macos"))]
+fn start_time(stat: &str) -> Option<&str> {
+    let (_, fields) = stat.rsplit_once(')')?;
+    // The start time is the 22nd field, and the fields here begin with the
+    // third.
+    fields.split_whitespace().nth(19)
+}
+
+/// The real user ID of a process, from the contents of its
+/// `/proc/<pid>/status`.
+#[cfg(not(target_os = "macos"))]
+fn real_uid(status: &str) -> Option<&str> {
+    status
+        .lines()
+        .find_map(|line| line.strip_prefix("Uid:"))
+        .and_then(|ids| ids.split_whitespace().next())
+}
+
+/// Identify this process to pkcheck as `pid,start-time,uid`.
+#[cfg(not(target_os = "macos"))]
+fn process_subject() -> Result<String> {
+    let stat = std::fs::read_to_string("/proc/self/stat")?;
+    let status = std::fs::read_to_string("/proc/self/status")?;
+    let (Some(started), Some(uid)) = (start_time(&stat), real_uid(&status)) else {
+        return Err(error::Desktop::NotVerified.into());
+    };
+    Ok(format!("{},{started},{uid}", std::process::id()))
+}
+
+/// Make sure the user has been verified, if the profile asks for it, before
+/// revealing a secret. We only ask once for each run.
+pub(crate) fn verify_user() -> Result<()> {
+    if !VERIFY.load(Ordering::Relaxed) || VERIFIED.load(Ordering::Relaxed) {
+        return Ok(());
+    }
+    let _pause = progress::pause();
+    #[cfg(target_os = "macos")]
+    let extra = [];
+    #[cfg(not(target_os = "macos"))]
+    let extra = [process_subject()?];
+    match run(VERIFIER, &extra, &[]) {
+        Ok(Some(())) => {}
+        Ok(None) => return Err(error::Desktop::NoVerifier.into()),
+        Err(error::Error::Desktop(error::Desktop::Failed { .. })) => {
+            return Err(error::Desktop::NotVerified.into());
+        }
+        Err(e) => return Err(e),
+    }
+    VERIFIED.store(true, Ordering::Relaxed);
+    Ok(())
+}
+
+/// Stop showing notifications about the clipboard.
+pub(crate) fn disable_notifications() {
+    NOTIFICATIONS.store(false, Ordering::Relaxed);
+}
+
+/// Show a desktop notification, if notifications are turned on. Not every
+/// desktop can show them, so failing to is never an error.
+fn notify(body: &str) {
+    if !NOTIFICATIONS.load(Ordering::Relaxed) {
+        return;
+    }
+    let Some((name, args)) = NOTIFIER.split_first() else {
+        return;
+    };
+
+    match Command::new(name)
+        .args(args)
+        .arg(&*metadata::CLIENT_DISPLAY_NAME)
+        .arg(body)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+    {
+        Ok(status) if status.success() => {}
+        Ok(status) => debug!("{} failed ({})", name, status),
+        Err(e) => debug!("We could not run {}: {}", name, e),
+    }
+}
+
+/// Tell the user that a field of an entry was copied to the clipboard, and
+/// when it will be cleared, if it will be.
+pub(crate) fn notify_copied(field: &dyn Display, entry: &str, clear_after: Option<Duration>) {
+    notify(&match clear_after {
+        Some(after) => locale::format(
+            "notify-copied-clearing",
+            &[
+                ("field", field),
+                ("entry", &entry),
+                ("seconds", &after.as_secs()),
+            ],
+        ),
+        None => locale::format("notify-copied", &[("field", field), ("entry", &entry)]),
+    });
+}
+
+/// Tell the user that a field of an entry was cleared from the clipboard.
+pub(crate) fn notify_cleared(field: &dyn Display, entry: &str) {
+    notify(&locale::format(
+        "notify-cleared",
+        &[("field", field), ("entry", &entry)],
+    ));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(not(target_os = "macos"))]
+    #[test]
+    fn start_time_skips_the_program_name() {
+        let stat = "4242 (karp) (x) S 1 4242 4242 0 -1 4194560 100 0 0 0 1 2 0 0 20 0 1 0 \
+                    987654 12345678 900 18446744073709551615";
+        assert_eq!(start_time(stat), Some("987654"));
+        assert_eq!(start_time("4242 karp"), None);
+    }
+
+    #[cfg(not(target_os = "macos"))]
+    #[test]
+    fn real_uid_is_the_first_of_four() {
+        let status =
+            "Name:\tkarp\nUmask:\t0022\nUid:\t1000\t0\t0\t0\nGid:\t1000\t1000\t1000\t1000\n";
+        assert_eq!(real_uid(status), Some("1000"));
+    }
+}
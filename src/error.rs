@@ -8,11 +8,24 @@ use thiserror::Error;
 use tokio::sync::{mpsc, oneshot, watch};
 
 use crate::client;
+use crate::kdbx::error as kdbx_error;
 use crate::keepass::error as keepass_error;
 use crate::keepassxc::error as keepassxc_error;
+#[cfg(feature = "secret-service")]
+use crate::secret_service::error as secret_service_error;
+use crate::storage;
 
 pub(crate) type Result<T, E = Error> = result::Result<T, E>;
 
+/// The status we exit with when a command fails.
+pub(crate) const EXIT_FAILURE: i32 = 1;
+/// The status we exit with when the backend couldn't be authenticated with.
+/// Usage errors exit with 2.
+pub(crate) const EXIT_AUTH_FAILED: i32 = 3;
+/// The status we exit with when we'd have to ask the user something, but
+/// input is turned off.
+pub(crate) const EXIT_INPUT_REQUIRED: i32 = 4;
+
 #[derive(Error, Debug)]
 pub(crate) enum Error {
     #[error("IO operation failed: {0}")]
@@ -23,22 +36,197 @@ pub(crate) enum Error {
     Conversion(#[from] Conversion),
     #[error("storage error: {0}")]
     Storage(#[from] Storage),
+    #[error("snapshot error: {0}")]
+    Snapshot(#[from] Snapshot),
+    #[error("configuration error: {0}")]
+    Config(#[from] Config),
     #[error("password retrieval error: {0}")]
     Password(#[from] Password),
+    #[error("desktop integration error: {0}")]
+    Desktop(#[from] Desktop),
+    #[error("SSH tunnel error: {0}")]
+    Tunnel(#[from] Tunnel),
+    #[error("generation error: {0}")]
+    Generator(#[from] Generator),
+    #[error("editing error: {0}")]
+    Edit(#[from] Edit),
     #[error("KeePassRPC error: {0}")]
     Keepassrpc(keepass_error::Error),
     #[error("KeePassXC error: {0}")]
     Keepassxc(#[from] keepassxc_error::Error),
+    #[error("database file error: {0}")]
+    Kdbx(#[from] kdbx_error::Error),
+    #[cfg(feature = "secret-service")]
+    #[error("secret service error: {0}")]
+    SecretService(#[from] secret_service_error::Error),
     #[error("internal communication error: {0}")]
     Internal(#[from] Internal),
+    #[error("line editing error: {0}")]
+    Readline(rustyline::error::ReadlineError),
     #[error("command execution failed")]
     Command,
     #[error("operation cancelled")]
     Cancelled,
+    #[error("this needs an answer from the user, but input is turned off")]
+    InputRequired,
+    #[error("the session is not valid: {0}")]
+    SessionInvalid(Box<Error>),
+    #[error("memory locking failed: {0}")]
+    LockMemory(io::Error),
     #[error(r#"group "{}" does not have a child group named "{}""#, .parent.path.escape_default(), .name.escape_default())]
     GroupNotFound { parent: client::Group, name: String },
     #[error(r#"group "{}" does not have an entry named "{}""#, .parent.path.escape_default(), .name.escape_default())]
     EntryNotFound { parent: client::Group, name: String },
+    #[error("no entry has the ID {}", .id.simple())]
+    EntryIdNotFound { id: uuid::Uuid },
+    #[error("the profile has no backends to use")]
+    NoBackends,
+    #[error("neither KeePassXC nor KeePassRPC is running; start one, or give its URL with --url")]
+    NoBackendFound,
+    #[error(r#""{}" is not a valid entry path or ID"#, .0.escape_default())]
+    InvalidEntryRef(String),
+    #[error(r#""{}" is not a credential of the form [NAME=]ENTRY:FIELD"#, .0.escape_default())]
+    InvalidCredential(String),
+    #[error(r#""{}" is not a mapping of the form HOST=ENTRY"#, .0.escape_default())]
+    InvalidMapping(String),
+    #[error(r#"the entry does not have a field named "{}""#, .0.escape_default())]
+    FieldNotFound(String),
+    #[error("systemd-creds failed ({0})")]
+    SystemdCreds(std::process::ExitStatus),
+    #[error(r#"no open database is named "{}""#, .0.escape_default())]
+    DatabaseNotFound(String),
+    #[error(r#"the active database does not have the hash "{}""#, .0.escape_default())]
+    DatabaseNotActive(String),
+    #[error("the entry does not have a URL")]
+    EntryHasNoUrl,
+    #[error("invalid auto-type sequence at {0}")]
+    InvalidSequence(String),
+    #[error("the placeholder {{{0}}} could not be expanded")]
+    UnknownPlaceholder(String),
+    #[error("the backend can't generate passwords; use --generate-password=local instead")]
+    GeneratorUnsupported,
+    #[error("the backend doesn't tell us when the database is locked or unlocked")]
+    WatchUnsupported,
+    #[error("the backend doesn't give the full path of each entry's group, so --allow-group can't be enforced")]
+    GroupPathsUnsupported,
+    #[error("the entry does not have a field of type {0}")]
+    FieldTypeNotFound(client::FormFieldType),
+    #[error("the TOTP seed of the entry is not valid: {0}")]
+    InvalidTotpSeed(String),
+    #[error("the code is not valid")]
+    TotpMismatch,
+}
+
+impl Error {
+    /// A short name for what sort of error this is, for programs to match on.
+    pub(crate) const fn kind(&self) -> &'static str {
+        match *self {
+            Self::Io(_) => "io",
+            Self::Json(_) => "json",
+            Self::Conversion(_) => "conversion",
+            Self::Storage(_) => "storage",
+            Self::Snapshot(_) => "snapshot",
+            Self::Config(_) => "config",
+            Self::Password(_) => "password",
+            Self::Desktop(_) => "desktop",
+            Self::Tunnel(_) => "tunnel",
+            Self::Generator(_) => "generator",
+            Self::Edit(_) => "edit",
+            Self::Keepassrpc(_) => "keepassrpc",
+            Self::Keepassxc(ref e) => e.kind(),
+            Self::Kdbx(_) => "kdbx",
+            #[cfg(feature = "secret-service")]
+            Self::SecretService(_) => "secret_service",
+            Self::Internal(_) => "internal",
+            Self::Readline(_) => "readline",
+            Self::Command => "command",
+            Self::Cancelled => "cancelled",
+            Self::InputRequired => "input_required",
+            Self::SessionInvalid(_) => "session_invalid",
+            Self::LockMemory(_) => "lock_memory",
+            Self::GroupNotFound { .. } => "group_not_found",
+            Self::EntryNotFound { .. } => "entry_not_found",
+            Self::EntryIdNotFound { .. } => "entry_id_not_found",
+            Self::NoBackends => "no_backends",
+            Self::NoBackendFound => "no_backend_found",
+            Self::InvalidEntryRef(_) => "invalid_entry_ref",
+            Self::InvalidCredential(_) => "invalid_credential",
+            Self::InvalidMapping(_) => "invalid_mapping",
+            Self::FieldNotFound(_) => "field_not_found",
+            Self::SystemdCreds(_) => "systemd_creds",
+            Self::DatabaseNotFound(_) => "database_not_found",
+            Self::DatabaseNotActive(_) => "database_not_active",
+            Self::EntryHasNoUrl => "entry_has_no_url",
+            Self::InvalidSequence(_) => "invalid_sequence",
+            Self::UnknownPlaceholder(_) => "unknown_placeholder",
+            Self::GeneratorUnsupported => "generator_unsupported",
+            Self::WatchUnsupported => "watch_unsupported",
+            Self::GroupPathsUnsupported => "group_paths_unsupported",
+            Self::FieldTypeNotFound(_) => "field_type_not_found",
+            Self::InvalidTotpSeed(_) => "invalid_totp_seed",
+            Self::TotpMismatch => "totp_mismatch",
+        }
+    }
+
+    /// The status to exit with because of this error.
+    pub(crate) const fn exit_code(&self) -> i32 {
+        // LINT: Errors we haven't given a status of their own, including any
+        // added later, exit with the general one.
+        #[allow(clippy::wildcard_enum_match_arm)]
+        match *self {
+            Self::Password(Password::TooManyAttempts(_)) | Self::Desktop(Desktop::NotVerified) => {
+                EXIT_AUTH_FAILED
+            }
+            Self::Password(Password::NoPrompt) | Self::InputRequired => EXIT_INPUT_REQUIRED,
+            _ => EXIT_FAILURE,
+        }
+    }
+
+    /// Whether the error means there was nothing to connect to, as opposed to
+    /// a failure of the backend once we reached it.
+    pub(crate) fn is_unreachable(&self) -> bool {
+        // LINT: Other errors, including any added later, come from a backend
+        // we reached.
+        #[allow(clippy::wildcard_enum_match_arm)]
+        match *self {
+            Self::Io(ref e) => matches!(
+                e.kind(),
+                io::ErrorKind::ConnectionRefused
+                    | io::ErrorKind::ConnectionReset
+                    | io::ErrorKind::ConnectionAborted
+                    | io::ErrorKind::NotFound
+                    | io::ErrorKind::AddrNotAvailable
+                    | io::ErrorKind::TimedOut
+            ),
+            Self::Keepassxc(ref e) => e.is_not_running(),
+            _ => false,
+        }
+    }
+
+    /// The error code the backend gave, if the error came from one.
+    pub(crate) fn code(&self) -> Option<String> {
+        // LINT: Only errors from a backend can have a code, including any
+        // added later.
+        #[allow(clippy::wildcard_enum_match_arm)]
+        match *self {
+            Self::Keepassrpc(ref e) => e.code(),
+            Self::Keepassxc(ref e) => e.code(),
+            Self::SessionInvalid(ref e) => e.code(),
+            _ => None,
+        }
+    }
+}
+
+impl From<rustyline::error::ReadlineError> for Error {
+    // LINT: The error is non-exhaustive and its variants differ between
+    // platforms.
+    #[allow(clippy::wildcard_enum_match_arm)]
+    fn from(value: rustyline::error::ReadlineError) -> Self {
+        match value {
+            rustyline::error::ReadlineError::Io(e) => Self::Io(e),
+            err => Self::Readline(err),
+        }
+    }
 }
 
 impl From<keepass_error::Error> for Error {
@@ -56,7 +244,8 @@ impl From<keepass_error::Error> for Error {
 impl From<pinentry::Error> for Error {
     fn from(value: pinentry::Error) -> Self {
         match value {
-            pinentry::Error::Cancelled | pinentry::Error::Timeout => Self::Cancelled,
+            pinentry::Error::Cancelled => Self::Cancelled,
+            pinentry::Error::Timeout => Self::Password(Password::Timeout),
             pinentry::Error::Io(e) => Self::Io(e),
             pinentry::Error::Encoding(e) => Self::Conversion(Conversion::Encoding(e)),
             err @ pinentry::Error::Gpg(_) => Self::Password(Password::Pinentry(err)),
@@ -102,15 +291,28 @@ pub(crate) enum Conversion {
     NumericalRepresentation(#[from] num_bigint::TryFromBigIntError<num_bigint::BigInt>),
     #[error("encrypted data has invalid padding: {0}")]
     Padding(#[from] block_padding::UnpadError),
+    #[error("could not parse data as a UUID: {0}")]
+    Uuid(#[from] uuid::Error),
+    #[error("could not parse data as a URL: {0}")]
+    Url(#[from] url::ParseError),
 }
 
 #[derive(Error, Debug)]
 pub(crate) enum Storage {
     #[error("client identifier in storage differs from identifier bound to stream (are you running multiple instances at the same time?)")]
     Conflict,
-    #[cfg(feature = "keychain")]
     #[error("no OS-specific filesystem configuration found")]
     NoProjectDirs,
+    #[error("{0} storage is not supported by this build")]
+    Unsupported(storage::Kind),
+    #[error("no session data is stored for this URL")]
+    NoSessionData,
+    #[error("session data has an invalid format version")]
+    InvalidVersion,
+    #[error("session data was stored in format version {0}, which is newer than we support")]
+    UnsupportedVersion(u32),
+    #[error("session file {} can be accessed by other users (mode {mode:o}); restrict its permissions or use --allow-insecure-session-file", .path.display())]
+    InsecurePermissions { path: std::path::PathBuf, mode: u32 },
     #[cfg(feature = "secret-service")]
     #[error("secret service error: {0}")]
     SecretService(#[from] oo7::Error),
@@ -119,14 +321,112 @@ pub(crate) enum Storage {
     SecurityFramework(#[from] security_framework::base::Error),
 }
 
+#[derive(Error, Debug)]
+pub(crate) enum Snapshot {
+    #[error("snapshot could not be authenticated")]
+    AuthenticationFailure,
+    #[error("entries can't be changed while answering from a snapshot")]
+    ReadOnly,
+}
+
+#[derive(Error, Debug)]
+pub(crate) enum Config {
+    #[error("could not parse configuration file: {0}")]
+    Parse(#[from] toml::de::Error),
+    #[error("could not serialize configuration file: {0}")]
+    Serialize(#[from] toml::ser::Error),
+    #[error("could not parse configuration file: {0}")]
+    ParseDocument(#[from] toml_edit::TomlError),
+    #[error("could not serialize configuration file: {0}")]
+    SerializeDocument(#[from] toml_edit::ser::Error),
+    #[error("could not determine where the configuration file is located")]
+    NoPath,
+    #[error(r#"no profile named "{}" is configured"#, .0.escape_default())]
+    UnknownProfile(String),
+    #[error(r#"no alias named "{}" is configured"#, .0.escape_default())]
+    UnknownAlias(String),
+    #[error(r#""{}" is not a setting"#, .0.escape_default())]
+    InvalidKey(String),
+    #[error(r#""{}" is not set"#, .0.escape_default())]
+    NotSet(String),
+    #[error(r#"the profile "{}" already has a URL; use --force to replace it"#, .0.escape_default())]
+    UrlAlreadySet(String),
+    #[error(r#"invalid value for "{}": {source}"#, .key.escape_default())]
+    Invalid {
+        key: String,
+        source: toml::de::Error,
+    },
+}
+
 #[derive(Error, Debug)]
 pub(crate) enum Password {
     #[error("no password prompt available")]
     NoPrompt,
+    #[error("the password was wrong {0} times, so we gave up")]
+    TooManyAttempts(u32),
+    #[error("nobody entered the password in time")]
+    Timeout,
     #[error("Pinentry implementation error: {0}")]
     Pinentry(pinentry::Error),
 }
 
+#[derive(Error, Debug)]
+pub(crate) enum Desktop {
+    #[error("no program for opening URLs could be found")]
+    NoOpener,
+    #[error("no program for using the clipboard could be found")]
+    NoClipboard,
+    #[error("no program for typing into other windows could be found")]
+    NoTyper,
+    #[error("no program for verifying the user could be found")]
+    NoVerifier,
+    #[error("the user could not be verified")]
+    NotVerified,
+    #[error("{program} failed ({status})")]
+    Failed {
+        program: &'static str,
+        status: std::process::ExitStatus,
+    },
+}
+
+#[derive(Error, Debug)]
+pub(crate) enum Tunnel {
+    #[error("ssh could not be found")]
+    NoSsh,
+    #[error("ssh exited before the tunnel was ready ({0})")]
+    Exited(std::process::ExitStatus),
+    #[error("URLs with the scheme {0} can't be reached through SSH")]
+    Unsupported(String),
+    #[error("the URL must give the host and port, or the socket path, on the other machine")]
+    NoRemoteTarget,
+}
+
+#[derive(Error, Debug)]
+pub(crate) enum Generator {
+    #[error("could not read the word list {}: {source}", .path.display())]
+    ReadWordlist {
+        path: std::path::PathBuf,
+        source: io::Error,
+    },
+    #[error("the word list {} has fewer than two words", .path.display())]
+    ShortWordlist { path: std::path::PathBuf },
+}
+
+#[derive(Error, Debug)]
+pub(crate) enum Edit {
+    #[error("{program} failed ({status})")]
+    EditorFailed {
+        program: String,
+        status: std::process::ExitStatus,
+    },
+    #[error("could not parse the edited entry: {0}")]
+    Parse(toml::de::Error),
+    #[error("could not write the entry for editing: {0}")]
+    Serialize(toml::ser::Error),
+    #[error(r#"field "{}" is new, so it needs a value"#, .0.escape_default())]
+    MissingValue(String),
+}
+
 #[derive(Error, Debug)]
 pub(crate) enum Internal {
     #[error("channel is closed")]
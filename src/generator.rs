@@ -0,0 +1,105 @@
+// SPDX-FileCopyrightText: 2024 Noah Fontes
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Generating secrets ourselves, for when the backend can't do it for us.
+
+use rand::Rng as _;
+use secrecy::SecretString;
+
+use crate::rng;
+
+/// The characters generated passwords are made of. Symbols that shells or
+/// URLs treat specially are left out, so passwords can be pasted anywhere.
+const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789!#%+-.=@^_";
+
+/// The number of characters in a generated password unless asked otherwise,
+/// which gives a little over 120 bits of entropy.
+pub(crate) const DEFAULT_PASSWORD_LENGTH: usize = 20;
+
+/// Generate a password of the given number of characters, each drawn
+/// uniformly from the alphabet.
+pub(crate) fn password(length: usize) -> SecretString {
+    SecretString::new(rng::map(|rng| {
+        (0..length)
+            .map(|_| char::from(ALPHABET[rng.gen_range(0..ALPHABET.len())]))
+            .collect()
+    }))
+}
+
+/// Read the words out of a word list. Lines may hold just a word, or be in the
+/// format of the EFF lists, where each word follows the dice rolls that select
+/// it. Blank lines and repeated words are ignored, so repeats don't make some
+/// words more likely than others.
+pub(crate) fn parse_wordlist(text: &str) -> Vec<&str> {
+    let mut words: Vec<&str> = text
+        .lines()
+        .filter_map(|line| {
+            let mut columns = line.split_whitespace();
+            match (columns.next(), columns.next()) {
+                (Some(rolls), Some(word)) if rolls.bytes().all(|b| b.is_ascii_digit()) => {
+                    Some(word)
+                }
+                (Some(word), None) => Some(word),
+                (Some(_), Some(_)) | (None, _) => None,
+            }
+        })
+        .collect();
+    words.sort_unstable();
+    words.dedup();
+    words
+}
+
+/// Generate a passphrase of the given number of words, each drawn uniformly
+/// from the word list, joined by the separator.
+pub(crate) fn passphrase(wordlist: &[&str], count: usize, separator: &str) -> SecretString {
+    SecretString::new(rng::map(|rng| {
+        (0..count)
+            .map(|_| wordlist[rng.gen_range(0..wordlist.len())])
+            .collect::<Vec<_>>()
+            .join(separator)
+    }))
+}
+
+/// The entropy, in bits, of a secret made of the given number of symbols
+/// drawn uniformly from an alphabet of the given size.
+// LINT: Sizes of alphabets and secrets are far too small to lose precision.
+#[allow(clippy::cast_precision_loss)]
+pub(crate) fn entropy(alphabet: usize, count: usize) -> f64 {
+    (alphabet as f64).log2() * count as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use secrecy::ExposeSecret as _;
+
+    use super::*;
+
+    #[test]
+    fn passwords_use_the_alphabet() {
+        let generated = password(64);
+        assert_eq!(generated.expose_secret().len(), 64);
+        assert!(generated
+            .expose_secret()
+            .bytes()
+            .all(|byte| ALPHABET.contains(&byte)));
+    }
+
+    #[test]
+    fn wordlists_in_either_format() {
+        assert_eq!(
+            parse_wordlist("11111\tabacus\n11112\tabdomen\n\n11113 abacus\n"),
+            ["abacus", "abdomen"]
+        );
+        assert_eq!(parse_wordlist("zebra\napple\n"), ["apple", "zebra"]);
+        assert!(parse_wordlist("not a word\n").is_empty());
+    }
+
+    #[test]
+    fn passphrases_join_words() {
+        let generated = passphrase(&["correct", "horse"], 4, "-");
+        let words: Vec<_> = generated.expose_secret().split('-').collect();
+        assert_eq!(words.len(), 4);
+        assert!(words.iter().all(|word| ["correct", "horse"].contains(word)));
+    }
+}
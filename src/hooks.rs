@@ -0,0 +1,225 @@
+// SPDX-FileCopyrightText: 2024 Noah Fontes
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Running the user's own commands when something happens, such as locking
+//! the screen when the database is locked or keeping an audit log of the
+//! entries we read.
+//!
+//! Each hook is given what happened in environment variables, never any
+//! secrets, and runs alongside us: we don't wait for it to finish.
+
+use std::{
+    num::NonZeroUsize,
+    process::{Command, Stdio},
+    sync::OnceLock,
+    thread,
+};
+
+use async_trait::async_trait;
+use futures_util::stream::BoxStream;
+use log::{debug, warn};
+use secrecy::SecretString;
+use uuid::Uuid;
+
+use crate::{client, config::Hooks, error::Result};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) enum Event {
+    /// We authenticated with the backend, or associated with it anew.
+    SessionAuthenticated,
+    /// The backend told us the database was locked.
+    DatabaseLocked,
+    /// The backend told us the database was unlocked.
+    DatabaseUnlocked,
+    /// We read an entry, along with its secrets, by itself or in the results
+    /// of a search.
+    EntryAccessed,
+    /// We cleared something we'd copied from the clipboard.
+    ClipboardCleared,
+}
+
+impl Event {
+    const fn name(self) -> &'static str {
+        match self {
+            Self::SessionAuthenticated => "session-authenticated",
+            Self::DatabaseLocked => "database-locked",
+            Self::DatabaseUnlocked => "database-unlocked",
+            Self::EntryAccessed => "entry-accessed",
+            Self::ClipboardCleared => "clipboard-cleared",
+        }
+    }
+
+    fn command(self, hooks: &Hooks) -> Option<&[String]> {
+        match self {
+            Self::SessionAuthenticated => hooks.session_authenticated.as_deref(),
+            Self::DatabaseLocked => hooks.database_locked.as_deref(),
+            Self::DatabaseUnlocked => hooks.database_unlocked.as_deref(),
+            Self::EntryAccessed => hooks.entry_accessed.as_deref(),
+            Self::ClipboardCleared => hooks.clipboard_cleared.as_deref(),
+        }
+    }
+}
+
+static HOOKS: OnceLock<Hooks> = OnceLock::new();
+
+/// Use the hooks from the configuration file.
+pub(crate) fn configure(hooks: Hooks) {
+    let _ = HOOKS.set(hooks);
+}
+
+/// Run the hook for the event, if there is one, with `KARP_EVENT` and the
+/// given variables set in its environment.
+pub(crate) fn fire(event: Event, vars: &[(&str, &str)]) {
+    let Some(command) = HOOKS.get().and_then(|hooks| event.command(hooks)) else {
+        return;
+    };
+    let Some((name, args)) = command.split_first() else {
+        return;
+    };
+
+    debug!("Running the {} hook", event.name());
+    let spawned = Command::new(name)
+        .args(args)
+        .env("KARP_EVENT", event.name())
+        .envs(vars.iter().copied())
+        .stdin(Stdio::null())
+        .spawn();
+    let mut child = match spawned {
+        Ok(child) => child,
+        Err(e) => {
+            warn!("We could not run the {} hook: {}", event.name(), e);
+            return;
+        }
+    };
+    let _ = thread::spawn(move || match child.wait() {
+        Ok(status) if status.success() => {}
+        Ok(status) => warn!("The {} hook failed ({})", event.name(), status),
+        Err(e) => warn!("We could not wait for the {} hook: {}", event.name(), e),
+    });
+}
+
+fn entry_accessed(entry: &client::Entry) {
+    fire(
+        Event::EntryAccessed,
+        &[
+            ("KARP_ENTRY_ID", &entry.id),
+            ("KARP_ENTRY_TITLE", &entry.title),
+            (
+                "KARP_ENTRY_GROUP",
+                entry
+                    .parent
+                    .as_ref()
+                    .map_or("", |group| group.path.as_str()),
+            ),
+        ],
+    );
+}
+
+/// A client that runs the hook for entries it reads.
+pub(crate) struct Client<'channel> {
+    inner: Box<dyn client::Client + Send + Sync + 'channel>,
+}
+
+impl<'channel> Client<'channel> {
+    pub(crate) fn new(inner: Box<dyn client::Client + Send + Sync + 'channel>) -> Self {
+        Self { inner }
+    }
+}
+
+#[async_trait]
+impl client::Client for Client<'_> {
+    async fn get_entry(
+        &self,
+        group_names: &mut (dyn Iterator<Item = &str> + Send + Sync),
+        title: &str,
+    ) -> Result<client::Entry> {
+        let entry = self.inner.get_entry(group_names, title).await?;
+        entry_accessed(&entry);
+        Ok(entry)
+    }
+
+    async fn get_entry_by_id(&self, id: Uuid) -> Result<client::Entry> {
+        let entry = self.inner.get_entry_by_id(id).await?;
+        entry_accessed(&entry);
+        Ok(entry)
+    }
+
+    async fn find_entries(
+        &self,
+        query: &str,
+        limit: Option<NonZeroUsize>,
+    ) -> Result<Vec<client::Entry>> {
+        // Searches give us whole entries, secrets and all, even when only
+        // their titles are shown, so each one counts as read.
+        let entries = self.inner.find_entries(query, limit).await?;
+        entries.iter().for_each(entry_accessed);
+        Ok(entries)
+    }
+
+    async fn create_entry(
+        &self,
+        group_names: &mut (dyn Iterator<Item = &str> + Send + Sync),
+        entry: &client::Entry,
+    ) -> Result<()> {
+        self.inner.create_entry(group_names, entry).await
+    }
+
+    async fn update_entry(&self, id: Uuid, entry: &client::Entry) -> Result<()> {
+        self.inner.update_entry(id, entry).await
+    }
+
+    async fn list_group(
+        &self,
+        group_names: &mut (dyn Iterator<Item = &str> + Send + Sync),
+    ) -> Result<client::GroupListing> {
+        self.inner.list_group(group_names).await
+    }
+
+    async fn databases(&self) -> Result<Vec<client::Database>> {
+        self.inner.databases().await
+    }
+
+    async fn features(&self) -> Result<Vec<String>> {
+        self.inner.features().await
+    }
+
+    async fn generate_password(&self) -> Result<SecretString> {
+        self.inner.generate_password().await
+    }
+
+    async fn watch(&self) -> Result<BoxStream<'static, client::DatabaseEvent>> {
+        self.inner.watch().await
+    }
+
+    async fn settings(&self) -> Result<Vec<client::Setting>> {
+        self.inner.settings().await
+    }
+
+    fn group_paths(&self) -> bool {
+        self.inner.group_paths()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{config::Config, error};
+
+    #[test]
+    fn hooks_are_read_from_the_configuration() -> Result<()> {
+        let config: Config = toml::from_str(
+            r#"
+            [hooks]
+            database_locked = ["loginctl", "lock-session"]
+            "#,
+        )
+        .map_err(error::Config::from)?;
+        assert_eq!(
+            Event::DatabaseLocked.command(&config.hooks),
+            Some(&["loginctl".to_owned(), "lock-session".to_owned()][..])
+        );
+        assert_eq!(Event::DatabaseUnlocked.command(&config.hooks), None);
+        Ok(())
+    }
+}
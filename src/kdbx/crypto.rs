@@ -0,0 +1,137 @@
+// SPDX-FileCopyrightText: 2024 Noah Fontes
+//
+// SPDX-License-Identifier: Apache-2.0
+
+use std::io::Read as _;
+
+use aes::{
+    cipher::{BlockDecryptMut as _, BlockEncrypt as _, KeyInit as _, KeyIvInit as _},
+    Aes256,
+};
+use chacha20::ChaCha20;
+use flate2::read::GzDecoder;
+use salsa20::{cipher::StreamCipher as _, Salsa20};
+use sha2::{Digest as _, Sha256, Sha512};
+
+use super::error::Error;
+
+/// Transform a composite key by encrypting it with AES-256 in ECB mode the
+/// given number of times.
+pub(super) fn aes_kdf(key: &[u8; 32], seed: &[u8; 32], rounds: u64) -> [u8; 32] {
+    let cipher = Aes256::new(seed.into());
+    let mut blocks = [
+        aes::Block::clone_from_slice(&key[..16]),
+        aes::Block::clone_from_slice(&key[16..]),
+    ];
+    for _ in 0..rounds {
+        cipher.encrypt_blocks(&mut blocks);
+    }
+
+    let mut hash = Sha256::new();
+    for block in &blocks {
+        hash.update(block);
+    }
+    hash.finalize().into()
+}
+
+pub(super) fn aes_cbc_decrypt(key: &[u8; 32], iv: &[u8], data: &[u8]) -> Result<Vec<u8>, Error> {
+    let iv_block: [u8; 16] = iv
+        .try_into()
+        .map_err(|_| Error::Corrupt("invalid encryption IV"))?;
+    cbc::Decryptor::<Aes256>::new(key.into(), &iv_block.into())
+        .decrypt_padded_vec_mut::<block_padding::Pkcs7>(data)
+        .map_err(|_| Error::InvalidKey)
+}
+
+pub(super) fn chacha20_decrypt(key: &[u8; 32], iv: &[u8], data: &[u8]) -> Result<Vec<u8>, Error> {
+    let nonce: [u8; 12] = iv
+        .try_into()
+        .map_err(|_| Error::Corrupt("invalid encryption IV"))?;
+    let mut decrypted = data.to_vec();
+    ChaCha20::new(key.into(), &nonce.into()).apply_keystream(&mut decrypted);
+    Ok(decrypted)
+}
+
+const SALSA20_NONCE: [u8; 8] = [0xe8, 0x30, 0x09, 0x4b, 0x97, 0x20, 0x5d, 0x2a];
+
+/// The cipher that protects sensitive values inside the database XML.
+pub(super) enum InnerStream {
+    None,
+    Salsa20(Box<Salsa20>),
+    ChaCha20(Box<ChaCha20>),
+}
+
+impl InnerStream {
+    pub(super) fn new(id: u32, key: &[u8]) -> Result<Self, Error> {
+        match id {
+            0 => Ok(Self::None),
+            2 => {
+                let stream_key: [u8; 32] = Sha256::digest(key).into();
+                Ok(Self::Salsa20(Box::new(Salsa20::new(
+                    &stream_key.into(),
+                    &SALSA20_NONCE.into(),
+                ))))
+            }
+            3 => {
+                let hash = Sha512::digest(key);
+                let mut stream_key = [0_u8; 32];
+                stream_key.copy_from_slice(&hash[..32]);
+                let mut nonce = [0_u8; 12];
+                nonce.copy_from_slice(&hash[32..44]);
+                Ok(Self::ChaCha20(Box::new(ChaCha20::new(
+                    &stream_key.into(),
+                    &nonce.into(),
+                ))))
+            }
+            id => Err(Error::UnsupportedStreamCipher(id)),
+        }
+    }
+
+    pub(super) fn apply(&mut self, data: &mut [u8]) {
+        match self {
+            Self::None => {}
+            Self::Salsa20(cipher) => cipher.apply_keystream(data),
+            Self::ChaCha20(cipher) => cipher.apply_keystream(data),
+        }
+    }
+}
+
+/// Decompress a gzip member, checking its CRC.
+pub(super) fn gunzip(data: &[u8]) -> Result<Vec<u8>, Error> {
+    let mut out = vec![];
+    let _ = GzDecoder::new(data)
+        .read_to_end(&mut out)
+        .map_err(|_| Error::Corrupt("invalid compressed data"))?;
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gunzip_checks_crc() -> Result<(), Box<dyn std::error::Error>> {
+        use std::io::Write as _;
+
+        let mut encoder = flate2::write::GzEncoder::new(vec![], flate2::Compression::default());
+        encoder.write_all(b"<KeePassFile/>")?;
+        let mut compressed = encoder.finish()?;
+        assert_eq!(gunzip(&compressed)?, b"<KeePassFile/>");
+
+        let crc = compressed.len() - 8;
+        compressed[crc] ^= 1;
+        assert!(gunzip(&compressed).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn aes_kdf_rounds() {
+        let key = [0x11; 32];
+        let seed = [0x22; 32];
+        assert_eq!(
+            aes_kdf(&key, &seed, 0),
+            <[u8; 32]>::from(Sha256::digest(key))
+        );
+        assert_ne!(aes_kdf(&key, &seed, 1), aes_kdf(&key, &seed, 2));
+    }
+}
@@ -0,0 +1,40 @@
+// SPDX-FileCopyrightText: 2024 Noah Fontes
+//
+// SPDX-License-Identifier: Apache-2.0
+
+use thiserror::Error;
+use uuid::Uuid;
+
+#[derive(Error, Debug)]
+pub(crate) enum Error {
+    #[error("file is not a KeePass database")]
+    NotKdbx,
+    #[error("unsupported database format version {0}.{1}")]
+    UnsupportedVersion(u16, u16),
+    #[error("unsupported cipher {}", .0.hyphenated())]
+    UnsupportedCipher(Uuid),
+    #[error("unsupported key derivation function {}", .0.hyphenated())]
+    UnsupportedKdf(Uuid),
+    #[error("unsupported inner stream cipher {0}")]
+    UnsupportedStreamCipher(u32),
+    #[error("unsupported compression algorithm {0}")]
+    UnsupportedCompression(u32),
+    #[error("key derivation {parameter} of {value} is above the limit of {max}")]
+    KdfLimit {
+        parameter: &'static str,
+        value: u64,
+        max: u32,
+    },
+    #[error("database is corrupt: {0}")]
+    Corrupt(&'static str),
+    #[error("the password or key file is incorrect")]
+    InvalidKey,
+    #[error("key file is not valid")]
+    InvalidKeyFile,
+    #[error("database XML is malformed: {0}")]
+    Xml(&'static str),
+    #[error("database XML is malformed: {0}")]
+    XmlSyntax(#[from] roxmltree::Error),
+    #[error("databases opened from a file are read-only")]
+    ReadOnly,
+}
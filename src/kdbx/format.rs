@@ -0,0 +1,463 @@
+// SPDX-FileCopyrightText: 2024 Noah Fontes
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! The KDBX 3.1 and 4.x containers: the outer header, key derivation, and the
+//! encrypted block stream that holds the database XML.
+
+use std::collections::HashMap;
+
+use hmac::{Hmac, Mac as _};
+use sha2::{Digest as _, Sha256, Sha512};
+use uuid::{uuid, Uuid};
+
+use super::{
+    crypto::{self, InnerStream},
+    error::Error,
+    key::CompositeKey,
+};
+
+const SIGNATURE_1: u32 = 0x9aa2_d903;
+const SIGNATURE_2: u32 = 0xb54b_fb67;
+
+const CIPHER_AES256: Uuid = uuid!("31c1f2e6-bf71-4350-be58-05216afc5aff");
+const CIPHER_CHACHA20: Uuid = uuid!("d6038a2b-8b6f-4cb5-a524-339a31dbb59a");
+
+const KDF_AES_KDBX3: Uuid = uuid!("c9d9f39a-628a-4460-bf74-0d08c18a4fea");
+const KDF_AES_KDBX4: Uuid = uuid!("7c02bb82-79a7-4ac0-927d-114a00648238");
+const KDF_ARGON2D: Uuid = uuid!("ef636ddf-8c29-444b-91f7-a9a403e30a0c");
+const KDF_ARGON2ID: Uuid = uuid!("9e298b19-56db-4773-b23d-fc3ec6f0a1e6");
+
+/// The most Argon2 work we'll do for a database. The parameters come from an
+/// unauthenticated header, so without a limit a crafted file could have us
+/// allocate any amount of memory. KeePassXC's own settings stay well below
+/// these.
+const ARGON2_MAX_MEMORY_KIB: u32 = 4 * 1024 * 1024;
+const ARGON2_MAX_ITERATIONS: u32 = 10_000;
+const ARGON2_MAX_PARALLELISM: u32 = 256;
+
+mod field {
+    pub(super) const END: u8 = 0;
+    pub(super) const CIPHER_ID: u8 = 2;
+    pub(super) const COMPRESSION_FLAGS: u8 = 3;
+    pub(super) const MASTER_SEED: u8 = 4;
+    pub(super) const TRANSFORM_SEED: u8 = 5;
+    pub(super) const TRANSFORM_ROUNDS: u8 = 6;
+    pub(super) const ENCRYPTION_IV: u8 = 7;
+    pub(super) const PROTECTED_STREAM_KEY: u8 = 8;
+    pub(super) const STREAM_START_BYTES: u8 = 9;
+    pub(super) const INNER_RANDOM_STREAM_ID: u8 = 10;
+    pub(super) const KDF_PARAMETERS: u8 = 11;
+
+    pub(super) const INNER_STREAM_ID: u8 = 1;
+    pub(super) const INNER_STREAM_KEY: u8 = 2;
+}
+
+struct Reader<'data> {
+    data: &'data [u8],
+}
+
+impl<'data> Reader<'data> {
+    fn take(&mut self, n: usize) -> Result<&'data [u8], Error> {
+        if self.data.len() < n {
+            return Err(Error::Corrupt("unexpected end of data"));
+        }
+        let (taken, rest) = self.data.split_at(n);
+        self.data = rest;
+        Ok(taken)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], Error> {
+        let mut array = [0_u8; N];
+        array.copy_from_slice(self.take(N)?);
+        Ok(array)
+    }
+
+    fn u8(&mut self) -> Result<u8, Error> {
+        Ok(self.array::<1>()?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, Error> {
+        Ok(u16::from_le_bytes(self.array()?))
+    }
+
+    fn u32(&mut self) -> Result<u32, Error> {
+        Ok(u32::from_le_bytes(self.array()?))
+    }
+
+    fn len_u32(&mut self) -> Result<usize, Error> {
+        usize::try_from(self.u32()?).map_err(|_| Error::Corrupt("invalid length"))
+    }
+}
+
+fn to_u32(data: &[u8]) -> Result<u32, Error> {
+    Ok(u32::from_le_bytes(
+        data.try_into()
+            .map_err(|_| Error::Corrupt("invalid header field"))?,
+    ))
+}
+
+fn to_u64(data: &[u8]) -> Result<u64, Error> {
+    Ok(u64::from_le_bytes(
+        data.try_into()
+            .map_err(|_| Error::Corrupt("invalid header field"))?,
+    ))
+}
+
+/// Check a key derivation parameter against our limit for it.
+fn limit(parameter: &'static str, value: u64, max: u32) -> Result<u32, Error> {
+    u32::try_from(value)
+        .ok()
+        .filter(|limited| *limited <= max)
+        .ok_or(Error::KdfLimit {
+            parameter,
+            value,
+            max,
+        })
+}
+
+fn to_array(data: &[u8]) -> Result<[u8; 32], Error> {
+    data.try_into()
+        .map_err(|_| Error::Corrupt("invalid header field"))
+}
+
+/// Parse a KDBX 4 variant dictionary, keeping the raw bytes of each value.
+fn variant_dictionary(data: &[u8]) -> Result<HashMap<String, Vec<u8>>, Error> {
+    let mut reader = Reader { data };
+    let version = reader.u16()?;
+    if version >> 8_u16 != 1 {
+        return Err(Error::Corrupt("unsupported KDF parameter format"));
+    }
+
+    let mut values = HashMap::new();
+    loop {
+        if reader.u8()? == 0 {
+            return Ok(values);
+        }
+        let len = reader.len_u32()?;
+        let name = String::from_utf8_lossy(reader.take(len)?).into_owned();
+        let len = reader.len_u32()?;
+        let _ = values.insert(name, reader.take(len)?.to_vec());
+    }
+}
+
+enum Kdf {
+    Aes {
+        seed: [u8; 32],
+        rounds: u64,
+    },
+    Argon2 {
+        algorithm: argon2::Algorithm,
+        params: HashMap<String, Vec<u8>>,
+    },
+}
+
+impl Kdf {
+    fn from_parameters(params: HashMap<String, Vec<u8>>) -> Result<Self, Error> {
+        let uuid = params
+            .get("$UUID")
+            .and_then(|uuid| Uuid::from_slice(uuid).ok())
+            .ok_or(Error::Corrupt("missing key derivation function"))?;
+        match uuid {
+            KDF_AES_KDBX3 | KDF_AES_KDBX4 => Ok(Self::Aes {
+                seed: to_array(params.get("S").map_or(&[][..], Vec::as_slice))?,
+                rounds: to_u64(params.get("R").map_or(&[][..], Vec::as_slice))?,
+            }),
+            KDF_ARGON2D => Ok(Self::Argon2 {
+                algorithm: argon2::Algorithm::Argon2d,
+                params,
+            }),
+            KDF_ARGON2ID => Ok(Self::Argon2 {
+                algorithm: argon2::Algorithm::Argon2id,
+                params,
+            }),
+            uuid => Err(Error::UnsupportedKdf(uuid)),
+        }
+    }
+
+    fn transform(&self, key: &CompositeKey) -> Result<[u8; 32], Error> {
+        match self {
+            Self::Aes { seed, rounds } => Ok(crypto::aes_kdf(key.as_bytes(), seed, *rounds)),
+            Self::Argon2 { algorithm, params } => {
+                let bytes = |name: &str| params.get(name).map_or(&[][..], Vec::as_slice);
+                let out_of_range = || Error::Corrupt("KDF parameter out of range");
+
+                let version = argon2::Version::try_from(to_u32(bytes("V"))?)
+                    .map_err(|_| Error::Corrupt("unsupported Argon2 version"))?;
+                let mut builder = argon2::ParamsBuilder::new();
+                let _ = builder
+                    .m_cost(limit(
+                        "memory (KiB)",
+                        to_u64(bytes("M"))? / 1024,
+                        ARGON2_MAX_MEMORY_KIB,
+                    )?)
+                    .t_cost(limit(
+                        "iterations",
+                        to_u64(bytes("I"))?,
+                        ARGON2_MAX_ITERATIONS,
+                    )?)
+                    .p_cost(limit(
+                        "parallelism",
+                        u64::from(to_u32(bytes("P"))?),
+                        ARGON2_MAX_PARALLELISM,
+                    )?)
+                    .output_len(32);
+                if !bytes("A").is_empty() {
+                    let _ = builder
+                        .data(argon2::AssociatedData::new(bytes("A")).map_err(|_| out_of_range())?);
+                }
+                let argon2_params = builder.build().map_err(|_| out_of_range())?;
+                let hasher = if bytes("K").is_empty() {
+                    argon2::Argon2::new(*algorithm, version, argon2_params)
+                } else {
+                    argon2::Argon2::new_with_secret(bytes("K"), *algorithm, version, argon2_params)
+                        .map_err(|_| out_of_range())?
+                };
+
+                let mut transformed = [0_u8; 32];
+                hasher
+                    .hash_password_into(key.as_bytes(), bytes("S"), &mut transformed)
+                    .map_err(|_| out_of_range())?;
+                Ok(transformed)
+            }
+        }
+    }
+}
+
+/// The decrypted contents of a database.
+pub(super) struct Payload {
+    pub(super) xml: Vec<u8>,
+    pub(super) inner_stream: InnerStream,
+}
+
+struct Header {
+    major_version: u16,
+    fields: HashMap<u8, Vec<u8>>,
+    /// The raw header, which KDBX 4 authenticates.
+    raw: Vec<u8>,
+}
+
+impl Header {
+    fn read(reader: &mut Reader<'_>) -> Result<Self, Error> {
+        let start = reader.data;
+        if reader.u32()? != SIGNATURE_1 || reader.u32()? != SIGNATURE_2 {
+            return Err(Error::NotKdbx);
+        }
+        let minor_version = reader.u16()?;
+        let major_version = reader.u16()?;
+        if !(major_version == 3 && minor_version >= 1 || major_version == 4) {
+            return Err(Error::UnsupportedVersion(major_version, minor_version));
+        }
+
+        let mut fields = HashMap::new();
+        loop {
+            let id = reader.u8()?;
+            let len = if major_version == 3 {
+                usize::from(reader.u16()?)
+            } else {
+                reader.len_u32()?
+            };
+            let value = reader.take(len)?;
+            if id == field::END {
+                break;
+            }
+            let _ = fields.insert(id, value.to_vec());
+        }
+
+        Ok(Self {
+            major_version,
+            fields,
+            raw: start[..start.len() - reader.data.len()].to_vec(),
+        })
+    }
+
+    fn field(&self, id: u8) -> Result<&[u8], Error> {
+        self.fields
+            .get(&id)
+            .map(Vec::as_slice)
+            .ok_or(Error::Corrupt("missing header field"))
+    }
+
+    fn kdf(&self) -> Result<Kdf, Error> {
+        if self.major_version == 3 {
+            Ok(Kdf::Aes {
+                seed: to_array(self.field(field::TRANSFORM_SEED)?)?,
+                rounds: to_u64(self.field(field::TRANSFORM_ROUNDS)?)?,
+            })
+        } else {
+            Kdf::from_parameters(variant_dictionary(self.field(field::KDF_PARAMETERS)?)?)
+        }
+    }
+
+    fn decrypt(&self, key: &[u8; 32], data: &[u8]) -> Result<Vec<u8>, Error> {
+        let iv = self.field(field::ENCRYPTION_IV)?;
+        let cipher = Uuid::from_slice(self.field(field::CIPHER_ID)?)
+            .map_err(|_| Error::Corrupt("invalid cipher"))?;
+        match cipher {
+            CIPHER_AES256 => crypto::aes_cbc_decrypt(key, iv, data),
+            CIPHER_CHACHA20 => crypto::chacha20_decrypt(key, iv, data),
+            cipher => Err(Error::UnsupportedCipher(cipher)),
+        }
+    }
+
+    fn decompress(&self, data: Vec<u8>) -> Result<Vec<u8>, Error> {
+        match to_u32(self.field(field::COMPRESSION_FLAGS)?)? {
+            0 => Ok(data),
+            1 => crypto::gunzip(&data),
+            other => Err(Error::UnsupportedCompression(other)),
+        }
+    }
+}
+
+/// Read the blocks of a KDBX 3.1 payload, each checked with a SHA-256 hash.
+fn read_hashed_blocks(mut reader: Reader<'_>) -> Result<Vec<u8>, Error> {
+    let mut out = vec![];
+    loop {
+        let _index = reader.u32()?;
+        let hash = reader.take(32)?;
+        let len = reader.len_u32()?;
+        if len == 0 {
+            return Ok(out);
+        }
+        let block = reader.take(len)?;
+        if Sha256::digest(block)[..] != *hash {
+            return Err(Error::Corrupt("block hash mismatch"));
+        }
+        out.extend_from_slice(block);
+    }
+}
+
+fn block_hmac(base_key: &[u8], index: u64) -> Result<Hmac<Sha256>, Error> {
+    let key = Sha512::new()
+        .chain_update(index.to_le_bytes())
+        .chain_update(base_key)
+        .finalize();
+    Hmac::<Sha256>::new_from_slice(&key).map_err(|_| Error::Corrupt("invalid HMAC key"))
+}
+
+/// Read the blocks of a KDBX 4 payload, each authenticated with an HMAC.
+fn read_hmac_blocks(mut reader: Reader<'_>, base_key: &[u8]) -> Result<Vec<u8>, Error> {
+    let mut out = vec![];
+    for index in 0_u64.. {
+        let expected = reader.take(32)?;
+        let len_bytes = reader.array::<4>()?;
+        let len = usize::try_from(u32::from_le_bytes(len_bytes))
+            .map_err(|_| Error::Corrupt("invalid length"))?;
+        let block = reader.take(len)?;
+
+        let mut mac = block_hmac(base_key, index)?;
+        mac.update(&index.to_le_bytes());
+        mac.update(&len_bytes);
+        mac.update(block);
+        mac.verify_slice(expected)
+            .map_err(|_| Error::Corrupt("block HMAC mismatch"))?;
+
+        if len == 0 {
+            break;
+        }
+        out.extend_from_slice(block);
+    }
+    Ok(out)
+}
+
+/// Decrypt a database with the given key.
+pub(super) fn read(data: &[u8], key: &CompositeKey) -> Result<Payload, Error> {
+    let mut reader = Reader { data };
+    let header = Header::read(&mut reader)?;
+
+    let master_seed = header.field(field::MASTER_SEED)?;
+    let transformed = header.kdf()?.transform(key)?;
+    let encryption_key: [u8; 32] = Sha256::new()
+        .chain_update(master_seed)
+        .chain_update(transformed)
+        .finalize()
+        .into();
+
+    if header.major_version == 3 {
+        let decrypted = header.decrypt(&encryption_key, reader.data)?;
+        let start_bytes = header.field(field::STREAM_START_BYTES)?;
+        let Some(blocks) = decrypted.strip_prefix(start_bytes) else {
+            return Err(Error::InvalidKey);
+        };
+        let xml = header.decompress(read_hashed_blocks(Reader { data: blocks })?)?;
+
+        let stream_id = to_u32(header.field(field::INNER_RANDOM_STREAM_ID)?)?;
+        let inner_stream = InnerStream::new(stream_id, header.field(field::PROTECTED_STREAM_KEY)?)?;
+        return Ok(Payload { xml, inner_stream });
+    }
+
+    let expected_hash = reader.take(32)?;
+    if Sha256::digest(&header.raw)[..] != *expected_hash {
+        return Err(Error::Corrupt("header hash mismatch"));
+    }
+    let hmac_base_key = Sha512::new()
+        .chain_update(master_seed)
+        .chain_update(transformed)
+        .chain_update([1])
+        .finalize();
+    let mut mac = block_hmac(&hmac_base_key, u64::MAX)?;
+    mac.update(&header.raw);
+    mac.verify_slice(reader.take(32)?)
+        .map_err(|_| Error::InvalidKey)?;
+
+    let encrypted = read_hmac_blocks(reader, &hmac_base_key)?;
+    let decrypted = header.decompress(header.decrypt(&encryption_key, &encrypted)?)?;
+
+    // KDBX 4 moved the inner stream settings and attachments into a header
+    // in front of the XML.
+    let mut reader = Reader { data: &decrypted };
+    let mut stream_id = None;
+    let mut stream_key = None;
+    loop {
+        let id = reader.u8()?;
+        let len = reader.len_u32()?;
+        let value = reader.take(len)?;
+        match id {
+            field::END => break,
+            field::INNER_STREAM_ID => stream_id = Some(to_u32(value)?),
+            field::INNER_STREAM_KEY => stream_key = Some(value),
+            _ => {}
+        }
+    }
+    let (Some(id), Some(key)) = (stream_id, stream_key) else {
+        return Err(Error::Corrupt("missing inner header field"));
+    };
+
+    Ok(Payload {
+        xml: reader.data.to_vec(),
+        inner_stream: InnerStream::new(id, key)?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn argon2_limits() {
+        let kdf = |memory: u64, iterations: u64, parallelism: u32| Kdf::Argon2 {
+            algorithm: argon2::Algorithm::Argon2d,
+            params: HashMap::from([
+                ("V".to_owned(), 0x13_u32.to_le_bytes().to_vec()),
+                ("M".to_owned(), memory.to_le_bytes().to_vec()),
+                ("I".to_owned(), iterations.to_le_bytes().to_vec()),
+                ("P".to_owned(), parallelism.to_le_bytes().to_vec()),
+                ("S".to_owned(), vec![0; 32]),
+            ]),
+        };
+        let key = CompositeKey::new(None, None);
+
+        assert!(kdf(64 * 1024, 2, 1).transform(&key).is_ok());
+        for too_much in [
+            kdf(u64::MAX, 2, 1),
+            kdf(64 * 1024 * 1024 * 1024, 2, 1),
+            kdf(64 * 1024, u64::from(u32::MAX) + 1, 1),
+            kdf(64 * 1024, 2, 1024),
+        ] {
+            assert!(matches!(
+                too_much.transform(&key),
+                Err(Error::KdfLimit { .. })
+            ));
+        }
+    }
+}
@@ -0,0 +1,146 @@
+// SPDX-FileCopyrightText: 2024 Noah Fontes
+//
+// SPDX-License-Identifier: Apache-2.0
+
+use roxmltree::Document;
+use secrecy::{ExposeSecret as _, SecretString};
+use sha2::{Digest as _, Sha256};
+
+use super::{error::Error, xml};
+
+/// The key material a database is locked with: a password, a key file, or
+/// both.
+pub(super) struct CompositeKey([u8; 32]);
+
+impl CompositeKey {
+    /// Combine the given key components. An empty password is left out, as
+    /// KeePassXC does, so databases locked with only a key file open without
+    /// one.
+    pub(super) fn new(password: Option<&SecretString>, key_file: Option<&[u8; 32]>) -> Self {
+        let mut hash = Sha256::new();
+        if let Some(p) = password.filter(|p| !p.expose_secret().is_empty()) {
+            hash.update(Sha256::digest(p.expose_secret().as_bytes()));
+        }
+        if let Some(k) = key_file {
+            hash.update(k);
+        }
+        Self(hash.finalize().into())
+    }
+
+    pub(super) const fn as_bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+}
+
+fn decode_hex(data: &str) -> Option<Vec<u8>> {
+    let digits: Vec<u8> = data
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .map(|c| c.to_digit(16).and_then(|d| u8::try_from(d).ok()))
+        .collect::<Option<_>>()?;
+    if digits.len() % 2 != 0 {
+        return None;
+    }
+    Some(
+        digits
+            .chunks_exact(2)
+            .map(|pair| pair[0] << 4_u8 | pair[1])
+            .collect(),
+    )
+}
+
+fn parse_xml_key_file(data: &str) -> Result<Option<[u8; 32]>, Error> {
+    let Ok(document) = Document::parse(data) else {
+        return Ok(None);
+    };
+    let root = document.root_element();
+    if !root.has_tag_name("KeyFile") {
+        return Ok(None);
+    }
+
+    let version = xml::child(root, "Meta")
+        .and_then(|meta| xml::child_text(meta, "Version"))
+        .unwrap_or_default();
+    let data = xml::child(root, "Key")
+        .and_then(|key| xml::child(key, "Data"))
+        .ok_or(Error::InvalidKeyFile)?;
+
+    let key = if version.trim().starts_with("2.") {
+        let key = decode_hex(&xml::text(data)).ok_or(Error::InvalidKeyFile)?;
+        if let Some(expected) = data.attribute("Hash").and_then(decode_hex) {
+            if Sha256::digest(&key)[..expected.len().min(32)] != expected {
+                return Err(Error::InvalidKeyFile);
+            }
+        }
+        key
+    } else {
+        base64::decode(xml::text(data).trim()).map_err(|_| Error::InvalidKeyFile)?
+    };
+    key.try_into().map(Some).map_err(|_| Error::InvalidKeyFile)
+}
+
+/// Derive the key component of a key file from its contents. XML key files
+/// and files of exactly 32 bytes (or 64 hexadecimal digits) hold the key
+/// directly; anything else is hashed.
+pub(super) fn key_file(data: &[u8]) -> Result<[u8; 32], Error> {
+    if let Ok(text) = std::str::from_utf8(data) {
+        if let Some(key) = parse_xml_key_file(text)? {
+            return Ok(key);
+        }
+        if data.len() == 64 {
+            if let Some(key) = decode_hex(text).and_then(|key| key.try_into().ok()) {
+                return Ok(key);
+            }
+        }
+    }
+    if let Ok(key) = data.try_into() {
+        return Ok(key);
+    }
+    Ok(Sha256::digest(data).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fmt::Write as _;
+
+    use super::*;
+
+    fn to_hex(data: &[u8]) -> String {
+        data.iter().fold(String::new(), |mut out, b| {
+            let _ = write!(out, "{b:02X}");
+            out
+        })
+    }
+
+    #[test]
+    fn key_file_formats() -> Result<(), Error> {
+        let key: [u8; 32] = Sha256::digest(b"key").into();
+        let hex = to_hex(&key);
+
+        assert_eq!(key_file(&key)?, key);
+        assert_eq!(key_file(hex.as_bytes())?, key);
+        assert_eq!(key_file(b"key")?, key);
+
+        let v1 = format!(
+            "<KeyFile><Meta><Version>1.00</Version></Meta><Key><Data>{}</Data></Key></KeyFile>",
+            base64::encode(key)
+        );
+        assert_eq!(key_file(v1.as_bytes())?, key);
+
+        let hash = to_hex(&Sha256::digest(key)[..4]);
+        let v2 = |hash: &str| {
+            format!(
+                r#"<?xml version="1.0" encoding="utf-8"?>
+<KeyFile><Meta><Version>2.0</Version></Meta><Key><Data Hash="{}">
+    {} {}
+</Data></Key></KeyFile>"#,
+                hash,
+                &hex[..32],
+                &hex[32..]
+            )
+        };
+        assert_eq!(key_file(v2(&hash).as_bytes())?, key);
+        assert!(key_file(v2("00000000").as_bytes()).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,261 @@
+// SPDX-FileCopyrightText: 2024 Noah Fontes
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Read KeePass databases directly from their files, for when neither KeePass
+//! nor KeePassXC is running.
+
+mod crypto;
+mod database;
+pub(crate) mod error;
+mod format;
+mod key;
+mod xml;
+
+use std::{
+    num::{NonZeroU32, NonZeroUsize},
+    path::PathBuf,
+    sync::Arc,
+};
+
+use async_trait::async_trait;
+use futures_util::future::{self, BoxFuture};
+use tokio::{fs, task};
+use uuid::Uuid;
+
+use crate::{
+    client,
+    error::{self as base_error, Result},
+    locale, password,
+};
+
+use database::Database;
+
+struct Client {
+    database: Database,
+    path: PathBuf,
+    title_match: client::TitleMatch,
+}
+
+impl Client {
+    fn get_group<'db>(
+        &'db self,
+        group_names: &mut (dyn Iterator<Item = &str> + Send + Sync),
+    ) -> Result<(&'db database::Group, Vec<&'db str>)> {
+        let mut path = vec![];
+        let mut group = &self.database.root;
+        for group_name in group_names {
+            group = group
+                .groups
+                .iter()
+                .find(|g| client::same_name(&g.name, group_name))
+                .ok_or_else(|| base_error::Error::GroupNotFound {
+                    parent: client::Group {
+                        path: path.join("/"),
+                    },
+                    name: group_name.to_owned(),
+                })?;
+            path.push(group.name.as_str());
+        }
+        Ok((group, path))
+    }
+}
+
+#[async_trait]
+impl client::Client for Client {
+    async fn get_entry(
+        &self,
+        group_names: &mut (dyn Iterator<Item = &str> + Send + Sync),
+        title: &str,
+    ) -> Result<client::Entry> {
+        let (group, path) = self.get_group(group_names)?;
+        self.title_match
+            .select(&group.entries, |entry| entry.title(), title)
+            .map(|entry| entry.to_client(&path))
+            .ok_or_else(|| base_error::Error::EntryNotFound {
+                parent: client::Group {
+                    path: path.join("/"),
+                },
+                name: title.to_owned(),
+            })
+    }
+
+    async fn get_entry_by_id(&self, id: Uuid) -> Result<client::Entry> {
+        let mut found = None;
+        self.database
+            .root
+            .walk(&mut vec![], None, &mut |path, entry| {
+                if found.is_none() && entry.uuid == id {
+                    found = Some(entry.to_client(path));
+                }
+            });
+        found.ok_or(base_error::Error::EntryIdNotFound { id })
+    }
+
+    async fn find_entries(
+        &self,
+        query: &str,
+        limit: Option<NonZeroUsize>,
+    ) -> Result<Vec<client::Entry>> {
+        let wanted = limit.map_or(usize::MAX, NonZeroUsize::get);
+        let mut found = vec![];
+        self.database.root.walk(
+            &mut vec![],
+            self.database.recycle_bin,
+            &mut |path, entry| {
+                if found.len() < wanted && entry.matches(query) {
+                    found.push(entry.to_client(path));
+                }
+            },
+        );
+        Ok(found)
+    }
+
+    async fn create_entry(
+        &self,
+        _: &mut (dyn Iterator<Item = &str> + Send + Sync),
+        _: &client::Entry,
+    ) -> Result<()> {
+        Err(error::Error::ReadOnly.into())
+    }
+
+    async fn update_entry(&self, _: Uuid, _: &client::Entry) -> Result<()> {
+        Err(error::Error::ReadOnly.into())
+    }
+
+    async fn list_group(
+        &self,
+        group_names: &mut (dyn Iterator<Item = &str> + Send + Sync),
+    ) -> Result<client::GroupListing> {
+        let (group, _) = self.get_group(group_names)?;
+        Ok(client::GroupListing {
+            groups: group.groups.iter().map(|g| g.name.clone()).collect(),
+            entries: group
+                .entries
+                .iter()
+                .map(|entry| entry.title().to_owned())
+                .collect(),
+        })
+    }
+
+    async fn databases(&self) -> Result<Vec<client::Database>> {
+        let name = if self.database.name.is_empty() {
+            self.path
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned())
+                .unwrap_or_default()
+        } else {
+            self.database.name.clone()
+        };
+
+        Ok(vec![client::Database {
+            name,
+            selector: self.path.display().to_string(),
+            active: true,
+            association: None,
+        }])
+    }
+
+    async fn features(&self) -> Result<Vec<String>> {
+        // There's no server to negotiate features with.
+        Ok(vec![])
+    }
+}
+
+/// Settings that change how we open the database.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Options {
+    /// The key file the database is locked with, in addition to or instead of
+    /// a password.
+    pub(crate) key_file: Option<PathBuf>,
+    /// How many times to ask for the password before giving up, or `None`
+    /// to keep asking.
+    pub(crate) max_password_attempts: Option<NonZeroU32>,
+    /// How the title of an entry we're asked for is compared with the titles
+    /// in its group.
+    pub(crate) title_match: client::TitleMatch,
+}
+
+pub(crate) struct Protocol<Prompt: password::Prompt> {
+    prompt: Arc<Prompt>,
+    path: PathBuf,
+    options: Options,
+}
+
+impl<Prompt: password::Prompt> Protocol<Prompt> {
+    pub(crate) fn new(prompt: Arc<Prompt>, path: PathBuf, options: Options) -> Self {
+        Self {
+            prompt,
+            path,
+            options,
+        }
+    }
+
+    async fn open(&self) -> Result<Database> {
+        let data: Arc<[u8]> = fs::read(&self.path).await?.into();
+        let key_file = match self.options.key_file {
+            Some(ref path) => Some(key::key_file(&fs::read(path).await?)?),
+            None => None,
+        };
+
+        let mut prompt_error: Option<String> = None;
+        let mut failed = 0;
+        loop {
+            let mut req = password::RequestBuilder::new()
+                .with_description(&locale::text("prompt-kdbx-description"));
+            // Databases locked with only a key file have no password to
+            // enter.
+            if key_file.is_some() {
+                req = req.allow_empty();
+            }
+            if let Some(ref error) = prompt_error {
+                req = req.with_error(error);
+            }
+
+            let password = self
+                .prompt
+                .prompt(req.into_request())
+                .await?
+                .ok_or(base_error::Password::NoPrompt)?;
+            let key = key::CompositeKey::new(Some(&password), key_file.as_ref());
+
+            // Deriving the key is deliberately expensive, so it gets a thread
+            // of its own.
+            let contents = Arc::clone(&data);
+            match task::spawn_blocking(move || Database::open(&contents, &key)).await? {
+                Err(error::Error::InvalidKey) => {
+                    failed += 1;
+                    password::retry_after_failure(failed, self.options.max_password_attempts)
+                        .await?;
+                    prompt_error = Some(locale::text("prompt-kdbx-incorrect"));
+                }
+                result => return Ok(result?),
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl<'channel, Prompt: password::Prompt + 'channel> client::Protocol<'channel>
+    for Protocol<Prompt>
+{
+    async fn channel(
+        &self,
+    ) -> Result<(
+        BoxFuture<'channel, Result<()>>,
+        Box<dyn client::Client + Send + Sync + 'channel>,
+    )> {
+        let database = self.open().await?;
+
+        // The whole database is read up front, so there's no connection to
+        // service.
+        Ok((
+            Box::pin(future::ok(())),
+            Box::new(Client {
+                database,
+                path: self.path.clone(),
+                title_match: self.options.title_match,
+            }),
+        ))
+    }
+}
@@ -0,0 +1,40 @@
+// SPDX-FileCopyrightText: 2024 Noah Fontes
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Helpers for walking the documents KeePass writes.
+
+use roxmltree::Node;
+
+/// The child elements of a node.
+pub(super) fn elements<'doc, 'input>(
+    node: Node<'doc, 'input>,
+) -> impl Iterator<Item = Node<'doc, 'input>> {
+    node.children().filter(Node::is_element)
+}
+
+pub(super) fn children_named<'doc, 'input>(
+    node: Node<'doc, 'input>,
+    name: &'static str,
+) -> impl Iterator<Item = Node<'doc, 'input>> {
+    elements(node).filter(move |element| element.has_tag_name(name))
+}
+
+pub(super) fn child<'doc, 'input>(
+    node: Node<'doc, 'input>,
+    name: &str,
+) -> Option<Node<'doc, 'input>> {
+    elements(node).find(|element| element.has_tag_name(name))
+}
+
+/// The text directly inside an element.
+pub(super) fn text(node: Node<'_, '_>) -> String {
+    node.children()
+        .filter(Node::is_text)
+        .filter_map(|text| text.text())
+        .collect()
+}
+
+pub(super) fn child_text(node: Node<'_, '_>, name: &str) -> Option<String> {
+    child(node, name).map(text)
+}
@@ -4,9 +4,11 @@
 
 #![allow(dead_code)]
 
+use std::collections::BTreeMap;
+
 use async_trait::async_trait;
-use secrecy::SecretString;
-use serde::Deserialize;
+use secrecy::{ExposeSecret as _, SecretString};
+use serde::{Deserialize, Serialize, Serializer};
 use serde_repr::Deserialize_repr;
 use serde_with::{base64::Base64, serde_as};
 use tokio::sync::{mpsc, oneshot};
@@ -36,7 +38,7 @@ impl Call {
     }
 }
 
-#[derive(Copy, Clone, Debug, Deserialize, PartialEq)]
+#[derive(Copy, Clone, Debug, Deserialize, PartialEq, Serialize)]
 pub(super) enum FormFieldType {
     #[serde(rename = "FFTradio")]
     Radio,
@@ -65,7 +67,22 @@ impl From<FormFieldType> for client::FormFieldType {
     }
 }
 
-#[derive(Copy, Clone, Debug, Deserialize, PartialEq)]
+impl From<client::FormFieldType> for FormFieldType {
+    fn from(value: client::FormFieldType) -> Self {
+        match value {
+            client::FormFieldType::Radio => Self::Radio,
+            client::FormFieldType::Username => Self::Username,
+            // KeePassRPC has no type for one-time passwords. Entries we send it
+            // leave them out, so this only matters for other callers.
+            client::FormFieldType::Text | client::FormFieldType::Totp => Self::Text,
+            client::FormFieldType::Password => Self::Password,
+            client::FormFieldType::Select => Self::Select,
+            client::FormFieldType::Checkbox => Self::Checkbox,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, Deserialize, PartialEq, Serialize)]
 pub(super) enum PlaceholderHandling {
     Default,
     Enabled,
@@ -84,12 +101,78 @@ pub(super) struct FormField {
     pub(super) placeholder_handling: PlaceholderHandling,
 }
 
+impl From<PlaceholderHandling> for client::PlaceholderHandling {
+    fn from(value: PlaceholderHandling) -> Self {
+        match value {
+            PlaceholderHandling::Default => Self::Default,
+            PlaceholderHandling::Enabled => Self::Enabled,
+            PlaceholderHandling::Disabled => Self::Disabled,
+        }
+    }
+}
+
+impl From<client::PlaceholderHandling> for PlaceholderHandling {
+    fn from(value: client::PlaceholderHandling) -> Self {
+        match value {
+            client::PlaceholderHandling::Default => Self::Default,
+            client::PlaceholderHandling::Enabled => Self::Enabled,
+            client::PlaceholderHandling::Disabled => Self::Disabled,
+        }
+    }
+}
+
 impl From<FormField> for client::FormField {
     fn from(value: FormField) -> Self {
         Self {
             type_: value.type_.into(),
             display_name: value.display_name,
             value: value.value,
+            metadata: Some(client::FormFieldMetadata {
+                id: value.id,
+                name: value.name,
+                page: value.page,
+                placeholder_handling: value.placeholder_handling.into(),
+            }),
+        }
+    }
+}
+
+fn serialize_secret<S: Serializer>(value: &SecretString, serializer: S) -> Result<S::Ok, S::Error> {
+    value.expose_secret().serialize(serializer)
+}
+
+/// The form of a form field we send to the server when creating or updating
+/// an entry.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct FormFieldInput {
+    pub(super) type_: FormFieldType,
+    pub(super) display_name: String,
+    #[serde(serialize_with = "serialize_secret")]
+    pub(super) value: SecretString,
+    pub(super) id: String,
+    pub(super) name: String,
+    pub(super) page: i32,
+    pub(super) placeholder_handling: PlaceholderHandling,
+}
+
+impl From<&client::FormField> for FormFieldInput {
+    fn from(value: &client::FormField) -> Self {
+        let metadata = value
+            .metadata
+            .clone()
+            .unwrap_or_else(|| client::FormFieldMetadata {
+                page: 1,
+                ..client::FormFieldMetadata::default()
+            });
+        Self {
+            type_: value.type_.into(),
+            display_name: value.display_name.clone(),
+            value: value.value.clone(),
+            id: metadata.id,
+            name: metadata.name,
+            page: metadata.page,
+            placeholder_handling: metadata.placeholder_handling.into(),
         }
     }
 }
@@ -137,12 +220,40 @@ impl From<Entry> for client::Entry {
             id: value.unique_id,
             parent: value.parent.map(client::Group::from),
             title: value.title,
+            urls: value.urls,
             form_fields: value
                 .form_field_list
                 .unwrap_or_default()
                 .into_iter()
                 .map(client::FormField::from)
                 .collect(),
+            database: value.db.map(|db| db.name),
+            source: None,
+        }
+    }
+}
+
+/// The form of an entry we send to the server when creating or updating it.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct EntryInput {
+    #[serde(rename = "uRLs")]
+    pub(super) urls: Vec<String>,
+    pub(super) title: String,
+    pub(super) form_field_list: Vec<FormFieldInput>,
+}
+
+impl From<&client::Entry> for EntryInput {
+    fn from(value: &client::Entry) -> Self {
+        Self {
+            urls: value.urls.clone(),
+            title: value.title.clone(),
+            form_field_list: value
+                .form_fields
+                .iter()
+                .filter(|field| field.type_ != client::FormFieldType::Totp)
+                .map(Into::into)
+                .collect(),
         }
     }
 }
@@ -196,12 +307,37 @@ pub(super) trait Executor {
             .await
             .map_err(error::Internal::from)?;
         match *lrx.await.map_err(error::Internal::from)??.variant() {
-            ResponseVariant::Result(ref r) => Ok(serde_json::from_value(r.clone())?),
+            ResponseVariant::Result(ref r) => Ok(Self::Response::deserialize(r)?),
             ResponseVariant::Error(ref e) => Err(keepass_error::Api::ServerError(e.clone()).into()),
         }
     }
 }
 
+pub(super) struct AddLogin {
+    pub(super) login: EntryInput,
+    pub(super) parent_uuid: String,
+    pub(super) db_file_name: Option<String>,
+}
+
+impl TryFrom<AddLogin> for Request {
+    type Error = error::Error;
+
+    fn try_from(value: AddLogin) -> Result<Self, Self::Error> {
+        Ok(Self::new(
+            "AddLogin",
+            [
+                serde_json::to_value(&value.login)?,
+                value.parent_uuid.into(),
+                serde_json::to_value(value.db_file_name.as_ref())?,
+            ],
+        ))
+    }
+}
+
+impl Executor for AddLogin {
+    type Response = Entry;
+}
+
 #[derive(Debug, Deserialize, PartialEq)]
 pub(super) struct FindLogins {
     pub(super) unsanitized_urls: Vec<String>,
@@ -240,6 +376,28 @@ impl Executor for FindLogins {
     type Response = Vec<Entry>;
 }
 
+pub(super) struct GeneratePassword {
+    /// The name of the generator profile to use, or an empty string for the
+    /// one KeePass last used.
+    pub(super) profile_name: String,
+    /// The URL the password is for, which KeePass only uses to fill in
+    /// placeholders in the profile.
+    pub(super) url: String,
+}
+
+impl From<GeneratePassword> for Request {
+    fn from(value: GeneratePassword) -> Self {
+        Self::new(
+            "GeneratePassword",
+            [value.profile_name.into(), value.url.into()],
+        )
+    }
+}
+
+impl Executor for GeneratePassword {
+    type Response = SecretString;
+}
+
 pub(super) struct GetAllChildEntries {
     pub(super) uuid: String,
 }
@@ -282,6 +440,32 @@ impl Executor for GetChildGroups {
     type Response = Vec<Group>;
 }
 
+/// The settings of KeePassRPC that browser extensions read. The plugin has
+/// sent different ones over time, so any we don't know are kept as they are.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct Configuration {
+    /// The databases KeePass opened most recently.
+    #[serde(default)]
+    pub(super) known_databases: Vec<String>,
+    #[serde(default)]
+    pub(super) auto_commit: bool,
+    #[serde(flatten)]
+    pub(super) other: BTreeMap<String, serde_json::Value>,
+}
+
+pub(super) struct GetCurrentKfConfig;
+
+impl From<GetCurrentKfConfig> for Request {
+    fn from(_: GetCurrentKfConfig) -> Self {
+        Self::new("GetCurrentKFConfig", [])
+    }
+}
+
+impl Executor for GetCurrentKfConfig {
+    type Response = Configuration;
+}
+
 pub(super) struct GetRoot;
 
 impl From<GetRoot> for Request {
@@ -293,3 +477,38 @@ impl From<GetRoot> for Request {
 impl Executor for GetRoot {
     type Response = Group;
 }
+
+/// How the server should reconcile the URLs of an existing entry with the ones
+/// we send in an update.
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[repr(u8)]
+pub(super) enum UrlMergeMode {
+    Replace = 1,
+}
+
+pub(super) struct UpdateLogin {
+    pub(super) login: EntryInput,
+    pub(super) old_login_uuid: String,
+    pub(super) url_merge_mode: UrlMergeMode,
+    pub(super) db_file_name: Option<String>,
+}
+
+impl TryFrom<UpdateLogin> for Request {
+    type Error = error::Error;
+
+    fn try_from(value: UpdateLogin) -> Result<Self, Self::Error> {
+        Ok(Self::new(
+            "UpdateLogin",
+            [
+                serde_json::to_value(&value.login)?,
+                value.old_login_uuid.into(),
+                (value.url_merge_mode as u8).into(),
+                serde_json::to_value(value.db_file_name.as_ref())?,
+            ],
+        ))
+    }
+}
+
+impl Executor for UpdateLogin {
+    type Response = Entry;
+}
@@ -24,6 +24,21 @@ pub(crate) enum Error {
     ChallengeResponse(#[from] ChallengeResponse),
 }
 
+impl Error {
+    /// The name KeePassRPC gave the error, if it came from KeePassRPC.
+    pub(crate) fn code(&self) -> Option<String> {
+        match *self {
+            Self::Api(Api::ServerError(ref e)) => Some(e.name().to_owned()),
+            Self::Api(Api::SetupError(ref e)) => Some(format!("{:?}", e.code())),
+            Self::Io(_)
+            | Self::Websocket(_)
+            | Self::Api(_)
+            | Self::Srp(_)
+            | Self::ChallengeResponse(_) => None,
+        }
+    }
+}
+
 impl From<tokio_tungstenite::tungstenite::Error> for Error {
     fn from(value: tokio_tungstenite::tungstenite::Error) -> Self {
         // LINT: Deliberate fall-through that should catch future cases added to
@@ -42,12 +57,18 @@ pub(crate) enum Api {
     StreamEnded,
     #[error("server sent a message that we did not expect to receive: {0:?}")]
     UnhandledMessage(model::Message),
-    #[error("server error: {}: {}", .0.name(), .0.message())]
+    #[error("server error: {0}")]
     ServerError(model::jsonrpc::Error),
-    #[error("server security level is too low for us to accept and continue processing (wanted at least {0:?}, but got {1:?})")]
+    #[error("server rejected the connection: {0}")]
+    SetupError(model::setup::Error),
+    #[error("server security level is too low for us to accept and continue processing (wanted at least {0:?}, but got {1:?}); use --security-level to change the level we accept")]
     SecurityLevelTooLow(model::setup::SecurityLevel, model::setup::SecurityLevel),
     #[error("encrypted message could not be authenticated")]
     MessageAuthenticationFailure,
+    #[error("server does not support {}, which this operation needs; you may need to upgrade KeePassRPC", .0.name().unwrap_or("a required feature"))]
+    MissingFeature(model::setup::ServerFeature),
+    #[error("KeePassRPC {} is too old; upgrade the plugin to version {} or later", model::setup::format_version(*.0), model::setup::format_version(model::setup::MINIMUM_SERVER_VERSION))]
+    ServerTooOld(u32),
 }
 
 impl From<Api> for error::Error {
@@ -3,22 +3,27 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use std::collections::HashMap;
+use std::num::NonZeroU32;
 use std::sync::Arc;
 
 use async_recursion::async_recursion;
 use async_trait::async_trait;
 use futures_util::{lock::Mutex, stream, SinkExt, Stream, StreamExt};
-use log::warn;
+use log::{debug, warn};
 use num_bigint::RandBigInt;
 use secrecy::{ExposeSecret, SecretString};
 use sha2::{Digest, Sha256};
 use subtle::ConstantTimeEq;
-use tokio::select;
+use tokio::{
+    select,
+    sync::{broadcast, watch},
+};
 use uuid::Uuid;
 
 use crate::{
+    client,
     error::{self, Result},
-    password, rng,
+    hooks, locale, metadata, password, progress, rng,
     storage::{self, IsPersistent, Storage},
 };
 
@@ -39,7 +44,7 @@ impl<T: Storage<session::Data>> BoundStorage<'_, T> {
         Ok(self
             .get()
             .await?
-            .and_then(|session_data| session_data.session_key().as_ref().map(f)))
+            .and_then(|session_data| session_data.session_key().map(f)))
     }
 }
 
@@ -49,6 +54,46 @@ impl<T: IsPersistent> IsPersistent for BoundStorage<'_, T> {
     }
 }
 
+/// A connection that has finished negotiation, along with the features the
+/// server said it supports.
+struct Authenticated<'storage, T> {
+    storage: BoundStorage<'storage, T>,
+    features: Vec<model::setup::ServerFeature>,
+}
+
+/// What we tell the server about ourselves during connection negotiation, and
+/// how patient we are with it.
+pub(super) struct Negotiation {
+    pub(super) security_level: model::setup::SecurityLevel,
+    pub(super) client_display_name: String,
+    pub(super) client_display_description: Option<String>,
+    pub(super) max_password_attempts: Option<NonZeroU32>,
+}
+
+impl Negotiation {
+    /// What to ask of the server, from the options we were given and the
+    /// defaults for the storage.
+    fn new<T>(options: &super::Options, storage: &dyn Storage<T>) -> Self {
+        Self {
+            security_level: options
+                .security_level
+                .unwrap_or_else(|| storage_security_level(storage)),
+            client_display_name: options
+                .client_display_name
+                .clone()
+                .unwrap_or_else(|| metadata::CLIENT_DISPLAY_NAME.clone()),
+            client_display_description: options
+                .client_display_description
+                .clone()
+                .or_else(|| metadata::CLIENT_DISPLAY_DESCRIPTION.clone()),
+            max_password_attempts: options.max_password_attempts,
+        }
+    }
+}
+
+/// The security level we require when none is selected explicitly. Keys that
+/// outlive the process need at least a medium level of protection from the
+/// server, and keys that don't can demand the highest level.
 fn storage_security_level<T>(storage: &dyn Storage<T>) -> model::setup::SecurityLevel {
     if storage.is_persistent() {
         model::setup::SecurityLevel::Medium
@@ -57,6 +102,17 @@ fn storage_security_level<T>(storage: &dyn Storage<T>) -> model::setup::Security
     }
 }
 
+/// Stop at the first message from a KeePassRPC that's too old for us, which
+/// would otherwise fail later on with a message we don't understand.
+fn check_server_version(msg: &model::Message) -> Result<()> {
+    match msg.as_setup().map(model::setup::Setup::version) {
+        Some(version) if version < model::setup::MINIMUM_SERVER_VERSION => {
+            Err(keepass_error::Api::ServerTooOld(version).into())
+        }
+        _ => Ok(()),
+    }
+}
+
 #[async_trait]
 impl<T: Storage<session::Data>> Storage<session::Data> for BoundStorage<'_, T> {
     async fn get(&mut self) -> Result<Option<session::Data>> {
@@ -104,16 +160,16 @@ async fn srp_computed<
 >(
     storage: &'storage mut Storage,
     prompt: &Prompt,
+    negotiation: &Negotiation,
     message_stream: &mut MessageStream,
     negotiate: srp::Protocol<srp::Computed>,
-) -> Result<BoundStorage<'storage, Storage>> {
+    features: Vec<model::setup::ServerFeature>,
+    failed: u32,
+) -> Result<Authenticated<'storage, Storage>> {
     message_stream
         .send(model::Message::Setup(model::setup::Setup::new(
             model::setup::Variant::SrpProofToServer {
-                srp: model::setup::SrpProofToServer::new(
-                    &negotiate,
-                    storage_security_level(storage),
-                ),
+                srp: model::setup::SrpProofToServer::new(&negotiate, negotiation.security_level),
             },
         )))
         .await?;
@@ -122,15 +178,18 @@ async fn srp_computed<
         .next()
         .await
         .ok_or(keepass_error::Api::StreamEnded)??;
+    check_server_version(&msg)?;
     match msg.as_setup().map(model::setup::Setup::variant) {
         Some(model::setup::Variant::Error { error })
             if error == &model::setup::ErrorCode::AuthFailed =>
         {
             warn!("Authentication failed, so you need to try again: {}", error);
+            password::retry_after_failure(failed + 1, negotiation.max_password_attempts).await?;
             srp_init(
                 storage,
                 prompt,
-                Some("Incorrect password.".to_owned()),
+                negotiation,
+                failed + 1,
                 message_stream,
                 srp::ProtocolBuilder::new()
                     .with_identifier(negotiate.identifier())
@@ -139,10 +198,10 @@ async fn srp_computed<
             .await
         }
         Some(model::setup::Variant::SrpProofToClient { srp, .. })
-            if srp.security_level() < storage_security_level(storage) =>
+            if srp.security_level() < negotiation.security_level =>
         {
             Err(keepass_error::Api::SecurityLevelTooLow(
-                storage_security_level(storage),
+                negotiation.security_level,
                 srp.security_level(),
             )
             .into())
@@ -157,11 +216,17 @@ async fn srp_computed<
                 ))
                 .await?;
 
-            Ok(BoundStorage {
-                delegate: storage,
-                identifier: authenticated.identifier(),
+            Ok(Authenticated {
+                storage: BoundStorage {
+                    delegate: storage,
+                    identifier: authenticated.identifier(),
+                },
+                features,
             })
         }
+        Some(model::setup::Variant::Error { error }) => {
+            Err(keepass_error::Api::SetupError(error.clone()).into())
+        }
         _ => Err(keepass_error::Api::UnhandledMessage(msg).into()),
     }
 }
@@ -170,10 +235,11 @@ async fn srp_computed<
 async fn srp_init<'storage, Storage, Prompt, MessageStream>(
     storage: &'storage mut Storage,
     prompt: &Prompt,
-    prompt_error: Option<String>,
+    negotiation: &Negotiation,
+    failed: u32,
     message_stream: &mut MessageStream,
     negotiate: srp::Protocol<srp::Init>,
-) -> Result<BoundStorage<'storage, Storage>>
+) -> Result<Authenticated<'storage, Storage>>
 where
     Storage: storage::Storage<session::Data>,
     Prompt: password::Prompt,
@@ -189,8 +255,10 @@ where
             model::setup::Variant::ClientInit(model::setup::ClientInit::new(
                 model::setup::ClientInitVariant::Srp(model::setup::SrpIdentifyToServer::new(
                     &negotiate,
-                    storage_security_level(storage),
+                    negotiation.security_level,
                 )),
+                &negotiation.client_display_name,
+                negotiation.client_display_description.as_deref(),
             )),
         )))
         .await?;
@@ -199,21 +267,22 @@ where
         .next()
         .await
         .ok_or(keepass_error::Api::StreamEnded)??;
+    check_server_version(&msg)?;
     match msg.as_setup().map(model::setup::Setup::variant) {
         Some(model::setup::Variant::SrpIdentifyToClient { srp, .. })
-            if srp.security_level() < storage_security_level(storage) =>
+            if srp.security_level() < negotiation.security_level =>
         {
             Err(keepass_error::Api::SecurityLevelTooLow(
-                storage_security_level(storage),
+                negotiation.security_level,
                 srp.security_level(),
             )
             .into())
         }
-        Some(model::setup::Variant::SrpIdentifyToClient { srp, .. }) => {
+        Some(model::setup::Variant::SrpIdentifyToClient { features, srp }) => {
             // Get matching password from user.
             let mut req = password::RequestBuilder::new();
-            if let Some(error) = prompt_error {
-                req = req.with_error(&error);
+            if failed > 0 {
+                req = req.with_error(&locale::text("prompt-incorrect-password"));
             }
 
             let password = prompt
@@ -224,11 +293,17 @@ where
             srp_computed(
                 storage,
                 prompt,
+                negotiation,
                 message_stream,
                 negotiate.compute(srp.public_key(), srp.salt(), password.expose_secret()),
+                features.clone(),
+                failed,
             )
             .await
         }
+        Some(model::setup::Variant::Error { error }) => {
+            Err(keepass_error::Api::SetupError(error.clone()).into())
+        }
         _ => Err(keepass_error::Api::UnhandledMessage(msg).into()),
     }
 }
@@ -239,9 +314,11 @@ async fn key_negotiate<
     MessageStream: message::Stream,
 >(
     mut storage: BoundStorage<'storage, Storage>,
+    negotiation: &Negotiation,
     message_stream: &mut MessageStream,
     their_challenge: &str,
-) -> Result<BoundStorage<'storage, Storage>> {
+    features: Vec<model::setup::ServerFeature>,
+) -> Result<Authenticated<'storage, Storage>> {
     let my_challenge = rng::map(|rng| rng.gen_biguint(256).to_str_radix(16));
     let my_response = Sha256::new_with_prefix("1")
         .chain_update({
@@ -258,7 +335,11 @@ async fn key_negotiate<
     message_stream
         .send(model::Message::Setup(model::setup::Setup::new(
             model::setup::Variant::KeyClientNegotiation {
-                key: model::setup::KeyClientNegotiation::new(&my_challenge, &my_response),
+                key: model::setup::KeyClientNegotiation::new(
+                    &my_challenge,
+                    &my_response,
+                    negotiation.security_level,
+                ),
             },
         )))
         .await?;
@@ -267,6 +348,7 @@ async fn key_negotiate<
         .next()
         .await
         .ok_or(keepass_error::Api::StreamEnded)??;
+    check_server_version(&msg)?;
     match msg.as_setup().map(model::setup::Setup::variant) {
         Some(model::setup::Variant::Error { error })
             if error == &model::setup::ErrorCode::AuthFailed =>
@@ -274,10 +356,10 @@ async fn key_negotiate<
             Err(keepass_error::ChallengeResponse::ClientResponseMismatch(error.clone()).into())
         }
         Some(model::setup::Variant::KeyServerResponse { key, .. })
-            if key.security_level() < storage_security_level(&storage) =>
+            if key.security_level() < negotiation.security_level =>
         {
             Err(keepass_error::Api::SecurityLevelTooLow(
-                storage_security_level(&storage),
+                negotiation.security_level,
                 key.security_level(),
             )
             .into())
@@ -298,7 +380,10 @@ async fn key_negotiate<
                 return Err(keepass_error::ChallengeResponse::ServerResponseMismatch.into());
             }
 
-            Ok(storage)
+            Ok(Authenticated { storage, features })
+        }
+        Some(model::setup::Variant::Error { error }) => {
+            Err(keepass_error::Api::SetupError(error.clone()).into())
         }
         _ => Err(keepass_error::Api::UnhandledMessage(msg).into()),
     }
@@ -312,16 +397,19 @@ async fn key_init<
 >(
     storage: &'storage mut Storage,
     prompt: &Prompt,
+    negotiation: &Negotiation,
     message_stream: &mut MessageStream,
     identifier: Uuid,
-) -> Result<BoundStorage<'storage, Storage>> {
+) -> Result<Authenticated<'storage, Storage>> {
     message_stream
         .send(model::Message::Setup(model::setup::Setup::new(
             model::setup::Variant::ClientInit(model::setup::ClientInit::new(
                 model::setup::ClientInitVariant::Key {
                     username: identifier.to_string(),
-                    security_level: model::setup::SecurityLevel::Medium,
+                    security_level: negotiation.security_level,
                 },
+                &negotiation.client_display_name,
+                negotiation.client_display_description.as_deref(),
             )),
         )))
         .await?;
@@ -330,34 +418,48 @@ async fn key_init<
         .next()
         .await
         .ok_or(keepass_error::Api::StreamEnded)??;
+    check_server_version(&msg)?;
     match msg.as_setup().map(model::setup::Setup::variant) {
         Some(model::setup::Variant::Error { error }) if error.is_auth_error() => {
             warn!(
                 "Authentication failed, so we have to start over with SRP: {}",
                 error
             );
-            srp_init(storage, prompt, None, message_stream, srp::Protocol::new()).await
+            srp_init(
+                storage,
+                prompt,
+                negotiation,
+                0,
+                message_stream,
+                srp::Protocol::new(),
+            )
+            .await
         }
         Some(model::setup::Variant::KeyServerChallenge { key, .. })
-            if key.security_level() < storage_security_level(storage) =>
+            if key.security_level() < negotiation.security_level =>
         {
             Err(keepass_error::Api::SecurityLevelTooLow(
-                storage_security_level(storage),
+                negotiation.security_level,
                 key.security_level(),
             )
             .into())
         }
-        Some(model::setup::Variant::KeyServerChallenge { key, .. }) => {
+        Some(model::setup::Variant::KeyServerChallenge { features, key }) => {
             key_negotiate(
                 BoundStorage {
                     delegate: storage,
                     identifier,
                 },
+                negotiation,
                 message_stream,
                 key.server_challenge(),
+                features.clone(),
             )
             .await
         }
+        Some(model::setup::Variant::Error { error }) => {
+            Err(keepass_error::Api::SetupError(error.clone()).into())
+        }
         _ => Err(keepass_error::Api::UnhandledMessage(msg).into()),
     }
 }
@@ -374,16 +476,27 @@ async fn authenticate<
 >(
     storage: &'storage mut Storage,
     prompt: &Prompt,
+    negotiation: &Negotiation,
     message_stream: &mut MessageStream,
-) -> Result<BoundStorage<'storage, Storage>> {
+) -> Result<Authenticated<'storage, Storage>> {
     match storage.get().await {
-        Ok(Some(session_data)) => match *session_data.session_key() {
-            Some(_) => key_init(storage, prompt, message_stream, session_data.identifier()).await,
+        Ok(Some(session_data)) => match session_data.session_key() {
+            Some(_) => {
+                key_init(
+                    storage,
+                    prompt,
+                    negotiation,
+                    message_stream,
+                    session_data.identifier(),
+                )
+                .await
+            }
             None => {
                 srp_init(
                     storage,
                     prompt,
-                    None,
+                    negotiation,
+                    0,
                     message_stream,
                     srp::ProtocolBuilder::new()
                         .with_identifier(session_data.identifier())
@@ -392,17 +505,95 @@ async fn authenticate<
                 .await
             }
         },
-        Ok(None) => srp_init(storage, prompt, None, message_stream, srp::Protocol::new()).await,
+        Ok(None) => {
+            srp_init(
+                storage,
+                prompt,
+                negotiation,
+                0,
+                message_stream,
+                srp::Protocol::new(),
+            )
+            .await
+        }
         Err(e) => {
             warn!(
                 "Failed to decode session data, so we have to start over: {}",
                 e
             );
-            srp_init(storage, prompt, None, message_stream, srp::Protocol::new()).await
+            srp_init(
+                storage,
+                prompt,
+                negotiation,
+                0,
+                message_stream,
+                srp::Protocol::new(),
+            )
+            .await
         }
     }
 }
 
+/// The method KeePassRPC calls to tell us something happened, with the
+/// number of its signal as the only parameter.
+const SIGNAL_METHOD: &str = "KPRPCListener";
+
+/// The signals KeePassRPC sends when a database is opened or closed, as
+/// `DATABASE_OPEN` and `DATABASE_CLOSED` in its `Signal` enum. The others,
+/// such as `DATABASE_OPENING` (2) and `DATABASE_CLOSING` (4), come before the
+/// change has happened, so we don't act on them.
+const SIGNAL_DATABASE_OPEN: u64 = 3;
+const SIGNAL_DATABASE_CLOSED: u64 = 5;
+
+/// The number of a signal, which may be sent as a number or as text.
+fn signal_number(param: &serde_json::Value) -> Option<u64> {
+    param
+        .as_u64()
+        .or_else(|| param.as_str().and_then(|text| text.trim().parse().ok()))
+}
+
+/// Deal with a request or notification the server sent us of its own accord,
+/// returning the reply to send, if it needs one.
+fn handle_server_request(
+    req: &model::jsonrpc::Request,
+    events: &broadcast::Sender<client::DatabaseEvent>,
+) -> Option<model::jsonrpc::Jsonrpc> {
+    if req.method() == SIGNAL_METHOD {
+        // Kee doesn't answer signals, and neither do we.
+        let signal = req.params().first().and_then(signal_number);
+        let (event, hook) = match signal {
+            Some(SIGNAL_DATABASE_OPEN) => (
+                client::DatabaseEvent::Unlocked,
+                hooks::Event::DatabaseUnlocked,
+            ),
+            Some(SIGNAL_DATABASE_CLOSED) => {
+                (client::DatabaseEvent::Locked, hooks::Event::DatabaseLocked)
+            }
+            _ => {
+                debug!("Ignoring KeePassRPC signal {:?}", signal);
+                return None;
+            }
+        };
+        hooks::fire(hook, &[("KARP_BACKEND", "keepassrpc")]);
+        // Nobody may be watching, which is fine.
+        let _ = events.send(event);
+        return None;
+    }
+
+    let Some(id) = req.id() else {
+        debug!("Ignoring notification {}", req.method());
+        return None;
+    };
+    warn!("KeePassRPC called {}, which we don't know", req.method());
+    Some(model::jsonrpc::Jsonrpc::Response(
+        model::jsonrpc::Response::error(
+            id.clone(),
+            "MethodNotFound",
+            &format!("karp does not know the method {}", req.method()),
+        ),
+    ))
+}
+
 pub(super) async fn run<
     Storage: storage::Storage<session::Data>,
     Prompt: password::Prompt,
@@ -411,6 +602,9 @@ pub(super) async fn run<
 >(
     storage: Arc<Mutex<Storage>>,
     prompt: Arc<Prompt>,
+    options: super::Options,
+    features: watch::Sender<Option<Arc<[model::setup::ServerFeature]>>>,
+    events: broadcast::Sender<client::DatabaseEvent>,
     mut message_stream: MessageStream,
     mut call_stream: CallStream,
 ) -> Result<()> {
@@ -418,8 +612,28 @@ pub(super) async fn run<
 
     'reauthenticate: loop {
         let mut locked_storage = storage.lock().await;
-        let mut bound_storage =
-            authenticate(&mut *locked_storage, prompt.as_ref(), &mut message_stream).await?;
+        // Other processes may be negotiating with the same session data, so we
+        // take turns with them until we're done.
+        let process_lock = storage::Lock::acquire(&*locked_storage).await?;
+        let negotiation = Negotiation::new(&options, &*locked_storage);
+        let authenticating = progress::start(progress::Phase::Authenticating);
+        let Authenticated {
+            storage: mut bound_storage,
+            features: server_features,
+        } = authenticate(
+            &mut *locked_storage,
+            prompt.as_ref(),
+            &negotiation,
+            &mut message_stream,
+        )
+        .await?;
+        drop(authenticating);
+        drop(process_lock);
+        hooks::fire(
+            hooks::Event::SessionAuthenticated,
+            &[("KARP_BACKEND", "keepassrpc")],
+        );
+        let _ = features.send_replace(Some(server_features.into()));
 
         let mut backfilled_call_stream = stream::iter(pending_call.take()).chain(&mut call_stream);
         let mut calls: HashMap<model::jsonrpc::Id, Call> = HashMap::new();
@@ -441,6 +655,21 @@ pub(super) async fn run<
                                 }
                             }
                         }
+                        Some(Some(Ok(model::jsonrpc::Jsonrpc::Request(req)))) => {
+                            let Some(reply) = handle_server_request(&req, &events) else {
+                                continue;
+                            };
+                            let enc = bound_storage
+                                .map_session_key(|session_key| {
+                                    model::Message::new_from_jsonrpc(session_key, &reply)
+                                })
+                                .await
+                                .unwrap_or(None)
+                                .transpose()?;
+                            if let Some(reply_msg) = enc {
+                                message_stream.send(reply_msg).await?;
+                            }
+                        }
                         Some(None) => return Err(keepass_error::Api::StreamEnded.into()),
                         None => {
                             // We will have no way to decrypt this message, so
@@ -452,7 +681,7 @@ pub(super) async fn run<
                             continue 'reauthenticate;
                         }
                         _ => return Err(keepass_error::Api::UnhandledMessage(msg).into()),
-                    };
+                    }
                 },
                 candidate = backfilled_call_stream.next() => {
                     match candidate {
@@ -468,21 +697,64 @@ pub(super) async fn run<
                             if let Some(msg) = enc {
                                 message_stream.send(msg).await?;
                                 if let model::jsonrpc::Jsonrpc::Request(ref req) = call.req {
-                                    if let Some(id) = req.id().as_ref() {
+                                    if let Some(id) = req.id() {
                                         assert!(calls.insert(id.clone(), call).is_none());
                                     }
-                                };
+                                }
                             } else {
                                 pending_call = Some(call);
                                 continue 'reauthenticate;
-                            };
+                            }
                         }
                         None => {
                             return Ok(());
                         }
-                    };
+                    }
                 }
-            };
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(text: &str) -> serde_json::Result<model::jsonrpc::Request> {
+        match serde_json::from_str(text)? {
+            model::jsonrpc::Jsonrpc::Request(req) => Ok(req),
+            message @ model::jsonrpc::Jsonrpc::Response(_) => {
+                panic!("expected a request, got {message:?}")
+            }
+        }
+    }
+
+    #[test]
+    fn signals_become_events() -> serde_json::Result<()> {
+        let (events, mut received) = broadcast::channel(4);
+        for text in [
+            r#"{"jsonrpc": "2.0", "method": "KPRPCListener", "params": [3]}"#,
+            r#"{"jsonrpc": "2.0", "method": "KPRPCListener", "params": ["5"], "id": 1}"#,
+            r#"{"jsonrpc": "2.0", "method": "KPRPCListener", "params": [4]}"#,
+        ] {
+            assert_eq!(handle_server_request(&request(text)?, &events), None);
         }
+        assert_eq!(
+            received.try_recv().ok(),
+            Some(client::DatabaseEvent::Unlocked)
+        );
+        assert_eq!(
+            received.try_recv().ok(),
+            Some(client::DatabaseEvent::Locked)
+        );
+        assert!(received.try_recv().is_err());
+
+        let unknown =
+            request(r#"{"jsonrpc": "2.0", "method": "Frobnicate", "params": [], "id": 2}"#)?;
+        assert!(matches!(
+            handle_server_request(&unknown, &events),
+            Some(model::jsonrpc::Jsonrpc::Response(_))
+        ));
+        Ok(())
     }
 }
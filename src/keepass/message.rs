@@ -10,7 +10,10 @@ use std::{
 use log::debug;
 use tokio::io::{AsyncRead, AsyncWrite};
 
-use crate::error::{self, Result};
+use crate::{
+    error::{self, Result},
+    trace,
+};
 
 use super::{error as keepassrpc_error, model};
 
@@ -41,6 +44,12 @@ impl<S: AsyncRead + AsyncWrite + Unpin> futures_util::Stream for WebSocketStream
                             .into_text()
                             .map_err(Into::<keepassrpc_error::Error>::into)?;
                         debug!("Received raw message: {}", text);
+                        trace::record_json(
+                            super::TRACE_PROTOCOL,
+                            trace::Direction::Received,
+                            trace::Layer::Envelope,
+                            text.as_bytes(),
+                        );
                         text
                     })?)
                 });
@@ -61,6 +70,12 @@ impl<S: AsyncRead + AsyncWrite + Unpin> futures_util::Sink<model::Message> for W
 
     fn start_send(mut self: Pin<&mut Self>, item: model::Message) -> Result<(), Self::Error> {
         debug!("Sending message: {:?}", item);
+        trace::record(
+            super::TRACE_PROTOCOL,
+            trace::Direction::Sent,
+            trace::Layer::Envelope,
+            &item,
+        );
         Pin::new(&mut self.0)
             .start_send(tokio_tungstenite::tungstenite::Message::Text(
                 serde_json::to_string(&item)?,
@@ -10,21 +10,36 @@ mod model;
 pub(crate) mod session;
 mod srp;
 
-use std::sync::Arc;
+use std::{
+    num::{NonZeroU32, NonZeroUsize},
+    sync::Arc,
+};
 
 use async_trait::async_trait;
-use futures_util::{future::BoxFuture, lock::Mutex};
-use tokio::{net::TcpStream, sync::mpsc};
+use futures_util::{
+    future::BoxFuture,
+    lock::Mutex,
+    stream::{self, BoxStream},
+    StreamExt as _,
+};
+use log::warn;
+use secrecy::{ExposeSecret as _, SecretString, SecretVec};
+use tokio::{
+    net::TcpStream,
+    sync::{broadcast, mpsc, watch},
+};
 use tokio_stream::wrappers::ReceiverStream;
 use tokio_tungstenite::{
     connect_async,
     tungstenite::{
+        self,
         client::IntoClientRequest,
         http::{header, HeaderValue},
     },
     MaybeTlsStream,
 };
 use url::Url;
+use uuid::Uuid;
 
 use crate::{
     client,
@@ -34,24 +49,90 @@ use crate::{
 
 use api::Executor as _;
 
+pub(crate) use model::setup::SecurityLevel;
+
+/// The origin we claim to connect from when none is configured.
+const DEFAULT_ORIGIN: &str = "karp://karp";
+
+/// The name we give this protocol in transcripts.
+const TRACE_PROTOCOL: &str = "keepassrpc";
+
 struct Client {
     tx: mpsc::Sender<api::Call>,
+    features: watch::Receiver<Option<Arc<[model::setup::ServerFeature]>>>,
+    events: broadcast::Receiver<client::DatabaseEvent>,
+    database: Option<String>,
+    title_match: client::TitleMatch,
 }
 
 impl Client {
-    fn new(tx: mpsc::Sender<api::Call>) -> Self {
-        Self { tx }
+    fn new(
+        tx: mpsc::Sender<api::Call>,
+        features: watch::Receiver<Option<Arc<[model::setup::ServerFeature]>>>,
+        events: broadcast::Receiver<client::DatabaseEvent>,
+        database: Option<String>,
+        title_match: client::TitleMatch,
+    ) -> Self {
+        Self {
+            tx,
+            features,
+            events,
+            database,
+            title_match,
+        }
     }
-}
 
-#[async_trait]
-impl client::Client for Client {
-    async fn get_entry(
+    /// Wait for the worker to finish negotiating with the server, then return
+    /// the features the server said it supports.
+    async fn server_features(&self) -> Result<Arc<[model::setup::ServerFeature]>> {
+        let mut features = self.features.clone();
+        loop {
+            if let Some(ref negotiated) = *features.borrow_and_update() {
+                return Ok(Arc::clone(negotiated));
+            }
+            features
+                .changed()
+                .await
+                .map_err(base_error::Internal::from)?;
+        }
+    }
+
+    /// Fail unless the server supports the given feature.
+    async fn require_feature(&self, feature: model::setup::ServerFeature) -> Result<()> {
+        if self.server_features().await?.contains(&feature) {
+            Ok(())
+        } else {
+            Err(error::Api::MissingFeature(feature).into())
+        }
+    }
+
+    /// Find the open database selected by name or file name, if one was
+    /// selected.
+    async fn database(&self) -> Result<Option<api::Database>> {
+        let Some(ref name) = self.database else {
+            return Ok(None);
+        };
+
+        api::GetAllDatabases {
+            full_details: false,
+        }
+        .execute(self.tx.clone())
+        .await?
+        .into_iter()
+        .find(|db| db.name == *name || db.file_name == *name)
+        .map(Some)
+        .ok_or_else(|| base_error::Error::DatabaseNotFound(name.clone()))
+    }
+
+    async fn get_group(
         &self,
+        database: Option<&api::Database>,
         group_names: &mut (dyn Iterator<Item = &str> + Send + Sync),
-        entry_title: &str,
-    ) -> Result<client::Entry> {
-        let mut group = api::GetRoot.execute(self.tx.clone()).await?;
+    ) -> Result<api::Group> {
+        let mut group = match database {
+            Some(db) => db.root.clone(),
+            None => api::GetRoot.execute(self.tx.clone()).await?,
+        };
         for group_name in group_names {
             let child_groups = api::GetChildGroups {
                 uuid: group.unique_id.clone(),
@@ -61,22 +142,36 @@ impl client::Client for Client {
 
             group = child_groups
                 .into_iter()
-                .find(|g| g.title == group_name)
+                .find(|g| client::same_name(&g.title, group_name))
                 .ok_or(base_error::Error::GroupNotFound {
                     parent: group.into(),
                     name: group_name.to_owned(),
                 })?;
         }
 
+        Ok(group)
+    }
+}
+
+#[async_trait]
+impl client::Client for Client {
+    async fn get_entry(
+        &self,
+        group_names: &mut (dyn Iterator<Item = &str> + Send + Sync),
+        entry_title: &str,
+    ) -> Result<client::Entry> {
+        let database = self.database().await?;
+        let group = self.get_group(database.as_ref(), group_names).await?;
+
         let entries = api::GetAllChildEntries {
             uuid: group.clone().unique_id,
         }
         .execute(self.tx.clone())
         .await?;
 
-        Ok(entries
-            .into_iter()
-            .find(|entry| entry.title == entry_title)
+        Ok(self
+            .title_match
+            .select(entries, |entry| &entry.title, entry_title)
             .ok_or(base_error::Error::EntryNotFound {
                 parent: group.into(),
                 name: entry_title.to_owned(),
@@ -84,37 +179,238 @@ impl client::Client for Client {
             .into())
     }
 
-    async fn find_entries(&self, query: &str) -> Result<Vec<client::Entry>> {
+    async fn get_entry_by_id(&self, id: Uuid) -> Result<client::Entry> {
+        // KeePass formats identifiers as uppercase hex strings without any
+        // separators.
+        let unique_id = id.simple().to_string().to_ascii_uppercase();
+        let database = self.database().await?;
+
+        api::FindLogins {
+            unsanitized_urls: vec![],
+            action_url: None,
+            http_realm: None,
+            require_full_url_matches: false,
+            unique_id: Some(unique_id.clone()),
+            db_root_id: database.map(|db| db.root.unique_id),
+            free_text_search: None,
+            username: None,
+        }
+        .execute(self.tx.clone())
+        .await?
+        .into_iter()
+        .find(|entry| entry.unique_id.eq_ignore_ascii_case(&unique_id))
+        .map(Into::into)
+        .ok_or(base_error::Error::EntryIdNotFound { id })
+    }
+
+    async fn find_entries(
+        &self,
+        query: &str,
+        limit: Option<NonZeroUsize>,
+    ) -> Result<Vec<client::Entry>> {
+        let database = self.database().await?;
+
         Ok(api::FindLogins {
             unsanitized_urls: vec![],
             action_url: None,
             http_realm: None,
             require_full_url_matches: false,
             unique_id: None,
-            db_root_id: None,
+            db_root_id: database.map(|db| db.root.unique_id),
             free_text_search: Some(query.to_string()),
             username: None,
         }
         .execute(self.tx.clone())
         .await?
         .into_iter()
+        // FindLogins has no limit of its own, so the most we can do is not
+        // keep the rest.
+        .take(limit.map_or(usize::MAX, NonZeroUsize::get))
         .map(Into::into)
         .collect())
     }
+
+    async fn create_entry(
+        &self,
+        group_names: &mut (dyn Iterator<Item = &str> + Send + Sync),
+        entry: &client::Entry,
+    ) -> Result<()> {
+        let database = self.database().await?;
+        let group = self.get_group(database.as_ref(), group_names).await?;
+
+        _ = api::AddLogin {
+            login: entry.into(),
+            parent_uuid: group.unique_id,
+            db_file_name: database.map(|db| db.file_name),
+        }
+        .execute(self.tx.clone())
+        .await?;
+        Ok(())
+    }
+
+    async fn update_entry(&self, id: Uuid, entry: &client::Entry) -> Result<()> {
+        // Older servers merge URLs into the existing entry instead of
+        // replacing them.
+        self.require_feature(model::setup::ServerFeature::FeatureEntryUrlReplacement)
+            .await?;
+        let database = self.database().await?;
+
+        _ = api::UpdateLogin {
+            login: entry.into(),
+            old_login_uuid: id.simple().to_string().to_ascii_uppercase(),
+            url_merge_mode: api::UrlMergeMode::Replace,
+            db_file_name: database.map(|db| db.file_name),
+        }
+        .execute(self.tx.clone())
+        .await?;
+        Ok(())
+    }
+
+    async fn list_group(
+        &self,
+        group_names: &mut (dyn Iterator<Item = &str> + Send + Sync),
+    ) -> Result<client::GroupListing> {
+        let database = self.database().await?;
+        let group = self.get_group(database.as_ref(), group_names).await?;
+
+        let groups = api::GetChildGroups {
+            uuid: group.unique_id.clone(),
+        }
+        .execute(self.tx.clone())
+        .await?;
+        let entries = api::GetAllChildEntries {
+            uuid: group.unique_id,
+        }
+        .execute(self.tx.clone())
+        .await?;
+
+        Ok(client::GroupListing {
+            groups: groups.into_iter().map(|g| g.title).collect(),
+            entries: entries.into_iter().map(|entry| entry.title).collect(),
+        })
+    }
+
+    async fn databases(&self) -> Result<Vec<client::Database>> {
+        // KeePassRPC only reports databases that are open and unlocked.
+        Ok(api::GetAllDatabases {
+            full_details: false,
+        }
+        .execute(self.tx.clone())
+        .await?
+        .into_iter()
+        .map(|db| client::Database {
+            name: db.name,
+            selector: db.file_name,
+            active: db.active,
+            association: None,
+        })
+        .collect())
+    }
+
+    async fn features(&self) -> Result<Vec<String>> {
+        Ok(self
+            .server_features()
+            .await?
+            .iter()
+            .filter_map(|feature| feature.name())
+            .map(str::to_owned)
+            .collect())
+    }
+
+    async fn settings(&self) -> Result<Vec<client::Setting>> {
+        let config = api::GetCurrentKfConfig.execute(self.tx.clone()).await?;
+        let setting = |name: &str, value: String| client::Setting {
+            name: name.to_owned(),
+            value,
+        };
+        let mut settings: Vec<_> = config
+            .known_databases
+            .into_iter()
+            .map(|path| setting("knownDatabases", path))
+            .collect();
+        settings.push(setting("autoCommit", config.auto_commit.to_string()));
+        settings.extend(config.other.into_iter().map(|(name, value)| {
+            setting(
+                &name,
+                value
+                    .as_str()
+                    .map_or_else(|| value.to_string(), str::to_owned),
+            )
+        }));
+        Ok(settings)
+    }
+
+    async fn watch(&self) -> Result<BoxStream<'static, client::DatabaseEvent>> {
+        // The stream ends along with the connection, when the manager drops
+        // the sender.
+        Ok(
+            stream::unfold(self.events.resubscribe(), |mut rx| async move {
+                loop {
+                    match rx.recv().await {
+                        Ok(event) => return Some((event, rx)),
+                        Err(broadcast::error::RecvError::Lagged(missed)) => {
+                            warn!("Missed {missed} events from KeePassRPC");
+                        }
+                        Err(broadcast::error::RecvError::Closed) => return None,
+                    }
+                }
+            })
+            .boxed(),
+        )
+    }
+
+    async fn generate_password(&self) -> Result<SecretString> {
+        api::GeneratePassword {
+            profile_name: String::new(),
+            url: String::new(),
+        }
+        .execute(self.tx.clone())
+        .await
+    }
+}
+
+/// Settings that change how we talk to KeePassRPC.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Options {
+    /// The name or file name of the database to use instead of the active
+    /// one.
+    pub(crate) database: Option<String>,
+    /// The security level to require of the server instead of one derived
+    /// from how the session key is stored.
+    pub(crate) security_level: Option<SecurityLevel>,
+    /// The name KeePass shows when asking to authorize us.
+    pub(crate) client_display_name: Option<String>,
+    /// The description KeePass shows when asking to authorize us.
+    pub(crate) client_display_description: Option<String>,
+    /// The value of the `Origin` header to send when opening the WebSocket.
+    pub(crate) origin: Option<String>,
+    /// How many times to ask for the password before giving up, or `None`
+    /// to keep asking.
+    pub(crate) max_password_attempts: Option<NonZeroU32>,
+    /// How the title of an entry we're asked for is compared with the titles
+    /// in its group.
+    pub(crate) title_match: client::TitleMatch,
 }
 
 pub(crate) struct Protocol<Storage: storage::Storage<session::Data>, Prompt: password::Prompt> {
     storage: Arc<Mutex<Storage>>,
     prompt: Arc<Prompt>,
     url: Url,
+    options: Options,
 }
 
 impl<Storage: storage::Storage<session::Data>, Prompt: password::Prompt> Protocol<Storage, Prompt> {
-    pub(crate) fn new(storage: Arc<Mutex<Storage>>, prompt: Arc<Prompt>, url: Url) -> Self {
+    pub(crate) fn new(
+        storage: Arc<Mutex<Storage>>,
+        prompt: Arc<Prompt>,
+        url: Url,
+        options: Options,
+    ) -> Self {
         Self {
             storage,
             prompt,
             url,
+            options,
         }
     }
 
@@ -124,9 +420,13 @@ impl<Storage: storage::Storage<session::Data>, Prompt: password::Prompt> Protoco
             .as_ref()
             .into_client_request()
             .map_err(Into::<error::Error>::into)?;
-        let _ = req
-            .headers_mut()
-            .append(header::ORIGIN, HeaderValue::from_static("karp://karp"));
+        let origin = match self.options.origin {
+            Some(ref origin) => HeaderValue::from_str(origin).map_err(|e| {
+                Into::<error::Error>::into(tungstenite::Error::HttpFormat(e.into()))
+            })?,
+            None => HeaderValue::from_static(DEFAULT_ORIGIN),
+        };
+        let _ = req.headers_mut().append(header::ORIGIN, origin);
 
         let (stream, _) = connect_async(req)
             .await
@@ -150,13 +450,45 @@ impl<
     )> {
         let storage = Arc::clone(&self.storage);
         let prompt = Arc::clone(&self.prompt);
+        let options = self.options.clone();
         let (tx, rx) = mpsc::channel(16);
+        let (features_tx, features_rx) = watch::channel(None);
+        let (event_tx, event_rx) = broadcast::channel(16);
         let message_stream = self.new_stream().await?;
 
         let worker = async move {
-            manager::run(storage, prompt, message_stream, ReceiverStream::new(rx)).await
+            manager::run(
+                storage,
+                prompt,
+                options,
+                features_tx,
+                event_tx,
+                message_stream,
+                ReceiverStream::new(rx),
+            )
+            .await
         };
 
-        Ok((Box::pin(worker), Box::new(Client::new(tx))))
+        Ok((
+            Box::pin(worker),
+            Box::new(Client::new(
+                tx,
+                features_rx,
+                event_rx,
+                self.options.database.clone(),
+                self.options.title_match,
+            )),
+        ))
+    }
+
+    async fn session_secret(&self) -> Result<Option<SecretVec<u8>>> {
+        let data = self.storage.lock().await.get().await?;
+        Ok(data
+            .as_ref()
+            .and_then(session::Data::session_key)
+            .map(|key| {
+                let bytes: secrecy::Secret<[u8; 32]> = key.into();
+                SecretVec::new(bytes.expose_secret().to_vec())
+            }))
     }
 }
@@ -14,8 +14,8 @@ use subtle::ConstantTimeEq;
 
 use crate::{
     error::{self, Result},
-    keepass::error as keepass_error,
-    rng,
+    keepass::{error as keepass_error, TRACE_PROTOCOL},
+    rng, trace,
 };
 
 use super::hash;
@@ -57,6 +57,12 @@ impl<T> EncryptedJson<T> {
         rng::map(|rng| rng.fill(&mut *iv));
 
         let plaintext = Secret::new(serde_json::to_string(&msg)?);
+        trace::record_json(
+            TRACE_PROTOCOL,
+            trace::Direction::Sent,
+            trace::Layer::Payload,
+            plaintext.expose_secret().as_bytes(),
+        );
 
         let encryptor = cbc::Encryptor::<aes::Aes256>::new(
             Key::<cbc::Encryptor<aes::Aes256>>::from_slice(key.expose_secret()),
@@ -95,6 +101,12 @@ impl<T> EncryptedJson<T> {
                 .decrypt_padded_vec_mut::<block_padding::Pkcs7>(&self.message)
                 .map_err(error::Conversion::from)?,
         );
+        trace::record_json(
+            TRACE_PROTOCOL,
+            trace::Direction::Received,
+            trace::Layer::Payload,
+            plaintext.expose_secret(),
+        );
 
         Ok(serde_json::from_slice(plaintext.expose_secret())?)
     }
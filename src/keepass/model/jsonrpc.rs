@@ -2,10 +2,12 @@
 //
 // SPDX-License-Identifier: Apache-2.0
 
+use std::fmt::{Display, Formatter};
+
 use rand::Rng;
 use serde::{Deserialize, Serialize};
 
-use crate::rng;
+use crate::{memory, rng};
 
 #[derive(Debug, Deserialize, Clone, Serialize, PartialEq, Eq, Hash)]
 #[serde(untagged)]
@@ -37,8 +39,16 @@ impl Request {
         })
     }
 
-    pub(in crate::keepass) const fn id(&self) -> &Option<Id> {
-        &self.id
+    pub(in crate::keepass) const fn id(&self) -> Option<&Id> {
+        self.id.as_ref()
+    }
+
+    pub(in crate::keepass) fn method(&self) -> &str {
+        &self.method
+    }
+
+    pub(in crate::keepass) fn params(&self) -> &[serde_json::Value] {
+        &self.params
     }
 }
 
@@ -53,9 +63,22 @@ impl Error {
     pub(in crate::keepass) fn name(&self) -> &str {
         &self.name
     }
+}
 
-    pub(in crate::keepass) fn message(&self) -> &str {
-        &self.message
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.name, self.message)?;
+        if let Some(errors) = self.errors.as_deref().filter(|errors| !errors.is_empty()) {
+            f.write_str(" (")?;
+            for (i, error) in errors.iter().enumerate() {
+                if i > 0 {
+                    f.write_str("; ")?;
+                }
+                write!(f, "{error}")?;
+            }
+            f.write_str(")")?;
+        }
+        Ok(())
     }
 }
 
@@ -74,6 +97,18 @@ pub(crate) struct Response {
 }
 
 impl Response {
+    /// A reply refusing a request the server made of us.
+    pub(in crate::keepass) fn error(id: Id, name: &str, message: &str) -> Self {
+        Self {
+            id,
+            variant: ResponseVariant::Error(Error {
+                name: name.to_owned(),
+                message: message.to_owned(),
+                errors: None,
+            }),
+        }
+    }
+
     pub(in crate::keepass) const fn id(&self) -> &Id {
         &self.id
     }
@@ -83,9 +118,57 @@ impl Response {
     }
 }
 
+/// Results can hold the entries we looked up, passwords and all, so we don't
+/// leave them behind in freed memory.
+impl Drop for Response {
+    fn drop(&mut self) {
+        if let ResponseVariant::Result(ref mut result) = self.variant {
+            memory::zeroize_json(result);
+        }
+    }
+}
+
 #[derive(Debug, Deserialize, Clone, Serialize, PartialEq)]
 #[serde(untagged)]
 pub(crate) enum Jsonrpc {
     Request(Request),
     Response(Response),
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn error_display_includes_nested_errors() -> serde_json::Result<()> {
+        let error: Error = serde_json::from_str(
+            r#"{
+                "name": "ValidationError",
+                "message": "request rejected",
+                "errors": [
+                    {"name": "UrlPolicy", "message": "scheme not allowed"},
+                    {"name": "Nested", "message": "outer", "errors": [{"name": "Inner", "message": "cause"}]}
+                ]
+            }"#,
+        )?;
+
+        assert_eq!(
+            error.to_string(),
+            "ValidationError: request rejected (UrlPolicy: scheme not allowed; Nested: outer (Inner: cause))"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn server_signals_are_requests() -> serde_json::Result<()> {
+        let message: Jsonrpc = serde_json::from_str(
+            r#"{"jsonrpc": "2.0", "method": "KPRPCListener", "params": [4], "id": 12}"#,
+        )?;
+        let Jsonrpc::Request(req) = message else {
+            panic!("expected a request, got {message:?}");
+        };
+        assert_eq!(req.method(), "KPRPCListener");
+        assert_eq!(req.params(), [serde_json::json!(4_u64)]);
+        Ok(())
+    }
+}
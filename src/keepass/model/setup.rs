@@ -4,6 +4,7 @@
 
 use std::fmt::{Display, Formatter};
 
+use clap::ValueEnum;
 use serde::{Deserialize, Serialize};
 use serde_repr::{Deserialize_repr, Serialize_repr};
 use uuid::Uuid;
@@ -29,6 +30,10 @@ pub(crate) struct Error {
 }
 
 impl Error {
+    pub(in crate::keepass) const fn code(&self) -> ErrorCode {
+        self.code
+    }
+
     pub(in crate::keepass) fn is_auth_error(&self) -> bool {
         matches!(self.code, ErrorCode::AuthFailed | ErrorCode::AuthExpired)
     }
@@ -61,9 +66,9 @@ pub(in crate::keepass) enum ClientFeature {
     Other,
 }
 
-#[derive(Debug, Deserialize, Serialize, PartialEq)]
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq)]
 #[non_exhaustive]
-pub(in crate::keepass) enum ServerFeature {
+pub(crate) enum ServerFeature {
     #[serde(rename = "KPRPC_FEATURE_VERSION_1_6")]
     FeatureVersion1_6,
     #[serde(rename = "KPRPC_GENERAL_CLIENTS")]
@@ -82,7 +87,26 @@ pub(in crate::keepass) enum ServerFeature {
     Other,
 }
 
-#[derive(Debug, Deserialize_repr, Serialize_repr, PartialEq, PartialOrd, Clone, Copy)]
+impl ServerFeature {
+    /// The name the server uses for the feature, or [`None`] for features we
+    /// don't know about.
+    pub(crate) const fn name(self) -> Option<&'static str> {
+        match self {
+            Self::FeatureVersion1_6 => Some("KPRPC_FEATURE_VERSION_1_6"),
+            Self::GeneralClients => Some("KPRPC_GENERAL_CLIENTS"),
+            Self::FeatureKeeBrand => Some("KPRPC_FEATURE_KEE_BRAND"),
+            Self::EntriesWithNoUrl => Some("KPRPC_ENTRIES_WITH_NO_URL"),
+            Self::FieldDefaultNameAndIdEmpty => Some("KPRPC_FIELD_DEFAULT_NAME_AND_ID_EMPTY"),
+            Self::OpenAndFocusDatabase => Some("KPRPC_OPEN_AND_FOCUS_DATABASE"),
+            Self::FeatureEntryUrlReplacement => Some("KPRPC_FEATURE_ENTRY_URL_REPLACEMENT"),
+            Self::Other => None,
+        }
+    }
+}
+
+#[derive(
+    Debug, Deserialize_repr, Serialize_repr, PartialEq, PartialOrd, Clone, Copy, ValueEnum,
+)]
 #[repr(i32)]
 pub(crate) enum SecurityLevel {
     Low = 1_i32,
@@ -130,11 +154,15 @@ pub(in crate::keepass) struct KeyClientNegotiation {
 }
 
 impl KeyClientNegotiation {
-    pub(in crate::keepass) fn new(client_challenge: &str, client_response: &Hash) -> Self {
+    pub(in crate::keepass) fn new(
+        client_challenge: &str,
+        client_response: &Hash,
+        security_level: SecurityLevel,
+    ) -> Self {
         Self {
             client_challenge: client_challenge.to_owned(),
             client_response: client_response.clone(),
-            security_level: SecurityLevel::Medium,
+            security_level,
         }
     }
 }
@@ -286,15 +314,19 @@ pub(in crate::keepass) struct ClientInit {
 }
 
 impl ClientInit {
-    pub(in crate::keepass) fn new(variant: ClientInitVariant) -> Self {
+    pub(in crate::keepass) fn new(
+        variant: ClientInitVariant,
+        client_display_name: &str,
+        client_display_description: Option<&str>,
+    ) -> Self {
         Self {
             features: vec![
                 ClientFeature::FeatureVersion1_6,
                 ClientFeature::FeatureWarnUserWhenFeatureMissing,
             ],
             client_type_id: metadata::CLIENT_TYPE_ID.to_owned(),
-            client_display_name: Some(metadata::CLIENT_DISPLAY_NAME.to_owned()),
-            client_display_description: metadata::CLIENT_DISPLAY_DESCRIPTION.to_owned(),
+            client_display_name: Some(client_display_name.to_owned()),
+            client_display_description: client_display_description.map(str::to_owned),
             variant,
         }
     }
@@ -330,6 +362,17 @@ pub(in crate::keepass) enum Variant {
     },
 }
 
+/// The oldest KeePassRPC we work with: 1.6, the first to report the features
+/// we rely on.
+pub(in crate::keepass) const MINIMUM_SERVER_VERSION: u32 = u32::from_be_bytes([0, 1, 6, 0]);
+
+/// Format a protocol version, which packs the major, minor, and patch numbers
+/// into its low three bytes.
+pub(in crate::keepass) fn format_version(version: u32) -> String {
+    let [_, major, minor, patch] = version.to_be_bytes();
+    format!("{major}.{minor}.{patch}")
+}
+
 #[derive(Debug, Deserialize, Serialize, PartialEq)]
 pub(crate) struct Setup {
     version: u32,
@@ -348,6 +391,11 @@ impl Setup {
     pub(in crate::keepass) const fn variant(&self) -> &Variant {
         &self.variant
     }
+
+    /// The version of the protocol the sender speaks.
+    pub(in crate::keepass) const fn version(&self) -> u32 {
+        self.version
+    }
 }
 
 #[cfg(test)]
@@ -357,6 +405,12 @@ mod tests {
     use serde_test::{assert_tokens, Configure, Token};
     use uuid::uuid;
 
+    #[test]
+    fn versions_are_formatted() {
+        assert_eq!(format_version(u32::from_be_bytes([0, 1, 16, 2])), "1.16.2");
+        assert_eq!(format_version(MINIMUM_SERVER_VERSION), "1.6.0");
+    }
+
     #[test]
     fn setup_client_init_key() {
         let msg = Setup::new(Variant::ClientInit(ClientInit::new(
@@ -364,6 +418,8 @@ mod tests {
                 username: "test".to_owned(),
                 security_level: SecurityLevel::Medium,
             },
+            &metadata::CLIENT_DISPLAY_NAME,
+            metadata::CLIENT_DISPLAY_DESCRIPTION.as_deref(),
         )));
 
         assert_tokens(
@@ -428,6 +484,8 @@ mod tests {
             .into_protocol();
         let msg = Setup::new(Variant::ClientInit(ClientInit::new(
             ClientInitVariant::Srp(SrpIdentifyToServer::new(&srp, SecurityLevel::Medium)),
+            &metadata::CLIENT_DISPLAY_NAME,
+            metadata::CLIENT_DISPLAY_DESCRIPTION.as_deref(),
         )));
 
         assert_tokens(
@@ -5,6 +5,8 @@
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
+use crate::{error::Result, storage};
+
 use super::model;
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -31,11 +33,20 @@ impl Data {
         }
     }
 
-    pub(super) const fn identifier(&self) -> Uuid {
+    pub(crate) const fn identifier(&self) -> Uuid {
         self.identifier
     }
 
-    pub(super) const fn session_key(&self) -> &Option<model::hash::Secret> {
-        &self.session_key
+    pub(super) const fn session_key(&self) -> Option<&model::hash::Secret> {
+        self.session_key.as_ref()
+    }
+}
+
+impl storage::Versioned for Data {
+    const VERSION: u32 = 1;
+
+    fn migrate(_: u32, _: &mut serde_json::Value) -> Result<()> {
+        // Version 1 only added the version itself.
+        Ok(())
     }
 }
@@ -2,10 +2,11 @@
 //
 // SPDX-License-Identifier: Apache-2.0
 
+use std::sync::LazyLock;
+
 use digest::Digest;
 use num_bigint::{BigInt, Sign};
 use num_traits::Zero;
-use once_cell::sync::Lazy;
 use rand::{Rng as _, RngCore};
 use secrecy::{ExposeSecret, SecretString};
 use sha1::Sha1;
@@ -16,7 +17,7 @@ use crate::{error::Result, rng};
 
 use super::{error as keepass_error, model};
 
-static PARAM_N: Lazy<BigInt> = Lazy::new(|| {
+static PARAM_N: LazyLock<BigInt> = LazyLock::new(|| {
     BigInt::from_bytes_be(
         Sign::Plus,
         &[
@@ -28,9 +29,9 @@ static PARAM_N: Lazy<BigInt> = Lazy::new(|| {
     )
 });
 
-static PARAM_GENERATOR: Lazy<BigInt> = Lazy::new(|| BigInt::from(2_u32));
+static PARAM_GENERATOR: LazyLock<BigInt> = LazyLock::new(|| BigInt::from(2_u32));
 
-static PARAM_K: Lazy<BigInt> = Lazy::new(|| {
+static PARAM_K: LazyLock<BigInt> = LazyLock::new(|| {
     let (_, n_bytes) = PARAM_N.to_bytes_be();
 
     let (_, mut generator_bytes) = PARAM_GENERATOR.to_bytes_le();
@@ -184,6 +185,8 @@ pub(crate) struct ProtocolBuilder<'rng> {
     identifier: Option<Uuid>,
 }
 
+// LINT: The lifetime is only referenced by test-only methods.
+#[cfg_attr(not(test), allow(clippy::elidable_lifetime_names))]
 impl<'rng> ProtocolBuilder<'rng> {
     pub(crate) fn new() -> Self {
         Self {
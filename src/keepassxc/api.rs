@@ -4,9 +4,11 @@
 
 #![allow(dead_code)]
 
+use std::collections::BTreeMap;
+
 use async_trait::async_trait;
-use secrecy::SecretString;
-use serde::{Deserialize, Serialize};
+use secrecy::{ExposeSecret as _, SecretString};
+use serde::{Deserialize, Serialize, Serializer};
 use serde_with::{base64::Base64, json::JsonString, serde_as};
 use tokio::sync::{mpsc, oneshot};
 
@@ -17,10 +19,41 @@ use crate::{
 
 use super::model;
 
+/// The prefix KeePassXC requires of the names of attributes it gives browser
+/// extensions.
+const KPH_PREFIX: &str = "KPH:";
+
+/// The oldest KeePassXC we work with, the first to answer
+/// `get-database-groups`.
+pub(super) const MINIMUM_VERSION: [u32; 3] = [2, 6, 0];
+
+/// Fail if KeePassXC tells us a version older than the one we need. Versions
+/// we can't read, such as those of forks, are given the benefit of the doubt.
+pub(super) fn check_version(version: Option<&str>) -> Result<()> {
+    let Some(text) = version else {
+        return Ok(());
+    };
+    let mut parts = [0_u32; 3];
+    for (part, component) in parts.iter_mut().zip(text.split('.')) {
+        let digits: String = component.chars().take_while(char::is_ascii_digit).collect();
+        let Ok(number) = digits.parse() else {
+            return Ok(());
+        };
+        *part = number;
+    }
+    if parts < MINIMUM_VERSION {
+        return Err(super::error::Api::ServerTooOld(text.to_owned()).into());
+    }
+    Ok(())
+}
+
 #[derive(Debug)]
 pub(super) struct Call {
     pub(super) action: String,
     pub(super) req: serde_json::Value,
+    /// Whether KeePassXC should ask the user to unlock the database if it's
+    /// locked, instead of failing the request.
+    pub(super) trigger_unlock: bool,
     pub(super) tx: oneshot::Sender<Result<serde_json::Value>>,
 }
 
@@ -28,9 +61,15 @@ impl Call {
     pub(super) fn new(
         action: String,
         req: serde_json::Value,
+        trigger_unlock: bool,
         tx: oneshot::Sender<Result<serde_json::Value>>,
     ) -> Self {
-        Self { action, req, tx }
+        Self {
+            action,
+            req,
+            trigger_unlock,
+            tx,
+        }
     }
 }
 
@@ -41,7 +80,7 @@ impl model::HasAction for Call {
 }
 
 impl Serialize for Call {
-    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
         self.req.serialize(serializer)
     }
 }
@@ -60,16 +99,21 @@ impl<T: HasConstAction> model::HasAction for T {
 pub(super) trait Executor: model::HasAction {
     type Response;
 
-    async fn execute(self, tx: mpsc::Sender<Call>) -> Result<Self::Response>
+    async fn execute(self, tx: mpsc::Sender<Call>, trigger_unlock: bool) -> Result<Self::Response>
     where
         Self: Serialize + Sized,
         Self::Response: for<'de> Deserialize<'de>,
     {
         let req = serde_json::to_value(&self)?;
         let (ltx, lrx) = oneshot::channel();
-        tx.send(Call::new(self.action().to_owned(), req, ltx))
-            .await
-            .map_err(error::Internal::from)?;
+        tx.send(Call::new(
+            self.action().to_owned(),
+            req,
+            trigger_unlock,
+            ltx,
+        ))
+        .await
+        .map_err(error::Internal::from)?;
         Ok(serde_json::from_value(
             lrx.await.map_err(error::Internal::from)??,
         )?)
@@ -84,8 +128,19 @@ pub(super) struct Entry {
     pub(super) name: String,
     pub(super) password: SecretString,
     pub(super) uuid: String,
+    /// The name of the group the entry is in, without its parents.
     pub(super) group: String,
+    /// The UUID of the group the entry is in, which tells apart groups with
+    /// the same name, if KeePassXC sends it.
+    #[serde(default)]
+    pub(super) group_uuid: Option<String>,
     pub(super) totp: Option<String>,
+    /// Advanced attributes whose names start with `KPH:`, each in an object
+    /// of its own. KeePassXC only includes these when "Return advanced string
+    /// fields which start with KPH" is turned on in its browser integration
+    /// settings; there is no way to ask for them in a request.
+    #[serde(default)]
+    pub(super) string_fields: Vec<BTreeMap<String, SecretString>>,
     #[serde_as(as = "JsonString")]
     #[serde(default)]
     pub(super) expired: bool,
@@ -93,18 +148,40 @@ pub(super) struct Entry {
 
 impl From<Entry> for client::Entry {
     fn from(value: Entry) -> Self {
-        let form_fields = vec![
+        let mut form_fields = vec![
             client::FormField {
                 type_: client::FormFieldType::Username,
                 display_name: "KeePass username".to_owned(),
                 value: value.login.into(),
+                metadata: None,
             },
             client::FormField {
                 type_: client::FormFieldType::Password,
                 display_name: "KeePass password".to_owned(),
                 value: value.password,
+                metadata: None,
             },
         ];
+        if let Some(totp) = value.totp.filter(|totp| !totp.is_empty()) {
+            form_fields.push(client::FormField {
+                type_: client::FormFieldType::Totp,
+                display_name: "KeePass TOTP".to_owned(),
+                value: totp.into(),
+                metadata: None,
+            });
+        }
+        for (name, field_value) in value.string_fields.into_iter().flatten() {
+            let display_name = name
+                .strip_prefix(KPH_PREFIX)
+                .map_or(name.as_str(), str::trim_start)
+                .to_owned();
+            form_fields.push(client::FormField {
+                type_: client::FormFieldType::Text,
+                display_name,
+                value: field_value,
+                metadata: None,
+            });
+        }
 
         client::Entry {
             id: value.uuid,
@@ -114,7 +191,10 @@ impl From<Entry> for client::Entry {
                 Some(client::Group { path: value.group })
             },
             title: value.name,
+            urls: vec![],
             form_fields,
+            database: None,
+            source: None,
         }
     }
 }
@@ -147,6 +227,9 @@ pub(super) struct ChangePublicKeysResponse {
     pub(super) nonce: [u8; model::key_material::NONCE_SIZE],
     #[serde_as(as = "Base64")]
     pub(super) public_key: [u8; model::key_material::KEY_SIZE],
+    /// The version of KeePassXC, such as `2.7.6`.
+    #[serde(default)]
+    pub(super) version: Option<String>,
 }
 
 impl model::HasNonce for ChangePublicKeysResponse {
@@ -169,6 +252,12 @@ impl HasConstAction for GetDatabaseHashRequest {
 #[serde(rename_all = "camelCase")]
 pub(super) struct GetDatabaseHashResponse {
     pub(super) hash: String,
+    #[serde(default)]
+    pub(super) version: Option<String>,
+}
+
+impl Executor for GetDatabaseHashRequest {
+    type Response = GetDatabaseHashResponse;
 }
 
 #[serde_as]
@@ -236,6 +325,101 @@ impl Executor for GetLoginsRequest {
     type Response = GetLoginsResponse;
 }
 
+#[derive(Clone, Debug, Serialize)]
+pub(super) struct GetDatabaseGroupsRequest {}
+
+impl HasConstAction for GetDatabaseGroupsRequest {
+    const ACTION: &'static str = "get-database-groups";
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct Group {
+    pub(super) name: String,
+    pub(super) uuid: String,
+    #[serde(default)]
+    pub(super) children: Vec<Group>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct GroupList {
+    pub(super) groups: Vec<Group>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct GetDatabaseGroupsResponse {
+    pub(super) groups: GroupList,
+}
+
+impl Executor for GetDatabaseGroupsRequest {
+    type Response = GetDatabaseGroupsResponse;
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub(super) struct GeneratePasswordRequest {}
+
+impl HasConstAction for GeneratePasswordRequest {
+    const ACTION: &'static str = "generate-password";
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub(super) struct GeneratedPassword {
+    pub(super) password: SecretString,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct GeneratePasswordResponse {
+    /// The password the user chose in the generator, as KeePassXC 2.7 and
+    /// later send it.
+    #[serde(default)]
+    pub(super) password: Option<SecretString>,
+    /// The passwords earlier versions generate without asking the user.
+    #[serde(default)]
+    pub(super) entries: Vec<GeneratedPassword>,
+}
+
+impl Executor for GeneratePasswordRequest {
+    type Response = GeneratePasswordResponse;
+}
+
+fn serialize_secret<S: Serializer>(value: &SecretString, serializer: S) -> Result<S::Ok, S::Error> {
+    value.expose_secret().serialize(serializer)
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct SetLoginRequest {
+    pub(super) url: String,
+    pub(super) submit_url: Option<String>,
+    pub(super) login: String,
+    #[serde(serialize_with = "serialize_secret")]
+    pub(super) password: SecretString,
+    pub(super) group: Option<String>,
+    pub(super) group_uuid: Option<String>,
+    /// The entry to update, or `None` to create a new entry.
+    pub(super) uuid: Option<String>,
+}
+
+impl HasConstAction for SetLoginRequest {
+    const ACTION: &'static str = "set-login";
+}
+
+#[serde_as]
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct SetLoginResponse {
+    #[serde_as(as = "JsonString")]
+    #[serde(default)]
+    pub(super) success: bool,
+}
+
+impl Executor for SetLoginRequest {
+    type Response = SetLoginResponse;
+}
+
 #[derive(Debug)]
 pub(super) enum Signal {
     DatabaseLocked,
@@ -250,3 +434,79 @@ impl model::HasAction for Signal {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn old_versions_are_refused() {
+        assert!(check_version(None).is_ok());
+        assert!(check_version(Some("2.7.6")).is_ok());
+        assert!(check_version(Some("2.6.0")).is_ok());
+        assert!(check_version(Some("2.8.0-snapshot")).is_ok());
+        assert!(check_version(Some("unknown")).is_ok());
+        assert!(check_version(Some("2.5.4")).is_err());
+        assert!(check_version(Some("2.2")).is_err());
+        assert!(check_version(Some("2.5.4")).is_err_and(|e| e
+            .to_string()
+            .contains("KeePassXC 2.5.4 is too old; upgrade it to version 2.6.0")));
+    }
+
+    #[test]
+    fn entry_form_fields() -> serde_json::Result<()> {
+        let entry: Entry = serde_json::from_value(json!({
+            "login": "alice",
+            "name": "Example",
+            "password": "hunter2",
+            "uuid": "04040404040404040404040404040404",
+            "group": "Web",
+            "totp": "123456",
+            "stringFields": [{"KPH: tenant": "acme"}, {"KPH:region": "eu"}],
+        }))?;
+        let fields: Vec<_> = client::Entry::from(entry)
+            .form_fields
+            .into_iter()
+            .map(|field| {
+                (
+                    field.type_,
+                    field.display_name,
+                    field.value.expose_secret().clone(),
+                )
+            })
+            .collect();
+
+        assert_eq!(
+            fields,
+            [
+                (
+                    client::FormFieldType::Username,
+                    "KeePass username".to_owned(),
+                    "alice".to_owned()
+                ),
+                (
+                    client::FormFieldType::Password,
+                    "KeePass password".to_owned(),
+                    "hunter2".to_owned()
+                ),
+                (
+                    client::FormFieldType::Totp,
+                    "KeePass TOTP".to_owned(),
+                    "123456".to_owned()
+                ),
+                (
+                    client::FormFieldType::Text,
+                    "tenant".to_owned(),
+                    "acme".to_owned()
+                ),
+                (
+                    client::FormFieldType::Text,
+                    "region".to_owned(),
+                    "eu".to_owned()
+                ),
+            ]
+        );
+        Ok(())
+    }
+}
@@ -14,6 +14,40 @@ pub(crate) enum Error {
     Api(#[from] Api),
     #[error("Cryptography error: {0}")]
     Cryptography(#[from] crypto_box::aead::Error),
+    #[error("the socket of KeePassXC's browser integration could not be found in {}", .0.iter().map(|path| path.display().to_string()).collect::<Vec<_>>().join(", "))]
+    NoSocket(Vec<std::path::PathBuf>),
+    #[error("KeePassXC is not running or browser integration is disabled — enable it under Settings → Browser Integration (nothing is listening on {})", .0.display())]
+    NotRunning(std::path::PathBuf),
+    #[error("KeePassXC's browser integration can only save the URL, username, and password of an entry, not {}", .0.join(", "))]
+    Unsupported(Vec<String>),
+}
+
+impl Error {
+    /// The error code KeePassXC gave, if the error came from KeePassXC.
+    pub(crate) fn code(&self) -> Option<String> {
+        match *self {
+            Self::Api(Api::ServerError(ref e)) => Some(format!("{:?}", e.error_code())),
+            Self::Api(_)
+            | Self::Cryptography(_)
+            | Self::NoSocket(_)
+            | Self::NotRunning(_)
+            | Self::Unsupported(_) => None,
+        }
+    }
+
+    /// Whether KeePassXC wasn't there to connect to.
+    pub(crate) const fn is_not_running(&self) -> bool {
+        matches!(*self, Self::NoSocket(_) | Self::NotRunning(_))
+    }
+
+    /// A short name for what sort of error this is, for programs to match on.
+    pub(crate) const fn kind(&self) -> &'static str {
+        if self.is_not_running() {
+            "keepassxc_not_running"
+        } else {
+            "keepassxc"
+        }
+    }
 }
 
 #[derive(Error, Debug)]
@@ -24,6 +58,15 @@ pub(crate) enum Api {
     InvalidNonce,
     #[error("server error ({:?}): {}", .0.error_code(), .0.error())]
     ServerError(model::Error),
+    #[error("server did not complete the request successfully")]
+    Unsuccessful,
+    #[error("a message of {0} bytes is too large to send")]
+    MessageTooLarge(usize),
+    #[error(
+        "KeePassXC {0} is too old; upgrade it to version {minimum} or later",
+        minimum = super::api::MINIMUM_VERSION.map(|part| part.to_string()).join(".")
+    )]
+    ServerTooOld(String),
 }
 
 impl From<Api> for error::Error {
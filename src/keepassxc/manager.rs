@@ -3,7 +3,8 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use std::{
-    collections::HashMap,
+    collections::VecDeque,
+    io::{self, IsTerminal as _, Write as _},
     pin::Pin,
     sync::Arc,
     task::{Context, Poll},
@@ -16,12 +17,18 @@ use futures_util::{
 };
 use log::{debug, info, warn};
 use secrecy::ExposeSecret;
-use tokio::{select, sync::watch};
+use tokio::{
+    select,
+    sync::{broadcast, watch},
+    task,
+};
 
 use crate::{
+    client,
     error::{self, Result},
+    hooks,
     keepassxc::{api::HasConstAction as _, model::HasNonce as _},
-    storage,
+    locale, progress, storage,
 };
 
 use super::{
@@ -75,6 +82,43 @@ impl<S: message::Stream> Stream for SignalForwardingStream<S> {
     }
 }
 
+/// A call we've sent the request for and are waiting on the response to.
+struct Pending {
+    nonce: model::key_material::Nonce,
+    request_id: String,
+    call: Call,
+}
+
+impl Pending {
+    fn fail(self, err: error::Error) {
+        if self.call.tx.send(Err(err)).is_err() {
+            warn!(
+                "Failed to inform disconnected call receiver {:?} of error",
+                self.call.req
+            );
+        }
+    }
+}
+
+/// Remove the call that an error response belongs to. Errors aren't
+/// encrypted, so they carry no nonce; KeePassXC echoes the request ID when it
+/// can, and otherwise answers each client's requests in the order they were
+/// sent.
+fn take_failed(
+    calls: &mut VecDeque<Pending>,
+    request_id: Option<&str>,
+    action: &str,
+) -> Option<Pending> {
+    let index = request_id
+        .and_then(|id| calls.iter().position(|pending| pending.request_id == id))
+        .or_else(|| {
+            calls
+                .iter()
+                .position(|pending| pending.call.action() == action)
+        })?;
+    calls.remove(index)
+}
+
 pub(super) struct Manager<
     Storage: storage::Storage<session::Data>,
     MessageStream: message::Stream,
@@ -86,8 +130,17 @@ pub(super) struct Manager<
     message_builder: model::EncryptedMessageBuilder,
     signal_rx: watch::Receiver<Option<api::Signal>>,
     call_rx: CallStream,
-    calls: HashMap<model::key_material::Nonce, Call>,
+    /// Calls waiting on responses, in the order we sent their requests.
+    calls: VecDeque<Pending>,
+    /// Whether requests have been written to the sink without being flushed.
+    unflushed: bool,
     key: Option<model::Key>,
+    association_name: Option<String>,
+    association_tx: watch::Sender<Option<String>>,
+    /// Where we pass on the signals KeePassXC sends, for clients watching the
+    /// database.
+    event_tx: broadcast::Sender<client::DatabaseEvent>,
+    interactive: bool,
 }
 
 impl<
@@ -100,7 +153,12 @@ impl<
         storage: Arc<Mutex<Storage>>,
         message_stream: MessageStream,
         call_rx: CallStream,
+        association_name: Option<String>,
+        association_tx: watch::Sender<Option<String>>,
+        event_tx: broadcast::Sender<client::DatabaseEvent>,
+        interactive: bool,
     ) -> Result<Self> {
+        let _progress = progress::start(progress::Phase::Authenticating);
         let (mut message_tx, message_rx) = message_stream.split();
         let (mut message_forwarding_rx, signal_rx) = forward_signals(message_rx);
         let message_builder = model::MessageBuilder::new();
@@ -120,6 +178,7 @@ impl<
                 .ok_or(keepassxc_error::Api::StreamEnded)??,
             &nonce,
         )?;
+        api::check_version(resp.version.as_deref())?;
 
         Ok(Self {
             storage,
@@ -129,8 +188,13 @@ impl<
                 .encrypt(&model::key_material::PublicKey::from_bytes(resp.public_key)),
             signal_rx,
             call_rx,
-            calls: HashMap::new(),
+            calls: VecDeque::new(),
+            unflushed: false,
             key: None,
+            association_name,
+            association_tx,
+            event_tx,
+            interactive,
         })
     }
 
@@ -142,7 +206,8 @@ impl<
                         keepassxc_error::Api::ServerError(e),
                     )) = &err
                     {
-                        if e.error_code() == model::ErrorCode::DatabaseNotOpened {
+                        if e.error_code() == model::ErrorCode::DatabaseNotOpened && self.interactive
+                        {
                             info!("Database is locked; waiting for unlock signal");
                             return Ok(None);
                         }
@@ -150,6 +215,15 @@ impl<
 
                     Err(err)
                 })?;
+                if self.key.is_some() {
+                    hooks::fire(
+                        hooks::Event::SessionAuthenticated,
+                        &[("KARP_BACKEND", "keepassxc")],
+                    );
+                }
+                let _ = self
+                    .association_tx
+                    .send_replace(self.key.as_ref().map(|key| key.id.clone()));
             }
 
             select! {
@@ -161,6 +235,13 @@ impl<
                     let msg = candidate.ok_or(keepassxc_error::Api::StreamEnded)??;
                     self = self.handle_message(msg)?;
                 }
+                // Flushing separately from queueing requests means we keep
+                // reading responses while the server catches up, so any
+                // number of calls can be in flight at once.
+                result = self.message_tx.flush(), if self.unflushed => {
+                    result?;
+                    self.unflushed = false;
+                }
                 candidate = self.call_rx.next(), if self.key.is_some() => {
                     match candidate {
                         Some(call) => self = self.handle_call(call).await?,
@@ -177,8 +258,14 @@ impl<
             match *signal {
                 api::Signal::DatabaseLocked => {
                     self.key = None;
+                    hooks::fire(hooks::Event::DatabaseLocked, &[]);
+                    // Nobody may be watching, which is fine.
+                    let _ = self.event_tx.send(client::DatabaseEvent::Locked);
+                }
+                api::Signal::DatabaseUnlocked => {
+                    hooks::fire(hooks::Event::DatabaseUnlocked, &[]);
+                    let _ = self.event_tx.send(client::DatabaseEvent::Unlocked);
                 }
-                api::Signal::DatabaseUnlocked => {}
             }
         }
     }
@@ -192,47 +279,35 @@ impl<
         debug!("Received response: {:?}", msg);
 
         let action = msg.action().to_owned();
-        let resp: Result<(serde_json::Value, Call)> =
+        let request_id = msg.request_id().map(str::to_owned);
+        let calls = &mut self.calls;
+        let resp: Result<(serde_json::Value, Pending)> =
             self.message_builder
                 .decode_response_with(msg, |encrypted_value| {
-                    self.calls
-                        .remove(encrypted_value.nonce())
+                    calls
+                        .iter()
+                        .position(|pending| pending.nonce == *encrypted_value.nonce())
+                        .and_then(|index| calls.remove(index))
                         .ok_or(keepassxc_error::Api::InvalidNonce.into())
                 });
         match resp {
-            Ok((value, call)) => {
-                if call.tx.send(Ok(value)).is_err() {
+            Ok((value, pending)) => {
+                if pending.call.tx.send(Ok(value)).is_err() {
                     warn!(
                         "Failed to inform disconnected call receiver {:?} of response",
-                        call.req
+                        pending.call.req
                     );
                 }
             }
             Err(error::Error::Keepassxc(keepassxc_error::Error::Api(
                 keepassxc_error::Api::ServerError(err),
             ))) => {
-                self.calls = self
-                    .calls
-                    .into_iter()
-                    .filter_map(|(nonce, call)| {
-                        if call.action() == action {
-                            let result = call.tx.send(Err(error::Error::Keepassxc(
-                                keepassxc_error::Error::Api(keepassxc_error::Api::ServerError(
-                                    err.clone(),
-                                )),
-                            )));
-                            if result.is_err() {
-                                warn!(
-                                    "Failed to inform disconnected call receiver {:?} of error",
-                                    call.req
-                                );
-                            }
-                            None
-                        } else {
-                            Some((nonce, call))
-                        }
-                    })
-                    .collect();
+                if let Some(pending) = take_failed(&mut self.calls, request_id.as_deref(), &action)
+                {
+                    pending.fail(keepassxc_error::Api::ServerError(err).into());
+                } else {
+                    warn!("Received error for a request we didn't send: {:?}", err);
+                }
             }
             Err(err) => return Err(err),
         }
@@ -246,16 +321,23 @@ impl<
         let (req, nonce) = self.message_builder.encode_request(
             &self.key.clone().into_iter().collect::<Vec<_>>(),
             &call,
-            false,
+            call.trigger_unlock,
         )?;
-        self.message_tx.send(req).await?;
+        let request_id = req.id().to_owned();
+        self.message_tx.feed(req).await?;
+        self.unflushed = true;
 
-        assert!(self.calls.insert(nonce, call).is_none());
+        self.calls.push_back(Pending {
+            nonce,
+            request_id,
+            call,
+        });
 
         Ok(self)
     }
 
     async fn authenticate(&mut self) -> Result<model::Key> {
+        let _progress = progress::start(progress::Phase::Authenticating);
         let get_database_hash_resp: api::GetDatabaseHashResponse = {
             let (req, nonce) = self.message_builder.encode_request(
                 &[],
@@ -274,8 +356,15 @@ impl<
                 &nonce,
             )?
         };
-
-        let mut storage = self.storage.lock().await;
+        api::check_version(get_database_hash_resp.version.as_deref())?;
+
+        // Locked through our own handle, so `self` stays free for the requests
+        // we make while holding it.
+        let shared_storage = Arc::clone(&self.storage);
+        let mut storage = shared_storage.lock().await;
+        // Other processes may be associating with the same session data, so
+        // we take turns with them until we're done.
+        let _process_lock = storage::Lock::acquire(&*storage).await?;
         let mut session = storage.get().await?.unwrap_or_default();
         if let Some(key) = session.keys.get(&get_database_hash_resp.hash) {
             let public_key = key.id_key.expose_secret().as_ref().public_key().to_bytes();
@@ -312,27 +401,37 @@ impl<
             }
         }
 
+        if !self.interactive {
+            return Err(error::Error::InputRequired);
+        }
+
+        // The browser protocol has no way for us to name the association, so
+        // the best we can do is to tell the user what to enter when KeePassXC
+        // asks.
+        if let Some(ref name) = self.association_name {
+            warn!(
+                r#"KeePassXC will ask you to name this connection; enter "{}" so you can tell it apart from others"#,
+                name.escape_default()
+            );
+        }
+
         let id_key = model::key_material::SecretKey::generate();
         let public_key = id_key.expose_secret().as_ref().public_key().to_bytes();
 
-        let associate_resp: api::AssociateResponse = {
-            let (req, nonce) = self.message_builder.encode_request(
-                &[],
-                &api::AssociateRequest {
-                    key: self.message_builder.client_key().to_bytes(),
-                    id_key: public_key,
-                },
-                false,
-            )?;
-            self.message_tx.send(req).await?;
-
-            self.message_builder.decode_response(
-                self.message_rx
-                    .next()
-                    .await
-                    .ok_or(keepassxc_error::Api::StreamEnded)??,
-                &nonce,
-            )?
+        let mut attempts = 1_usize;
+        let associate_resp = loop {
+            match self.associate(public_key).await {
+                Ok(resp) => break resp,
+                Err(error::Error::Keepassxc(keepassxc_error::Error::Api(
+                    keepassxc_error::Api::ServerError(ref err),
+                ))) if err.error_code() == model::ErrorCode::AssociationFailed
+                    && attempts < MAX_ASSOCIATION_ATTEMPTS
+                    && offer_to_retry_association().await? =>
+                {
+                    attempts += 1;
+                }
+                Err(err) => return Err(err),
+            }
         };
 
         _ = session.keys.insert(
@@ -340,6 +439,7 @@ impl<
             session::Key {
                 id: associate_resp.id.clone(),
                 id_key,
+                name: self.association_name.clone(),
             },
         );
         storage.update(&session).await?;
@@ -348,4 +448,87 @@ impl<
             key: public_key,
         })
     }
+
+    /// Ask KeePassXC to associate with the key, which the user has to allow.
+    async fn associate(
+        &mut self,
+        id_key: [u8; model::key_material::KEY_SIZE],
+    ) -> Result<api::AssociateResponse> {
+        let _approval = progress::start(progress::Phase::WaitingForApproval);
+        let (req, nonce) = self.message_builder.encode_request(
+            &[],
+            &api::AssociateRequest {
+                key: self.message_builder.client_key().to_bytes(),
+                id_key,
+            },
+            false,
+        )?;
+        self.message_tx.send(req).await?;
+
+        self.message_builder.decode_response(
+            self.message_rx
+                .next()
+                .await
+                .ok_or(keepassxc_error::Api::StreamEnded)??,
+            &nonce,
+        )
+    }
+}
+
+/// How many times we ask KeePassXC to associate with us when the user turns
+/// it down.
+const MAX_ASSOCIATION_ATTEMPTS: usize = 3;
+
+/// Tell the user how to allow the association they turned down, and ask
+/// whether to try again. We only ask at a terminal.
+async fn offer_to_retry_association() -> Result<bool> {
+    if !io::stdin().is_terminal() {
+        return Ok(false);
+    }
+    let _pause = progress::pause();
+    task::spawn_blocking(|| {
+        eprintln!("{}", locale::text("associate-denied"));
+        eprint!("{}", locale::text("associate-retry"));
+        io::stderr().flush()?;
+        let mut line = String::new();
+        let _ = io::stdin().read_line(&mut line)?;
+        let answer = line.trim().to_lowercase();
+        Ok(answer.is_empty() || answer.starts_with(&locale::text("associate-retry-yes")))
+    })
+    .await?
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::sync::oneshot;
+
+    use super::*;
+
+    fn pending(action: &str, request_id: &str) -> Pending {
+        let (tx, _) = oneshot::channel();
+        Pending {
+            nonce: model::key_material::Nonce::default(),
+            request_id: request_id.to_owned(),
+            call: Call::new(action.to_owned(), serde_json::Value::Null, false, tx),
+        }
+    }
+
+    #[test]
+    fn errors_fail_one_call() {
+        let mut calls: VecDeque<_> = [
+            pending("get-logins", "a"),
+            pending("get-logins", "b"),
+            pending("set-login", "c"),
+        ]
+        .into_iter()
+        .collect();
+        let mut take = |request_id, action| {
+            take_failed(&mut calls, request_id, action).map(|pending| pending.request_id)
+        };
+
+        assert_eq!(take(Some("b"), "get-logins").as_deref(), Some("b"));
+        assert_eq!(take(None, "set-login").as_deref(), Some("c"));
+        assert_eq!(take(Some("unknown"), "get-logins").as_deref(), Some("a"));
+        assert_eq!(take(None, "get-logins"), None);
+    }
 }
@@ -10,7 +10,10 @@ use serde_json::Deserializer;
 use tokio::io::{AsyncRead, AsyncWrite};
 use tokio_util::bytes::{Buf, BufMut as _};
 
-use crate::error::{self, Result};
+use crate::{
+    error::{self, Result},
+    trace,
+};
 
 use super::model;
 
@@ -28,22 +31,61 @@ pub(super) trait Stream:
 
 impl<T: Sink + futures_util::Stream<Item = Result<model::Response>>> Stream for T {}
 
+/// The size of the length that precedes each message with native messaging
+/// framing.
+const LENGTH_PREFIX_SIZE: usize = 4;
+
 /// A codec for encoding and decoding JSON messages that are undelimited and
-/// unframed.
+/// unframed, as on KeePassXC's socket, or framed as browsers frame native
+/// messages, each preceded by its length as a 32-bit integer in native byte
+/// order, as `keepassxc-proxy` expects.
 pub(super) struct JsonCodec<I, O> {
+    length_prefixed: bool,
     _input_marker: PhantomData<I>,
     _output_marker: PhantomData<O>,
 }
 
 impl<I, O> JsonCodec<I, O> {
-    pub(super) fn new() -> Self {
+    pub(super) fn new(length_prefixed: bool) -> Self {
         Self {
+            length_prefixed,
             _input_marker: PhantomData,
             _output_marker: PhantomData,
         }
     }
 }
 
+fn record_received(message: &[u8]) -> Result<()> {
+    debug!(
+        "Received raw message: {:?}",
+        std::str::from_utf8(message).map_err(Into::<error::Conversion>::into)?
+    );
+    trace::record_json(
+        super::TRACE_PROTOCOL,
+        trace::Direction::Received,
+        trace::Layer::Envelope,
+        message,
+    );
+    Ok(())
+}
+
+/// Take the next message framed with its length off the buffer, if all of it
+/// has arrived.
+fn take_length_prefixed(
+    buf: &mut tokio_util::bytes::BytesMut,
+) -> Option<tokio_util::bytes::BytesMut> {
+    let mut prefix = [0; LENGTH_PREFIX_SIZE];
+    prefix.copy_from_slice(buf.get(..LENGTH_PREFIX_SIZE)?);
+    let len = usize::try_from(u32::from_ne_bytes(prefix)).unwrap_or(usize::MAX);
+    let total = LENGTH_PREFIX_SIZE.saturating_add(len);
+    if buf.len() < total {
+        buf.reserve(total - buf.len());
+        return None;
+    }
+    buf.advance(LENGTH_PREFIX_SIZE);
+    Some(buf.split_to(len))
+}
+
 impl<I, O: for<'de> Deserialize<'de>> tokio_util::codec::Decoder for JsonCodec<I, O> {
     type Item = O;
     type Error = error::Error;
@@ -52,6 +94,14 @@ impl<I, O: for<'de> Deserialize<'de>> tokio_util::codec::Decoder for JsonCodec<I
         &mut self,
         buf: &mut tokio_util::bytes::BytesMut,
     ) -> Result<Option<Self::Item>, Self::Error> {
+        if self.length_prefixed {
+            let Some(message) = take_length_prefixed(buf) else {
+                return Ok(None);
+            };
+            record_received(&message)?;
+            return Ok(Some(serde_json::from_slice(&message)?));
+        }
+
         let mut iter = Deserializer::from_slice(buf).into_iter();
         let value: Result<Option<Self::Item>> = iter.next().unwrap_or(Ok(None)).or_else(|err| {
             if err.is_eof() {
@@ -66,11 +116,7 @@ impl<I, O: for<'de> Deserialize<'de>> tokio_util::codec::Decoder for JsonCodec<I
             }
         });
         if let &Ok(Some(_)) = &value {
-            debug!(
-                "Received raw message: {:?}",
-                std::str::from_utf8(&buf[..iter.byte_offset()])
-                    .map_err(Into::<error::Conversion>::into)?
-            );
+            record_received(&buf[..iter.byte_offset()])?;
         }
         buf.advance(iter.byte_offset());
         value
@@ -86,7 +132,22 @@ impl<I: Serialize + Debug, O> tokio_util::codec::Encoder<I> for JsonCodec<I, O>
         buf: &mut tokio_util::bytes::BytesMut,
     ) -> Result<(), Self::Error> {
         debug!("Sending message: {:?}", item);
-        Ok(serde_json::to_writer(&mut buf.writer(), &item)?)
+        trace::record(
+            super::TRACE_PROTOCOL,
+            trace::Direction::Sent,
+            trace::Layer::Envelope,
+            &item,
+        );
+        if !self.length_prefixed {
+            return Ok(serde_json::to_writer(&mut buf.writer(), &item)?);
+        }
+
+        let message = serde_json::to_vec(&item)?;
+        let len = u32::try_from(message.len())
+            .map_err(|_| super::error::Api::MessageTooLarge(message.len()))?;
+        buf.put_u32_ne(len);
+        buf.put_slice(&message);
+        Ok(())
     }
 }
 
@@ -104,8 +165,47 @@ impl<T> AsMut<tokio_util::codec::Framed<T, JsonCodec<model::Request, model::Resp
     }
 }
 
+impl<T: AsyncRead + AsyncWrite> JsonMessageStream<T> {
+    /// Exchange messages framed with their lengths, as native messaging does.
+    pub(super) fn length_prefixed(stream: T) -> Self {
+        Self(tokio_util::codec::Framed::new(stream, JsonCodec::new(true)))
+    }
+}
+
 impl<T: AsyncRead + AsyncWrite> From<T> for JsonMessageStream<T> {
     fn from(stream: T) -> Self {
-        Self(tokio_util::codec::Framed::new(stream, JsonCodec::new()))
+        Self(tokio_util::codec::Framed::new(
+            stream,
+            JsonCodec::new(false),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+    use tokio_util::{
+        bytes::BytesMut,
+        codec::{Decoder as _, Encoder as _},
+    };
+
+    use super::*;
+
+    #[test]
+    fn length_prefixed_messages_wait_for_the_whole_frame() -> Result<()> {
+        let mut codec = JsonCodec::<Value, Value>::new(true);
+        let mut buf = BytesMut::new();
+        codec.encode(json!({"action": "test-associate"}), &mut buf)?;
+        assert_eq!(buf.get(..4), Some(&27_u32.to_ne_bytes()[..]));
+
+        let mut partial = buf.split_to(10);
+        assert_eq!(codec.decode(&mut partial)?, None);
+        partial.unsplit(buf);
+        assert_eq!(
+            codec.decode(&mut partial)?,
+            Some(json!({"action": "test-associate"}))
+        );
+        assert!(partial.is_empty());
+        Ok(())
     }
 }
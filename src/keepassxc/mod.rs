@@ -7,15 +7,36 @@ pub(crate) mod error;
 mod manager;
 mod message;
 mod model;
+mod process;
 pub(crate) mod session;
 
-use std::{path::PathBuf, sync::Arc};
+use std::{
+    env,
+    ffi::OsString,
+    io, iter,
+    num::NonZeroUsize,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
 
-use api::Executor as _;
+use api::{Executor as _, HasConstAction as _};
 use async_trait::async_trait;
-use futures_util::{future::BoxFuture, lock::Mutex};
-use tokio::{net::UnixStream, sync::mpsc};
+use futures_util::{
+    future::BoxFuture,
+    lock::Mutex,
+    stream::{self, BoxStream},
+    StreamExt as _,
+};
+use log::warn;
+use secrecy::{ExposeSecret as _, SecretString, SecretVec};
+use tokio::{
+    io::{AsyncRead, AsyncWrite},
+    net::{TcpStream, UnixStream},
+    sync::{broadcast, mpsc, watch},
+};
 use tokio_stream::wrappers::ReceiverStream;
+use unicode_normalization::UnicodeNormalization as _;
+use uuid::Uuid;
 
 use crate::{
     client,
@@ -23,14 +44,325 @@ use crate::{
     storage,
 };
 
+/// The name we give this protocol in transcripts.
+const TRACE_PROTOCOL: &str = "keepassxc";
+
+/// The name of the socket KeePassXC listens on for browser integration.
+const SOCKET_NAME: &str = "org.keepassxc.KeePassXC.BrowserServer";
+
+/// Where KeePassXC may have put its socket, in the order `keepassxc-proxy`
+/// looks: the application directory KeePassXC 2.7 and its Flatpak use, the
+/// runtime directory earlier versions used, the common directory of its Snap,
+/// and the temporary directory where there's no runtime directory.
+fn socket_candidates(runtime: Option<&Path>, home: Option<&Path>, temp: &Path) -> Vec<PathBuf> {
+    let mut candidates = vec![];
+    if let Some(dir) = runtime {
+        candidates.push(dir.join("app/org.keepassxc.KeePassXC").join(SOCKET_NAME));
+        candidates.push(dir.join(SOCKET_NAME));
+    }
+    if let Some(dir) = home {
+        candidates.push(dir.join("snap/keepassxc/common").join(SOCKET_NAME));
+    }
+    candidates.push(temp.join(SOCKET_NAME));
+    candidates
+}
+
+/// Find the socket of a running KeePassXC, honoring `XDG_RUNTIME_DIR` and
+/// `TMPDIR` as KeePassXC does.
+pub(crate) fn find_socket() -> Result<PathBuf> {
+    let var = |name| {
+        env::var_os(name)
+            .filter(|value| !value.is_empty())
+            .map(PathBuf::from)
+    };
+    let candidates = socket_candidates(
+        var("XDG_RUNTIME_DIR").as_deref(),
+        var("HOME").as_deref(),
+        &env::temp_dir(),
+    );
+    match candidates.iter().find(|path| path.exists()) {
+        Some(path) => Ok(path.clone()),
+        None => Err(error::Error::NoSocket(candidates).into()),
+    }
+}
+
+/// Explain a failure to connect to the socket, which usually means KeePassXC
+/// isn't there to listen on it.
+fn socket_error(path: &Path, e: io::Error) -> base_error::Error {
+    if matches!(
+        e.kind(),
+        io::ErrorKind::NotFound | io::ErrorKind::ConnectionRefused
+    ) {
+        error::Error::NotRunning(path.to_owned()).into()
+    } else {
+        e.into()
+    }
+}
+
+/// A group we found by path among the database groups.
+struct Location {
+    group: api::Group,
+    /// The names of the group and its parents, as they're stored in the
+    /// database.
+    path: Vec<String>,
+    /// How many groups in the database have the same name as this one,
+    /// including it.
+    namesakes: usize,
+}
+
+/// Count the groups in the tree that have the given name.
+fn count_named(group: &api::Group, name: &str) -> usize {
+    usize::from(client::same_name(&group.name, name))
+        + group
+            .children
+            .iter()
+            .map(|child| count_named(child, name))
+            .sum::<usize>()
+}
+
 struct Client {
     tx: mpsc::Sender<api::Call>,
+    association: watch::Receiver<Option<String>>,
+    events: broadcast::Receiver<client::DatabaseEvent>,
+    database: Option<String>,
+    trigger_unlock: bool,
+    title_match: client::TitleMatch,
 }
 
 impl Client {
-    pub(crate) fn new(tx: mpsc::Sender<api::Call>) -> Self {
-        Self { tx }
+    pub(crate) fn new(
+        tx: mpsc::Sender<api::Call>,
+        association: watch::Receiver<Option<String>>,
+        events: broadcast::Receiver<client::DatabaseEvent>,
+        database: Option<String>,
+        trigger_unlock: bool,
+        title_match: client::TitleMatch,
+    ) -> Self {
+        Self {
+            tx,
+            association,
+            events,
+            database,
+            trigger_unlock,
+            title_match,
+        }
+    }
+
+    /// Make sure the active database is the one selected by hash, if one was
+    /// selected. KeePassXC only ever serves requests from the active
+    /// database.
+    async fn check_database(&self) -> Result<()> {
+        let Some(ref hash) = self.database else {
+            return Ok(());
+        };
+
+        let resp = api::GetDatabaseHashRequest {
+            action: api::GetDatabaseHashRequest::ACTION.to_owned(),
+        }
+        .execute(self.tx.clone(), self.trigger_unlock)
+        .await?;
+        if resp.hash == *hash {
+            Ok(())
+        } else {
+            Err(base_error::Error::DatabaseNotActive(hash.clone()))
+        }
+    }
+
+    /// Find a group by the names of it and its parents.
+    async fn get_group(
+        &self,
+        group_names: &mut (dyn Iterator<Item = &str> + Send + Sync),
+    ) -> Result<Location> {
+        let resp = api::GetDatabaseGroupsRequest {}
+            .execute(self.tx.clone(), self.trigger_unlock)
+            .await?;
+
+        let root = resp
+            .groups
+            .groups
+            .into_iter()
+            .next()
+            .ok_or(error::Api::ServerError(model::Error::new(
+                model::ErrorCode::NoGroupsFound,
+            )))?;
+        let mut path = vec![];
+        let mut group = &root;
+        for group_name in group_names {
+            group = group
+                .children
+                .iter()
+                .find(|g| client::same_name(&g.name, group_name))
+                .ok_or_else(|| base_error::Error::GroupNotFound {
+                    parent: client::Group {
+                        path: path.join("/"),
+                    },
+                    name: group_name.to_owned(),
+                })?;
+            path.push(group.name.clone());
+        }
+
+        Ok(Location {
+            namesakes: count_named(&root, &group.name),
+            group: group.clone(),
+            path,
+        })
+    }
+
+    async fn set_login(&self, req: api::SetLoginRequest) -> Result<()> {
+        // Writes are explicit requests to change the database, so we may as
+        // well ask the user to unlock it.
+        if req.execute(self.tx.clone(), true).await?.success {
+            Ok(())
+        } else {
+            Err(error::Api::Unsuccessful.into())
+        }
+    }
+}
+
+/// The code of an error KeePassXC answered a request with, if that's what
+/// went wrong.
+fn server_error_code(err: &base_error::Error) -> Option<model::ErrorCode> {
+    if let base_error::Error::Keepassxc(error::Error::Api(error::Api::ServerError(ref e))) = *err {
+        Some(e.error_code())
+    } else {
+        None
+    }
+}
+
+/// Pick the entry we asked for by path out of the logins KeePassXC found,
+/// which may include entries of other groups whose paths look the same to
+/// it. When we couldn't resolve the group, we take what we're given.
+fn select_entry(
+    entries: Vec<api::Entry>,
+    resolved: Option<&Location>,
+    title: &str,
+    title_match: client::TitleMatch,
+) -> Option<api::Entry> {
+    let Some(location) = resolved else {
+        return entries.into_iter().next();
+    };
+    title_match.select(
+        entries.into_iter().filter(|entry| match entry.group_uuid {
+            Some(ref uuid) => uuid.eq_ignore_ascii_case(&location.group.uuid),
+            // The name of a group only tells it apart from the others when
+            // none has the same name. When one does, KeePassXC has compared
+            // the whole path for us, which is all we have to go on.
+            None => location.namesakes > 1 || client::same_name(&entry.group, &location.group.name),
+        }),
+        |entry| &entry.name,
+        title,
+    )
+}
+
+/// The fields of an entry other than the username and password we send with
+/// `set-login`.
+fn unsent_fields(entry: &client::Entry) -> Vec<&client::FormField> {
+    let login = |type_| {
+        entry
+            .form_fields
+            .iter()
+            .position(|field| field.type_ == type_)
+    };
+    let sent = [
+        login(client::FormFieldType::Username),
+        login(client::FormFieldType::Password),
+    ];
+    entry
+        .form_fields
+        .iter()
+        .enumerate()
+        .filter(|&(i, _)| !sent.contains(&Some(i)))
+        .map(|(_, field)| field)
+        .collect()
+}
+
+/// Build the request that saves an entry, refusing if it changes anything
+/// `set-login` can't save: the title, any URL after the first, or fields other
+/// than the username and password. New entries are compared with an empty
+/// one, except for the title, which KeePassXC picks itself.
+fn set_login_request(
+    entry: &client::Entry,
+    existing: Option<&client::Entry>,
+) -> Result<api::SetLoginRequest, error::Error> {
+    let field_value = |type_| {
+        entry
+            .form_fields
+            .iter()
+            .find(|field| field.type_ == type_)
+            .map(|field| field.value.clone())
+    };
+
+    let mut unsupported = vec![];
+    match existing {
+        Some(stored) if stored.title != entry.title => {
+            unsupported.push("its title".to_owned());
+        }
+        Some(_) => {}
+        // KeePassXC derives the title of new entries from the URL, falling
+        // back to the URL itself when it has no host.
+        None => {
+            let derived = entry.urls.first().map(|url| {
+                url::Url::parse(url)
+                    .ok()
+                    .and_then(|parsed| parsed.host_str().map(str::to_owned))
+                    .unwrap_or_else(|| url.clone())
+            });
+            if let Some(title) = derived.filter(|title| *title != entry.title) {
+                warn!(
+                    "KeePassXC will name the new entry {:?} after its URL, not {:?}",
+                    title, entry.title
+                );
+            }
+        }
+    }
+    if entry.urls.len() > 1 {
+        unsupported.push("URLs after the first".to_owned());
+    }
+    // KeePassXC gives us the current one-time password rather than its seed,
+    // so its value changes on its own; one only counts as changed when it's
+    // added or removed.
+    let before = existing.map(unsent_fields).unwrap_or_default();
+    for field in unsent_fields(entry) {
+        let unchanged = before.iter().any(|old| {
+            old.type_ == field.type_
+                && old.display_name == field.display_name
+                && (field.type_ == client::FormFieldType::Totp
+                    || old.value.expose_secret() == field.value.expose_secret())
+        });
+        if !unchanged {
+            unsupported.push(field.display_name.clone());
+        }
+    }
+    for old in before {
+        let kept = entry
+            .form_fields
+            .iter()
+            .any(|field| field.type_ == old.type_ && field.display_name == old.display_name);
+        if !kept {
+            unsupported.push(old.display_name.clone());
+        }
     }
+    if !unsupported.is_empty() {
+        return Err(error::Error::Unsupported(unsupported));
+    }
+
+    // The title stands in for the URL when there's nothing better.
+    Ok(api::SetLoginRequest {
+        url: entry
+            .urls
+            .first()
+            .cloned()
+            .unwrap_or_else(|| entry.title.clone()),
+        submit_url: None,
+        login: field_value(client::FormFieldType::Username)
+            .map(|value| value.expose_secret().clone())
+            .unwrap_or_default(),
+        password: field_value(client::FormFieldType::Password)
+            .unwrap_or_else(|| SecretString::new(String::new())),
+        group: None,
+        group_uuid: None,
+        uuid: None,
+    })
 }
 
 #[async_trait]
@@ -40,32 +372,95 @@ impl client::Client for Client {
         group_names: &mut (dyn Iterator<Item = &str> + Send + Sync),
         entry_title: &str,
     ) -> Result<client::Entry> {
-        // This "looks" like a URL, but it isn't. It shouldn't be
-        // percent-encoded or anything.
-        let mut path = group_names.collect::<Vec<_>>();
-        path.push(entry_title);
+        self.check_database().await?;
+
+        // Resolve the group ourselves first, so we know it exists and can tell
+        // its entries apart from those of groups with similar names. Without
+        // access to the groups, we trust KeePassXC to find the path.
+        let path = group_names.collect::<Vec<_>>();
+        let location = match self.get_group(&mut path.iter().copied()).await {
+            Ok(found) => Some(found),
+            Err(err)
+                if server_error_code(&err).is_some_and(|code| {
+                    matches!(
+                        code,
+                        model::ErrorCode::NoGroupsFound
+                            | model::ErrorCode::AccessToAllEntriesDenied
+                    )
+                }) =>
+            {
+                None
+            }
+            Err(err) => return Err(err),
+        };
+        let not_found = || base_error::Error::EntryNotFound {
+            parent: client::Group {
+                path: path.join("/"),
+            },
+            name: entry_title.to_owned(),
+        };
 
+        // This "looks" like a URL, but it isn't. It shouldn't be
+        // percent-encoded or anything. KeePassXC compares it as-is, so we
+        // give it the names as they're stored where we know them.
         let mut url = "keepassxc://by-path/".to_owned();
-        url.push_str(&path.join("/"));
+        match location {
+            Some(ref found) => {
+                for group_name in &found.path {
+                    url.push_str(group_name);
+                    url.push('/');
+                }
+            }
+            None => {
+                for group_name in &path {
+                    url.extend(group_name.nfc());
+                    url.push('/');
+                }
+            }
+        }
+        url.extend(entry_title.nfc());
 
         let resp = api::GetLoginsRequest {
             url,
             submit_url: None,
             http_auth: None,
         }
-        .execute(self.tx.clone())
+        .execute(self.tx.clone(), self.trigger_unlock)
+        .await
+        .map_err(|err| {
+            if server_error_code(&err) == Some(model::ErrorCode::NoLoginsFound) {
+                not_found()
+            } else {
+                err
+            }
+        })?;
+
+        Ok(select_entry(
+            resp.entries,
+            location.as_ref(),
+            entry_title,
+            self.title_match,
+        )
+        .ok_or_else(not_found)?
+        .into())
+    }
+
+    async fn get_entry_by_id(&self, id: Uuid) -> Result<client::Entry> {
+        self.check_database().await?;
+
+        let resp = api::GetLoginsRequest {
+            url: format!("keepassxc://by-uuid/{}", id.simple()),
+            submit_url: None,
+            http_auth: None,
+        }
+        .execute(self.tx.clone(), self.trigger_unlock)
         .await
         .map_err(|err| {
             if let base_error::Error::Keepassxc(error::Error::Api(error::Api::ServerError(e))) =
                 &err
             {
                 if e.error_code() == model::ErrorCode::NoLoginsFound {
-                    return base_error::Error::EntryNotFound {
-                        parent: client::Group {
-                            path: path[..path.len() - 1].join("/"),
-                        },
-                        name: entry_title.to_owned(),
-                    };
+                    return base_error::Error::EntryIdNotFound { id };
                 }
             }
 
@@ -76,22 +471,23 @@ impl client::Client for Client {
             .entries
             .into_iter()
             .next()
-            .ok_or(base_error::Error::EntryNotFound {
-                parent: client::Group {
-                    path: path[..path.len() - 1].join("/"),
-                },
-                name: entry_title.to_owned(),
-            })?
+            .ok_or(base_error::Error::EntryIdNotFound { id })?
             .into())
     }
 
-    async fn find_entries(&self, query: &str) -> Result<Vec<client::Entry>> {
+    async fn find_entries(
+        &self,
+        query: &str,
+        limit: Option<NonZeroUsize>,
+    ) -> Result<Vec<client::Entry>> {
+        self.check_database().await?;
+
         let resp = api::GetLoginsRequest {
             url: query.to_owned(),
             submit_url: None,
             http_auth: None,
         }
-        .execute(self.tx.clone())
+        .execute(self.tx.clone(), self.trigger_unlock)
         .await
         .or_else(|err| {
             if let base_error::Error::Keepassxc(error::Error::Api(error::Api::ServerError(e))) =
@@ -108,28 +504,224 @@ impl client::Client for Client {
             Err(err)
         })?;
 
-        Ok(resp.entries.into_iter().map(Into::into).collect())
+        // KeePassXC has no way to ask for fewer logins, so the most we can do
+        // is not keep the rest.
+        Ok(resp
+            .entries
+            .into_iter()
+            .take(limit.map_or(usize::MAX, NonZeroUsize::get))
+            .map(Into::into)
+            .collect())
+    }
+
+    async fn create_entry(
+        &self,
+        group_names: &mut (dyn Iterator<Item = &str> + Send + Sync),
+        entry: &client::Entry,
+    ) -> Result<()> {
+        self.check_database().await?;
+
+        let request = set_login_request(entry, None)?;
+        let Location { group, .. } = self.get_group(group_names).await?;
+
+        self.set_login(api::SetLoginRequest {
+            group: Some(group.name),
+            group_uuid: Some(group.uuid),
+            ..request
+        })
+        .await
+    }
+
+    async fn update_entry(&self, id: Uuid, entry: &client::Entry) -> Result<()> {
+        self.check_database().await?;
+
+        let existing = self.get_entry_by_id(id).await?;
+        self.set_login(api::SetLoginRequest {
+            uuid: Some(id.simple().to_string()),
+            ..set_login_request(entry, Some(&existing))?
+        })
+        .await
+    }
+
+    async fn list_group(
+        &self,
+        group_names: &mut (dyn Iterator<Item = &str> + Send + Sync),
+    ) -> Result<client::GroupListing> {
+        self.check_database().await?;
+
+        // The browser protocol has no way to enumerate the entries in a group.
+        let Location { group, .. } = self.get_group(group_names).await?;
+
+        Ok(client::GroupListing {
+            groups: group.children.into_iter().map(|g| g.name).collect(),
+            entries: vec![],
+        })
+    }
+
+    async fn databases(&self) -> Result<Vec<client::Database>> {
+        // KeePassXC only serves the active database, and requests wait until
+        // it is unlocked.
+        let hash = api::GetDatabaseHashRequest {
+            action: api::GetDatabaseHashRequest::ACTION.to_owned(),
+        }
+        .execute(self.tx.clone(), self.trigger_unlock)
+        .await?
+        .hash;
+        let name = self
+            .get_group(&mut iter::empty())
+            .await
+            .map(|root| root.group.name)
+            .unwrap_or_default();
+
+        Ok(vec![client::Database {
+            name,
+            selector: hash,
+            active: true,
+            association: self.association.borrow().clone(),
+        }])
+    }
+
+    async fn features(&self) -> Result<Vec<String>> {
+        // The browser protocol doesn't negotiate features.
+        Ok(vec![])
+    }
+
+    fn group_paths(&self) -> bool {
+        // Logins only come with the name of their group.
+        false
+    }
+
+    async fn generate_password(&self) -> Result<SecretString> {
+        // KeePassXC shows its generator and waits for the user to pick a
+        // password, so there's no point in asking for one while it's locked.
+        let resp = api::GeneratePasswordRequest {}
+            .execute(self.tx.clone(), true)
+            .await?;
+        resp.password
+            .or_else(|| resp.entries.into_iter().next().map(|entry| entry.password))
+            .ok_or_else(|| error::Api::Unsuccessful.into())
+    }
+
+    async fn watch(&self) -> Result<BoxStream<'static, client::DatabaseEvent>> {
+        // The stream ends along with the connection, when the manager drops
+        // the sender.
+        Ok(
+            stream::unfold(self.events.resubscribe(), |mut rx| async move {
+                loop {
+                    match rx.recv().await {
+                        Ok(event) => return Some((event, rx)),
+                        Err(broadcast::error::RecvError::Lagged(missed)) => {
+                            warn!("Missed {missed} events from KeePassXC");
+                        }
+                        Err(broadcast::error::RecvError::Closed) => return None,
+                    }
+                }
+            })
+            .boxed(),
+        )
     }
 }
 
+/// Settings that change how we talk to KeePassXC.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Options {
+    /// The hash of the database that must be active for requests to succeed.
+    pub(crate) database: Option<String>,
+    /// The name to suggest the user give the association when KeePassXC asks
+    /// for one.
+    pub(crate) association_name: Option<String>,
+    /// Whether requests that only read from the database should ask the user
+    /// to unlock it when it's locked. Requests that write always do.
+    pub(crate) trigger_unlock: bool,
+    /// Whether we may wait for the user to approve a new association or to
+    /// unlock the database, instead of failing.
+    pub(crate) interactive: bool,
+    /// How the title of an entry we're asked for is compared with the titles
+    /// of the logins KeePassXC finds for its path.
+    pub(crate) title_match: client::TitleMatch,
+}
+
+/// Where KeePassXC, or something that relays its messages, listens.
+#[derive(Clone, Debug)]
+pub(crate) enum Endpoint {
+    /// The Unix domain socket KeePassXC itself listens on.
+    Socket(PathBuf),
+    /// The socket of whichever KeePassXC is running when we connect, looked
+    /// for where KeePassXC puts it.
+    Search,
+    /// A TCP address, as `host:port`, such as one `socat` forwards to the
+    /// socket.
+    Tcp(String),
+    /// A program we start and talk to over its standard input and output,
+    /// such as `keepassxc-proxy`, and whether it frames messages with their
+    /// lengths like native messaging does.
+    Program {
+        path: PathBuf,
+        args: Vec<OsString>,
+        length_prefixed: bool,
+    },
+}
+
+/// A connection to KeePassXC over any transport.
+trait Stream: AsyncRead + AsyncWrite + Send + Sync + Unpin {}
+
+impl<T: AsyncRead + AsyncWrite + Send + Sync + Unpin> Stream for T {}
+
 pub(crate) struct Protocol<Storage: storage::Storage<session::Data>> {
     storage: Arc<Mutex<Storage>>,
-    socket_path: PathBuf,
+    endpoint: Endpoint,
+    options: Options,
 }
 
 impl<Storage: storage::Storage<session::Data>> Protocol<Storage> {
-    pub(crate) fn new(storage: Arc<Mutex<Storage>>, socket_path: PathBuf) -> Self {
+    pub(crate) fn new(storage: Arc<Mutex<Storage>>, endpoint: Endpoint, options: Options) -> Self {
         Self {
             storage,
-            socket_path,
+            endpoint,
+            options,
         }
     }
 
-    async fn new_stream(&self) -> Result<message::JsonMessageStream<UnixStream>> {
-        UnixStream::connect(&self.socket_path)
-            .await
-            .map_err(Into::into)
-            .map(Into::into)
+    async fn new_stream(&self) -> Result<message::JsonMessageStream<Box<dyn Stream>>> {
+        let (stream, length_prefixed): (Box<dyn Stream>, _) = match self.endpoint {
+            Endpoint::Socket(ref path) => (
+                Box::new(
+                    UnixStream::connect(path)
+                        .await
+                        .map_err(|e| socket_error(path, e))?,
+                ),
+                false,
+            ),
+            Endpoint::Search => {
+                let path = find_socket()?;
+                (
+                    Box::new(
+                        UnixStream::connect(&path)
+                            .await
+                            .map_err(|e| socket_error(&path, e))?,
+                    ),
+                    false,
+                )
+            }
+            Endpoint::Tcp(ref address) => {
+                let stream = TcpStream::connect(address.as_str()).await?;
+                stream.set_nodelay(true)?;
+                (Box::new(stream), false)
+            }
+            Endpoint::Program {
+                ref path,
+                ref args,
+                length_prefixed,
+            } => (
+                Box::new(process::ChildStream::spawn(path, args)?),
+                length_prefixed,
+            ),
+        };
+        Ok(if length_prefixed {
+            message::JsonMessageStream::length_prefixed(stream)
+        } else {
+            stream.into()
+        })
     }
 }
 
@@ -144,16 +736,324 @@ impl<'channel, Storage: storage::Storage<session::Data> + 'channel> client::Prot
         Box<dyn client::Client + Send + Sync + 'channel>,
     )> {
         let storage = Arc::clone(&self.storage);
+        let association_name = self.options.association_name.clone();
+        let interactive = self.options.interactive;
         let (tx, rx) = mpsc::channel(16);
+        let (association_tx, association_rx) = watch::channel(None);
+        let (event_tx, event_rx) = broadcast::channel(16);
         let mut message_stream = self.new_stream().await?;
 
         let worker = async move {
-            manager::Manager::new(storage, message_stream.as_mut(), ReceiverStream::new(rx))
-                .await?
-                .run()
-                .await
+            manager::Manager::new(
+                storage,
+                message_stream.as_mut(),
+                ReceiverStream::new(rx),
+                association_name,
+                association_tx,
+                event_tx,
+                interactive,
+            )
+            .await?
+            .run()
+            .await
+        };
+
+        Ok((
+            Box::pin(worker),
+            Box::new(Client::new(
+                tx,
+                association_rx,
+                event_rx,
+                self.options.database.clone(),
+                self.options.trigger_unlock,
+                self.options.title_match,
+            )),
+        ))
+    }
+
+    async fn session_secret(&self) -> Result<Option<SecretVec<u8>>> {
+        let data = self.storage.lock().await.get().await?.unwrap_or_default();
+        let mut keys: Vec<_> = data.keys.iter().collect();
+        if keys.is_empty() {
+            return Ok(None);
+        }
+
+        // Every association contributes, so the secret changes along with any
+        // of them.
+        keys.sort_by_key(|&(hash, _)| hash);
+        Ok(Some(SecretVec::new(
+            keys.into_iter()
+                .flat_map(|(_, key)| key.id_key.expose_secret().as_ref().to_bytes())
+                .collect(),
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn entries_are_matched_by_group_and_title() -> serde_json::Result<()> {
+        let entry = |name: &str, group: &str| {
+            serde_json::from_value::<api::Entry>(serde_json::json!({
+                "login": "alice",
+                "name": name,
+                "password": "hunter2",
+                "uuid": "04040404040404040404040404040404",
+                "group": group,
+            }))
+        };
+        let group = Location {
+            group: serde_json::from_value(serde_json::json!({
+                "name": "Web",
+                "uuid": "05050505050505050505050505050505",
+            }))?,
+            path: vec!["Web".to_owned()],
+            namesakes: 1,
+        };
+        let entries = vec![
+            entry("Example", "Web (old)")?,
+            entry("Example 2", "Web")?,
+            entry("Example", "Web")?,
+        ];
+
+        let found = select_entry(
+            entries.clone(),
+            Some(&group),
+            "Example",
+            client::TitleMatch::Exact,
+        );
+        assert_eq!(
+            found.map(|e| (e.name, e.group)),
+            Some(("Example".to_owned(), "Web".to_owned()))
+        );
+        assert!(select_entry(
+            entries.clone(),
+            Some(&group),
+            "Exam",
+            client::TitleMatch::Exact
+        )
+        .is_none());
+        assert_eq!(
+            select_entry(
+                entries.clone(),
+                Some(&group),
+                "Exam",
+                client::TitleMatch::Prefix
+            )
+            .map(|e| e.name),
+            Some("Example 2".to_owned())
+        );
+        assert_eq!(
+            select_entry(entries, None, "Example", client::TitleMatch::Exact).map(|e| e.group),
+            Some("Web (old)".to_owned())
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn groups_with_the_same_name_are_told_apart() -> serde_json::Result<()> {
+        let root: api::Group = serde_json::from_value(serde_json::json!({
+            "name": "Root",
+            "uuid": "00000000000000000000000000000000",
+            "children": [
+                {
+                    "name": "Work",
+                    "uuid": "01010101010101010101010101010101",
+                    "children": [{ "name": "Email", "uuid": "02020202020202020202020202020202" }],
+                },
+                {
+                    "name": "Home",
+                    "uuid": "03030303030303030303030303030303",
+                    "children": [{ "name": "Email", "uuid": "04040404040404040404040404040404" }],
+                },
+            ],
+        }))?;
+        assert_eq!(count_named(&root, "Email"), 2);
+        let work = Location {
+            group: root.children[0].children[0].clone(),
+            path: vec!["Work".to_owned(), "Email".to_owned()],
+            namesakes: count_named(&root, "Email"),
+        };
+
+        let entry = |login: &str, group_uuid: &str| {
+            serde_json::from_value::<api::Entry>(serde_json::json!({
+                "login": login,
+                "name": "Example",
+                "password": "hunter2",
+                "uuid": "05050505050505050505050505050505",
+                "group": "Email",
+                "groupUuid": group_uuid,
+            }))
         };
+        let entries = vec![
+            entry("home", "04040404040404040404040404040404")?,
+            entry("work", "02020202020202020202020202020202")?,
+        ];
+        assert_eq!(
+            select_entry(entries, Some(&work), "Example", client::TitleMatch::Exact)
+                .map(|e| e.login),
+            Some("work".to_owned())
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn new_entries_only_keep_login_fields() {
+        let field = |type_, display_name: &str, value: &str| client::FormField {
+            type_,
+            display_name: display_name.to_owned(),
+            value: SecretString::new(value.to_owned()),
+            metadata: None,
+        };
+        let mut entry = client::Entry {
+            id: String::new(),
+            parent: None,
+            title: "example.com".to_owned(),
+            urls: vec!["https://example.com/login".to_owned()],
+            form_fields: vec![
+                field(client::FormFieldType::Username, "Username", "alice"),
+                field(client::FormFieldType::Password, "Password", "hunter2"),
+            ],
+            database: None,
+            source: None,
+        };
+        assert!(set_login_request(&entry, None).is_ok());
+
+        entry.urls.push("https://example.org".to_owned());
+        entry
+            .form_fields
+            .push(field(client::FormFieldType::Text, "PIN", "1234"));
+        match set_login_request(&entry, None) {
+            Err(error::Error::Unsupported(unsaved)) => assert_eq!(
+                unsaved,
+                vec!["URLs after the first".to_owned(), "PIN".to_owned()]
+            ),
+            other => panic!("unexpected result: {other:?}"),
+        }
+    }
+
+    #[test]
+    fn edits_are_sent_or_refused() -> std::result::Result<(), Box<dyn std::error::Error>> {
+        let stored: client::Entry = serde_json::from_value::<api::Entry>(serde_json::json!({
+            "login": "alice",
+            "name": "Example",
+            "password": "hunter2",
+            "uuid": "04040404040404040404040404040404",
+            "group": "Web",
+            "totp": "123456",
+            "stringFields": [{ "KPH: PIN": "1234" }],
+        }))?
+        .into();
+        let unsaved = |edited: &client::Entry| match set_login_request(edited, Some(&stored)) {
+            Err(error::Error::Unsupported(unsaved)) => Some(unsaved),
+            _ => None,
+        };
+        let edit = |change: &dyn Fn(&mut client::Entry)| {
+            let mut edited = stored.clone();
+            change(&mut edited);
+            edited
+        };
+        let set = |type_: client::FormFieldType, value: &'static str| {
+            move |entry: &mut client::Entry| {
+                if let Some(field) = entry.form_fields.iter_mut().find(|f| f.type_ == type_) {
+                    field.value = SecretString::new(value.to_owned());
+                }
+            }
+        };
+
+        // What set-login can carry is sent.
+        let edited = edit(&|entry| {
+            set(client::FormFieldType::Username, "bob")(entry);
+            set(client::FormFieldType::Password, "correct horse")(entry);
+            entry.urls = vec!["https://example.com".to_owned()];
+        });
+        let request = set_login_request(&edited, Some(&stored))?;
+        assert_eq!(request.login, "bob");
+        assert_eq!(request.password.expose_secret(), "correct horse");
+        assert_eq!(request.url, "https://example.com");
+
+        // A new code for the same one-time password is just time passing.
+        assert!(unsaved(&edit(&set(client::FormFieldType::Totp, "654321"))).is_none());
+
+        // Everything else is refused.
+        for (edited, field) in [
+            (
+                edit(&|entry| entry.title = "Renamed".to_owned()),
+                "its title",
+            ),
+            (
+                edit(&|entry| {
+                    entry.urls = vec![
+                        "https://a.example".to_owned(),
+                        "https://b.example".to_owned(),
+                    ];
+                }),
+                "URLs after the first",
+            ),
+            (edit(&set(client::FormFieldType::Text, "4321")), "PIN"),
+            (
+                edit(&|entry| {
+                    entry
+                        .form_fields
+                        .retain(|f| f.type_ != client::FormFieldType::Totp);
+                }),
+                "KeePass TOTP",
+            ),
+            (
+                edit(&|entry| {
+                    entry.form_fields.push(client::FormField {
+                        type_: client::FormFieldType::Totp,
+                        display_name: "Backup TOTP".to_owned(),
+                        value: SecretString::new("otpauth://totp/?secret=JBSWY3DP".to_owned()),
+                        metadata: None,
+                    });
+                }),
+                "Backup TOTP",
+            ),
+        ] {
+            assert_eq!(unsaved(&edited), Some(vec![field.to_owned()]), "{field}");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn missing_sockets_mean_keepassxc_is_not_running() {
+        let path = Path::new("/run/user/1000/org.keepassxc.KeePassXC.BrowserServer");
+        for kind in [io::ErrorKind::NotFound, io::ErrorKind::ConnectionRefused] {
+            assert_eq!(
+                socket_error(path, io::Error::from(kind)).kind(),
+                "keepassxc_not_running"
+            );
+        }
+        assert_eq!(
+            socket_error(path, io::Error::from(io::ErrorKind::PermissionDenied)).kind(),
+            "io"
+        );
+    }
 
-        Ok((Box::pin(worker), Box::new(Client::new(tx))))
+    #[test]
+    fn sockets_are_looked_for_where_keepassxc_puts_them() {
+        assert_eq!(
+            socket_candidates(
+                Some(Path::new("/run/user/1000")),
+                Some(Path::new("/home/me")),
+                Path::new("/tmp"),
+            ),
+            [
+                "/run/user/1000/app/org.keepassxc.KeePassXC/org.keepassxc.KeePassXC.BrowserServer",
+                "/run/user/1000/org.keepassxc.KeePassXC.BrowserServer",
+                "/home/me/snap/keepassxc/common/org.keepassxc.KeePassXC.BrowserServer",
+                "/tmp/org.keepassxc.KeePassXC.BrowserServer",
+            ]
+            .map(PathBuf::from)
+        );
+        assert_eq!(
+            socket_candidates(None, None, Path::new("/var/folders/xy/T")),
+            [PathBuf::from(
+                "/var/folders/xy/T/org.keepassxc.KeePassXC.BrowserServer"
+            )]
+        );
     }
 }
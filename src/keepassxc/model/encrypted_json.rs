@@ -9,9 +9,12 @@ use secrecy::{ExposeSecret as _, Secret};
 use serde::{Deserialize, Serialize};
 use serde_with::{base64::Base64, serde_as};
 
-use crate::error::Result;
+use crate::{error::Result, trace};
 
-use super::{super::error as keepassxc_error, key_material, HasNonce};
+use super::{
+    super::{error as keepassxc_error, TRACE_PROTOCOL},
+    key_material, HasNonce,
+};
 
 #[serde_as]
 #[derive(Deserialize, Serialize, PartialEq)]
@@ -31,6 +34,12 @@ impl<T> EncryptedJson<T> {
     {
         let nonce = key_material::generate_nonce();
         let plaintext = Secret::new(serde_json::to_string(&msg)?);
+        trace::record_json(
+            TRACE_PROTOCOL,
+            trace::Direction::Sent,
+            trace::Layer::Payload,
+            plaintext.expose_secret().as_bytes(),
+        );
 
         Ok(Self {
             message: secret
@@ -54,6 +63,12 @@ impl<T> EncryptedJson<T> {
                 .decrypt(&self.nonce.into(), &*self.message)
                 .map_err(Into::<keepassxc_error::Error>::into)?,
         );
+        trace::record_json(
+            TRACE_PROTOCOL,
+            trace::Direction::Received,
+            trace::Layer::Payload,
+            plaintext.expose_secret(),
+        );
 
         Ok(serde_json::from_slice(plaintext.expose_secret())?)
     }
@@ -5,6 +5,7 @@
 use crypto_box::{aead::AeadCore, SalsaBox};
 pub(in crate::keepassxc) use crypto_box::{Nonce, PublicKey, KEY_SIZE};
 use digest::typenum::Unsigned;
+use rand_core::CryptoRngCore;
 use secrecy::ExposeSecret;
 use serde::{Deserialize, Serialize};
 
@@ -40,9 +41,13 @@ pub(in crate::keepassxc) struct SecretKey(secrecy::Secret<SecretKeyMaterial>);
 
 impl SecretKey {
     pub(in crate::keepassxc) fn generate() -> Self {
-        Self(secrecy::Secret::new(SecretKeyMaterial(rng::map(
-            crypto_box::SecretKey::generate,
-        ))))
+        rng::map(|rng| Self::generate_with_rng(rng))
+    }
+
+    pub(in crate::keepassxc) fn generate_with_rng(rng: &mut (dyn CryptoRngCore + Send)) -> Self {
+        Self(secrecy::Secret::new(SecretKeyMaterial(
+            crypto_box::SecretKey::generate(&mut &mut *rng),
+        )))
     }
 }
 
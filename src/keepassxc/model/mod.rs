@@ -10,6 +10,7 @@ use serde_repr::{Deserialize_repr, Serialize_repr};
 use serde_with::{base64::Base64, json::JsonString, serde_as, DisplayFromStr};
 
 use rand::Rng as _;
+use rand_core::CryptoRngCore;
 
 use crate::{error::Result, rng};
 
@@ -104,6 +105,12 @@ impl Request {
     }
 }
 
+impl Request {
+    pub(super) fn id(&self) -> &str {
+        &self.id
+    }
+}
+
 impl HasAction for Request {
     fn action(&self) -> &str {
         &self.action
@@ -161,6 +168,13 @@ pub(crate) struct Error {
 }
 
 impl Error {
+    pub(in crate::keepassxc) fn new(error_code: ErrorCode) -> Self {
+        Self {
+            error: String::new(),
+            error_code,
+        }
+    }
+
     pub(in crate::keepassxc) fn error(&self) -> &str {
         &self.error
     }
@@ -174,6 +188,10 @@ impl Error {
 #[serde(rename_all = "camelCase")]
 pub(super) struct Response {
     action: String,
+    /// The ID of the request this responds to, which KeePassXC echoes back
+    /// when it can.
+    #[serde(rename = "requestID", default, skip_serializing_if = "Option::is_none")]
+    request_id: Option<String>,
     #[serde(flatten)]
     error: Option<Error>,
     #[serde(flatten)]
@@ -186,6 +204,12 @@ impl HasAction for Response {
     }
 }
 
+impl Response {
+    pub(super) fn request_id(&self) -> Option<&str> {
+        self.request_id.as_deref()
+    }
+}
+
 fn increment_nonce(nonce: &key_material::Nonce) -> key_material::Nonce {
     *key_material::Nonce::from_slice(
         &crypto_bigint::U192::from_le_slice(nonce)
@@ -201,10 +225,16 @@ pub(super) struct MessageBuilder {
 
 impl MessageBuilder {
     pub(super) fn new() -> Self {
+        rng::map(|rng| Self::with_rng(rng))
+    }
+
+    /// Generate the client ID and key from the given random number generator
+    /// instead of the one for the current thread.
+    pub(super) fn with_rng(rng: &mut (dyn CryptoRngCore + Send)) -> Self {
         let mut client_id = [0; key_material::KEY_SIZE];
-        rng::map(|rng| rng.fill(&mut client_id));
+        rng.fill_bytes(&mut client_id);
 
-        let client_secret = key_material::SecretKey::generate();
+        let client_secret = key_material::SecretKey::generate_with_rng(rng);
 
         Self {
             client_id,
@@ -326,3 +356,20 @@ impl EncryptedMessageBuilder {
         Ok(resp)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng as _;
+    use rand_chacha::ChaCha20Rng;
+
+    use super::*;
+
+    #[test]
+    fn message_builder_with_rng_is_reproducible() {
+        let first = MessageBuilder::with_rng(&mut ChaCha20Rng::seed_from_u64(1));
+        let second = MessageBuilder::with_rng(&mut ChaCha20Rng::seed_from_u64(1));
+
+        assert_eq!(first.client_id, second.client_id);
+        assert_eq!(first.client_key(), second.client_key());
+    }
+}
@@ -0,0 +1,84 @@
+// SPDX-FileCopyrightText: 2024 Noah Fontes
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Talking to KeePassXC through a helper program, such as `keepassxc-proxy`,
+//! over its standard input and output.
+
+use std::{
+    ffi::OsString,
+    io,
+    os::unix::net,
+    path::Path,
+    pin::Pin,
+    process::{Child, Command, Stdio},
+    task::{Context, Poll},
+};
+
+use log::debug;
+use tokio::{
+    io::{AsyncRead, AsyncWrite, ReadBuf},
+    net::UnixStream,
+};
+
+/// The standard input and output of a program we started, which is stopped
+/// when this is dropped.
+pub(super) struct ChildStream {
+    stream: UnixStream,
+    child: Child,
+}
+
+impl ChildStream {
+    /// Start the program with its standard input and output connected to
+    /// one end of a socket pair, and keep the other end. A socket carries
+    /// both directions, so one can stand in for two pipes.
+    pub(super) fn spawn(program: &Path, args: &[OsString]) -> io::Result<Self> {
+        let (ours, theirs) = net::UnixStream::pair()?;
+        let child = Command::new(program)
+            .args(args)
+            .stdin(Stdio::from(std::os::fd::OwnedFd::from(theirs.try_clone()?)))
+            .stdout(Stdio::from(std::os::fd::OwnedFd::from(theirs)))
+            .spawn()?;
+        ours.set_nonblocking(true)?;
+        Ok(Self {
+            stream: UnixStream::from_std(ours)?,
+            child,
+        })
+    }
+}
+
+impl Drop for ChildStream {
+    fn drop(&mut self) {
+        if let Err(e) = self.child.kill().and_then(|()| self.child.wait()) {
+            debug!("We could not stop the helper program: {}", e);
+        }
+    }
+}
+
+impl AsyncRead for ChildStream {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.stream).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for ChildStream {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.stream).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.stream).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.stream).poll_shutdown(cx)
+    }
+}
@@ -4,17 +4,102 @@
 
 use std::collections::HashMap;
 
+use secrecy::ExposeSecret as _;
 use serde::{Deserialize, Serialize};
+use serde_with::{base64::Base64, serde_as};
+
+use crate::{error::Result, storage};
 
 use super::model;
 
+// LINT: The field names are part of the stored session data.
+#[allow(clippy::struct_field_names)]
 #[derive(Clone, Serialize, Deserialize)]
 pub(super) struct Key {
     pub(super) id: String,
     pub(super) id_key: model::key_material::SecretKey,
+    /// The name we asked the user to give the association, if any. The
+    /// identifier is whatever the user actually entered.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(super) name: Option<String>,
 }
 
 #[derive(Clone, Default, Serialize, Deserialize)]
 pub(crate) struct Data {
     pub(super) keys: HashMap<String, Key>,
 }
+
+/// An association in the form other KeePassXC clients keep it: the name
+/// KeePassXC knows us by, and the public half of our identity key, which is
+/// what KeePassXC checks when we test the association.
+#[serde_as]
+#[derive(Serialize)]
+pub(crate) struct Association {
+    /// The hash KeePassXC gives the database the association is for.
+    hash: String,
+    id: String,
+    #[serde_as(as = "Base64")]
+    key: [u8; model::key_material::KEY_SIZE],
+}
+
+impl Data {
+    /// The associations in this session, ordered by database.
+    pub(crate) fn associations(&self) -> Vec<Association> {
+        let mut associations: Vec<_> = self
+            .keys
+            .iter()
+            .map(|(hash, key)| Association {
+                hash: hash.clone(),
+                id: key.id.clone(),
+                key: key.id_key.expose_secret().as_ref().public_key().to_bytes(),
+            })
+            .collect();
+        associations.sort_unstable_by(|a, b| a.hash.cmp(&b.hash));
+        associations
+    }
+}
+
+impl storage::Versioned for Data {
+    const VERSION: u32 = 1;
+
+    fn migrate(_: u32, _: &mut serde_json::Value) -> Result<()> {
+        // Version 1 only added the version itself.
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng as _;
+    use rand_chacha::ChaCha20Rng;
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn associations_export_public_key() -> serde_json::Result<()> {
+        let id_key =
+            model::key_material::SecretKey::generate_with_rng(&mut ChaCha20Rng::seed_from_u64(1));
+        let public_key = id_key.expose_secret().as_ref().public_key();
+        let data = Data {
+            keys: HashMap::from([(
+                "hash".to_owned(),
+                Key {
+                    id: "karp".to_owned(),
+                    id_key,
+                    name: None,
+                },
+            )]),
+        };
+
+        assert_eq!(
+            serde_json::to_value(data.associations())?,
+            json!([{
+                "hash": "hash",
+                "id": "karp",
+                "key": base64::encode(public_key.as_bytes()),
+            }])
+        );
+        Ok(())
+    }
+}
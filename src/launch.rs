@@ -0,0 +1,121 @@
+// SPDX-FileCopyrightText: 2024 Noah Fontes
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Starting KeePass or KeePassXC when it isn't running, so the first lookup
+//! after logging in doesn't fail just because nobody opened it yet.
+
+use std::{
+    process::{Command, Stdio},
+    time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
+use futures_util::future::BoxFuture;
+use log::{debug, warn};
+use secrecy::SecretVec;
+use tokio::time;
+
+use crate::{
+    client,
+    error::{self, Result},
+};
+
+/// How long we wait for the program to start listening.
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How often we try to connect while the program starts.
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// A protocol that runs a program to start the backend when there's nothing
+/// to connect to, then connects once it's listening.
+pub(crate) struct Protocol {
+    inner: Box<dyn client::Protocol<'static> + Send + Sync>,
+    command: Vec<String>,
+}
+
+impl Protocol {
+    pub(crate) fn new(
+        inner: Box<dyn client::Protocol<'static> + Send + Sync>,
+        command: Vec<String>,
+    ) -> Self {
+        Self { inner, command }
+    }
+
+    /// Run the program, then keep trying to connect until it's listening,
+    /// it fails, or we give up. The error we return is the one from the last
+    /// time we tried.
+    async fn launch(
+        &self,
+        first: error::Error,
+    ) -> Result<(
+        BoxFuture<'static, Result<()>>,
+        Box<dyn client::Client + Send + Sync + 'static>,
+    )> {
+        let Some((name, args)) = self.command.split_first() else {
+            return Err(first);
+        };
+        debug!("Nothing is listening yet, so we're running {}", name);
+        let spawned = Command::new(name)
+            .args(args)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn();
+        let mut child = match spawned {
+            Ok(child) => child,
+            Err(e) => {
+                warn!("We could not run {} to start the backend: {}", name, e);
+                return Err(first);
+            }
+        };
+
+        let deadline = Instant::now() + TIMEOUT;
+        let mut last = first;
+        loop {
+            // Programs that hand off to another and exit, like `open` on
+            // macOS, are fine; ones that fail mean there's no point waiting.
+            match child.try_wait() {
+                Ok(Some(status)) if !status.success() => {
+                    warn!("{} could not start the backend ({})", name, status);
+                    return Err(last);
+                }
+                Ok(_) => {}
+                Err(e) => debug!("We could not check on {}: {}", name, e),
+            }
+            if Instant::now() >= deadline {
+                warn!(
+                    "The backend wasn't listening {} seconds after we ran {}",
+                    TIMEOUT.as_secs(),
+                    name
+                );
+                return Err(last);
+            }
+            time::sleep(POLL_INTERVAL).await;
+
+            match self.inner.channel().await {
+                Err(e) if e.is_unreachable() => last = e,
+                result => return result,
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl client::Protocol<'static> for Protocol {
+    async fn channel(
+        &self,
+    ) -> Result<(
+        BoxFuture<'static, Result<()>>,
+        Box<dyn client::Client + Send + Sync + 'static>,
+    )> {
+        match self.inner.channel().await {
+            Err(e) if e.is_unreachable() => self.launch(e).await,
+            result => result,
+        }
+    }
+
+    async fn session_secret(&self) -> Result<Option<SecretVec<u8>>> {
+        self.inner.session_secret().await
+    }
+}
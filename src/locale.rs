@@ -0,0 +1,214 @@
+// SPDX-FileCopyrightText: 2024 Noah Fontes
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Catalogs of the messages we show to users, in each language we support.
+//!
+//! Catalogs use a subset of the Fluent syntax: each message is a single line
+//! of the form `id = text`, and text may contain placeables that are either
+//! named arguments, like `{ $error }`, or string literals, like `{" "}`.
+
+use std::{collections::HashMap, env, fmt::Display, sync::LazyLock, sync::OnceLock};
+
+use log::warn;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) enum Locale {
+    English,
+    German,
+}
+
+impl Locale {
+    /// Find the locale for a language tag, like `de`, `de-AT`, or a POSIX
+    /// locale name, like `de_DE.UTF-8`.
+    pub(crate) fn from_tag(tag: &str) -> Option<Self> {
+        let language = tag
+            .split(['_', '-', '.', '@'])
+            .next()
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        match language.as_str() {
+            "en" | "c" | "posix" => Some(Self::English),
+            "de" => Some(Self::German),
+            _ => None,
+        }
+    }
+
+    fn catalog(self) -> &'static Catalog {
+        static ENGLISH: LazyLock<Catalog> =
+            LazyLock::new(|| parse_catalog(include_str!("../locales/en/karp.ftl")));
+        static GERMAN: LazyLock<Catalog> =
+            LazyLock::new(|| parse_catalog(include_str!("../locales/de/karp.ftl")));
+
+        match self {
+            Self::English => &ENGLISH,
+            Self::German => &GERMAN,
+        }
+    }
+}
+
+static SELECTED: OnceLock<Locale> = OnceLock::new();
+
+/// Select the locale to show messages in. Without an explicit choice, we use
+/// the first of `LC_ALL`, `LC_MESSAGES`, and `LANG` that is set.
+pub(crate) fn select(explicit: Option<&str>) {
+    let locale = match explicit {
+        Some(tag) => Locale::from_tag(tag).unwrap_or_else(|| {
+            warn!(
+                "We do not have messages for the locale {}, so we will use English",
+                tag
+            );
+            Locale::English
+        }),
+        None => from_env(),
+    };
+    if SELECTED.set(locale).is_err() {
+        warn!("The locale was already selected, so we will not change it");
+    }
+}
+
+fn from_env() -> Locale {
+    ["LC_ALL", "LC_MESSAGES", "LANG"]
+        .into_iter()
+        .filter_map(|var| env::var(var).ok())
+        .find(|value| !value.is_empty())
+        .and_then(|value| Locale::from_tag(&value))
+        .unwrap_or(Locale::English)
+}
+
+fn selected() -> Locale {
+    *SELECTED.get_or_init(from_env)
+}
+
+/// Look up a message that takes no arguments.
+pub(crate) fn text(id: &str) -> String {
+    format(id, &[])
+}
+
+/// Look up a message and fill in its arguments. If the selected locale has no
+/// such message, we fall back to English, and then to the ID itself.
+pub(crate) fn format(id: &str, args: &[(&str, &dyn Display)]) -> String {
+    format_in(selected(), id, args)
+}
+
+/// Translate the header of a table column, given its English name.
+pub(crate) fn header(name: &str) -> String {
+    let id = format!("header-{}", name.to_lowercase().replace(' ', "-"));
+    match lookup(selected(), &id) {
+        Some(message) => message.format(&[]),
+        None => name.to_owned(),
+    }
+}
+
+fn format_in(locale: Locale, id: &str, args: &[(&str, &dyn Display)]) -> String {
+    match lookup(locale, id) {
+        Some(message) => message.format(args),
+        None => id.to_owned(),
+    }
+}
+
+fn lookup(locale: Locale, id: &str) -> Option<&'static Message> {
+    locale
+        .catalog()
+        .get(id)
+        .or_else(|| Locale::English.catalog().get(id))
+}
+
+#[derive(Debug, PartialEq)]
+enum Piece {
+    Text(String),
+    Argument(String),
+}
+
+#[derive(Debug, PartialEq)]
+struct Message(Vec<Piece>);
+
+impl Message {
+    fn parse(text: &str) -> Self {
+        let mut pieces = vec![];
+        let mut literal = String::new();
+        let mut rest = text;
+        while let Some(start) = rest.find('{') {
+            literal.push_str(&rest[..start]);
+            let Some(len) = rest[start..].find('}') else {
+                break;
+            };
+            let placeable = rest[start + 1..start + len].trim();
+            if let Some(name) = placeable.strip_prefix('$') {
+                pieces.push(Piece::Text(std::mem::take(&mut literal)));
+                pieces.push(Piece::Argument(name.trim().to_owned()));
+            } else {
+                literal.push_str(placeable.trim_matches('"'));
+            }
+            rest = &rest[start + len + 1..];
+        }
+        literal.push_str(rest);
+        pieces.push(Piece::Text(literal));
+        pieces.retain(|piece| !matches!(*piece, Piece::Text(ref text) if text.is_empty()));
+        Self(pieces)
+    }
+
+    fn format(&self, args: &[(&str, &dyn Display)]) -> String {
+        self.0
+            .iter()
+            .map(|piece| match *piece {
+                Piece::Text(ref text) => text.clone(),
+                Piece::Argument(ref name) => args
+                    .iter()
+                    .find(|&&(arg, _)| arg == name)
+                    .map_or_else(|| format!("{{${name}}}"), |&(_, value)| value.to_string()),
+            })
+            .collect()
+    }
+}
+
+type Catalog = HashMap<&'static str, Message>;
+
+fn parse_catalog(source: &'static str) -> Catalog {
+    source
+        .lines()
+        .filter(|line| !line.starts_with('#'))
+        .filter_map(|line| line.split_once('='))
+        .map(|(id, text)| (id.trim(), Message::parse(text.trim())))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn locale_from_tag() {
+        assert_eq!(Locale::from_tag("de_DE.UTF-8"), Some(Locale::German));
+        assert_eq!(Locale::from_tag("de-AT"), Some(Locale::German));
+        assert_eq!(Locale::from_tag("C"), Some(Locale::English));
+        assert_eq!(Locale::from_tag("xx"), None);
+    }
+
+    #[test]
+    fn catalogs_format_arguments() {
+        assert_eq!(
+            format_in(Locale::German, "error-encountered", &[("error", &"kaputt")]),
+            "Es ist ein Fehler aufgetreten: kaputt"
+        );
+        assert_eq!(
+            format_in(Locale::English, "prompt-label-terminal", &[]),
+            "Password: "
+        );
+        assert_eq!(
+            format_in(Locale::German, "no-such-message", &[]),
+            "no-such-message"
+        );
+    }
+
+    #[test]
+    fn catalogs_cover_english() {
+        let english = Locale::English.catalog();
+        let german = Locale::German.catalog();
+        let missing: Vec<_> = english
+            .keys()
+            .filter(|id| !german.contains_key(*id))
+            .collect();
+        assert!(missing.is_empty(), "German is missing {missing:?}");
+    }
+}
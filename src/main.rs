@@ -43,87 +43,476 @@
 )]
 #![cfg_attr(not(test), warn(clippy::panic_in_result_fn))]
 
+mod aggregate;
 mod client;
 mod command;
+mod config;
+mod desktop;
 mod error;
+mod generator;
+mod hooks;
+mod kdbx;
 mod keepass;
 mod keepassxc;
+mod launch;
+mod locale;
+mod memory;
 mod metadata;
+mod output;
+mod pager;
 mod password;
+mod placeholder;
+mod progress;
+mod reveal;
 mod rng;
+#[cfg(feature = "secret-service")]
+mod secret_service;
+mod snapshot;
 mod storage;
+mod timings;
+mod totp;
+mod trace;
+mod tunnel;
 
-use std::{path::PathBuf, process, sync::Arc};
+use std::{
+    collections::BTreeMap,
+    env,
+    ffi::OsString,
+    num::{NonZeroU16, NonZeroU32},
+    path::PathBuf,
+    process,
+    sync::Arc,
+    time::Instant,
+};
 
 use async_trait::async_trait;
-use clap::{Parser, Subcommand};
-use client::{Client, Protocol};
+use clap::{CommandFactory as _, Parser, Subcommand};
+use client::{Channel, Client, Connector, FormFieldType, Protocol};
+use command::Context;
 use error::Result;
-use futures_util::lock::Mutex;
-use log::{error, warn};
-use serde::{Deserialize, Serialize};
+use futures_util::{future, lock::Mutex};
+use log::{error, warn, LevelFilter};
 use url::Url;
 
 #[derive(Debug, Subcommand)]
 enum Command {
+    Alias(command::alias::Command),
+    Benchmark(command::benchmark::Command),
+    Cleanup(command::cleanup::Command),
+    Completions(command::completions::Command),
+    Config(command::config::Command),
+    GeneratePassphrase(command::generate_passphrase::Command),
+    Init(command::init::Command),
+    Man(command::man::Command),
+    Schema(command::schema::Command),
+    Session(command::session::Command),
+    VerifySession(command::verify_session::Command),
+    #[command(flatten)]
+    Backend(BackendCommand),
+}
+
+/// Commands that need a connection to a backend.
+#[derive(Debug, Subcommand)]
+enum BackendCommand {
+    #[command(name = "__complete", hide = true)]
+    Complete(command::complete::Command),
+    Copy(command::copy::Command),
+    CreateEntry(command::create_entry::Command),
+    CurlAuth(command::curl_auth::Command),
+    Diff(command::diff::Command),
+    Edit(command::edit::Command),
     GetFormFields(command::get_form_fields::Command),
+    GetTotp(command::get_totp::Command),
+    Monitor(command::monitor::Command),
+    Netrc(command::netrc::Command),
+    OpenUrl(command::open_url::Command),
+    /// Print the password of an entry.
+    Password(command::field::Command),
     Search(command::search::Command),
+    Serve(command::serve::Command),
+    Shell(command::shell::Command),
+    Status(command::status::Command),
+    SystemdCreds(command::systemd_creds::Command),
+    Totp(command::totp::Command),
+    Type(command::type_::Command),
+    UpdateEntry(command::update_entry::Command),
+    /// Print the username of an entry.
+    Username(command::field::Command),
+}
+
+impl BackendCommand {
+    /// Whether the command may ask the user for a password.
+    const fn is_interactive(&self) -> bool {
+        !matches!(*self, Self::Complete(_))
+    }
 }
 
 #[async_trait]
-impl command::Command for Command {
-    async fn execute(self, client: impl Client + Send) -> Result<()> {
+impl command::Command for BackendCommand {
+    fn url(&self) -> Option<&Url> {
+        match *self {
+            Self::Copy(ref cmd) => cmd.url(),
+            Self::Complete(_)
+            | Self::CreateEntry(_)
+            | Self::CurlAuth(_)
+            | Self::Diff(_)
+            | Self::Edit(_)
+            | Self::GetFormFields(_)
+            | Self::GetTotp(_)
+            | Self::Monitor(_)
+            | Self::Netrc(_)
+            | Self::OpenUrl(_)
+            | Self::Password(_)
+            | Self::Search(_)
+            | Self::Serve(_)
+            | Self::Shell(_)
+            | Self::Status(_)
+            | Self::SystemdCreds(_)
+            | Self::Totp(_)
+            | Self::Type(_)
+            | Self::UpdateEntry(_)
+            | Self::Username(_) => None,
+        }
+    }
+
+    async fn execute(self, client: impl Client + Send + Sync, ctx: &Context<'_>) -> Result<()> {
         match self {
-            Self::GetFormFields(cmd) => cmd.execute(client).await,
-            Self::Search(cmd) => cmd.execute(client).await,
+            Self::Complete(cmd) => cmd.execute(client, ctx).await,
+            Self::Copy(cmd) => cmd.execute(client, ctx).await,
+            Self::CreateEntry(cmd) => cmd.execute(client, ctx).await,
+            Self::CurlAuth(cmd) => cmd.execute(client, ctx).await,
+            Self::Diff(cmd) => cmd.execute(client, ctx).await,
+            Self::Edit(cmd) => cmd.execute(client, ctx).await,
+            Self::GetFormFields(cmd) => cmd.execute(client, ctx).await,
+            Self::GetTotp(cmd) => cmd.execute(client, ctx).await,
+            Self::Monitor(cmd) => cmd.execute(client, ctx).await,
+            Self::Netrc(cmd) => cmd.execute(client, ctx).await,
+            Self::OpenUrl(cmd) => cmd.execute(client, ctx).await,
+            Self::Password(cmd) => {
+                cmd.into_get_form_fields(FormFieldType::Password)
+                    .execute(client, ctx)
+                    .await
+            }
+            Self::Search(cmd) => cmd.execute(client, ctx).await,
+            Self::Serve(cmd) => cmd.execute(client, ctx).await,
+            Self::Shell(cmd) => cmd.run::<Self>(&client, ctx).await,
+            Self::Status(cmd) => cmd.execute(client, ctx).await,
+            Self::SystemdCreds(cmd) => cmd.execute(client, ctx).await,
+            Self::Totp(cmd) => cmd.execute(client, ctx).await,
+            Self::Type(cmd) => cmd.execute(client, ctx).await,
+            Self::UpdateEntry(cmd) => cmd.execute(client, ctx).await,
+            Self::Username(cmd) => {
+                cmd.into_get_form_fields(FormFieldType::Username)
+                    .execute(client, ctx)
+                    .await
+            }
         }
     }
 }
 
-#[derive(Debug, Parser)]
-#[command(author, version, about)]
-struct Args {
+const DEFAULT_URL: &str = "ws://127.0.0.1:12546";
+
+/// Options that control how we connect to backends.
+// LINT: Each of these is an independent command-line flag.
+#[allow(clippy::struct_excessive_bools)]
+#[derive(Debug, clap::Args)]
+struct ConnectionArgs {
     /// The URL to connect to. For KeePassRPC, this is a WebSocket. For
-    /// KeePassXC, this is a file path to a Unix domain socket.
-    #[arg(long, env = "KARP_URL", default_value = "ws://127.0.0.1:12546", value_parser = Url::parse)]
-    url: Url,
+    /// KeePassXC, this is a file path to a Unix domain socket, or just `file:`
+    /// to look for the socket where KeePassXC and `keepassxc-proxy` put it,
+    /// under `XDG_RUNTIME_DIR`, a Snap home, or `TMPDIR`. A KeePassXC socket
+    /// forwarded to a TCP port, such as with `socat`, is given as
+    /// `tcp://host:port`. To start a helper like `keepassxc-proxy` and talk to
+    /// KeePassXC through its standard input and output, use a `stdio` URL
+    /// with its path, such as `stdio:/usr/bin/keepassxc-proxy`; add `arg`
+    /// parameters to pass it arguments, and `framing=none` if it doesn't frame
+    /// messages with their lengths like native messaging does. To read a
+    /// database file directly, use a `kdbx` URL with its path, such as
+    /// `kdbx:///home/me/passwords.kdbx`. To use the collections of the
+    /// Freedesktop Secret Service as groups, use `secret-service:`. Defaults
+    /// to the URL of the selected profile, or `ws://127.0.0.1:12546`.
+    #[arg(long, env = "KARP_URL", value_parser = Url::parse, value_hint = clap::ValueHint::Url)]
+    url: Option<Url>,
+
+    /// Reach the backend on another machine through SSH, given as a
+    /// destination like `me@desktop`. The URL is then the one the backend
+    /// has on that machine, such as `ws://127.0.0.1:12546`, or the path of the
+    /// KeePassXC socket there. Defaults to the destination in the selected
+    /// profile.
+    #[arg(long, env = "KARP_SSH", value_name = "DESTINATION", value_hint = clap::ValueHint::Hostname)]
+    ssh: Option<String>,
+
+    /// The database to use when several are open. For KeePassRPC, this is the
+    /// name or file name of the database. For KeePassXC, which only serves
+    /// the active database, this is the database hash, and commands fail if
+    /// another database is active.
+    #[arg(long, env = "KARP_DATABASE")]
+    database: Option<String>,
+
+    /// The security level to require of KeePassRPC. By default, we require a
+    /// medium level when the session key is cached and a high level
+    /// otherwise. Selecting a high level turns off caching of the session key,
+    /// so the password is requested for every connection.
+    #[arg(long, env = "KARP_SECURITY_LEVEL", value_enum)]
+    security_level: Option<keepass::SecurityLevel>,
+
+    /// The name KeePassRPC shows when asking to authorize this client, such
+    /// as `karp on build-agent-3`. Defaults to the name in the selected
+    /// profile, or `Karp`. KeePassXC asks the user to name the client
+    /// instead.
+    #[arg(long, env = "KARP_CLIENT_DISPLAY_NAME")]
+    client_display_name: Option<String>,
+
+    /// The description KeePassRPC shows when asking to authorize this client.
+    /// Defaults to the description in the selected profile, or the package
+    /// description.
+    #[arg(long, env = "KARP_CLIENT_DISPLAY_DESCRIPTION")]
+    client_display_description: Option<String>,
+
+    /// The `Origin` header to send to KeePassRPC, for servers that only accept
+    /// connections from particular origins. Defaults to the origin in the
+    /// selected profile, or `karp://karp`.
+    #[arg(long, env = "KARP_ORIGIN")]
+    origin: Option<String>,
+
+    /// The name to give this client when KeePassXC asks to associate with it,
+    /// so it can be told apart in the list of connected clients. KeePassXC
+    /// doesn't let us name the association ourselves, so we show the name to
+    /// enter instead. Defaults to the name in the selected profile.
+    #[arg(long, env = "KARP_ASSOCIATION_NAME")]
+    association_name: Option<String>,
+
+    /// Ask KeePassXC to show its unlock prompt when a command reads from a
+    /// locked database, instead of waiting for the database to be unlocked.
+    /// Commands that change entries always ask. Defaults to the setting in the
+    /// selected profile.
+    #[arg(long, env = "KARP_TRIGGER_UNLOCK")]
+    trigger_unlock: bool,
 
     /// Turn off caching of the shared key derived by connection negotiation.
-    #[arg(long)]
+    #[arg(long, env = "KARP_NO_CACHE_SESSION_KEY")]
     no_cache_session_key: bool,
 
+    /// Require Touch ID or the user's password before the session key cached
+    /// in Keychain can be read, so it can't be reused silently by anyone with
+    /// access to an unlocked session.
+    #[cfg(feature = "keychain")]
+    #[arg(long, env = "KARP_KEYCHAIN_REQUIRE_USER_PRESENCE")]
+    keychain_require_user_presence: bool,
+
+    /// Read the session file even if other users can access it. Without this,
+    /// we refuse to use a session key that may have been exposed.
+    #[arg(long, env = "KARP_ALLOW_INSECURE_SESSION_FILE")]
+    allow_insecure_session_file: bool,
+
+    /// Cache the session key in an unencrypted file when the keyring can't be
+    /// reached. Without this, we keep the session key in memory only, so it
+    /// has to be negotiated again next time, including in builds without
+    /// keyring support. Defaults to the setting in the selected profile.
+    #[arg(long, env = "KARP_ALLOW_INSECURE_STORAGE")]
+    allow_insecure_storage: bool,
+
+    /// Keep an encrypted snapshot of the entries we look up, and answer from
+    /// it when KeePassRPC or KeePassXC can't be reached. The snapshot is
+    /// protected with the cached session key, so it's only kept once a session
+    /// has been cached. Defaults to the setting in the selected profile.
+    #[arg(long, env = "KARP_SNAPSHOT")]
+    snapshot: bool,
+
+    /// The key file that, along with or instead of the master password, locks
+    /// a database read directly from a `kdbx` URL. Defaults to the key file
+    /// in the selected profile.
+    #[arg(long, env = "KARP_KEY_FILE", value_hint = clap::ValueHint::FilePath)]
+    key_file: Option<PathBuf>,
+
+    /// How the title of an entry given by path is compared with the titles of
+    /// the entries in its group. An entry with exactly that title is always
+    /// preferred. KeePassXC looks entries up by their whole path itself, so
+    /// it only finds titles that are close enough for it.
+    #[arg(
+        long = "match",
+        env = "KARP_MATCH",
+        value_name = "MODE",
+        value_enum,
+        default_value_t
+    )]
+    title_match: client::TitleMatch,
+
+    /// How many times to ask for the password of KeePassRPC or a database
+    /// file before giving up, waiting a moment after each wrong one. When
+    /// they're used up, we exit with status 3.
+    #[arg(long, env = "KARP_MAX_PASSWORD_ATTEMPTS", default_value = "3")]
+    max_password_attempts: NonZeroU32,
+
+    /// Never ask the user anything: not for a password, not to approve a new
+    /// KeePassXC association or unlock the database, and not in commands
+    /// like `edit` that are interactive by nature. Anything that would need
+    /// an answer fails at once with status 4 instead, so scripts and CI jobs
+    /// don't hang.
+    #[arg(long, env = "KARP_NO_INPUT")]
+    no_input: bool,
+
+    /// Give up on a password prompt that isn't answered within this many
+    /// seconds, instead of waiting for as long as it takes.
+    #[arg(long, env = "KARP_PROMPT_TIMEOUT", value_name = "SECONDS")]
+    prompt_timeout: Option<NonZeroU16>,
+
+    /// Read the password of KeePassRPC or a database file from this inherited
+    /// file descriptor, such as `3` for `karp --password-fd 3 ... 3<file`,
+    /// before asking for it. Only the first line is read, and only once, so
+    /// if it's wrong we fall back to asking.
+    #[arg(long, env = "KARP_PASSWORD_FD", value_name = "FD")]
+    password_fd: Option<u32>,
+
     /// The path to the Pinentry program to use when requesting the initial
     /// password from the plugin.
-    #[arg(long, value_hint = clap::ValueHint::ExecutablePath)]
+    #[arg(long, env = "KARP_PINENTRY_PROGRAM", value_hint = clap::ValueHint::ExecutablePath)]
     pinentry_program: Option<PathBuf>,
+}
+
+impl ConnectionArgs {
+    const fn storage_options(&self) -> storage::Options {
+        storage::Options {
+            #[cfg(feature = "keychain")]
+            require_user_presence: self.keychain_require_user_presence,
+            allow_insecure_permissions: self.allow_insecure_session_file,
+        }
+    }
+}
+
+#[derive(Debug, Parser)]
+#[command(author, version, about)]
+// LINT: Each of these is an independent command-line flag.
+#[allow(clippy::struct_excessive_bools)]
+struct Args {
+    /// The profile from the configuration file to use. Defaults to the
+    /// profile named by `default_profile` in the configuration file.
+    #[arg(long, env = "KARP_PROFILE")]
+    profile: Option<String>,
+
+    /// Write a transcript of the messages exchanged with KeePassRPC or
+    /// KeePassXC to the given file, for attaching to bug reports. Encrypted
+    /// messages are written as their decrypted contents, with passwords and
+    /// other secrets redacted, along with when each was sent or received.
+    #[arg(long, env = "KARP_TRACE", value_hint = clap::ValueHint::FilePath)]
+    trace: Option<PathBuf>,
+
+    /// Keep the session file, snapshots, shell history, and everything else
+    /// we write in this directory, instead of the data, state, and cache
+    /// directories of the platform (`XDG_DATA_HOME`, `XDG_STATE_HOME`, and
+    /// `XDG_CACHE_HOME` on Linux). The configuration file stays where it is.
+    #[arg(long, env = "KARP_DATA_DIR", value_hint = clap::ValueHint::DirPath)]
+    data_dir: Option<PathBuf>,
+
+    /// The language to show messages in, such as `de`. Defaults to the
+    /// language of the locale given by `LC_ALL`, `LC_MESSAGES`, or `LANG`.
+    #[arg(long, env = "KARP_LOCALE")]
+    locale: Option<String>,
+
+    /// Show more about what we're doing: once for debugging messages, twice
+    /// to trace everything. This overrides the default level of `KARP_LOG`.
+    #[arg(short, long, action = clap::ArgAction::Count, conflicts_with = "quiet")]
+    verbose: u8,
+
+    /// Show only errors.
+    #[arg(short, long, env = "KARP_QUIET")]
+    quiet: bool,
+
+    /// Print results as tables and plain values, or as JSON for other
+    /// programs to read. `karp schema` describes the JSON we print.
+    #[arg(long, env = "KARP_OUTPUT", value_enum, default_value_t)]
+    output: output::Format,
+
+    /// Print long tables straight to the terminal, instead of showing them
+    /// through `PAGER` (or `less -R`) when they don't fit.
+    #[arg(long, env = "KARP_NO_PAGER")]
+    no_pager: bool,
+
+    /// Don't show desktop notifications when something is copied to or
+    /// cleared from the clipboard.
+    #[arg(long, env = "KARP_NO_NOTIFICATIONS")]
+    no_notifications: bool,
+
+    /// Erase passwords and other secrets we print to the terminal after this
+    /// many seconds, or as soon as a key is pressed, so they don't stay on
+    /// screen or in the scrollback.
+    #[arg(long, env = "KARP_REVEAL", value_name = "SECONDS")]
+    reveal: Option<NonZeroU16>,
+
+    /// Lock all of our memory so session keys and decrypted entries can't be
+    /// swapped out to disk, and stop if we can't. This may need a higher
+    /// limit on locked memory than the system default; see `ulimit -l`.
+    /// Defaults to the setting in the selected profile.
+    #[arg(long, env = "KARP_HARDEN_MEMORY")]
+    harden_memory: bool,
+
+    /// Let the system write a core file if we crash, for debugging. Core
+    /// files hold whatever was in memory, including session keys and
+    /// passwords, so we turn them off by default.
+    #[arg(long, env = "KARP_ALLOW_CORE_DUMPS")]
+    allow_core_dumps: bool,
+
+    /// When the command finishes, show how long we spent connecting,
+    /// authenticating, and waiting on each request to the backend, and how
+    /// much of the rest was ours, to tell whether slowness comes from the
+    /// network, KeePass, or us.
+    #[arg(long, env = "KARP_TIMINGS")]
+    timings: bool,
+
+    #[command(flatten)]
+    connection: ConnectionArgs,
 
     #[clap(subcommand)]
     command: Command,
 }
 
-async fn get_session_storage<
-    T: Send + Serialize + Sync + for<'de> Deserialize<'de> + Clone + 'static,
->(
-    args: &Args,
+impl Args {
+    const fn log_level(&self) -> Option<LevelFilter> {
+        match (self.quiet, self.verbose) {
+            (true, _) => Some(LevelFilter::Error),
+            (false, 0) => None,
+            (false, 1) => Some(LevelFilter::Debug),
+            (false, _) => Some(LevelFilter::Trace),
+        }
+    }
+}
+
+#[cfg_attr(not(feature = "secret-service"), allow(clippy::unused_async))]
+#[cfg_attr(
+    not(any(feature = "secret-service", feature = "keychain")),
+    allow(unused_variables)
+)]
+async fn get_session_storage<T: storage::Versioned + Send + Sync + Clone + 'static>(
+    args: &ConnectionArgs,
+    allow_insecure_storage: bool,
+    url: &Url,
 ) -> Box<dyn storage::Storage<T>> {
     if !args.no_cache_session_key {
         #[cfg(feature = "secret-service")]
-        match storage::SecretService::new(&args.url).await {
+        match storage::SecretService::new(url).await {
             Ok(secret_service_storage) => return Box::new(secret_service_storage),
             Err(e) => {
-                warn!("We need to fall back to unencrypted file storage because we can't connect to the secret service: {}", e);
+                warn!("We can't connect to the secret service: {}", e);
             }
         }
 
         #[cfg(feature = "keychain")]
-        match storage::Keychain::new(&args.url) {
+        match storage::Keychain::new(url, args.storage_options().require_user_presence) {
             Ok(keychain_storage) => return Box::new(keychain_storage),
             Err(e) => {
-                warn!("We need to fall back to unencrypted file storage because we can't connect to Keychain: {}", e);
+                warn!("We can't connect to Keychain: {}", e);
             }
         }
 
-        if let Some(file_storage) = storage::File::new("session.json") {
+        // Getting here means there's no keyring, or it's unavailable, and an
+        // unencrypted file would be a quiet downgrade.
+        if !allow_insecure_storage {
+            warn!("We will keep the session key in memory only, instead of in an unencrypted file; use --allow-insecure-storage to allow the file");
+        } else if let Some(file_storage) = storage::File::new(
+            metadata::Dir::State,
+            storage::SESSION_FILE,
+            args.storage_options().allow_insecure_permissions,
+        ) {
             return Box::new(file_storage);
         }
     }
@@ -131,55 +520,556 @@ async fn get_session_storage<
     Box::new(storage::Memory::<T>::new())
 }
 
-async fn run(args: Args) -> Result<()> {
-    let prompt: Vec<Box<dyn password::Prompt>> = vec![
-        Box::new(args.pinentry_program.clone().map_or_else(
-            password::PinentryPrompt::new,
-            password::PinentryPrompt::new_with_executable,
-        )),
-        Box::new(password::RpasswordPrompt),
-    ];
-
-    let proto: Box<dyn Protocol<'_> + Send> = match args.url.scheme() {
-        "ws" | "wss" => Box::new(keepass::Protocol::new(
-            Arc::new(Mutex::new(get_session_storage(&args).await)),
-            Arc::new(prompt),
-            args.url,
-        )),
-        "file" => Box::new(keepassxc::Protocol::new(
-            Arc::new(Mutex::new(get_session_storage(&args).await)),
-            args.url.to_file_path().map_err(|()| {
-                error!("The URL {} is not a valid file path", args.url);
-                error::Error::Command
-            })?,
-        )),
-        _ => {
-            error!(
-                "The URL scheme {} of URL {} is not supported",
-                args.url.scheme(),
-                args.url
+/// Where the KeePassXC a `file`, `tcp`, or `stdio` URL points to listens. A
+/// `file` URL without a path means the socket has to be looked for.
+fn keepassxc_endpoint(url: &Url) -> Result<keepassxc::Endpoint> {
+    if url.scheme() == "stdio" {
+        let mut args = vec![];
+        let mut length_prefixed = true;
+        for (key, value) in url.query_pairs() {
+            match (&*key, &*value) {
+                ("arg", arg) => args.push(arg.into()),
+                ("framing", "native") => length_prefixed = true,
+                ("framing", "none") => length_prefixed = false,
+                _ => {
+                    error!("The URL {} has an unknown parameter {}={}", url, key, value);
+                    return Err(error::Error::Command);
+                }
+            }
+        }
+        return Ok(keepassxc::Endpoint::Program {
+            path: PathBuf::from(url.path()),
+            args,
+            length_prefixed,
+        });
+    }
+
+    if url.scheme() == "tcp" {
+        if let (Some(host), Some(port)) = (url.host_str(), url.port()) {
+            return Ok(keepassxc::Endpoint::Tcp(format!("{host}:{port}")));
+        }
+        error!("The URL {} must have a host and a port", url);
+        return Err(error::Error::Command);
+    }
+
+    match url.to_file_path() {
+        Ok(path) if path.parent().is_none() => Ok(keepassxc::Endpoint::Search),
+        Ok(path) => Ok(keepassxc::Endpoint::Socket(path)),
+        Err(()) => {
+            error!("The URL {} is not a valid file path", url);
+            Err(error::Error::Command)
+        }
+    }
+}
+
+/// Constructs protocols for backends on behalf of the selected command.
+struct Backends<'args> {
+    args: &'args ConnectionArgs,
+    profile: &'args config::Profile,
+    interactive: bool,
+    /// Whether we may answer from a snapshot when the backend can't be
+    /// reached, if snapshots are turned on.
+    allow_snapshot: bool,
+}
+
+impl Backends<'_> {
+    fn allow_insecure_storage(&self) -> bool {
+        self.args.allow_insecure_storage || self.profile.allow_insecure_storage.unwrap_or_default()
+    }
+
+    async fn protocol(&self, url: &Url) -> Result<Box<dyn Protocol<'static> + Send + Sync>> {
+        let reached = match self.args.ssh.as_deref().or(self.profile.ssh.as_deref()) {
+            Some(destination) => {
+                let forward = tunnel::Forward::new(destination, url)?;
+                let inner = self.backend_protocol(url, &forward.url(url)).await?;
+                Box::new(tunnel::Protocol::new(inner, forward))
+            }
+            None => self.backend_protocol(url, url).await?,
+        };
+        let proto = match self.profile.launch_command {
+            Some(ref command) => Box::new(launch::Protocol::new(reached, command.clone())),
+            None => reached,
+        };
+
+        let snapshot = self.allow_snapshot
+            && (self.args.snapshot || self.profile.snapshot.unwrap_or_default());
+        Ok(
+            if snapshot && matches!(url.scheme(), "ws" | "wss" | "file" | "tcp" | "stdio") {
+                Box::new(snapshot::Protocol::new(
+                    proto,
+                    url.clone(),
+                    self.args.allow_insecure_session_file,
+                    self.args.title_match,
+                ))
+            } else {
+                proto
+            },
+        )
+    }
+
+    /// The ways we may ask for the password of the backend at the URL, in the
+    /// order we try them.
+    fn prompt(&self, url: &Url) -> Vec<Box<dyn password::Prompt>> {
+        let mut prompt: Vec<Box<dyn password::Prompt>> = Vec::new();
+        if let Some(fd) = self.args.password_fd {
+            prompt.push(Box::new(password::FdPrompt::new(fd)));
+        }
+        if self.interactive {
+            prompt.extend::<[Box<dyn password::Prompt>; 2]>([
+                Box::new(
+                    self.args
+                        .pinentry_program
+                        .clone()
+                        .map_or_else(
+                            password::PinentryPrompt::new,
+                            password::PinentryPrompt::new_with_executable,
+                        )
+                        .with_text(self.profile.pinentry_text(url))
+                        .with_timeout(self.args.prompt_timeout),
+                ),
+                Box::new(password::RpasswordPrompt::new().with_timeout(self.args.prompt_timeout)),
+            ]);
+        }
+        prompt
+    }
+
+    /// Construct the protocol for the backend at the URL, which is reached at
+    /// `connect_url` when that's different, such as through a tunnel.
+    async fn backend_protocol(
+        &self,
+        url: &Url,
+        connect_url: &Url,
+    ) -> Result<Box<dyn Protocol<'static> + Send + Sync>> {
+        let prompt = self.prompt(url);
+
+        Ok(match url.scheme() {
+            "ws" | "wss" => {
+                let storage = if self.args.security_level == Some(keepass::SecurityLevel::High) {
+                    Box::new(storage::Memory::new())
+                } else {
+                    get_session_storage(self.args, self.allow_insecure_storage(), url).await
+                };
+                Box::new(keepass::Protocol::new(
+                    Arc::new(Mutex::new(storage)),
+                    Arc::new(prompt),
+                    connect_url.clone(),
+                    keepass::Options {
+                        database: self.args.database.clone(),
+                        max_password_attempts: Some(self.args.max_password_attempts),
+                        title_match: self.args.title_match,
+                        security_level: self.args.security_level,
+                        client_display_name: self
+                            .args
+                            .client_display_name
+                            .clone()
+                            .or_else(|| self.profile.client_display_name.clone()),
+                        client_display_description: self
+                            .args
+                            .client_display_description
+                            .clone()
+                            .or_else(|| self.profile.client_display_description.clone()),
+                        origin: self
+                            .args
+                            .origin
+                            .clone()
+                            .or_else(|| self.profile.origin.clone()),
+                    },
+                ))
+            }
+            "kdbx" => Box::new(kdbx::Protocol::new(
+                Arc::new(prompt),
+                url.to_file_path().map_err(|()| {
+                    error!("The URL {} is not a valid file path", url);
+                    error::Error::Command
+                })?,
+                kdbx::Options {
+                    key_file: self
+                        .args
+                        .key_file
+                        .clone()
+                        .or_else(|| self.profile.key_file.clone()),
+                    max_password_attempts: Some(self.args.max_password_attempts),
+                    title_match: self.args.title_match,
+                },
+            )),
+            #[cfg(feature = "secret-service")]
+            "secret-service" => Box::new(secret_service::Protocol),
+            #[cfg(not(feature = "secret-service"))]
+            "secret-service" => {
+                error!("This build does not support the secret service");
+                return Err(error::Error::Command);
+            }
+            "file" | "tcp" | "stdio" => Box::new(keepassxc::Protocol::new(
+                Arc::new(Mutex::new(
+                    get_session_storage(self.args, self.allow_insecure_storage(), url).await,
+                )),
+                keepassxc_endpoint(connect_url)?,
+                keepassxc::Options {
+                    database: self.args.database.clone(),
+                    association_name: self
+                        .args
+                        .association_name
+                        .clone()
+                        .or_else(|| self.profile.association_name.clone()),
+                    trigger_unlock: self.args.trigger_unlock
+                        || self.profile.trigger_unlock.unwrap_or_default(),
+                    interactive: self.interactive,
+                    title_match: self.args.title_match,
+                },
+            )),
+            _ => {
+                error!(
+                    "The URL scheme {} of URL {} is not supported",
+                    url.scheme(),
+                    url
+                );
+                return Err(error::Error::Command);
+            }
+        })
+    }
+}
+
+#[async_trait]
+impl Connector for Backends<'_> {
+    async fn connect(&self, url: &Url) -> Result<Channel> {
+        Channel::open(self.protocol(url).await?.as_ref()).await
+    }
+}
+
+/// Replace the first subcommand argument with the expansion of the alias of
+/// the same name, if there is one. Aliases never take precedence over built-in
+/// commands.
+fn expand_alias(args: Vec<OsString>, aliases: &BTreeMap<String, Vec<String>>) -> Vec<OsString> {
+    let command = Args::command();
+    let takes_value = |arg: &clap::Arg| arg.get_action().takes_values();
+
+    let mut pos = 1;
+    while let Some(arg) = args.get(pos).and_then(|arg| arg.to_str()) {
+        if arg == "--" {
+            break;
+        } else if let Some(long) = arg.strip_prefix("--") {
+            if !long.contains('=')
+                && command
+                    .get_arguments()
+                    .any(|opt| opt.get_long() == Some(long) && takes_value(opt))
+            {
+                pos += 1;
+            }
+        } else if let Some(short) = arg.strip_prefix('-') {
+            let mut chars = short.chars();
+            if let (Some(c), None) = (chars.next(), chars.next()) {
+                if command
+                    .get_arguments()
+                    .any(|opt| opt.get_short() == Some(c) && takes_value(opt))
+                {
+                    pos += 1;
+                }
+            }
+        } else {
+            if command.find_subcommand(arg).is_some() {
+                break;
+            }
+            let Some(expansion) = aliases.get(arg) else {
+                break;
+            };
+
+            let mut expanded = args;
+            let _ = expanded.splice(
+                pos..=pos,
+                ["get-form-fields"]
+                    .into_iter()
+                    .chain(expansion.iter().map(String::as_str))
+                    .map(OsString::from),
             );
-            return Err(error::Error::Command);
+            return expanded;
+        }
+        pos += 1;
+    }
+
+    args
+}
+
+/// Select the URL to connect to, preferring one given by the command itself.
+fn select_url(
+    command_url: Option<&Url>,
+    args: &ConnectionArgs,
+    profile: &config::Profile,
+) -> Result<Url> {
+    match command_url.or(args.url.as_ref()).or(profile.url.as_ref()) {
+        Some(url) => Ok(url.clone()),
+        None => Ok(Url::parse(DEFAULT_URL).map_err(error::Conversion::from)?),
+    }
+}
+
+/// Set up the profile with the given name, connecting with the settings of
+/// the profile as it is.
+async fn init(
+    cmd: command::init::Command,
+    args: &ConnectionArgs,
+    profile_name: Option<&str>,
+    config: config::Config,
+    profile: &config::Profile,
+) -> Result<()> {
+    let backends = Backends {
+        args,
+        profile,
+        interactive: !args.no_input,
+        allow_snapshot: false,
+    };
+    // Checking that the session was kept means connecting without it being
+    // possible to ask for anything.
+    let verifier = Backends {
+        interactive: false,
+        ..backends
+    };
+    let setup = command::init::Setup {
+        config,
+        profile: profile_name,
+        url: args.url.as_ref(),
+        storage: args.storage_options(),
+        interactive: !args.no_input,
+    };
+    cmd.run(&backends, &verifier, setup).await
+}
+
+/// Run the command against all of the backends of a profile at once.
+async fn execute_on_members(
+    command: BackendCommand,
+    members: &BTreeMap<String, Url>,
+    backends: &Backends<'_>,
+    ctx: &Context<'_>,
+) -> Result<()> {
+    let channels = future::try_join_all(members.iter().map(|(name, url)| async {
+        Ok::<_, error::Error>((name.clone(), backends.connect(url).await?))
+    }))
+    .await?;
+    let client = aggregate::Client::new(
+        channels
+            .iter()
+            .map(|(name, channel)| (name.clone(), channel.client())),
+    );
+    let result = command::Command::execute(command, client, ctx).await;
+    for (_, channel) in channels {
+        channel.close().await?;
+    }
+    result
+}
+
+async fn run(args: Args, config: config::Config) -> Result<()> {
+    let profile = match config.profile(args.profile.as_deref()) {
+        // `init` creates the profile it's given, and `config` doesn't need one.
+        Err(error::Error::Config(error::Config::UnknownProfile(_)))
+            if matches!(args.command, Command::Init(_) | Command::Config(_)) =>
+        {
+            config::Profile::default()
+        }
+        result => result?,
+    };
+    if args.harden_memory || profile.harden_memory.unwrap_or_default() {
+        memory::lock().map_err(error::Error::LockMemory)?;
+    }
+    if profile.require_user_verification.unwrap_or_default() {
+        desktop::require_verification();
+    }
+    let command = match args.command {
+        Command::Alias(cmd) => return cmd.run(config),
+        Command::Config(cmd) => return cmd.run(),
+        Command::GeneratePassphrase(cmd) => return cmd.run(),
+        Command::Completions(cmd) => return cmd.run(&Args::command()),
+        Command::Man(cmd) => return cmd.run(Args::command()),
+        Command::Schema(cmd) => return cmd.run(),
+        Command::Benchmark(cmd) => {
+            let url = select_url(None, &args.connection, &profile)?;
+            let backends = Backends {
+                args: &args.connection,
+                profile: &profile,
+                interactive: !args.connection.no_input,
+                allow_snapshot: true,
+            };
+            return cmd.run(&backends, &url).await;
+        }
+        Command::Init(cmd) => {
+            return init(
+                cmd,
+                &args.connection,
+                args.profile.as_deref(),
+                config,
+                &profile,
+            )
+            .await
+        }
+        Command::Cleanup(cmd) => {
+            let url = select_url(None, &args.connection, &profile)?;
+            let options = args.connection.storage_options();
+            return cmd
+                .run(&config, &url, options, !args.connection.no_input)
+                .await;
+        }
+        Command::Session(cmd) => {
+            let url = select_url(None, &args.connection, &profile)?;
+            return cmd.run(&url, args.connection.storage_options()).await;
         }
+        Command::VerifySession(cmd) => {
+            let url = select_url(None, &args.connection, &profile)?;
+            let backends = Backends {
+                args: &args.connection,
+                profile: &profile,
+                interactive: false,
+                // Snapshots don't need a session, so they'd prove nothing.
+                allow_snapshot: false,
+            };
+            return cmd.run(&backends, &url).await;
+        }
+        Command::Backend(cmd) => cmd,
+    };
+
+    let backends = Backends {
+        args: &args.connection,
+        profile: &profile,
+        interactive: command.is_interactive() && !args.connection.no_input,
+        allow_snapshot: true,
     };
-    let (worker, client) = proto.channel().await?;
-    let worker_task = tokio::spawn(worker);
+    let ctx = Context::new(
+        &backends,
+        profile.group_prefix.as_deref(),
+        !args.connection.no_input,
+    );
+
+    // A URL given for this run alone takes the place of the backends of the
+    // profile.
+    let command_url = command::Command::url(&command).or(args.connection.url.as_ref());
+    if let Some(members) = profile
+        .backends
+        .as_ref()
+        .filter(|members| command_url.is_none() && !members.is_empty())
+    {
+        return execute_on_members(command, members, &backends, &ctx).await;
+    }
 
-    let result = command::Command::execute(args.command, client).await;
-    worker_task.await??;
+    let url = select_url(command_url, &args.connection, &profile)?;
+    let channel = backends.connect(&url).await?;
+    let result = command::Command::execute(command, channel.client(), &ctx).await;
+    channel.close().await?;
 
     result
 }
 
-#[tokio::main]
-async fn main() {
+/// Set up logging, optionally replacing the level given by `KARP_LOG` for
+/// everything it doesn't name explicitly.
+fn init_logger(level: Option<LevelFilter>) {
     let logger_env = env_logger::Env::new()
         .filter_or("KARP_LOG", "warn")
         .write_style("KARP_LOG_STYLE");
-    env_logger::Builder::from_env(logger_env).init();
+    let mut builder = env_logger::Builder::from_env(logger_env);
+    if let Some(selected) = level {
+        let _ = builder.filter_level(selected);
+    }
+    builder.init();
+}
 
-    if let Err(e) = run(Args::parse()).await {
-        error!("We encountered an error: {}", e);
-        process::exit(1);
+#[tokio::main]
+async fn main() {
+    // Mistakes in the configuration file shouldn't get in the way of help, or
+    // of fixing them with `config`.
+    let loaded = config::Config::load();
+    let aliases = loaded
+        .as_ref()
+        .map(|config| config.aliases.clone())
+        .unwrap_or_default();
+    let args = Args::parse_from(expand_alias(env::args_os().collect(), &aliases));
+    init_logger(args.log_level());
+    let config = match loaded {
+        Ok(config) => config,
+        Err(_) if matches!(args.command, Command::Config(_)) => config::Config::default(),
+        Err(e) => {
+            error!("{}", locale::format("error-encountered", &[("error", &e)]));
+            process::exit(1);
+        }
     };
+    if let Some(ref dir) = args.data_dir {
+        metadata::set_data_dir(dir.clone());
+    }
+    if !args.allow_core_dumps {
+        if let Err(e) = memory::disable_core_dumps() {
+            warn!("We could not turn off core dumps: {}", e);
+        }
+    }
+    if !args.quiet {
+        progress::enable();
+    }
+    if !args.no_pager {
+        pager::enable();
+    }
+    if args.no_notifications {
+        desktop::disable_notifications();
+    }
+    if let Some(seconds) = args.reveal {
+        reveal::enable(seconds.get());
+    }
+    locale::select(args.locale.as_deref());
+    hooks::configure(config.hooks.clone());
+    output::select(args.output);
+
+    if let Some(path) = args.trace.as_deref() {
+        if let Err(e) = trace::open(path) {
+            error!("We could not open the transcript {}: {}", path.display(), e);
+            process::exit(1);
+        }
+    }
+
+    let started = Instant::now();
+    if args.timings {
+        timings::enable();
+    }
+    let result = run(args, config).await;
+    timings::report(started);
+    if let Err(e) = result {
+        if output::is_json() {
+            output::print_error(&e, e.exit_code());
+        } else {
+            error!("{}", locale::format("error-encountered", &[("error", &e)]));
+        }
+        process::exit(e.exit_code());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn global_options_have_environment_variables() {
+        let command = Args::command();
+        let missing: Vec<_> = command
+            .get_arguments()
+            .filter(|arg| {
+                !arg.is_positional()
+                    && arg.get_env().is_none()
+                    && !matches!(arg.get_id().as_str(), "help" | "version" | "verbose")
+            })
+            .map(|arg| arg.get_id().to_string())
+            .collect();
+        assert!(
+            missing.is_empty(),
+            "no environment variable for {missing:?}"
+        );
+    }
+
+    #[cfg(not(feature = "keychain"))]
+    #[test]
+    fn completion_scripts_are_current() {
+        let scripts = command::completions::scripts(&Args::command());
+        for (name, contents) in [
+            ("_karp", include_str!("../contrib/completions/_karp")),
+            (
+                "karp.fish",
+                include_str!("../contrib/completions/karp.fish"),
+            ),
+        ] {
+            assert!(
+                scripts.iter().any(|script| *script == (name.to_owned(), contents.to_owned())),
+                "contrib/completions/{name} is out of date; run `karp completions --out-dir contrib/completions`"
+            );
+        }
+    }
 }
@@ -0,0 +1,85 @@
+// SPDX-FileCopyrightText: 2024 Noah Fontes
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Keeping secrets from outliving their use in memory.
+
+use std::io;
+
+use secrecy::zeroize::Zeroize as _;
+use serde_json::Value;
+
+/// Keep every page we have mapped, and every page we map from now on, out of
+/// swap, so session keys and decrypted messages are never written to disk.
+///
+/// This locks the whole process rather than the individual pages holding
+/// secrets, because secrets are moved between allocations we don't control as
+/// they're parsed and passed around.
+#[cfg(unix)]
+pub(crate) fn lock() -> io::Result<()> {
+    use nix::sys::mman::{mlockall, MlockAllFlags};
+
+    mlockall(MlockAllFlags::MCL_CURRENT | MlockAllFlags::MCL_FUTURE).map_err(io::Error::from)
+}
+
+#[cfg(not(unix))]
+pub(crate) fn lock() -> io::Result<()> {
+    Err(io::ErrorKind::Unsupported.into())
+}
+
+/// Stop a crash from leaving a core file behind, since it would hold whatever
+/// secrets were in memory at the time.
+#[cfg(unix)]
+pub(crate) fn disable_core_dumps() -> io::Result<()> {
+    use nix::sys::resource::{setrlimit, Resource};
+
+    setrlimit(Resource::RLIMIT_CORE, 0, 0)?;
+
+    // A limit on the size of core files doesn't apply when they're piped to a
+    // handler like systemd-coredump, and doesn't stop other processes of the
+    // same user from attaching to us, but being undumpable does both.
+    #[cfg(target_os = "linux")]
+    nix::sys::prctl::set_dumpable(false)?;
+
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub(crate) fn disable_core_dumps() -> io::Result<()> {
+    Ok(())
+}
+
+/// Overwrite every string in a JSON value, which may have been decrypted from
+/// a message, and leave it empty.
+pub(crate) fn zeroize_json(value: &mut Value) {
+    match *value {
+        Value::String(ref mut string) => string.zeroize(),
+        Value::Array(ref mut array) => {
+            for item in array.iter_mut() {
+                zeroize_json(item);
+            }
+            array.clear();
+        }
+        Value::Object(ref mut object) => {
+            for (_, field) in object.iter_mut() {
+                zeroize_json(field);
+            }
+            object.clear();
+        }
+        Value::Null | Value::Bool(_) | Value::Number(_) => {}
+    }
+    *value = Value::Null;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn zeroize_json_clears_nested_values() {
+        let mut value = json!({"entries": [{"password": "hunter2"}]});
+        zeroize_json(&mut value);
+        assert_eq!(value, Value::Null);
+    }
+}
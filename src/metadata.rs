@@ -2,16 +2,102 @@
 //
 // SPDX-License-Identifier: Apache-2.0
 
+use std::{
+    path::{Path, PathBuf},
+    sync::{LazyLock, OnceLock},
+};
+
 use directories::ProjectDirs;
 use inflector::Inflector;
-use once_cell::sync::Lazy;
+use log::{debug, warn};
+
+use crate::storage;
 
 pub(crate) const CLIENT_VERSION: u32 = u32::from_be_bytes([0, 2, 0, 0]);
-pub(crate) static CLIENT_TYPE_ID: Lazy<String> =
-    Lazy::new(|| option_env!("CARGO_PKG_NAME").unwrap_or("karp").to_owned());
-pub(crate) static CLIENT_DISPLAY_NAME: Lazy<String> = Lazy::new(|| CLIENT_TYPE_ID.to_title_case());
-pub(crate) static CLIENT_DISPLAY_DESCRIPTION: Lazy<Option<String>> =
-    Lazy::new(|| option_env!("CARGO_PKG_DESCRIPTION").map(str::to_owned));
-
-pub(crate) static PROJECT_DIRS: Lazy<Option<ProjectDirs>> =
-    Lazy::new(|| ProjectDirs::from("com", "NoahFontes", &CLIENT_DISPLAY_NAME));
+pub(crate) static CLIENT_TYPE_ID: LazyLock<String> =
+    LazyLock::new(|| option_env!("CARGO_PKG_NAME").unwrap_or("karp").to_owned());
+pub(crate) static CLIENT_DISPLAY_NAME: LazyLock<String> =
+    LazyLock::new(|| CLIENT_TYPE_ID.to_title_case());
+pub(crate) static CLIENT_DISPLAY_DESCRIPTION: LazyLock<Option<String>> =
+    LazyLock::new(|| option_env!("CARGO_PKG_DESCRIPTION").map(str::to_owned));
+
+pub(crate) static PROJECT_DIRS: LazyLock<Option<ProjectDirs>> =
+    LazyLock::new(|| ProjectDirs::from("com", "NoahFontes", &CLIENT_DISPLAY_NAME));
+
+/// A directory given on the command line to keep all of our files in.
+static DATA_DIR: OnceLock<PathBuf> = OnceLock::new();
+
+/// Keep every file we write in the given directory, instead of the
+/// directories the platform sets aside for them. The configuration file stays
+/// where it is.
+pub(crate) fn set_data_dir(path: PathBuf) {
+    let _ = DATA_DIR.set(path);
+}
+
+/// The kinds of files we keep, which the XDG base directory specification
+/// puts in separate directories.
+#[derive(Clone, Copy, Debug)]
+pub(crate) enum Dir {
+    /// Files the user gives us, such as word lists.
+    Data,
+    /// Files that should survive restarts but aren't worth backing up, such
+    /// as the session file and shell history.
+    State,
+    /// Files we can do without, such as snapshots.
+    Cache,
+}
+
+impl Dir {
+    /// Where files of this kind are kept. Platforms without a separate state
+    /// directory keep state with data.
+    pub(crate) fn path(self) -> Option<PathBuf> {
+        if let Some(dir) = DATA_DIR.get() {
+            return Some(dir.clone());
+        }
+        let dirs = PROJECT_DIRS.as_ref()?;
+        Some(
+            match self {
+                Self::Data => dirs.data_dir(),
+                Self::State => dirs.state_dir().unwrap_or_else(|| dirs.data_dir()),
+                Self::Cache => dirs.cache_dir(),
+            }
+            .to_owned(),
+        )
+    }
+
+    /// The path of a file of this kind. Earlier versions kept every file in
+    /// the data directory, so a file found only there is moved into place.
+    pub(crate) fn file<P: AsRef<Path>>(self, name: P) -> Option<PathBuf> {
+        let path = self.path()?.join(&name);
+        if DATA_DIR.get().is_some() || path.exists() {
+            return Some(path);
+        }
+        let Some(old) = PROJECT_DIRS
+            .as_ref()
+            .map(|dirs| dirs.data_dir().join(&name))
+            .filter(|old| *old != path && old.exists())
+        else {
+            return Some(path);
+        };
+
+        let moved = path
+            .parent()
+            .map_or(Ok(()), storage::create_dir)
+            .and_then(|()| std::fs::rename(&old, &path));
+        match moved {
+            Ok(()) => {
+                debug!("Moved {} to {}", old.display(), path.display());
+                Some(path)
+            }
+            Err(e) => {
+                warn!(
+                    "We could not move {} to {}: {}",
+                    old.display(),
+                    path.display(),
+                    e
+                );
+                Some(old)
+            }
+        }
+    }
+}
@@ -0,0 +1,81 @@
+// SPDX-FileCopyrightText: 2024 Noah Fontes
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Whether results are printed for people to read or as JSON for programs.
+//! The shapes of the JSON documents are described by `karp schema`.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use clap::ValueEnum;
+use serde::Serialize;
+
+use crate::error;
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub(crate) enum Format {
+    /// Tables and plain values.
+    #[default]
+    Text,
+    /// JSON documents.
+    Json,
+}
+
+static JSON: AtomicBool = AtomicBool::new(false);
+
+pub(crate) fn select(format: Format) {
+    JSON.store(format == Format::Json, Ordering::Relaxed);
+}
+
+/// Whether commands should print JSON.
+pub(crate) fn is_json() -> bool {
+    JSON.load(Ordering::Relaxed)
+}
+
+/// The error document described by `karp schema errors`.
+#[derive(Serialize)]
+struct ErrorDocument<'err> {
+    error: ErrorObject<'err>,
+}
+
+#[derive(Serialize)]
+struct ErrorObject<'err> {
+    kind: &'err str,
+    code: Option<String>,
+    message: String,
+    exit_code: i32,
+}
+
+fn error_document(error: &error::Error, exit_code: i32) -> ErrorDocument<'_> {
+    ErrorDocument {
+        error: ErrorObject {
+            kind: error.kind(),
+            code: error.code(),
+            message: error.to_string(),
+            exit_code,
+        },
+    }
+}
+
+/// Print an error to standard error as a JSON document on one line.
+pub(crate) fn print_error(error: &error::Error, exit_code: i32) {
+    match serde_json::to_string(&error_document(error, exit_code)) {
+        Ok(document) => eprintln!("{document}"),
+        Err(_) => eprintln!("{error}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn error_document_shape() -> error::Result<()> {
+        let error = error::Error::InvalidEntryRef("/".to_owned());
+        assert_eq!(
+            serde_json::to_string(&error_document(&error, 1))?,
+            r#"{"error":{"kind":"invalid_entry_ref","code":null,"message":"\"/\" is not a valid entry path or ID","exit_code":1}}"#
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,88 @@
+// SPDX-FileCopyrightText: 2024 Noah Fontes
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Showing output that doesn't fit in the terminal through the user's pager.
+
+use std::{
+    env,
+    fmt::Display,
+    io::{self, IsTerminal as _, Write as _},
+    process::{Command, Stdio},
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use log::warn;
+use terminal_size::{terminal_size, Height};
+
+use crate::error::Result;
+
+/// The pager to use when `PAGER` isn't set. `-R` passes the escape sequences
+/// of colored output through.
+const DEFAULT_PAGER: &[&str] = &["less", "-R"];
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Start paging output that is too long for the terminal, if standard output
+/// goes to one.
+pub(crate) fn enable() {
+    if io::stdout().is_terminal() {
+        ENABLED.store(true, Ordering::Relaxed);
+    }
+}
+
+fn pager() -> Vec<String> {
+    let configured = env::var("PAGER")
+        .ok()
+        .filter(|pager| !pager.trim().is_empty())
+        .and_then(|pager| match shell_words::split(&pager) {
+            Ok(words) => Some(words),
+            Err(e) => {
+                warn!("We could not parse PAGER, so we will use less: {}", e);
+                None
+            }
+        });
+    configured.unwrap_or_else(|| DEFAULT_PAGER.iter().map(|&word| word.to_owned()).collect())
+}
+
+/// Print something to standard output, through the pager if it has more lines
+/// than the terminal does.
+pub(crate) fn print(output: impl Display) -> Result<()> {
+    let text = output.to_string();
+    let fits = terminal_size()
+        .is_none_or(|(_, Height(height))| text.lines().count() < usize::from(height));
+    if !ENABLED.load(Ordering::Relaxed) || fits {
+        println!("{text}");
+        return Ok(());
+    }
+
+    let command = pager();
+    let Some((name, args)) = command.split_first() else {
+        println!("{text}");
+        return Ok(());
+    };
+    // Output may hold secrets, so less mustn't remember what was searched
+    // for in it.
+    let mut child = match Command::new(name)
+        .args(args)
+        .env("LESSHISTFILE", "-")
+        .stdin(Stdio::piped())
+        .spawn()
+    {
+        Ok(child) => child,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            println!("{text}");
+            return Ok(());
+        }
+        Err(e) => return Err(e.into()),
+    };
+    if let Some(mut stdin) = child.stdin.take() {
+        // The pager stops reading when the user quits it early.
+        match writeln!(stdin, "{text}") {
+            Err(e) if e.kind() != io::ErrorKind::BrokenPipe => return Err(e.into()),
+            Ok(()) | Err(_) => {}
+        }
+    }
+    let _ = child.wait()?;
+    Ok(())
+}
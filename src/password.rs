@@ -2,26 +2,77 @@
 //
 // SPDX-License-Identifier: Apache-2.0
 
-use std::{ffi::OsString, path::Path};
+use std::{
+    ffi::OsString,
+    fs::File,
+    io::{BufRead as _, BufReader},
+    num::{NonZeroU16, NonZeroU32},
+    path::Path,
+    sync::atomic::{AtomicBool, Ordering},
+    thread,
+    time::Duration,
+};
 
 use async_trait::async_trait;
 use secrecy::SecretString;
-use tokio::task;
+use tokio::{task, time};
 
-use crate::{error::Result, metadata};
+use crate::{
+    error::{self, Result},
+    locale, metadata, progress,
+};
+
+/// How long we wait after a wrong password before asking again, which slows
+/// down anything guessing.
+const RETRY_DELAY: Duration = Duration::from_secs(1);
+
+/// Wait a little after a wrong password, or give up if the user has used up
+/// their attempts. `failed` counts the wrong passwords so far, and no limit
+/// means we keep asking.
+pub(crate) async fn retry_after_failure(
+    failed: u32,
+    max_attempts: Option<NonZeroU32>,
+) -> Result<()> {
+    if max_attempts.is_some_and(|max| failed >= max.get()) {
+        return Err(error::Password::TooManyAttempts(failed).into());
+    }
+    task::spawn_blocking(|| thread::sleep(RETRY_DELAY)).await?;
+    Ok(())
+}
 
 #[derive(Debug, Default, Clone)]
 pub(crate) struct Request {
+    description: Option<String>,
+    allow_empty: bool,
     error: Option<String>,
 }
 
 pub(crate) struct RequestBuilder {
+    description: Option<String>,
+    allow_empty: bool,
     error: Option<String>,
 }
 
 impl RequestBuilder {
     pub(crate) const fn new() -> Self {
-        Self { error: None }
+        Self {
+            description: None,
+            allow_empty: false,
+            error: None,
+        }
+    }
+
+    /// Explain which password we want, for prompts that can show more than a
+    /// label.
+    pub(crate) fn with_description(mut self, description: &str) -> Self {
+        self.description = Some(description.to_owned());
+        self
+    }
+
+    /// Accept an empty password instead of asking again.
+    pub(crate) const fn allow_empty(mut self) -> Self {
+        self.allow_empty = true;
+        self
     }
 
     pub(crate) fn with_error(mut self, error: &str) -> Self {
@@ -30,7 +81,11 @@ impl RequestBuilder {
     }
 
     pub(crate) fn into_request(self) -> Request {
-        Request { error: self.error }
+        Request {
+            description: self.description,
+            allow_empty: self.allow_empty,
+            error: self.error,
+        }
     }
 }
 
@@ -59,49 +114,157 @@ impl<T: Prompt> Prompt for Vec<T> {
     }
 }
 
+/// Reads the password from a file descriptor inherited from whoever started us,
+/// so it never shows up in our arguments or environment. The first line is
+/// the password; it's read once, and if it's wrong we fall through to the
+/// prompts after this one.
+pub(crate) struct FdPrompt {
+    fd: u32,
+    used: AtomicBool,
+}
+
+impl FdPrompt {
+    pub(crate) const fn new(fd: u32) -> Self {
+        Self {
+            fd,
+            used: AtomicBool::new(false),
+        }
+    }
+}
+
+/// Read the first line from an open file descriptor of ours, without the line
+/// ending.
+fn read_fd(fd: u32) -> Result<SecretString> {
+    let mut line = String::new();
+    let _ = BufReader::new(File::open(format!("/dev/fd/{fd}"))?).read_line(&mut line)?;
+    Ok(SecretString::new(
+        line.trim_end_matches(['\r', '\n']).to_owned(),
+    ))
+}
+
+#[async_trait]
+impl Prompt for FdPrompt {
+    async fn prompt(&self, _req: Request) -> Result<Option<SecretString>> {
+        if self.used.swap(true, Ordering::Relaxed) {
+            return Ok(None);
+        }
+        let fd = self.fd;
+        Ok(Some(task::spawn_blocking(move || read_fd(fd)).await??))
+    }
+}
+
+/// Text to show in a Pinentry dialog in place of our own, so the user can tell
+/// which connection is asking.
+#[derive(Debug, Default, Clone)]
+pub(crate) struct PinentryText {
+    pub(crate) title: Option<String>,
+    pub(crate) prompt: Option<String>,
+    pub(crate) description: Option<String>,
+}
+
 pub(crate) struct PinentryPrompt {
     executable: Option<OsString>,
+    text: PinentryText,
+    timeout: Option<NonZeroU16>,
 }
 
 impl PinentryPrompt {
-    pub(crate) const fn new() -> Self {
-        Self { executable: None }
+    pub(crate) fn new() -> Self {
+        Self {
+            executable: None,
+            text: PinentryText::default(),
+            timeout: None,
+        }
     }
 
     pub(crate) fn new_with_executable<P: AsRef<Path>>(executable: P) -> Self {
         Self {
             executable: Some(executable.as_ref().as_os_str().into()),
+            text: PinentryText::default(),
+            timeout: None,
         }
     }
+
+    pub(crate) fn with_text(mut self, text: PinentryText) -> Self {
+        self.text = text;
+        self
+    }
+
+    /// Close the dialog if it isn't answered within the given number of
+    /// seconds.
+    pub(crate) const fn with_timeout(mut self, timeout: Option<NonZeroU16>) -> Self {
+        self.timeout = timeout;
+        self
+    }
 }
 
 #[async_trait]
 impl Prompt for PinentryPrompt {
     async fn prompt(&self, req: Request) -> Result<Option<SecretString>> {
+        struct Labels {
+            title: String,
+            prompt: String,
+            description: Option<String>,
+            required: String,
+            required_keepassrpc: String,
+            timeout: Option<NonZeroU16>,
+        }
+
         fn interact<'input>(
             mut input: pinentry::PassphraseInput<'input>,
-            title: &'input str,
-            error: Option<&'input String>,
+            labels: &'input Labels,
+            req: &'input Request,
         ) -> Result<SecretString> {
-            _ = input.required("You must enter the password presented by KeePassRPC to continue.");
-            _ = input.with_title(title);
-            _ = input.with_prompt("Password");
-            if let Some(e) = error {
+            match req.description {
+                Some(_) if !req.allow_empty => {
+                    _ = input.required(&labels.required);
+                }
+                None if !req.allow_empty => {
+                    _ = input.required(&labels.required_keepassrpc);
+                }
+                Some(_) | None => {}
+            }
+            if let Some(description) = labels.description.as_ref().or(req.description.as_ref()) {
+                _ = input.with_description(description);
+            }
+            _ = input.with_title(&labels.title);
+            _ = input.with_prompt(&labels.prompt);
+            if let Some(ref e) = req.error {
                 _ = input.with_error(e);
             }
+            if let Some(timeout) = labels.timeout {
+                _ = input.with_timeout(timeout.get());
+            }
 
             Ok(input.interact()?)
         }
 
-        let title = format!("Password - {}", *metadata::CLIENT_DISPLAY_NAME);
+        let labels = Labels {
+            title: self.text.title.clone().unwrap_or_else(|| {
+                locale::format(
+                    "prompt-title",
+                    &[("client", &*metadata::CLIENT_DISPLAY_NAME)],
+                )
+            }),
+            prompt: self
+                .text
+                .prompt
+                .clone()
+                .unwrap_or_else(|| locale::text("prompt-label")),
+            description: self.text.description.clone(),
+            required: locale::text("prompt-required"),
+            required_keepassrpc: locale::text("prompt-required-keepassrpc"),
+            timeout: self.timeout,
+        };
 
         let input = self
             .executable
             .as_ref()
             .and_then(pinentry::PassphraseInput::with_binary)
             .or_else(pinentry::PassphraseInput::with_default_binary)
-            .map(|input| task::spawn_blocking(move || interact(input, &title, req.error.as_ref())));
+            .map(|input| task::spawn_blocking(move || interact(input, &labels, &req)));
 
+        let _pause = progress::pause();
         Ok(match input {
             Some(fut) => Some(fut.await??),
             None => None,
@@ -109,20 +272,127 @@ impl Prompt for PinentryPrompt {
     }
 }
 
-pub(crate) struct RpasswordPrompt;
+pub(crate) struct RpasswordPrompt {
+    timeout: Option<NonZeroU16>,
+}
+
+impl RpasswordPrompt {
+    pub(crate) const fn new() -> Self {
+        Self { timeout: None }
+    }
+
+    /// Stop waiting if nothing is entered within the given number of seconds.
+    pub(crate) const fn with_timeout(mut self, timeout: Option<NonZeroU16>) -> Self {
+        self.timeout = timeout;
+        self
+    }
+}
+
+/// The settings of the terminal, saved so they can be put back if we stop
+/// waiting for a password while echo is turned off.
+#[cfg(unix)]
+struct Terminal {
+    tty: File,
+    attrs: nix::sys::termios::Termios,
+}
+
+#[cfg(unix)]
+impl Terminal {
+    fn save() -> Option<Self> {
+        let tty = File::open("/dev/tty").ok()?;
+        let attrs = nix::sys::termios::tcgetattr(&tty).ok()?;
+        Some(Self { tty, attrs })
+    }
+
+    fn restore(&self) {
+        let _ = nix::sys::termios::tcsetattr(
+            &self.tty,
+            nix::sys::termios::SetArg::TCSANOW,
+            &self.attrs,
+        );
+    }
+}
+
+#[cfg(not(unix))]
+struct Terminal;
+
+#[cfg(not(unix))]
+impl Terminal {
+    const fn save() -> Option<Self> {
+        None
+    }
+
+    const fn restore(&self) {}
+}
 
 #[async_trait]
 impl Prompt for RpasswordPrompt {
     async fn prompt(&self, req: Request) -> Result<Option<SecretString>> {
+        let _pause = progress::pause();
         if let Some(error) = req.error {
-            eprintln!("Error: {error}");
+            eprintln!(
+                "{}",
+                locale::format("prompt-error-terminal", &[("error", &error)])
+            );
+        }
+
+        let read = task::spawn_blocking(|| {
+            rpassword::prompt_password(locale::text("prompt-label-terminal")).map(SecretString::new)
+        });
+        let Some(timeout) = self.timeout else {
+            return Ok(Some(read.await??));
+        };
+
+        // The read can't be interrupted, so we leave it behind; we're about
+        // to exit anyway.
+        let terminal = Terminal::save();
+        if let Ok(password) = time::timeout(Duration::from_secs(timeout.get().into()), read).await {
+            return Ok(Some(password??));
         }
+        if let Some(ref saved) = terminal {
+            saved.restore();
+        }
+        eprintln!();
+        Err(error::Password::Timeout.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn fd_is_read_once() -> Result<()> {
+        use secrecy::ExposeSecret as _;
+        use std::os::fd::AsRawFd as _;
+
+        let path = std::env::temp_dir().join(format!("karp-fd-test-{}", std::process::id()));
+        std::fs::write(&path, "hunter2\nrest\n")?;
+        let file = File::open(&path)?;
+        let prompt = FdPrompt::new(file.as_raw_fd().unsigned_abs());
+
+        let password = prompt.prompt(Request::default()).await?;
+        assert_eq!(
+            password.as_ref().map(|p| p.expose_secret().as_str()),
+            Some("hunter2")
+        );
+        assert!(prompt.prompt(Request::default()).await?.is_none());
+
+        std::fs::remove_file(&path)?;
+        Ok(())
+    }
 
-        Ok(Some(
-            task::spawn_blocking(|| {
-                rpassword::prompt_password("Password: ").map(SecretString::new)
-            })
-            .await??,
-        ))
+    #[tokio::test]
+    async fn attempts_run_out() {
+        let max = NonZeroU32::new(2);
+        assert!(matches!(
+            retry_after_failure(2, max).await,
+            Err(error::Error::Password(error::Password::TooManyAttempts(2)))
+        ));
+        assert_eq!(
+            error::Error::from(error::Password::TooManyAttempts(2)).exit_code(),
+            error::EXIT_AUTH_FAILED
+        );
     }
 }
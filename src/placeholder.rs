@@ -0,0 +1,284 @@
+// SPDX-FileCopyrightText: 2024 Noah Fontes
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Expanding the placeholders KeePass lets field values contain, such as
+//! `{USERNAME}`, `{URL:HOST}`, or `{REF:P@I:...}`, the way KeePass would
+//! before typing or copying them.
+//!
+//! Placeholders we don't know, and references to entries we can't find, are
+//! left as they are, as KeePass does.
+
+use async_recursion::async_recursion;
+use secrecy::{ExposeSecret as _, SecretString};
+use url::Url;
+use uuid::Uuid;
+
+use crate::{
+    client::{Client, Entry, FormFieldType, PlaceholderHandling},
+    error::{self, Result},
+};
+
+/// How deeply placeholders may expand to values that contain placeholders
+/// themselves, so circular references end.
+const MAX_DEPTH: usize = 10;
+
+fn field_value(entry: &Entry, type_: FormFieldType) -> Option<&str> {
+    entry
+        .form_fields
+        .iter()
+        .find(|field| field.type_ == type_)
+        .map(|field| field.value.expose_secret().as_str())
+}
+
+/// The value a placeholder stands for that belongs to the entry itself, before
+/// any placeholders it contains are expanded.
+fn local(entry: &Entry, placeholder: &str) -> Option<String> {
+    let upper = placeholder.to_ascii_uppercase();
+    let url = || entry.urls.first().map(String::as_str);
+    let parsed_url = || url().and_then(|text| Url::parse(text).ok());
+
+    match upper.as_str() {
+        "TITLE" => Some(entry.title.clone()),
+        "USERNAME" => field_value(entry, FormFieldType::Username).map(str::to_owned),
+        "PASSWORD" => field_value(entry, FormFieldType::Password).map(str::to_owned),
+        "URL" => url().map(str::to_owned),
+        "URL:RMVSCM" => url().map(|text| {
+            text.split_once("://")
+                .map_or(text, |(_, rest)| rest)
+                .to_owned()
+        }),
+        "URL:SCM" => parsed_url().map(|url| url.scheme().to_owned()),
+        "URL:HOST" => parsed_url().and_then(|url| url.host_str().map(str::to_owned)),
+        "URL:PORT" => parsed_url()
+            .and_then(|url| url.port_or_known_default())
+            .map(|port| port.to_string()),
+        "URL:PATH" => parsed_url().map(|url| url.path().to_owned()),
+        "URL:QUERY" => parsed_url().map(|url| {
+            url.query()
+                .map(|query| format!("?{query}"))
+                .unwrap_or_default()
+        }),
+        "URL:USERINFO" => parsed_url().map(|url| match url.password() {
+            Some(password) => format!("{}:{password}", url.username()),
+            None => url.username().to_owned(),
+        }),
+        "URL:USERNAME" => parsed_url().map(|url| url.username().to_owned()),
+        "URL:PASSWORD" => parsed_url().map(|url| url.password().unwrap_or_default().to_owned()),
+        _ => upper.strip_prefix("S:").and_then(|name| {
+            entry
+                .form_fields
+                .iter()
+                .find(|field| field.display_name.eq_ignore_ascii_case(name))
+                .map(|field| field.value.expose_secret().clone())
+        }),
+    }
+}
+
+/// A reference to a field of another entry, like `{REF:P@I:<uuid>}`.
+#[derive(Debug, PartialEq)]
+struct Reference<'text> {
+    wanted: char,
+    search_in: char,
+    text: &'text str,
+}
+
+impl<'text> Reference<'text> {
+    fn parse(placeholder: &'text str) -> Option<Self> {
+        let spec = placeholder
+            .get(..4)
+            .filter(|prefix| prefix.eq_ignore_ascii_case("REF:"))
+            .and(placeholder.get(4..))?;
+        let (fields, text) = spec.split_once(':')?;
+        let (wanted, search_in) = fields.split_once('@')?;
+        let single = |field: &str| {
+            let mut chars = field.chars();
+            chars
+                .next()
+                .filter(|_| chars.next().is_none())
+                .map(|c| c.to_ascii_uppercase())
+        };
+        Some(Self {
+            wanted: single(wanted)?,
+            search_in: single(search_in)?,
+            text,
+        })
+    }
+
+    fn field(entry: &Entry, field: char) -> Option<&str> {
+        match field {
+            'T' => Some(&entry.title),
+            'U' => field_value(entry, FormFieldType::Username),
+            'P' => field_value(entry, FormFieldType::Password),
+            'A' => entry.urls.first().map(String::as_str),
+            'I' => Some(&entry.id),
+            _ => None,
+        }
+    }
+
+    fn matches(&self, entry: &Entry) -> bool {
+        Self::field(entry, self.search_in)
+            .is_some_and(|value| value.to_lowercase().contains(&self.text.to_lowercase()))
+    }
+
+    async fn find(&self, client: &(dyn Client + Send + Sync)) -> Result<Option<Entry>> {
+        if self.search_in == 'I' {
+            let Ok(id) = Uuid::try_parse(self.text) else {
+                return Ok(None);
+            };
+            return match client.get_entry_by_id(id).await {
+                Ok(entry) => Ok(Some(entry)),
+                Err(error::Error::EntryIdNotFound { .. }) => Ok(None),
+                Err(e) => Err(e),
+            };
+        }
+
+        Ok(client
+            .find_entries(self.text, None)
+            .await?
+            .into_iter()
+            .find(|entry| self.matches(entry)))
+    }
+}
+
+/// The fully expanded value of a single placeholder, without its braces, or
+/// `None` if we don't know it.
+async fn value(
+    client: &(dyn Client + Send + Sync),
+    entry: &Entry,
+    placeholder: &str,
+    depth: usize,
+) -> Result<Option<String>> {
+    Ok(match Reference::parse(placeholder) {
+        Some(reference) => match reference.find(client).await? {
+            Some(other) => match Reference::field(&other, reference.wanted) {
+                Some(found) => Some(expand(client, &other, found, depth + 1).await?),
+                None => None,
+            },
+            None => None,
+        },
+        None => match local(entry, placeholder) {
+            Some(found) => Some(expand(client, entry, &found, depth + 1).await?),
+            None => None,
+        },
+    })
+}
+
+#[async_recursion]
+async fn expand(
+    client: &(dyn Client + Send + Sync),
+    entry: &Entry,
+    text: &str,
+    depth: usize,
+) -> Result<String> {
+    if depth >= MAX_DEPTH {
+        return Ok(text.to_owned());
+    }
+
+    let mut expanded = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('{') {
+        expanded.push_str(&rest[..start]);
+        let Some(len) = rest[start..].find('}') else {
+            break;
+        };
+        let placeholder = &rest[start + 1..start + len];
+
+        match value(client, entry, placeholder, depth).await? {
+            Some(ref found) => expanded.push_str(found),
+            None => expanded.push_str(&rest[start..=start + len]),
+        }
+        rest = &rest[start + len + 1..];
+    }
+    expanded.push_str(rest);
+    Ok(expanded)
+}
+
+/// Expand a single placeholder, given without its braces, such as `USERNAME`
+/// or `REF:P@I:...`. Returns `None` if we don't know the placeholder.
+pub(crate) async fn expand_one(
+    client: &(dyn Client + Send + Sync),
+    entry: &Entry,
+    placeholder: &str,
+) -> Result<Option<String>> {
+    value(client, entry, placeholder, 0).await
+}
+
+/// Expand the placeholders in the URLs and form field values of an entry,
+/// except in fields that KeePassRPC says should be filled in as they are.
+pub(crate) async fn resolve(client: &(dyn Client + Send + Sync), entry: &Entry) -> Result<Entry> {
+    let mut resolved = entry.clone();
+    for (field, original) in resolved.form_fields.iter_mut().zip(&entry.form_fields) {
+        let disabled = original
+            .metadata
+            .as_ref()
+            .is_some_and(|metadata| metadata.placeholder_handling == PlaceholderHandling::Disabled);
+        if !disabled && original.value.expose_secret().contains('{') {
+            field.value =
+                SecretString::new(expand(client, entry, original.value.expose_secret(), 0).await?);
+        }
+    }
+    for (url, original) in resolved.urls.iter_mut().zip(&entry.urls) {
+        if original.contains('{') {
+            *url = expand(client, entry, original, 0).await?;
+        }
+    }
+    Ok(resolved)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::FormField;
+
+    fn entry() -> Entry {
+        let field = |type_, display_name: &str, value: &str| FormField {
+            type_,
+            display_name: display_name.to_owned(),
+            value: SecretString::new(value.to_owned()),
+            metadata: None,
+        };
+        Entry {
+            id: "04040404040404040404040404040404".to_owned(),
+            parent: None,
+            title: "Example".to_owned(),
+            urls: vec!["https://alice@example.com:8443/login?next=1".to_owned()],
+            form_fields: vec![
+                field(FormFieldType::Username, "Username", "alice"),
+                field(FormFieldType::Password, "Password", "hunter2"),
+                field(FormFieldType::Text, "Tenant", "acme"),
+            ],
+            database: None,
+            source: None,
+        }
+    }
+
+    #[test]
+    fn local_placeholders() {
+        let entry = entry();
+        assert_eq!(local(&entry, "username").as_deref(), Some("alice"));
+        assert_eq!(local(&entry, "URL:HOST").as_deref(), Some("example.com"));
+        assert_eq!(local(&entry, "URL:PORT").as_deref(), Some("8443"));
+        assert_eq!(
+            local(&entry, "URL:RMVSCM").as_deref(),
+            Some("alice@example.com:8443/login?next=1")
+        );
+        assert_eq!(local(&entry, "URL:QUERY").as_deref(), Some("?next=1"));
+        assert_eq!(local(&entry, "S:tenant").as_deref(), Some("acme"));
+        assert_eq!(local(&entry, "NOTES"), None);
+    }
+
+    #[test]
+    fn parse_reference() {
+        assert_eq!(
+            Reference::parse("REF:p@i:0404"),
+            Some(Reference {
+                wanted: 'P',
+                search_in: 'I',
+                text: "0404",
+            })
+        );
+        assert_eq!(Reference::parse("REF:PASSWORD@I:0404"), None);
+        assert_eq!(Reference::parse("USERNAME"), None);
+    }
+}
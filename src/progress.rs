@@ -0,0 +1,297 @@
+// SPDX-FileCopyrightText: 2024 Noah Fontes
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! A status line that shows what we're waiting for, so slow operations don't
+//! look like a hang.
+//!
+//! Work in progress is announced by holding a [`Guard`] for its phase. When
+//! several phases are in progress at once, such as a lookup that is waiting
+//! on authentication, the line shows the later one in the order of
+//! [`Phase`]. Nothing is drawn until a phase has lasted long enough to be
+//! noticed.
+
+use std::{
+    io::{self, IsTerminal as _, Write as _},
+    num::NonZeroUsize,
+    sync::{Mutex, OnceLock},
+    thread,
+    time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
+use futures_util::stream::BoxStream;
+use secrecy::SecretString;
+use uuid::Uuid;
+
+use crate::{client, error::Result, locale, timings};
+
+const FRAMES: &[char] = &['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
+const TICK: Duration = Duration::from_millis(100);
+const DELAY: Duration = Duration::from_millis(300);
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum Phase {
+    Fetching,
+    Connecting,
+    Authenticating,
+    WaitingForApproval,
+}
+
+impl Phase {
+    const ALL: [Self; 4] = [
+        Self::Fetching,
+        Self::Connecting,
+        Self::Authenticating,
+        Self::WaitingForApproval,
+    ];
+
+    fn message(self) -> String {
+        locale::text(match self {
+            Self::Fetching => "progress-fetching",
+            Self::Connecting => "progress-connecting",
+            Self::Authenticating => "progress-authenticating",
+            Self::WaitingForApproval => "progress-waiting-for-approval",
+        })
+    }
+
+    /// What we call this phase when reporting how long it took.
+    fn label(self, call: Option<&str>) -> String {
+        match (self, call) {
+            (Self::Fetching, Some(method)) => {
+                locale::format("timings-call", &[("method", &method)])
+            }
+            (Self::Fetching, None) => locale::text("timings-fetching"),
+            (Self::Connecting, _) => locale::text("timings-connecting"),
+            (Self::Authenticating, _) => locale::text("timings-authenticating"),
+            (Self::WaitingForApproval, _) => locale::text("timings-waiting-for-approval"),
+        }
+    }
+}
+
+#[derive(Default)]
+struct State {
+    active: [Vec<Instant>; Phase::ALL.len()],
+    paused: usize,
+    frame: usize,
+    drawn: bool,
+}
+
+impl State {
+    fn current(&self) -> Option<(Phase, Instant)> {
+        Phase::ALL.into_iter().rev().find_map(|phase| {
+            self.active[phase as usize]
+                .iter()
+                .min()
+                .map(|&started| (phase, started))
+        })
+    }
+
+    fn clear(&mut self) {
+        if self.drawn {
+            eprint!("\r\x1b[K");
+            let _ = io::stderr().flush();
+            self.drawn = false;
+        }
+    }
+
+    fn draw(&mut self) {
+        self.clear();
+        if self.paused > 0 {
+            return;
+        }
+        if let Some((phase, started)) = self.current() {
+            if started.elapsed() >= DELAY {
+                self.frame = (self.frame + 1) % FRAMES.len();
+                eprint!("{} {}", FRAMES[self.frame], phase.message());
+                let _ = io::stderr().flush();
+                self.drawn = true;
+            }
+        }
+    }
+}
+
+static STATE: OnceLock<Mutex<State>> = OnceLock::new();
+
+/// Start showing progress, if both standard output and standard error go to a
+/// terminal.
+pub(crate) fn enable() {
+    if !(io::stdout().is_terminal() && io::stderr().is_terminal()) {
+        return;
+    }
+    if STATE.set(Mutex::new(State::default())).is_ok() {
+        let _ = thread::spawn(|| loop {
+            thread::sleep(TICK);
+            with_state(State::draw);
+        });
+    }
+}
+
+fn with_state<F: FnOnce(&mut State)>(f: F) {
+    if let Some(Ok(mut state)) = STATE.get().map(Mutex::lock) {
+        f(&mut state);
+    }
+}
+
+/// Work in a given phase, which is shown as being in progress until this is
+/// dropped.
+#[must_use]
+pub(crate) struct Guard {
+    phase: Phase,
+    call: Option<&'static str>,
+    started: Instant,
+}
+
+impl Drop for Guard {
+    fn drop(&mut self) {
+        timings::record(|| self.phase.label(self.call), self.started);
+        with_state(|state| {
+            let active = &mut state.active[self.phase as usize];
+            if let Some(pos) = active.iter().position(|&started| started == self.started) {
+                let _ = active.swap_remove(pos);
+            }
+            state.draw();
+        });
+    }
+}
+
+fn start_as(phase: Phase, call: Option<&'static str>) -> Guard {
+    let started = Instant::now();
+    with_state(|state| state.active[phase as usize].push(started));
+    Guard {
+        phase,
+        call,
+        started,
+    }
+}
+
+pub(crate) fn start(phase: Phase) -> Guard {
+    start_as(phase, None)
+}
+
+/// Fetch entries with a request to the backend, which is reported by name
+/// with `--timings`.
+fn start_call(method: &'static str) -> Guard {
+    start_as(Phase::Fetching, Some(method))
+}
+
+/// Keeps the status line hidden until this is dropped, so it doesn't get in
+/// the way of prompting the user.
+#[must_use]
+pub(crate) struct Pause(());
+
+impl Drop for Pause {
+    fn drop(&mut self) {
+        with_state(|state| state.paused -= 1);
+    }
+}
+
+pub(crate) fn pause() -> Pause {
+    with_state(|state| {
+        state.paused += 1;
+        state.clear();
+    });
+    Pause(())
+}
+
+/// A client that shows its requests as fetching entries while they're in
+/// flight.
+pub(crate) struct Client<'channel> {
+    inner: Box<dyn client::Client + Send + Sync + 'channel>,
+}
+
+impl<'channel> Client<'channel> {
+    pub(crate) fn new(inner: Box<dyn client::Client + Send + Sync + 'channel>) -> Self {
+        Self { inner }
+    }
+}
+
+#[async_trait]
+impl client::Client for Client<'_> {
+    async fn get_entry(
+        &self,
+        group_names: &mut (dyn Iterator<Item = &str> + Send + Sync),
+        title: &str,
+    ) -> Result<client::Entry> {
+        let _progress = start_call("get_entry");
+        self.inner.get_entry(group_names, title).await
+    }
+
+    async fn get_entry_by_id(&self, id: Uuid) -> Result<client::Entry> {
+        let _progress = start_call("get_entry_by_id");
+        self.inner.get_entry_by_id(id).await
+    }
+
+    async fn find_entries(
+        &self,
+        query: &str,
+        limit: Option<NonZeroUsize>,
+    ) -> Result<Vec<client::Entry>> {
+        let _progress = start_call("find_entries");
+        self.inner.find_entries(query, limit).await
+    }
+
+    async fn create_entry(
+        &self,
+        group_names: &mut (dyn Iterator<Item = &str> + Send + Sync),
+        entry: &client::Entry,
+    ) -> Result<()> {
+        let _progress = start_call("create_entry");
+        self.inner.create_entry(group_names, entry).await
+    }
+
+    async fn update_entry(&self, id: Uuid, entry: &client::Entry) -> Result<()> {
+        let _progress = start_call("update_entry");
+        self.inner.update_entry(id, entry).await
+    }
+
+    async fn list_group(
+        &self,
+        group_names: &mut (dyn Iterator<Item = &str> + Send + Sync),
+    ) -> Result<client::GroupListing> {
+        let _progress = start_call("list_group");
+        self.inner.list_group(group_names).await
+    }
+
+    async fn databases(&self) -> Result<Vec<client::Database>> {
+        let _progress = start_call("databases");
+        self.inner.databases().await
+    }
+
+    async fn features(&self) -> Result<Vec<String>> {
+        self.inner.features().await
+    }
+
+    async fn generate_password(&self) -> Result<SecretString> {
+        let _progress = start_call("generate_password");
+        self.inner.generate_password().await
+    }
+
+    async fn watch(&self) -> Result<BoxStream<'static, client::DatabaseEvent>> {
+        self.inner.watch().await
+    }
+
+    async fn settings(&self) -> Result<Vec<client::Setting>> {
+        self.inner.settings().await
+    }
+
+    fn group_paths(&self) -> bool {
+        self.inner.group_paths()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn current_phase_is_the_latest() {
+        let mut state = State::default();
+        let started = Instant::now();
+        assert_eq!(state.current(), None);
+
+        state.active[Phase::Fetching as usize].push(started);
+        state.active[Phase::Authenticating as usize].push(started);
+        assert_eq!(state.current(), Some((Phase::Authenticating, started)));
+    }
+}
@@ -0,0 +1,117 @@
+// SPDX-FileCopyrightText: 2024 Noah Fontes
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Showing secrets on the terminal for a moment only, so a glance at a
+//! password doesn't leave it sitting in the scrollback.
+
+use std::{
+    io::{self, IsTerminal as _, Write as _},
+    sync::atomic::{AtomicU16, Ordering},
+    time::Duration,
+};
+
+use terminal_size::{terminal_size, Width};
+
+use crate::{error::Result, locale};
+
+/// How many seconds to show secrets for, or zero to leave them.
+static SECONDS: AtomicU16 = AtomicU16::new(0);
+
+/// Erase secrets we print to the terminal after the given number of seconds.
+pub(crate) fn enable(seconds: u16) {
+    SECONDS.store(seconds, Ordering::Relaxed);
+}
+
+/// The number of rows the text takes up on a terminal of the given width.
+fn rows(text: &str, width: usize) -> usize {
+    text.split('\n')
+        .map(|line| line.chars().count().div_ceil(width).max(1))
+        .sum()
+}
+
+/// Print secrets, one per line. If they go to a terminal and `--reveal` was
+/// given, wait until the time is up or a key is pressed, then erase them.
+pub(crate) fn print<'secret>(secrets: impl IntoIterator<Item = &'secret str>) -> Result<()> {
+    let seconds = SECONDS.load(Ordering::Relaxed);
+    let mut stdout = io::stdout().lock();
+    if seconds == 0 || !stdout.is_terminal() {
+        for secret in secrets {
+            writeln!(stdout, "{secret}")?;
+        }
+        return Ok(());
+    }
+
+    let width = terminal_size().map_or(usize::MAX, |(Width(columns), _)| {
+        usize::from(columns).max(1)
+    });
+    let hint = locale::format("reveal-hiding", &[("seconds", &seconds)]);
+    let mut shown = rows(&hint, width);
+    for secret in secrets {
+        writeln!(stdout, "{secret}")?;
+        shown += rows(secret, width);
+    }
+    writeln!(stdout, "{hint}")?;
+    stdout.flush()?;
+
+    wait(Duration::from_secs(seconds.into()));
+
+    // Move back up to the first secret and clear everything below it.
+    write!(stdout, "\x1b[{shown}A\r\x1b[J")?;
+    stdout.flush()?;
+    Ok(())
+}
+
+/// Wait for the given time, or until a key is pressed on the terminal. The
+/// key is swallowed rather than left for the shell.
+#[cfg(unix)]
+fn wait(timeout: Duration) {
+    use std::{fs::File, os::fd::AsFd as _, thread};
+
+    use nix::{
+        poll::{poll, PollFd, PollFlags, PollTimeout},
+        sys::termios::{self, LocalFlags, SetArg},
+    };
+
+    let Some((tty, saved)) = File::open("/dev/tty")
+        .ok()
+        .and_then(|tty| termios::tcgetattr(&tty).ok().map(|attrs| (tty, attrs)))
+    else {
+        thread::sleep(timeout);
+        return;
+    };
+
+    // Take keys one at a time without echoing them, and treat Ctrl-C as just
+    // another key so we still get to erase the secret.
+    let mut raw = saved.clone();
+    raw.local_flags
+        .remove(LocalFlags::ICANON | LocalFlags::ECHO | LocalFlags::ISIG);
+    if termios::tcsetattr(&tty, SetArg::TCSANOW, &raw).is_err() {
+        thread::sleep(timeout);
+        return;
+    }
+    let mut fds = [PollFd::new(tty.as_fd(), PollFlags::POLLIN)];
+    let _ = poll(
+        &mut fds,
+        PollTimeout::try_from(timeout).unwrap_or(PollTimeout::MAX),
+    );
+    let _ = termios::tcsetattr(&tty, SetArg::TCSAFLUSH, &saved);
+}
+
+#[cfg(not(unix))]
+fn wait(timeout: Duration) {
+    std::thread::sleep(timeout);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rows_count_wrapped_lines() {
+        assert_eq!(rows("hunter2", 80), 1);
+        assert_eq!(rows("", 80), 1);
+        assert_eq!(rows(&"x".repeat(81), 80), 2);
+        assert_eq!(rows("first\nsecond", 80), 2);
+    }
+}
@@ -3,15 +3,45 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use std::cell::RefCell;
+#[cfg(debug_assertions)]
+use std::{
+    env,
+    sync::atomic::{AtomicU64, Ordering},
+};
 
 use rand::{thread_rng, RngCore, SeedableRng};
 use rand_chacha::ChaCha20Rng;
 
+/// The environment variable that, in debug builds, seeds the random number
+/// generator with a number so a run can be reproduced.
+#[cfg(debug_assertions)]
+const SEED_VAR: &str = "KARP_RNG_SEED";
+
 thread_local! {
+    static RNG: RefCell<ChaCha20Rng> = RefCell::new(new_rng());
+}
+
+/// Create the generator for the current thread. When seeded from the
+/// environment, each thread draws from its own stream, numbered in the order
+/// the threads first ask for random numbers.
+fn new_rng() -> ChaCha20Rng {
+    #[cfg(debug_assertions)]
+    if let Some(seed) = env::var(SEED_VAR).ok().and_then(|var| var.parse().ok()) {
+        static STREAM: AtomicU64 = AtomicU64::new(0);
+
+        log::warn!(
+            "Random numbers are seeded from {}, so nothing we generate is secret",
+            SEED_VAR
+        );
+        let mut rng = ChaCha20Rng::seed_from_u64(seed);
+        rng.set_stream(STREAM.fetch_add(1, Ordering::Relaxed));
+        return rng;
+    }
+
     // LINT: We need a working random number generator for the program to
     // function.
     #[allow(clippy::expect_used)]
-    static RNG: RefCell<ChaCha20Rng> = RefCell::new(ChaCha20Rng::from_rng(thread_rng()).expect("random number generator failed to initialize"));
+    ChaCha20Rng::from_rng(thread_rng()).expect("random number generator failed to initialize")
 }
 
 pub(crate) fn map<F, R>(mut f: F) -> R
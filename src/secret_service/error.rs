@@ -0,0 +1,13 @@
+// SPDX-FileCopyrightText: 2024 Noah Fontes
+//
+// SPDX-License-Identifier: Apache-2.0
+
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub(crate) enum Error {
+    #[error("D-Bus error: {0}")]
+    DBus(#[from] oo7::dbus::Error),
+    #[error("entries can only be created directly inside a collection")]
+    NotCollection,
+}
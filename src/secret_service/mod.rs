@@ -0,0 +1,514 @@
+// SPDX-FileCopyrightText: 2024 Noah Fontes
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Treat the Freedesktop Secret Service as a store of entries, with each
+//! collection as a group and each item as an entry.
+
+pub(crate) mod error;
+
+use std::{collections::HashMap, num::NonZeroUsize};
+
+use async_trait::async_trait;
+use futures_util::future::{self, BoxFuture};
+use rand::RngCore as _;
+use secrecy::{ExposeSecret as _, SecretString};
+use sha2::{Digest as _, Sha256};
+use uuid::Uuid;
+
+use crate::{
+    client,
+    error::{self as base_error, Result},
+    rng,
+};
+
+/// The attribute we give items we create so they keep their identifier when
+/// they're renamed.
+const ID_ATTRIBUTE: &str = "karp.uuid";
+
+/// The attribute that marks items holding our own session data.
+const KIND_ATTRIBUTE: &str = "karp.kind";
+
+const USERNAME_ATTRIBUTES: [&str; 2] = ["username", "user"];
+const URL_ATTRIBUTES: [&str; 2] = ["url", "uri"];
+
+const CONTENT_TYPE: &str = "text/plain";
+
+struct Item {
+    id: Uuid,
+    collection: String,
+    label: String,
+    attributes: HashMap<String, String>,
+    inner: oo7::dbus::Item<'static>,
+}
+
+impl Item {
+    async fn new(collection: &str, inner: oo7::dbus::Item<'static>) -> Result<Self> {
+        let label = inner.label().await.map_err(error::Error::from)?;
+        let attributes = inner.attributes().await.map_err(error::Error::from)?;
+
+        // Items from other applications have no identifier of their own, so
+        // we make one up from the things least likely to change.
+        let stored_id = attributes
+            .get(ID_ATTRIBUTE)
+            .and_then(|id| Uuid::try_parse(id).ok());
+        let id = if let Some(id) = stored_id {
+            id
+        } else {
+            let created = inner.created().await.map_err(error::Error::from)?;
+            let hash = Sha256::new()
+                .chain_update(collection.as_bytes())
+                .chain_update([0])
+                .chain_update(label.as_bytes())
+                .chain_update([0])
+                .chain_update(created.as_nanos().to_le_bytes())
+                .finalize();
+            let mut bytes = [0_u8; 16];
+            bytes.copy_from_slice(&hash[..16]);
+            uuid::Builder::from_custom_bytes(bytes).into_uuid()
+        };
+
+        Ok(Self {
+            id,
+            collection: collection.to_owned(),
+            label,
+            attributes,
+            inner,
+        })
+    }
+
+    fn attribute(&self, names: &[&str]) -> Option<&str> {
+        names
+            .iter()
+            .find_map(|name| self.attributes.get(*name))
+            .map(String::as_str)
+    }
+
+    /// Whether the label, username, or URL contains the given text, ignoring
+    /// case.
+    fn matches(&self, query: &str) -> bool {
+        let needle = query.to_lowercase();
+        [
+            Some(self.label.as_str()),
+            self.attribute(&USERNAME_ATTRIBUTES),
+            self.attribute(&URL_ATTRIBUTES),
+        ]
+        .into_iter()
+        .flatten()
+        .any(|value| value.to_lowercase().contains(&needle))
+    }
+
+    async fn to_client(&self) -> Result<client::Entry> {
+        let secret = self.inner.secret().await.map_err(error::Error::from)?;
+
+        let mut form_fields = vec![
+            client::FormField {
+                type_: client::FormFieldType::Username,
+                display_name: "Username".to_owned(),
+                value: self
+                    .attribute(&USERNAME_ATTRIBUTES)
+                    .unwrap_or_default()
+                    .to_owned()
+                    .into(),
+                metadata: None,
+            },
+            client::FormField {
+                type_: client::FormFieldType::Password,
+                display_name: "Password".to_owned(),
+                value: String::from_utf8_lossy(&secret).into_owned().into(),
+                metadata: None,
+            },
+        ];
+        let mut attributes: Vec<_> = self
+            .attributes
+            .iter()
+            .filter(|&(name, _)| !is_reserved_attribute(name))
+            .collect();
+        attributes.sort();
+        form_fields.extend(
+            attributes
+                .into_iter()
+                .map(|(name, value)| client::FormField {
+                    type_: client::FormFieldType::Text,
+                    display_name: name.clone(),
+                    value: value.clone().into(),
+                    metadata: None,
+                }),
+        );
+
+        Ok(client::Entry {
+            id: self.id.simple().to_string(),
+            parent: Some(client::Group {
+                path: self.collection.clone(),
+            }),
+            title: self.label.clone(),
+            urls: self
+                .attribute(&URL_ATTRIBUTES)
+                .map(str::to_owned)
+                .into_iter()
+                .collect(),
+            form_fields,
+            database: None,
+            source: None,
+        })
+    }
+}
+
+/// Whether the attribute is one we present some other way, or one that only
+/// means something to the application that stored the item.
+fn is_reserved_attribute(name: &str) -> bool {
+    USERNAME_ATTRIBUTES.contains(&name)
+        || URL_ATTRIBUTES.contains(&name)
+        || name.starts_with("karp.")
+        || name.starts_with("xdg:")
+}
+
+/// Build the attributes and secret of an item from the form fields of the
+/// given entry.
+fn item_contents(id: Uuid, entry: &client::Entry) -> (HashMap<String, String>, SecretString) {
+    let mut attributes = HashMap::from([(ID_ATTRIBUTE.to_owned(), id.simple().to_string())]);
+    let mut secret = None;
+    for field in &entry.form_fields {
+        match field.type_ {
+            client::FormFieldType::Username => {
+                let _ = attributes
+                    .entry(USERNAME_ATTRIBUTES[0].to_owned())
+                    .or_insert_with(|| field.value.expose_secret().clone());
+            }
+            client::FormFieldType::Password if secret.is_none() => {
+                secret = Some(field.value.clone());
+            }
+            client::FormFieldType::Password
+            | client::FormFieldType::Text
+            | client::FormFieldType::Select
+            | client::FormFieldType::Radio
+            | client::FormFieldType::Checkbox => {
+                if !is_reserved_attribute(&field.display_name) {
+                    let _ = attributes
+                        .entry(field.display_name.clone())
+                        .or_insert_with(|| field.value.expose_secret().clone());
+                }
+            }
+            // A one-time password is only good for a few seconds, so there's
+            // no point in keeping it.
+            client::FormFieldType::Totp => {}
+        }
+    }
+    if let Some(url) = entry.urls.first() {
+        let _ = attributes.insert(URL_ATTRIBUTES[0].to_owned(), url.clone());
+    }
+
+    (
+        attributes,
+        secret.unwrap_or_else(|| SecretString::new(String::new())),
+    )
+}
+
+fn as_str_map(attributes: &HashMap<String, String>) -> HashMap<&str, &str> {
+    attributes
+        .iter()
+        .map(|(key, value)| (key.as_str(), value.as_str()))
+        .collect()
+}
+
+struct Client {
+    service: oo7::dbus::Service<'static>,
+}
+
+impl Client {
+    async fn collections(&self) -> Result<Vec<(String, oo7::dbus::Collection<'static>)>> {
+        let mut collections = vec![];
+        for collection in self
+            .service
+            .collections()
+            .await
+            .map_err(error::Error::from)?
+        {
+            let label = collection.label().await.map_err(error::Error::from)?;
+            collections.push((label, collection));
+        }
+        Ok(collections)
+    }
+
+    /// Find the collection named by the given group path. The root group,
+    /// which holds the collections, has no collection of its own.
+    async fn get_group(
+        &self,
+        group_names: &mut (dyn Iterator<Item = &str> + Send + Sync),
+    ) -> Result<Option<(String, oo7::dbus::Collection<'static>)>> {
+        let Some(collection_name) = group_names.next() else {
+            return Ok(None);
+        };
+
+        let found = self
+            .collections()
+            .await?
+            .into_iter()
+            .find(|(label, _)| label == collection_name)
+            .ok_or_else(|| base_error::Error::GroupNotFound {
+                parent: client::Group {
+                    path: String::new(),
+                },
+                name: collection_name.to_owned(),
+            })?;
+
+        // Collections are flat.
+        if let Some(group_name) = group_names.next() {
+            return Err(base_error::Error::GroupNotFound {
+                parent: client::Group { path: found.0 },
+                name: group_name.to_owned(),
+            });
+        }
+
+        Ok(Some(found))
+    }
+
+    async fn items(label: &str, collection: &oo7::dbus::Collection<'static>) -> Result<Vec<Item>> {
+        if collection.is_locked().await.map_err(error::Error::from)? {
+            collection.unlock().await.map_err(error::Error::from)?;
+        }
+
+        let mut items = vec![];
+        for inner in collection.items().await.map_err(error::Error::from)? {
+            let item = Item::new(label, inner).await?;
+            if !item.attributes.contains_key(KIND_ATTRIBUTE) {
+                items.push(item);
+            }
+        }
+        Ok(items)
+    }
+
+    async fn all_items(&self) -> Result<Vec<Item>> {
+        let mut items = vec![];
+        for (label, collection) in self.collections().await? {
+            items.extend(Self::items(&label, &collection).await?);
+        }
+        Ok(items)
+    }
+}
+
+#[async_trait]
+impl client::Client for Client {
+    async fn get_entry(
+        &self,
+        group_names: &mut (dyn Iterator<Item = &str> + Send + Sync),
+        title: &str,
+    ) -> Result<client::Entry> {
+        let not_found = |path: &str| base_error::Error::EntryNotFound {
+            parent: client::Group {
+                path: path.to_owned(),
+            },
+            name: title.to_owned(),
+        };
+
+        let Some((label, collection)) = self.get_group(group_names).await? else {
+            return Err(not_found(""));
+        };
+        match Self::items(&label, &collection)
+            .await?
+            .into_iter()
+            .find(|item| item.label == title)
+        {
+            Some(item) => item.to_client().await,
+            None => Err(not_found(&label)),
+        }
+    }
+
+    async fn get_entry_by_id(&self, id: Uuid) -> Result<client::Entry> {
+        match self
+            .all_items()
+            .await?
+            .into_iter()
+            .find(|item| item.id == id)
+        {
+            Some(item) => item.to_client().await,
+            None => Err(base_error::Error::EntryIdNotFound { id }),
+        }
+    }
+
+    async fn find_entries(
+        &self,
+        query: &str,
+        limit: Option<NonZeroUsize>,
+    ) -> Result<Vec<client::Entry>> {
+        // Matching only looks at attributes, so we can stop before asking for
+        // the secrets of any more items than we need.
+        let wanted = limit.map_or(usize::MAX, NonZeroUsize::get);
+        let mut found = vec![];
+        for item in self.all_items().await? {
+            if found.len() == wanted {
+                break;
+            }
+            if item.matches(query) {
+                found.push(item.to_client().await?);
+            }
+        }
+        Ok(found)
+    }
+
+    async fn create_entry(
+        &self,
+        group_names: &mut (dyn Iterator<Item = &str> + Send + Sync),
+        entry: &client::Entry,
+    ) -> Result<()> {
+        let (_, collection) = self
+            .get_group(group_names)
+            .await?
+            .ok_or(error::Error::NotCollection)?;
+
+        let id = rng::map(|rng| {
+            let mut bytes = [0_u8; 16];
+            rng.fill_bytes(&mut bytes);
+            uuid::Builder::from_random_bytes(bytes).into_uuid()
+        });
+        let (attributes, secret) = item_contents(id, entry);
+        let _ = collection
+            .create_item(
+                &entry.title,
+                as_str_map(&attributes),
+                secret.expose_secret(),
+                false,
+                CONTENT_TYPE,
+            )
+            .await
+            .map_err(error::Error::from)?;
+        Ok(())
+    }
+
+    async fn update_entry(&self, id: Uuid, entry: &client::Entry) -> Result<()> {
+        let item = self
+            .all_items()
+            .await?
+            .into_iter()
+            .find(|item| item.id == id)
+            .ok_or(base_error::Error::EntryIdNotFound { id })?;
+
+        // Keep the attributes other applications look their items up by.
+        let (mut attributes, secret) = item_contents(id, entry);
+        attributes.extend(
+            item.attributes
+                .iter()
+                .filter(|&(name, _)| name.starts_with("xdg:"))
+                .map(|(name, value)| (name.clone(), value.clone())),
+        );
+
+        item.inner
+            .set_label(&entry.title)
+            .await
+            .map_err(error::Error::from)?;
+        item.inner
+            .set_attributes(as_str_map(&attributes))
+            .await
+            .map_err(error::Error::from)?;
+        item.inner
+            .set_secret(secret.expose_secret(), CONTENT_TYPE)
+            .await
+            .map_err(error::Error::from)?;
+        Ok(())
+    }
+
+    async fn list_group(
+        &self,
+        group_names: &mut (dyn Iterator<Item = &str> + Send + Sync),
+    ) -> Result<client::GroupListing> {
+        Ok(match self.get_group(group_names).await? {
+            Some((label, collection)) => client::GroupListing {
+                groups: vec![],
+                entries: Self::items(&label, &collection)
+                    .await?
+                    .into_iter()
+                    .map(|item| item.label)
+                    .collect(),
+            },
+            None => client::GroupListing {
+                groups: self
+                    .collections()
+                    .await?
+                    .into_iter()
+                    .map(|(label, _)| label)
+                    .collect(),
+                entries: vec![],
+            },
+        })
+    }
+
+    async fn databases(&self) -> Result<Vec<client::Database>> {
+        let mut databases = vec![];
+        for (label, collection) in self.collections().await? {
+            databases.push(client::Database {
+                name: label.clone(),
+                selector: label,
+                active: !collection.is_locked().await.map_err(error::Error::from)?,
+                association: None,
+            });
+        }
+        Ok(databases)
+    }
+
+    async fn features(&self) -> Result<Vec<String>> {
+        // There's no server to negotiate features with.
+        Ok(vec![])
+    }
+}
+
+pub(crate) struct Protocol;
+
+#[async_trait]
+impl<'channel> client::Protocol<'channel> for Protocol {
+    async fn channel(
+        &self,
+    ) -> Result<(
+        BoxFuture<'channel, Result<()>>,
+        Box<dyn client::Client + Send + Sync + 'channel>,
+    )> {
+        let service = oo7::dbus::Service::new(oo7::dbus::Algorithm::Encrypted)
+            .await
+            .map_err(error::Error::from)?;
+
+        // Each call goes straight to D-Bus, so there's no connection of our
+        // own to service.
+        Ok((Box::pin(future::ok(())), Box::new(Client { service })))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn item_contents_from_entry() {
+        let id = Uuid::nil();
+        let field = |type_, display_name: &str, value: &str| client::FormField {
+            type_,
+            display_name: display_name.to_owned(),
+            value: value.to_owned().into(),
+            metadata: None,
+        };
+        let entry = client::Entry {
+            id: String::new(),
+            parent: None,
+            title: "Example".to_owned(),
+            urls: vec!["https://example.com".to_owned()],
+            form_fields: vec![
+                field(client::FormFieldType::Username, "Username", "alice"),
+                field(client::FormFieldType::Password, "Password", "hunter2"),
+                field(client::FormFieldType::Text, "service", "web"),
+                field(client::FormFieldType::Text, "karp.kind", "storage"),
+            ],
+            source: None,
+            database: None,
+        };
+
+        let (attributes, secret) = item_contents(id, &entry);
+        assert_eq!(secret.expose_secret(), "hunter2");
+        assert_eq!(
+            attributes,
+            HashMap::from([
+                (ID_ATTRIBUTE.to_owned(), id.simple().to_string()),
+                ("username".to_owned(), "alice".to_owned()),
+                ("url".to_owned(), "https://example.com".to_owned()),
+                ("service".to_owned(), "web".to_owned()),
+            ])
+        );
+    }
+}
@@ -0,0 +1,707 @@
+// SPDX-FileCopyrightText: 2024 Noah Fontes
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Mirror the entries we look up into an encrypted file, so we can still
+//! answer lookups from it when the backend can't be reached.
+
+use std::{
+    collections::BTreeSet,
+    num::NonZeroUsize,
+    path::PathBuf,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use aes::{
+    cipher::{BlockDecryptMut as _, BlockEncryptMut as _, KeyIvInit as _},
+    Aes256,
+};
+use async_trait::async_trait;
+use futures_util::{
+    future::{self, BoxFuture},
+    lock::Mutex,
+    stream::BoxStream,
+};
+use hmac::{Hmac, Mac as _};
+use log::{debug, warn};
+use rand::RngCore as _;
+use secrecy::{ExposeSecret as _, Secret, SecretString, SecretVec};
+use serde::{Deserialize, Serialize, Serializer};
+use serde_with::{base64::Base64, serde_as};
+use sha2::{Digest as _, Sha256};
+use url::Url;
+use uuid::Uuid;
+
+use crate::{
+    client,
+    error::{self, Result},
+    locale, metadata, rng,
+    storage::{self, Storage as _},
+};
+
+/// The directory in the user's cache directory that holds a snapshot for each
+/// URL.
+const SNAPSHOT_DIR: &str = "snapshots";
+
+/// Where the snapshots are kept.
+pub(crate) fn dir() -> Option<PathBuf> {
+    metadata::Dir::Cache
+        .path()
+        .map(|path| path.join(SNAPSHOT_DIR))
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+struct Field {
+    #[serde(rename = "type")]
+    type_: client::FormFieldType,
+    display_name: String,
+    #[serde(serialize_with = "serialize_secret")]
+    value: SecretString,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    metadata: Option<client::FormFieldMetadata>,
+}
+
+fn serialize_secret<S: Serializer>(value: &SecretString, serializer: S) -> Result<S::Ok, S::Error> {
+    value.expose_secret().serialize(serializer)
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+struct Entry {
+    id: String,
+    group: Option<String>,
+    title: String,
+    urls: Vec<String>,
+    fields: Vec<Field>,
+    #[serde(default)]
+    database: Option<String>,
+}
+
+impl From<&client::Entry> for Entry {
+    fn from(value: &client::Entry) -> Self {
+        Self {
+            id: value.id.clone(),
+            group: value.parent.as_ref().map(|group| group.path.clone()),
+            title: value.title.clone(),
+            urls: value.urls.clone(),
+            fields: value
+                .form_fields
+                .iter()
+                .map(|field| Field {
+                    type_: field.type_,
+                    display_name: field.display_name.clone(),
+                    value: field.value.clone(),
+                    metadata: field.metadata.clone(),
+                })
+                .collect(),
+            database: value.database.clone(),
+        }
+    }
+}
+
+impl From<&Entry> for client::Entry {
+    fn from(value: &Entry) -> Self {
+        Self {
+            id: value.id.clone(),
+            parent: value
+                .group
+                .as_ref()
+                .map(|path| client::Group { path: path.clone() }),
+            title: value.title.clone(),
+            urls: value.urls.clone(),
+            form_fields: value
+                .fields
+                .iter()
+                .map(|field| client::FormField {
+                    type_: field.type_,
+                    display_name: field.display_name.clone(),
+                    value: field.value.clone(),
+                    metadata: field.metadata.clone(),
+                })
+                .collect(),
+            database: value.database.clone(),
+            source: None,
+        }
+    }
+}
+
+impl Entry {
+    fn group(&self) -> &str {
+        self.group.as_deref().unwrap_or_default()
+    }
+
+    fn has_id(&self, id: Uuid) -> bool {
+        Uuid::try_parse(&self.id).is_ok_and(|entry_id| entry_id == id)
+    }
+}
+
+/// What a snapshot holds once it's decrypted.
+#[derive(Debug, Default, Deserialize, Serialize)]
+struct Contents {
+    /// When the snapshot was last updated, in seconds since the Unix epoch.
+    taken: u64,
+    entries: Vec<Entry>,
+}
+
+impl Contents {
+    /// Add the given entries, replacing any we already have with the same ID.
+    fn record(&mut self, entries: &[client::Entry]) {
+        for entry in entries {
+            self.entries.retain(|existing| existing.id != entry.id);
+            self.entries.push(entry.into());
+        }
+        self.taken = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+    }
+
+    fn age(&self) -> Duration {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH + Duration::from_secs(self.taken))
+            .unwrap_or_default()
+    }
+}
+
+/// The encrypted form of a snapshot, as it's kept in the file.
+#[serde_as]
+#[derive(Deserialize, Serialize)]
+struct Sealed {
+    #[serde_as(as = "Base64")]
+    iv: Vec<u8>,
+    #[serde_as(as = "Base64")]
+    data: Vec<u8>,
+    #[serde_as(as = "Base64")]
+    mac: Vec<u8>,
+}
+
+impl storage::Versioned for Sealed {
+    const VERSION: u32 = 1;
+
+    fn migrate(_: u32, _: &mut serde_json::Value) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// The keys a snapshot is encrypted and authenticated with, derived from the
+/// secret of the stored session.
+struct Keys {
+    encryption: Secret<[u8; 32]>,
+    authentication: Secret<[u8; 32]>,
+}
+
+impl Keys {
+    fn derive(secret: &SecretVec<u8>) -> Self {
+        let derive = |purpose: &[u8]| {
+            Secret::new(
+                Sha256::new()
+                    .chain_update(b"karp snapshot ")
+                    .chain_update(purpose)
+                    .chain_update([0])
+                    .chain_update(secret.expose_secret())
+                    .finalize()
+                    .into(),
+            )
+        };
+
+        Self {
+            encryption: derive(b"encryption"),
+            authentication: derive(b"authentication"),
+        }
+    }
+
+    fn mac(&self, iv: &[u8], data: &[u8]) -> Hmac<Sha256> {
+        // LINT: HMAC accepts keys of any length.
+        #[allow(clippy::expect_used)]
+        let mac = Hmac::<Sha256>::new_from_slice(self.authentication.expose_secret())
+            .expect("HMAC key is valid");
+        mac.chain_update(iv).chain_update(data)
+    }
+
+    fn seal(&self, contents: &Contents) -> Result<Sealed> {
+        let plaintext = SecretVec::new(serde_json::to_vec(contents)?);
+        let iv = rng::map(|rng| {
+            let mut iv = [0_u8; 16];
+            rng.fill_bytes(&mut iv);
+            iv
+        });
+        let data =
+            cbc::Encryptor::<Aes256>::new(self.encryption.expose_secret().into(), &iv.into())
+                .encrypt_padded_vec_mut::<block_padding::Pkcs7>(plaintext.expose_secret());
+        let mac = self.mac(&iv, &data).finalize().into_bytes().to_vec();
+
+        Ok(Sealed {
+            iv: iv.to_vec(),
+            data,
+            mac,
+        })
+    }
+
+    fn open(&self, sealed: &Sealed) -> Result<Contents> {
+        self.mac(&sealed.iv, &sealed.data)
+            .verify_slice(&sealed.mac)
+            .map_err(|_| error::Snapshot::AuthenticationFailure)?;
+
+        let iv: [u8; 16] = sealed
+            .iv
+            .as_slice()
+            .try_into()
+            .map_err(|_| error::Snapshot::AuthenticationFailure)?;
+        let plaintext = SecretVec::new(
+            cbc::Decryptor::<Aes256>::new(self.encryption.expose_secret().into(), &iv.into())
+                .decrypt_padded_vec_mut::<block_padding::Pkcs7>(&sealed.data)
+                .map_err(|_| error::Snapshot::AuthenticationFailure)?,
+        );
+        Ok(serde_json::from_slice(plaintext.expose_secret())?)
+    }
+}
+
+/// A snapshot file along with the keys that protect it.
+struct Snapshot {
+    storage: storage::File,
+    keys: Keys,
+}
+
+impl Snapshot {
+    fn new(url: &Url, secret: &SecretVec<u8>, allow_insecure_permissions: bool) -> Option<Self> {
+        let name =
+            Sha256::digest(url.as_str().as_bytes())
+                .iter()
+                .fold(String::new(), |mut name, byte| {
+                    use std::fmt::Write as _;
+
+                    let _ = write!(name, "{byte:02x}");
+                    name
+                });
+        let storage = storage::File::new(
+            metadata::Dir::Cache,
+            format!("{SNAPSHOT_DIR}/{name}.json"),
+            allow_insecure_permissions,
+        )?;
+
+        Some(Self {
+            storage,
+            keys: Keys::derive(secret),
+        })
+    }
+
+    async fn load(&mut self) -> Result<Option<Contents>> {
+        match self.storage.get().await? {
+            Some(sealed) => Ok(Some(self.keys.open(&sealed)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Add the given entries to the snapshot. A snapshot we can't read, such
+    /// as one taken under an earlier session, is started over.
+    async fn record(&mut self, entries: &[client::Entry]) -> Result<()> {
+        let mut contents = match self.load().await {
+            Ok(contents) => contents.unwrap_or_default(),
+            Err(e) => {
+                debug!("Starting a new snapshot because we can't read the old one: {e}");
+                Contents::default()
+            }
+        };
+        contents.record(entries);
+        let sealed = self.keys.seal(&contents)?;
+        self.storage.update(&sealed).await
+    }
+}
+
+/// A client that mirrors the entries it looks up into a snapshot.
+struct MirrorClient<'channel> {
+    inner: Box<dyn client::Client + Send + Sync + 'channel>,
+    snapshot: Mutex<Snapshot>,
+}
+
+impl MirrorClient<'_> {
+    async fn record(&self, entries: &[client::Entry]) {
+        // The lookup itself succeeded, so failing to take note of it shouldn't
+        // fail the command.
+        if let Err(e) = self.snapshot.lock().await.record(entries).await {
+            warn!("We couldn't update the snapshot: {e}");
+        }
+    }
+}
+
+#[async_trait]
+impl client::Client for MirrorClient<'_> {
+    async fn get_entry(
+        &self,
+        group_names: &mut (dyn Iterator<Item = &str> + Send + Sync),
+        title: &str,
+    ) -> Result<client::Entry> {
+        let entry = self.inner.get_entry(group_names, title).await?;
+        self.record(std::slice::from_ref(&entry)).await;
+        Ok(entry)
+    }
+
+    async fn get_entry_by_id(&self, id: Uuid) -> Result<client::Entry> {
+        let entry = self.inner.get_entry_by_id(id).await?;
+        self.record(std::slice::from_ref(&entry)).await;
+        Ok(entry)
+    }
+
+    async fn find_entries(
+        &self,
+        query: &str,
+        limit: Option<NonZeroUsize>,
+    ) -> Result<Vec<client::Entry>> {
+        let entries = self.inner.find_entries(query, limit).await?;
+        self.record(&entries).await;
+        Ok(entries)
+    }
+
+    async fn create_entry(
+        &self,
+        group_names: &mut (dyn Iterator<Item = &str> + Send + Sync),
+        entry: &client::Entry,
+    ) -> Result<()> {
+        self.inner.create_entry(group_names, entry).await
+    }
+
+    async fn update_entry(&self, id: Uuid, entry: &client::Entry) -> Result<()> {
+        self.inner.update_entry(id, entry).await
+    }
+
+    async fn list_group(
+        &self,
+        group_names: &mut (dyn Iterator<Item = &str> + Send + Sync),
+    ) -> Result<client::GroupListing> {
+        self.inner.list_group(group_names).await
+    }
+
+    async fn databases(&self) -> Result<Vec<client::Database>> {
+        self.inner.databases().await
+    }
+
+    async fn features(&self) -> Result<Vec<String>> {
+        self.inner.features().await
+    }
+
+    async fn generate_password(&self) -> Result<SecretString> {
+        self.inner.generate_password().await
+    }
+
+    async fn watch(&self) -> Result<BoxStream<'static, client::DatabaseEvent>> {
+        self.inner.watch().await
+    }
+
+    async fn settings(&self) -> Result<Vec<client::Setting>> {
+        self.inner.settings().await
+    }
+
+    fn group_paths(&self) -> bool {
+        self.inner.group_paths()
+    }
+}
+
+/// A client that answers from a snapshot instead of the backend.
+struct SnapshotClient {
+    contents: Contents,
+    title_match: client::TitleMatch,
+}
+
+impl SnapshotClient {
+    fn find(
+        &self,
+        limit: Option<NonZeroUsize>,
+        mut predicate: impl FnMut(&Entry) -> bool,
+    ) -> Vec<client::Entry> {
+        self.contents
+            .entries
+            .iter()
+            .filter(|entry| predicate(entry))
+            .take(limit.map_or(usize::MAX, NonZeroUsize::get))
+            .map(client::Entry::from)
+            .collect()
+    }
+}
+
+#[async_trait]
+impl client::Client for SnapshotClient {
+    async fn get_entry(
+        &self,
+        group_names: &mut (dyn Iterator<Item = &str> + Send + Sync),
+        title: &str,
+    ) -> Result<client::Entry> {
+        let path = group_names.collect::<Vec<_>>().join("/");
+        let in_group = self
+            .contents
+            .entries
+            .iter()
+            .filter(|entry| client::same_name(entry.group(), &path));
+        self.title_match
+            .select(in_group, |entry| &entry.title, title)
+            .map(client::Entry::from)
+            .ok_or_else(|| error::Error::EntryNotFound {
+                parent: client::Group { path },
+                name: title.to_owned(),
+            })
+    }
+
+    async fn get_entry_by_id(&self, id: Uuid) -> Result<client::Entry> {
+        self.find(None, |entry| entry.has_id(id))
+            .into_iter()
+            .next()
+            .ok_or(error::Error::EntryIdNotFound { id })
+    }
+
+    async fn find_entries(
+        &self,
+        query: &str,
+        limit: Option<NonZeroUsize>,
+    ) -> Result<Vec<client::Entry>> {
+        let needle = query.to_lowercase();
+        Ok(self.find(limit, |entry| {
+            entry.title.to_lowercase().contains(&needle)
+                || entry
+                    .urls
+                    .iter()
+                    .any(|url| url.to_lowercase().contains(&needle))
+        }))
+    }
+
+    async fn create_entry(
+        &self,
+        _: &mut (dyn Iterator<Item = &str> + Send + Sync),
+        _: &client::Entry,
+    ) -> Result<()> {
+        Err(error::Snapshot::ReadOnly.into())
+    }
+
+    async fn update_entry(&self, _: Uuid, _: &client::Entry) -> Result<()> {
+        Err(error::Snapshot::ReadOnly.into())
+    }
+
+    async fn list_group(
+        &self,
+        group_names: &mut (dyn Iterator<Item = &str> + Send + Sync),
+    ) -> Result<client::GroupListing> {
+        let path = group_names.collect::<Vec<_>>().join("/");
+        let prefix = if path.is_empty() {
+            String::new()
+        } else {
+            format!("{path}/")
+        };
+
+        // Only groups that had an entry looked up in them are known.
+        let groups: BTreeSet<_> = self
+            .contents
+            .entries
+            .iter()
+            .filter_map(|entry| entry.group().strip_prefix(&prefix))
+            .filter_map(|rest| rest.split('/').next())
+            .filter(|name| !name.is_empty())
+            .map(str::to_owned)
+            .collect();
+        Ok(client::GroupListing {
+            groups: groups.into_iter().collect(),
+            entries: self
+                .contents
+                .entries
+                .iter()
+                .filter(|entry| entry.group() == path)
+                .map(|entry| entry.title.clone())
+                .collect(),
+        })
+    }
+
+    async fn databases(&self) -> Result<Vec<client::Database>> {
+        Ok(vec![client::Database {
+            name: locale::format(
+                "snapshot-name",
+                &[("age", &format_age(self.contents.age()))],
+            ),
+            selector: String::new(),
+            active: false,
+            association: None,
+        }])
+    }
+
+    async fn features(&self) -> Result<Vec<String>> {
+        Ok(vec![])
+    }
+
+    fn group_paths(&self) -> bool {
+        // The snapshot doesn't know which backend took it, so it can't vouch
+        // for its groups.
+        false
+    }
+}
+
+fn format_age(age: Duration) -> String {
+    let secs = age.as_secs();
+    let (count, unit) = match secs {
+        0..=119 => (secs, "second"),
+        120..=7199 => (secs / 60, "minute"),
+        7200..=172_799 => (secs / 3600, "hour"),
+        _ => (secs / 86400, "day"),
+    };
+    if count == 1 {
+        locale::text(&format!("age-{unit}"))
+    } else {
+        locale::format(&format!("age-{unit}s"), &[("count", &count)])
+    }
+}
+
+pub(crate) struct Protocol {
+    inner: Box<dyn client::Protocol<'static> + Send + Sync>,
+    url: Url,
+    allow_insecure_permissions: bool,
+    title_match: client::TitleMatch,
+}
+
+impl Protocol {
+    pub(crate) fn new(
+        inner: Box<dyn client::Protocol<'static> + Send + Sync>,
+        url: Url,
+        allow_insecure_permissions: bool,
+        title_match: client::TitleMatch,
+    ) -> Self {
+        Self {
+            inner,
+            url,
+            allow_insecure_permissions,
+            title_match,
+        }
+    }
+}
+
+#[async_trait]
+impl client::Protocol<'static> for Protocol {
+    async fn channel(
+        &self,
+    ) -> Result<(
+        BoxFuture<'static, Result<()>>,
+        Box<dyn client::Client + Send + Sync + 'static>,
+    )> {
+        // The session has to be read before connecting, because the
+        // connection holds on to it until it's closed. The first connection
+        // under a new session has nothing to protect a snapshot with yet.
+        let stored =
+            self.inner.session_secret().await?.and_then(|secret| {
+                Snapshot::new(&self.url, &secret, self.allow_insecure_permissions)
+            });
+        let Some(mut snapshot) = stored else {
+            debug!("Not keeping a snapshot because there's no stored session to protect it with");
+            return self.inner.channel().await;
+        };
+
+        match self.inner.channel().await {
+            Ok((worker, inner)) => Ok((
+                worker,
+                Box::new(MirrorClient {
+                    inner,
+                    snapshot: Mutex::new(snapshot),
+                }),
+            )),
+            Err(e) if e.is_unreachable() => {
+                let Some(contents) = snapshot.load().await? else {
+                    return Err(e);
+                };
+                warn!(
+                    "{}",
+                    locale::format(
+                        "snapshot-answering",
+                        &[
+                            ("url", &self.url),
+                            ("error", &e),
+                            ("age", &format_age(contents.age())),
+                        ],
+                    )
+                );
+                Ok((
+                    Box::pin(future::ok(())),
+                    Box::new(SnapshotClient {
+                        contents,
+                        title_match: self.title_match,
+                    }),
+                ))
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    async fn session_secret(&self) -> Result<Option<SecretVec<u8>>> {
+        self.inner.session_secret().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::Client as _;
+
+    fn entry(id: &str, group: &str, title: &str) -> client::Entry {
+        client::Entry {
+            id: id.to_owned(),
+            parent: Some(client::Group {
+                path: group.to_owned(),
+            }),
+            title: title.to_owned(),
+            urls: vec![],
+            form_fields: vec![client::FormField {
+                type_: client::FormFieldType::Password,
+                display_name: "Password".to_owned(),
+                value: "hunter2".to_owned().into(),
+                metadata: None,
+            }],
+            source: None,
+            database: None,
+        }
+    }
+
+    #[test]
+    fn seals_and_opens() -> Result<()> {
+        let keys = Keys::derive(&SecretVec::new(vec![1; 32]));
+        let mut contents = Contents::default();
+        contents.record(&[entry("1", "Web", "Example")]);
+
+        let mut sealed = keys.seal(&contents)?;
+        assert!(!sealed.data.windows(7).any(|window| window == b"hunter2"));
+        let opened = keys.open(&sealed)?;
+        assert_eq!(opened.entries.len(), 1);
+        assert_eq!(opened.entries[0].fields[0].value.expose_secret(), "hunter2");
+
+        let other = Keys::derive(&SecretVec::new(vec![2; 32]));
+        assert!(other.open(&sealed).is_err());
+        sealed.data[0] ^= 1;
+        assert!(keys.open(&sealed).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn records_replace_by_id() {
+        let mut contents = Contents::default();
+        contents.record(&[entry("1", "Web", "Old"), entry("2", "Web", "Other")]);
+        contents.record(&[entry("1", "Web", "New")]);
+        let titles: Vec<_> = contents.entries.iter().map(|e| e.title.as_str()).collect();
+        assert_eq!(titles, ["Other", "New"]);
+    }
+
+    #[tokio::test]
+    async fn looks_up_titles_like_the_backends() -> Result<()> {
+        let mut contents = Contents::default();
+        contents.record(&[
+            entry("1", "Caf\u{e9}", "GitHub (old)"),
+            entry("2", "Caf\u{e9}", "GitHub"),
+        ]);
+        let client = SnapshotClient {
+            contents,
+            title_match: client::TitleMatch::Prefix,
+        };
+        let get = |title| {
+            let client = &client;
+            async move {
+                client
+                    .get_entry(&mut ["Cafe\u{301}"].into_iter(), title)
+                    .await
+                    .map(|entry| entry.id)
+            }
+        };
+        assert_eq!(get("GitHub").await?, "2");
+        assert_eq!(get("Git").await?, "1");
+        assert!(get("Hub").await.is_err());
+        Ok(())
+    }
+}
@@ -3,27 +3,107 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use std::{
-    fs, io,
+    fs,
+    io::{self, Write as _},
     path::{Path, PathBuf},
+    process,
 };
 
 use async_trait::async_trait;
-use serde::{Deserialize, Serialize};
+use log::warn;
 
-use crate::{error::Result, metadata};
+use crate::{
+    error::{self, Result},
+    metadata,
+};
 
-use super::{IsPersistent, Storage};
+use super::{IsPersistent, Storage, Versioned};
 
 pub(crate) struct File {
     path: PathBuf,
+    allow_insecure_permissions: bool,
 }
 
 impl File {
-    pub(crate) fn new<P: AsRef<Path>>(file: P) -> Option<Self> {
-        metadata::PROJECT_DIRS.as_ref().map(|dirs| Self {
-            path: dirs.data_dir().to_owned().join(file),
+    /// Create a new storage backend for the given file in one of our
+    /// directories. Unless `allow_insecure_permissions` is set, we refuse to read
+    /// the file if other users can access it.
+    pub(crate) fn new<P: AsRef<Path>>(
+        dir: metadata::Dir,
+        file: P,
+        allow_insecure_permissions: bool,
+    ) -> Option<Self> {
+        dir.file(file).map(|path| Self {
+            path,
+            allow_insecure_permissions,
         })
     }
+
+    // LINT: Masking the group and other bits reads better than counting
+    // trailing zeros.
+    #[cfg(unix)]
+    #[allow(clippy::verbose_bit_mask)]
+    fn check_permissions(&self, fp: &fs::File) -> Result<()> {
+        use std::os::unix::fs::PermissionsExt as _;
+
+        let mode = fp.metadata()?.permissions().mode() & 0o777;
+        if mode & 0o077 == 0 {
+            Ok(())
+        } else if self.allow_insecure_permissions {
+            warn!(
+                "Other users can access the session file {} (mode {:o})",
+                self.path.display(),
+                mode
+            );
+            Ok(())
+        } else {
+            Err(error::Storage::InsecurePermissions {
+                path: self.path.clone(),
+                mode,
+            }
+            .into())
+        }
+    }
+
+    #[cfg(not(unix))]
+    #[allow(clippy::unnecessary_wraps, clippy::unused_self)]
+    fn check_permissions(&self, _: &fs::File) -> Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(unix)]
+pub(crate) fn create_dir(path: &Path) -> io::Result<()> {
+    use std::os::unix::fs::DirBuilderExt as _;
+
+    fs::DirBuilder::new()
+        .recursive(true)
+        .mode(0o700)
+        .create(path)
+}
+
+#[cfg(not(unix))]
+pub(crate) fn create_dir(path: &Path) -> io::Result<()> {
+    fs::create_dir_all(path)
+}
+
+#[cfg(unix)]
+fn create_file(path: &Path) -> io::Result<fs::File> {
+    use std::os::unix::fs::OpenOptionsExt as _;
+
+    fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(path)
+}
+
+#[cfg(not(unix))]
+fn create_file(path: &Path) -> io::Result<fs::File> {
+    fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(path)
 }
 
 impl IsPersistent for File {
@@ -33,10 +113,13 @@ impl IsPersistent for File {
 }
 
 #[async_trait]
-impl<T: Send + Serialize + Sync + for<'de> Deserialize<'de>> Storage<T> for File {
+impl<T: Versioned + Send + Sync> Storage<T> for File {
     async fn get(&mut self) -> Result<Option<T>> {
         match fs::File::open(&self.path) {
-            Ok(fp) => Ok(Some(serde_json::from_reader::<fs::File, T>(fp)?)),
+            Ok(fp) => {
+                self.check_permissions(&fp)?;
+                Ok(Some(T::decode(serde_json::from_reader(fp)?)?))
+            }
             Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
             Err(e) => Err(e.into()),
         }
@@ -44,11 +127,27 @@ impl<T: Send + Serialize + Sync + for<'de> Deserialize<'de>> Storage<T> for File
 
     async fn update(&mut self, data: &T) -> Result<()> {
         if let Some(parent) = self.path.parent() {
-            fs::create_dir_all(parent)?;
+            create_dir(parent)?;
         }
-        let file = fs::File::create(&self.path)?;
-        serde_json::to_writer(file, data)?;
-        Ok(())
+
+        // Write to a temporary file next to the real one and move it into
+        // place, so readers never see a partially written file.
+        let mut temp_name = self.path.file_name().unwrap_or_default().to_owned();
+        temp_name.push(format!(".{}.tmp", process::id()));
+        let temp_path = self.path.with_file_name(temp_name);
+
+        let result = (|| {
+            let mut file = create_file(&temp_path)?;
+            serde_json::to_writer(&mut file, &data.encode()?)?;
+            file.flush()?;
+            file.sync_all()?;
+            fs::rename(&temp_path, &self.path)?;
+            Ok(())
+        })();
+        if result.is_err() {
+            let _ = fs::remove_file(&temp_path);
+        }
+        result
     }
 
     async fn clear(&mut self) -> Result<()> {
@@ -56,3 +155,43 @@ impl<T: Send + Serialize + Sync + for<'de> Deserialize<'de>> Storage<T> for File
         Ok(())
     }
 }
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::os::unix::fs::PermissionsExt as _;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn permissions() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("karp-file-test-{}", process::id()));
+        let mut storage = File {
+            path: dir.join("session.json"),
+            allow_insecure_permissions: false,
+        };
+
+        storage.update(&serde_json::json!(1_u32)).await?;
+        assert_eq!(
+            fs::metadata(&storage.path)?.permissions().mode() & 0o777,
+            0o600
+        );
+        assert_eq!(fs::metadata(&dir)?.permissions().mode() & 0o777, 0o700);
+        assert_eq!(
+            Storage::<serde_json::Value>::get(&mut storage).await?,
+            Some(serde_json::json!(1_u32))
+        );
+
+        fs::set_permissions(&storage.path, fs::Permissions::from_mode(0o644))?;
+        assert!(Storage::<serde_json::Value>::get(&mut storage)
+            .await
+            .is_err());
+        storage.allow_insecure_permissions = true;
+        assert_eq!(
+            Storage::<serde_json::Value>::get(&mut storage).await?,
+            Some(serde_json::json!(1_u32))
+        );
+
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+}
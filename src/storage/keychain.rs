@@ -4,24 +4,35 @@
 
 use async_trait::async_trait;
 use secrecy::{ExposeSecret as _, SecretVec};
-use security_framework::os::macos::keychain::{SecKeychain, SecPreferencesDomain};
-use serde::{Deserialize, Serialize};
+use security_framework::{
+    os::macos::keychain::{SecKeychain, SecPreferencesDomain},
+    passwords::{delete_generic_password, get_generic_password, set_generic_password_options},
+    passwords_options::{AccessControlOptions, PasswordOptions},
+};
 
 use crate::{
     error::{self, Result},
     metadata,
 };
 
-use super::{IsPersistent, Storage};
+use super::{IsPersistent, Storage, Versioned};
+
+/// The result code Keychain uses when no item matches a query
+/// (`errSecItemNotFound`).
+const ERR_SEC_ITEM_NOT_FOUND: i32 = -25300;
 
 pub(crate) struct Keychain {
     delegate: SecKeychain,
     service: String,
     account: String,
+    require_user_presence: bool,
 }
 
 impl Keychain {
-    pub(crate) fn new(url: &url::Url) -> Result<Self> {
+    /// Create a new storage backend for the session data of the given URL. If
+    /// `require_user_presence` is set, the item we create can only be read
+    /// after the user authenticates with Touch ID or their password.
+    pub(crate) fn new(url: &url::Url, require_user_presence: bool) -> Result<Self> {
         Ok(Self {
             delegate: SecKeychain::default_for_domain(SecPreferencesDomain::User)
                 .map_err(Into::<error::Storage>::into)?,
@@ -32,8 +43,17 @@ impl Keychain {
                 .to_string_lossy()
                 .to_string(),
             account: url.to_string(),
+            require_user_presence,
         })
     }
+
+    fn delete(&self) -> Result<()> {
+        match delete_generic_password(&self.service, &self.account) {
+            Ok(()) => Ok(()),
+            Err(err) if err.code() == ERR_SEC_ITEM_NOT_FOUND => Ok(()),
+            Err(err) => Err(Into::<error::Storage>::into(err).into()),
+        }
+    }
 }
 
 impl IsPersistent for Keychain {
@@ -43,36 +63,61 @@ impl IsPersistent for Keychain {
 }
 
 #[async_trait]
-impl<T: for<'de> Deserialize<'de> + Send + Serialize + Sync> Storage<T> for Keychain {
+impl<T: Versioned + Send + Sync> Storage<T> for Keychain {
     async fn get(&mut self) -> Result<Option<T>> {
-        let result = self
-            .delegate
-            .find_generic_password(&self.service, &self.account);
+        // Reading an item protected by an access control policy prompts the
+        // user here.
+        let result = if self.require_user_presence {
+            get_generic_password(&self.service, &self.account)
+        } else {
+            self.delegate
+                .find_generic_password(&self.service, &self.account)
+                .map(|(password, _)| password.to_vec())
+        };
         match result {
-            Ok((password, _)) => Ok(Some(serde_json::from_slice(&password)?)),
-            Err(err) if err.code() == -25300_i32 => Ok(None),
+            Ok(password) => Ok(Some(T::decode(serde_json::from_slice(&password)?)?)),
+            Err(err) if err.code() == ERR_SEC_ITEM_NOT_FOUND => Ok(None),
             Err(err) => Err(Into::<error::Storage>::into(err).into()),
         }
     }
 
     async fn update(&mut self, data: &T) -> Result<()> {
+        if self.require_user_presence {
+            // The access control policy of an item can only be set when it is
+            // created, so any existing item has to go first.
+            self.delete()?;
+
+            let mut options = PasswordOptions::new_generic_password(&self.service, &self.account);
+            options.set_access_control_options(AccessControlOptions::USER_PRESENCE);
+            set_generic_password_options(
+                SecretVec::new(serde_json::to_vec(&data.encode()?)?).expose_secret(),
+                options,
+            )
+            .map_err(Into::<error::Storage>::into)?;
+            return Ok(());
+        }
+
         self.delegate
             .set_generic_password(
                 &self.service,
                 &self.account,
-                SecretVec::new(serde_json::to_vec(data)?).expose_secret(),
+                SecretVec::new(serde_json::to_vec(&data.encode()?)?).expose_secret(),
             )
             .map_err(Into::<error::Storage>::into)?;
         Ok(())
     }
 
     async fn clear(&mut self) -> Result<()> {
+        if self.require_user_presence {
+            return self.delete();
+        }
+
         let result = self
             .delegate
             .find_generic_password(&self.service, &self.account);
         match result {
             Ok((_, item)) => item.delete(),
-            Err(err) if err.code() == -25300_i32 => {}
+            Err(err) if err.code() == ERR_SEC_ITEM_NOT_FOUND => {}
             Err(err) => return Err(Into::<error::Storage>::into(err).into()),
         };
         Ok(())
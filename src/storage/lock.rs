@@ -0,0 +1,104 @@
+// SPDX-FileCopyrightText: 2024 Noah Fontes
+//
+// SPDX-License-Identifier: Apache-2.0
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use fd_lock::RwLock;
+use log::info;
+use tokio::sync::oneshot;
+
+use crate::{
+    error::{self, Result},
+    metadata,
+};
+
+use super::{file, IsPersistent};
+
+const LOCK_FILE: &str = "session.lock";
+
+/// An advisory lock on the session data shared by every process the user
+/// runs. Holding it while negotiating a session makes concurrent invocations
+/// take turns instead of replacing each other's session data.
+pub(crate) struct Lock {
+    _release: oneshot::Sender<()>,
+}
+
+/// Take the lock on the given file, waiting for any other process to release
+/// it first, and hold it until the release sender is dropped.
+fn hold(
+    path: &Path,
+    acquired_tx: oneshot::Sender<io::Result<()>>,
+    release_rx: oneshot::Receiver<()>,
+) {
+    let opened = path
+        .parent()
+        .map_or(Ok(()), file::create_dir)
+        .and_then(|()| {
+            fs::OpenOptions::new()
+                .write(true)
+                .create(true)
+                .truncate(false)
+                .open(path)
+        });
+    let mut lock = match opened {
+        Ok(fp) => RwLock::new(fp),
+        Err(e) => {
+            let _ = acquired_tx.send(Err(e));
+            return;
+        }
+    };
+
+    if matches!(lock.try_write(), Err(ref e) if e.kind() == io::ErrorKind::WouldBlock) {
+        info!("Waiting for another process to finish connecting");
+    }
+
+    let result = lock.write();
+    match result {
+        Ok(_guard) => {
+            if acquired_tx.send(Ok(())).is_ok() {
+                let _ = release_rx.blocking_recv();
+            }
+        }
+        Err(e) => {
+            let _ = acquired_tx.send(Err(e));
+        }
+    }
+}
+
+impl Lock {
+    /// Where the lock file is kept.
+    pub(crate) fn path() -> Option<PathBuf> {
+        metadata::Dir::State.file(LOCK_FILE)
+    }
+
+    /// Wait until no other process holds the lock, then take it. Storage that
+    /// isn't shared with other processes doesn't need a lock, so we don't
+    /// take one for it.
+    pub(crate) async fn acquire<S: IsPersistent + Sync + ?Sized>(
+        storage: &S,
+    ) -> Result<Option<Self>> {
+        if !storage.is_persistent() {
+            return Ok(None);
+        }
+        let Some(path) = Self::path() else {
+            return Ok(None);
+        };
+
+        // The lock guard borrows the file, so a blocking task owns both until
+        // we drop the sender.
+        let (acquired_tx, acquired_rx) = oneshot::channel();
+        let (release_tx, release_rx) = oneshot::channel();
+        drop(tokio::task::spawn_blocking(move || {
+            hold(&path, acquired_tx, release_rx);
+        }));
+
+        acquired_rx.await.map_err(error::Internal::from)??;
+        Ok(Some(Self {
+            _release: release_tx,
+        }))
+    }
+}
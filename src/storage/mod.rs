@@ -5,20 +5,102 @@
 mod file;
 #[cfg(feature = "keychain")]
 mod keychain;
+mod lock;
 mod memory;
 #[cfg(feature = "secret-service")]
 mod secret_service;
+mod version;
 
 use async_trait::async_trait;
+use clap::ValueEnum;
+use url::Url;
 
-use crate::error::Result;
+use crate::{
+    error::{self, Result},
+    metadata,
+};
 
-pub(crate) use file::File;
+pub(crate) use file::{create_dir, File};
 #[cfg(feature = "keychain")]
 pub(crate) use keychain::Keychain;
+pub(crate) use lock::Lock;
 pub(crate) use memory::Memory;
 #[cfg(feature = "secret-service")]
 pub(crate) use secret_service::SecretService;
+pub(crate) use version::Versioned;
+
+/// The name of the file session data is kept in when no better storage is
+/// available.
+pub(crate) const SESSION_FILE: &str = "session.json";
+
+/// Settings that change how session data is stored.
+#[derive(Copy, Clone, Debug, Default)]
+pub(crate) struct Options {
+    /// Whether reading session data from Keychain needs the user to be
+    /// present.
+    #[cfg(feature = "keychain")]
+    pub(crate) require_user_presence: bool,
+    /// Whether to read a session file that other users can access.
+    pub(crate) allow_insecure_permissions: bool,
+}
+
+/// The places session data can be kept.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+pub(crate) enum Kind {
+    /// An unencrypted file in the user's state directory.
+    File,
+    /// The macOS Keychain.
+    Keychain,
+    /// A keyring implementing the freedesktop.org Secret Service API.
+    SecretService,
+}
+
+impl Kind {
+    /// The kinds of storage this build supports, in the order we prefer them.
+    pub(crate) const SUPPORTED: &'static [Self] = &[
+        #[cfg(feature = "secret-service")]
+        Self::SecretService,
+        #[cfg(feature = "keychain")]
+        Self::Keychain,
+        Self::File,
+    ];
+
+    /// Open the storage of this kind that holds the session data for the
+    /// given URL.
+    #[cfg_attr(not(feature = "secret-service"), allow(clippy::unused_async))]
+    #[cfg_attr(
+        not(any(feature = "secret-service", feature = "keychain")),
+        allow(unused_variables)
+    )]
+    pub(crate) async fn open<T>(self, url: &Url, options: Options) -> Result<Box<dyn Storage<T>>>
+    where
+        T: Versioned + Send + Sync + 'static,
+    {
+        match self {
+            Self::File => Ok(Box::new(
+                File::new(
+                    metadata::Dir::State,
+                    SESSION_FILE,
+                    options.allow_insecure_permissions,
+                )
+                .ok_or(error::Storage::NoProjectDirs)?,
+            )),
+            #[cfg(feature = "keychain")]
+            Self::Keychain => Ok(Box::new(Keychain::new(url, options.require_user_presence)?)),
+            #[cfg(feature = "secret-service")]
+            Self::SecretService => Ok(Box::new(SecretService::new(url).await?)),
+            #[allow(unreachable_patterns)]
+            unsupported => Err(error::Storage::Unsupported(unsupported).into()),
+        }
+    }
+}
+
+impl std::fmt::Display for Kind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let value = self.to_possible_value().ok_or(std::fmt::Error)?;
+        write!(f, "{}", value.get_name())
+    }
+}
 
 pub(crate) trait IsPersistent {
     fn is_persistent(&self) -> bool;
@@ -6,14 +6,13 @@ use std::collections::HashMap;
 
 use async_trait::async_trait;
 use secrecy::{ExposeSecret, SecretVec};
-use serde::{Deserialize, Serialize};
 
 use crate::{
     error::{self, Result},
     metadata,
 };
 
-use super::{IsPersistent, Storage};
+use super::{IsPersistent, Storage, Versioned};
 
 pub(crate) struct SecretService {
     keyring: oo7::Keyring,
@@ -54,12 +53,12 @@ impl IsPersistent for SecretService {
 }
 
 #[async_trait]
-impl<T: for<'de> Deserialize<'de> + Send + Serialize + Sync> Storage<T> for SecretService {
+impl<T: Versioned + Send + Sync> Storage<T> for SecretService {
     async fn get(&mut self) -> Result<Option<T>> {
         let data = match self.item().await? {
             Some(item) => {
                 let secret = item.secret().await.map_err(error::Storage::from)?;
-                serde_json::from_slice(&secret)?
+                Some(T::decode(serde_json::from_slice(&secret)?)?)
             }
             None => None,
         };
@@ -74,7 +73,7 @@ impl<T: for<'de> Deserialize<'de> + Send + Serialize + Sync> Storage<T> for Secr
                     .iter()
                     .map(|(key, value)| (key.as_str(), value.as_str()))
                     .collect(),
-                SecretVec::new(serde_json::to_vec(data)?).expose_secret(),
+                SecretVec::new(serde_json::to_vec(&data.encode()?)?).expose_secret(),
                 true,
             )
             .await
@@ -0,0 +1,107 @@
+// SPDX-FileCopyrightText: 2024 Noah Fontes
+//
+// SPDX-License-Identifier: Apache-2.0
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::error::{self, Result};
+
+/// The key that holds the format version in stored objects. Data stored
+/// without one predates versioning and is treated as version 0.
+const VERSION_KEY: &str = "version";
+
+/// Data whose stored format is versioned, so data written by an older release
+/// can be migrated forward when it's read instead of being discarded.
+pub(crate) trait Versioned: Serialize + for<'de> Deserialize<'de> {
+    /// The version of the format we write.
+    const VERSION: u32;
+
+    /// Rewrite data stored in the given version of the format to match the
+    /// next version.
+    fn migrate(version: u32, data: &mut Value) -> Result<()>;
+
+    fn decode(mut data: Value) -> Result<Self> {
+        let version = match data.as_object_mut().and_then(|obj| obj.remove(VERSION_KEY)) {
+            Some(value) => value
+                .as_u64()
+                .and_then(|v| u32::try_from(v).ok())
+                .ok_or(error::Storage::InvalidVersion)?,
+            None => 0,
+        };
+        if version > Self::VERSION {
+            return Err(error::Storage::UnsupportedVersion(version).into());
+        }
+
+        for from in version..Self::VERSION {
+            Self::migrate(from, &mut data)?;
+        }
+        Ok(serde_json::from_value(data)?)
+    }
+
+    fn encode(&self) -> Result<Value> {
+        let mut data = serde_json::to_value(self)?;
+        if let Some(obj) = data.as_object_mut() {
+            let _ = obj.insert(VERSION_KEY.to_owned(), Self::VERSION.into());
+        }
+        Ok(data)
+    }
+}
+
+/// Raw data is moved between storage as is, whatever its version.
+impl Versioned for Value {
+    const VERSION: u32 = 0;
+
+    fn migrate(_: u32, _: &mut Value) -> Result<()> {
+        Ok(())
+    }
+
+    fn decode(data: Value) -> Result<Self> {
+        Ok(data)
+    }
+
+    fn encode(&self) -> Result<Value> {
+        Ok(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[derive(Debug, Deserialize, PartialEq, Serialize)]
+    struct Data {
+        name: String,
+    }
+
+    impl Versioned for Data {
+        const VERSION: u32 = 2;
+
+        fn migrate(version: u32, data: &mut Value) -> Result<()> {
+            if version == 1 {
+                if let Some(obj) = data.as_object_mut() {
+                    let old = obj.remove("title").unwrap_or_default();
+                    let _ = obj.insert("name".to_owned(), old);
+                }
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn migrates_forward() -> Result<()> {
+        let expected = Data {
+            name: "test".to_owned(),
+        };
+        assert_eq!(Data::decode(json!({ "title": "test" }))?, expected);
+        assert_eq!(
+            Data::decode(json!({ "version": 1_u32, "title": "test" }))?,
+            expected
+        );
+        assert_eq!(Data::decode(expected.encode()?)?, expected);
+        assert!(Data::decode(json!({ "version": 3_u32, "name": "test" })).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,156 @@
+// SPDX-FileCopyrightText: 2024 Noah Fontes
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! A breakdown of where a command spent its time, so slowness can be put down
+//! to the network, the backend, or us.
+//!
+//! Each [`progress::Guard`](crate::progress::Guard) is recorded here when it's
+//! dropped. Phases can overlap: the first request made on a connection waits
+//! for authentication, so its time includes the handshake. What we report as
+//! our own is the time that none of them covers.
+
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use tabled::{
+    settings::{
+        object::{Rows, Segment},
+        Alignment, Format, Modify, Style,
+    },
+    Table, Tabled,
+};
+
+use crate::locale;
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static SPANS: Mutex<Vec<Span>> = Mutex::new(Vec::new());
+
+struct Span {
+    label: String,
+    started: Instant,
+    elapsed: Duration,
+}
+
+/// Start recording how long each phase takes.
+pub(crate) fn enable() {
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+/// Record a phase that started at the given time and just finished.
+pub(crate) fn record<F: FnOnce() -> String>(label: F, started: Instant) {
+    if !ENABLED.load(Ordering::Relaxed) {
+        return;
+    }
+    if let Ok(mut spans) = SPANS.lock() {
+        spans.push(Span {
+            label: label(),
+            started,
+            elapsed: started.elapsed(),
+        });
+    }
+}
+
+/// The time covered by at least one of the spans.
+fn covered(spans: &[Span]) -> Duration {
+    let mut intervals: Vec<_> = spans
+        .iter()
+        .map(|span| (span.started, span.started + span.elapsed))
+        .collect();
+    intervals.sort_unstable();
+
+    let mut total = Duration::ZERO;
+    let mut current: Option<(Instant, Instant)> = None;
+    for (start, end) in intervals {
+        current = match current {
+            Some((from, to)) if start <= to => Some((from, to.max(end))),
+            Some((from, to)) => {
+                total += to - from;
+                Some((start, end))
+            }
+            None => Some((start, end)),
+        };
+    }
+    if let Some((from, to)) = current {
+        total += to - from;
+    }
+    total
+}
+
+#[derive(Tabled)]
+struct Row {
+    #[tabled(rename = "Measurement")]
+    measurement: String,
+    #[tabled(rename = "Time")]
+    time: String,
+}
+
+impl Row {
+    fn new(measurement: String, duration: Duration) -> Self {
+        Self {
+            measurement,
+            time: format!("{:.3} ms", duration.as_secs_f64() * 1_000.0_f64),
+        }
+    }
+}
+
+/// Print what we recorded, if anything, to standard error, with the time
+/// since the command started.
+pub(crate) fn report(started: Instant) {
+    if !ENABLED.load(Ordering::Relaxed) {
+        return;
+    }
+    let Ok(spans) = SPANS.lock() else {
+        return;
+    };
+    let total = started.elapsed();
+    let other = total.saturating_sub(covered(&spans));
+
+    let rows = spans
+        .iter()
+        .map(|span| Row::new(span.label.clone(), span.elapsed))
+        .chain([
+            Row::new(locale::text("timings-other"), other),
+            Row::new(locale::text("timings-total"), total),
+        ]);
+    eprintln!(
+        "{}",
+        Table::new(rows)
+            .with(Style::rounded())
+            .with(Modify::new(Rows::first()).with(Format::content(locale::header)))
+            .with(Modify::new(Segment::new(1.., 1..)).with(Alignment::right()))
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn span(started: Instant, from_ms: u64, to_ms: u64) -> Span {
+        Span {
+            label: String::new(),
+            started: started + Duration::from_millis(from_ms),
+            elapsed: Duration::from_millis(to_ms - from_ms),
+        }
+    }
+
+    #[test]
+    fn overlapping_spans_are_counted_once() {
+        let started = Instant::now();
+        assert_eq!(covered(&[]), Duration::ZERO);
+        assert_eq!(
+            covered(&[
+                span(started, 0, 10),
+                span(started, 5, 20),
+                span(started, 6, 8),
+                span(started, 30, 35),
+            ]),
+            Duration::from_millis(25)
+        );
+    }
+}
@@ -0,0 +1,219 @@
+// SPDX-FileCopyrightText: 2024 Noah Fontes
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! A transcript of the messages we exchange with backends, for attaching to
+//! bug reports.
+//!
+//! Each line of the transcript is a JSON object recording when a message was
+//! sent or received, relative to when the transcript was opened. Messages are
+//! recorded twice when they carry an encrypted payload: once as the envelope
+//! seen on the wire, with the ciphertext left out, and once as the decrypted
+//! payload. Values that could reveal a password are redacted either way.
+
+use std::{
+    fs,
+    io::{self, Write as _},
+    path::Path,
+    sync::{Mutex, OnceLock},
+    time::Instant,
+};
+
+use log::warn;
+use secrecy::zeroize::Zeroize as _;
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::memory;
+
+/// Fields that hold secrets, or values from which a password could be
+/// recovered offline, such as SRP proofs.
+const SECRET_FIELDS: &[&str] = &["password", "totp", "stringFields", "M", "M2", "cr", "sr"];
+
+/// Fields of an envelope that hold ciphertext or its MAC.
+const ENCRYPTED_FIELDS: &[&str] = &["message", "hmac"];
+
+/// The type KeePassRPC gives form fields whose value is a password.
+const PASSWORD_FIELD_TYPE: &str = "FFTpassword";
+
+const REDACTED: &str = "<redacted>";
+const ENCRYPTED: &str = "<encrypted>";
+
+static TRACE: OnceLock<Mutex<Trace>> = OnceLock::new();
+
+struct Trace {
+    started: Instant,
+    file: fs::File,
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum Direction {
+    Sent,
+    Received,
+}
+
+/// How a recorded message relates to what went over the wire.
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum Layer {
+    /// The message as sent or received, with any ciphertext left out.
+    Envelope,
+    /// The decrypted contents of an encrypted message.
+    Payload,
+}
+
+#[derive(Serialize)]
+struct Record<'protocol> {
+    elapsed: f64,
+    protocol: &'protocol str,
+    direction: Direction,
+    layer: Layer,
+    message: Value,
+}
+
+/// Start writing a transcript to the file at the given path, replacing its
+/// contents.
+pub(crate) fn open(path: &Path) -> io::Result<()> {
+    let mut options = fs::OpenOptions::new();
+    let _ = options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt as _;
+
+        let _ = options.mode(0o600);
+    }
+
+    let file = options.open(path)?;
+    if TRACE
+        .set(Mutex::new(Trace {
+            started: Instant::now(),
+            file,
+        }))
+        .is_err()
+    {
+        warn!("A transcript is already being written, so we will not write another");
+    }
+    Ok(())
+}
+
+/// Add a message to the transcript, if one is being written.
+pub(crate) fn record<T: Serialize + ?Sized>(
+    protocol: &str,
+    direction: Direction,
+    layer: Layer,
+    message: &T,
+) {
+    write(protocol, direction, layer, || serde_json::to_value(message));
+}
+
+/// Add a message that is already encoded as JSON to the transcript, if one is
+/// being written.
+pub(crate) fn record_json(protocol: &str, direction: Direction, layer: Layer, json: &[u8]) {
+    write(protocol, direction, layer, || serde_json::from_slice(json));
+}
+
+fn write<F>(protocol: &str, direction: Direction, layer: Layer, message: F)
+where
+    F: FnOnce() -> serde_json::Result<Value>,
+{
+    let Some(shared) = TRACE.get() else {
+        return;
+    };
+
+    let result = message().map_err(io::Error::from).and_then(|mut value| {
+        redact(&mut value, layer);
+        let Ok(mut trace) = shared.lock() else {
+            return Ok(());
+        };
+        let mut record = Record {
+            elapsed: trace.started.elapsed().as_secs_f64(),
+            protocol,
+            direction,
+            layer,
+            message: value,
+        };
+        let mut line = serde_json::to_vec(&record)?;
+        memory::zeroize_json(&mut record.message);
+        line.push(b'\n');
+        let result = trace.file.write_all(&line);
+        line.zeroize();
+        result
+    });
+    if let Err(e) = result {
+        warn!("We could not add a message to the transcript: {}", e);
+    }
+}
+
+fn redact(value: &mut Value, layer: Layer) {
+    match *value {
+        Value::Object(ref mut object) => {
+            let is_password_field = object
+                .get("type")
+                .is_some_and(|ty| ty == PASSWORD_FIELD_TYPE);
+            for (key, field) in object.iter_mut() {
+                if SECRET_FIELDS.contains(&key.as_str()) || (is_password_field && key == "value") {
+                    memory::zeroize_json(field);
+                    *field = Value::String(REDACTED.to_owned());
+                } else if matches!(layer, Layer::Envelope)
+                    && field.is_string()
+                    && ENCRYPTED_FIELDS.contains(&key.as_str())
+                {
+                    *field = Value::String(ENCRYPTED.to_owned());
+                } else {
+                    redact(field, layer);
+                }
+            }
+        }
+        Value::Array(ref mut array) => {
+            for item in array {
+                redact(item, layer);
+            }
+        }
+        Value::Null | Value::Bool(_) | Value::Number(_) | Value::String(_) => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn redact_secrets() {
+        let mut value = json!({
+            "jsonrpc": {"message": "Y2lwaGVydGV4dA==", "iv": "aXY=", "hmac": "aG1hYw=="},
+            "srp": {"M": "proof", "I": "identifier"},
+            "formFieldList": [
+                {"name": "user", "type": "FFTusername", "value": "alice"},
+                {"name": "pass", "type": "FFTpassword", "value": "hunter2"},
+            ],
+            "entries": [{"login": "alice", "password": "hunter2"}],
+        });
+        redact(&mut value, Layer::Envelope);
+
+        assert_eq!(
+            value,
+            json!({
+                "jsonrpc": {"message": ENCRYPTED, "iv": "aXY=", "hmac": ENCRYPTED},
+                "srp": {"M": REDACTED, "I": "identifier"},
+                "formFieldList": [
+                    {"name": "user", "type": "FFTusername", "value": "alice"},
+                    {"name": "pass", "type": "FFTpassword", "value": REDACTED},
+                ],
+                "entries": [{"login": "alice", "password": REDACTED}],
+            })
+        );
+    }
+
+    #[test]
+    fn redact_keeps_payload_messages() {
+        let mut value = json!({"error": {"name": "Error", "message": "not found"}});
+        redact(&mut value, Layer::Payload);
+
+        assert_eq!(
+            value,
+            json!({"error": {"name": "Error", "message": "not found"}})
+        );
+    }
+}
@@ -0,0 +1,264 @@
+// SPDX-FileCopyrightText: 2024 Noah Fontes
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Reaching KeePassRPC or KeePassXC on another machine through SSH. We run
+//! `ssh` to forward a local port or socket to the one the backend listens on
+//! over there, and connect to ours instead, so SSH handles authentication
+//! with the user's own configuration and agent.
+
+use std::{
+    env, fs, io,
+    net::{Ipv4Addr, TcpListener, TcpStream},
+    path::PathBuf,
+    process::{self, Child, Stdio},
+    sync::Arc,
+    thread,
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use futures_util::{future::BoxFuture, lock::Mutex, FutureExt as _};
+use log::{debug, warn};
+use secrecy::SecretVec;
+use tokio::task;
+use url::Url;
+
+use crate::{
+    client,
+    error::{self, Result},
+    progress,
+};
+
+/// How often to check whether the tunnel is ready while `ssh` connects.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Where we listen for connections to forward.
+#[derive(Debug)]
+enum Local {
+    Port(u16),
+    Socket(PathBuf),
+}
+
+/// A forwarding from this machine to a backend on another.
+#[derive(Debug)]
+pub(crate) struct Forward {
+    destination: String,
+    local: Local,
+    remote: String,
+}
+
+impl Forward {
+    /// Plan a forwarding through the given SSH destination to the backend the
+    /// URL points to, as seen from the other machine.
+    pub(crate) fn new(destination: &str, url: &Url) -> Result<Self> {
+        let (local, remote) = match url.scheme() {
+            "ws" | "wss" | "tcp" => {
+                let host = url.host_str().ok_or(error::Tunnel::NoRemoteTarget)?;
+                let port = url
+                    .port_or_known_default()
+                    .ok_or(error::Tunnel::NoRemoteTarget)?;
+                let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0))?;
+                (
+                    Local::Port(listener.local_addr()?.port()),
+                    format!("{host}:{port}"),
+                )
+            }
+            "file" => {
+                // We can't look for the socket on the other machine, so it has
+                // to be given.
+                let path = url
+                    .to_file_path()
+                    .ok()
+                    .filter(|path| path.parent().is_some())
+                    .ok_or(error::Tunnel::NoRemoteTarget)?;
+                (
+                    Local::Socket(env::temp_dir().join(format!("karp-{}.sock", process::id()))),
+                    path.to_string_lossy().into_owned(),
+                )
+            }
+            scheme => return Err(error::Tunnel::Unsupported(scheme.to_owned()).into()),
+        };
+        Ok(Self {
+            destination: destination.to_owned(),
+            local,
+            remote,
+        })
+    }
+
+    /// The URL to connect to instead of the given one, which reaches it
+    /// through the tunnel.
+    pub(crate) fn url(&self, url: &Url) -> Url {
+        match self.local {
+            Local::Port(port) => {
+                let mut local = url.clone();
+                if local.set_ip_host(Ipv4Addr::LOCALHOST.into()).is_err()
+                    || local.set_port(Some(port)).is_err()
+                {
+                    warn!("We could not point {} at the tunnel", url);
+                }
+                local
+            }
+            Local::Socket(ref path) => Url::from_file_path(path).unwrap_or_else(|()| url.clone()),
+        }
+    }
+
+    fn spec(&self) -> String {
+        match self.local {
+            Local::Port(port) => format!("{}:{port}:{}", Ipv4Addr::LOCALHOST, self.remote),
+            Local::Socket(ref path) => format!("{}:{}", path.display(), self.remote),
+        }
+    }
+
+    fn is_ready(&self) -> bool {
+        match self.local {
+            Local::Port(port) => TcpStream::connect((Ipv4Addr::LOCALHOST, port)).is_ok(),
+            Local::Socket(ref path) => path.exists(),
+        }
+    }
+
+    /// Start `ssh` and wait until it's listening, which may involve the user
+    /// authenticating.
+    fn open(&self) -> Result<Ssh> {
+        debug!("Forwarding {} through {}", self.spec(), self.destination);
+        let child = process::Command::new("ssh")
+            .args(["-N", "-o", "ExitOnForwardFailure=yes"])
+            .args(["-o", "StreamLocalBindUnlink=yes"])
+            .arg("-L")
+            .arg(self.spec())
+            .arg("--")
+            .arg(&self.destination)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .spawn()
+            .map_err(|e| {
+                if e.kind() == io::ErrorKind::NotFound {
+                    error::Tunnel::NoSsh.into()
+                } else {
+                    error::Error::from(e)
+                }
+            })?;
+        let mut ssh = Ssh {
+            child,
+            socket: match self.local {
+                Local::Socket(ref path) => Some(path.clone()),
+                Local::Port(_) => None,
+            },
+        };
+
+        let _pause = progress::pause();
+        while !self.is_ready() {
+            if let Some(status) = ssh.child.try_wait()? {
+                return Err(error::Tunnel::Exited(status).into());
+            }
+            thread::sleep(POLL_INTERVAL);
+        }
+        Ok(ssh)
+    }
+}
+
+/// A running `ssh`, which is stopped when this is dropped.
+struct Ssh {
+    child: Child,
+    socket: Option<PathBuf>,
+}
+
+impl Drop for Ssh {
+    fn drop(&mut self) {
+        if let Err(e) = self.child.kill().and_then(|()| self.child.wait()) {
+            debug!("We could not stop ssh: {}", e);
+        }
+        if let Some(ref path) = self.socket {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+
+/// Connects to a backend through a tunnel, which is opened for the first
+/// connection and kept until every connection is closed.
+pub(crate) struct Protocol {
+    inner: Box<dyn client::Protocol<'static> + Send + Sync>,
+    forward: Arc<Forward>,
+    ssh: Mutex<Option<Arc<Ssh>>>,
+}
+
+impl Protocol {
+    pub(crate) fn new(
+        inner: Box<dyn client::Protocol<'static> + Send + Sync>,
+        forward: Forward,
+    ) -> Self {
+        Self {
+            inner,
+            forward: Arc::new(forward),
+            ssh: Mutex::new(None),
+        }
+    }
+}
+
+#[async_trait]
+impl client::Protocol<'static> for Protocol {
+    async fn channel(
+        &self,
+    ) -> Result<(
+        BoxFuture<'static, Result<()>>,
+        Box<dyn client::Client + Send + Sync + 'static>,
+    )> {
+        let ssh = {
+            let mut running = self.ssh.lock().await;
+            if let Some(ref ssh) = *running {
+                Arc::clone(ssh)
+            } else {
+                let forward = Arc::clone(&self.forward);
+                let ssh = Arc::new(task::spawn_blocking(move || forward.open()).await??);
+                *running = Some(Arc::clone(&ssh));
+                ssh
+            }
+        };
+
+        let (worker, client) = self.inner.channel().await?;
+        Ok((
+            // The worker owns the connection, so it keeps the tunnel open.
+            worker
+                .map(move |result| {
+                    drop(ssh);
+                    result
+                })
+                .boxed(),
+            client,
+        ))
+    }
+
+    async fn session_secret(&self) -> Result<Option<SecretVec<u8>>> {
+        self.inner.session_secret().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ports_are_forwarded_to_the_backend_host() -> Result<()> {
+        let url = Url::parse("ws://127.0.0.1:12546").map_err(error::Conversion::from)?;
+        let forward = Forward::new("me@desktop", &url)?;
+        let Local::Port(port) = forward.local else {
+            return Err(error::Error::Command);
+        };
+        assert_eq!(forward.remote, "127.0.0.1:12546");
+        assert_eq!(forward.url(&url).port(), Some(port));
+        assert_eq!(forward.spec(), format!("127.0.0.1:{port}:127.0.0.1:12546"));
+
+        let socket =
+            Url::parse("file:///run/user/1000/kpxc.sock").map_err(error::Conversion::from)?;
+        assert_eq!(
+            Forward::new("me@desktop", &socket)?.remote,
+            "/run/user/1000/kpxc.sock"
+        );
+        assert!(Forward::new(
+            "me@desktop",
+            &Url::parse("file:").map_err(error::Conversion::from)?
+        )
+        .is_err());
+        Ok(())
+    }
+}